@@ -3,20 +3,87 @@
 
 // TODO: remove the ^^ above once we get (more) stable
 // TODO: excessive String cloning in message parsing
-// TODO: logging
 // TODO: uuid test version (prime the UUID generator) -- also for message generation
 // TODO: test the json serialization of an empty Collections object
-// TODO: authentication
 // TODO: TAXII HTTP headers:
 // X-TAXII-Date-Added-First: timestamp
 // X-TAXII-Date-Added-Last: timestamp
 
+pub mod mirror;
+pub mod stix1;
 pub mod taxii;
 pub mod taxii21;
 
+use tracing_subscriber::{
+    filter::{LevelFilter, Targets},
+    layer::SubscriberExt,
+    util::SubscriberInitExt,
+};
+
+/// Install the global subscriber configured based on the RUST_LOG envvar, logging JSON lines
+/// instead of text when LOG_FORMAT=json.
+fn init_logging() {
+    if std::env::var("LOG_FORMAT").as_deref() != Ok("json") {
+        tracing_subscriber::fmt::init();
+        return;
+    }
+    let targets = std::env::var("RUST_LOG")
+        .ok()
+        .and_then(|v| v.parse::<Targets>().ok())
+        .unwrap_or_else(|| Targets::new().with_default(LevelFilter::INFO));
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .with_current_span(true)
+                .with_span_list(false),
+        )
+        .with(targets)
+        .init();
+}
+
 fn main() {
-    // install global subscriber configured based on RUST_LOG envvar.
-    tracing_subscriber::fmt::init();
+    init_logging();
+
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(|v| v.as_str()) == Some("mirror") {
+        let path = match args.get(2) {
+            Some(v) => v.as_str(),
+            None => "mirror.toml",
+        };
+        if let Err(err) = mirror::main(std::path::Path::new(path)) {
+            println!("err={}", err);
+        }
+        return;
+    }
+    if args.get(1).map(|v| v.as_str()) == Some("taxii11") {
+        let path = match args.get(2) {
+            Some(v) => v.as_str(),
+            None => "taxii11.toml",
+        };
+        if let Err(err) = taxii::server::main(std::path::Path::new(path)) {
+            println!("err={}", err);
+        }
+        return;
+    }
+    if args.get(1).map(|v| v.as_str()) == Some("replicate") {
+        let dry_run = args.iter().any(|v| v == "--dry-run");
+        let path = match args.iter().skip(2).find(|v| *v != "--dry-run") {
+            Some(v) => v.as_str(),
+            None => "replicator.toml",
+        };
+        if let Err(err) = taxii21::replicator::main(std::path::Path::new(path), dry_run) {
+            println!("err={}", err);
+        }
+        return;
+    }
+
+    if args.get(1).map(|v| v.as_str()) == Some("keys") {
+        if let Err(err) = taxii21::api_keys::main(&args[2..]) {
+            println!("err={}", err);
+        }
+        return;
+    }
 
     match taxii21::server::main() {
         Ok(v) => v,
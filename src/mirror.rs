@@ -0,0 +1,696 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{
+    stix1::{convert::convert_package, package::StixPackage},
+    taxii::{
+        archive::Archive,
+        client::AsyncClient,
+        content_block::ContentBlock,
+        errors::TaxiiError,
+        files::write_file_atomically,
+        poll::{PollParameters, TimeRange},
+        query::STIX_1_TARGETING_EXPRESSION_IDS,
+        server::{parse_stix_objects, STIX_JSON_21_BINDING},
+        types::ResponseType,
+        version::Version,
+    },
+    taxii21::{backend::Backend, file_backend::FileBackend, server::Object},
+};
+
+fn default_version() -> String {
+    String::from("1.1")
+}
+
+fn default_interval() -> u64 {
+    3600
+}
+
+/// A TAXII 1.x collection to mirror, and the TAXII 2.1 collection its content is written to.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FeedConfig {
+    /// The name the state of the feed is kept under.
+    pub name: String,
+    pub poll_url: String,
+    pub collection_name: String,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub password: String,
+    /// The TAXII version of the feed, `1.0` or `1.1`.
+    #[serde(default = "default_version")]
+    pub version: String,
+    /// Seconds between two polls of the feed.
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+    /// The id of the TAXII 2.1 collection the content is written to.
+    pub collection_id: String,
+}
+
+impl FeedConfig {
+    fn taxii_version(&self) -> Result<Version, TaxiiError> {
+        match self.version.as_str() {
+            "1.0" => Ok(Version::V10),
+            "1.1" => Ok(Version::V11),
+            v => Err(TaxiiError::Protocol(format!(
+                "feed {}: cannot mirror TAXII {}",
+                self.name, v
+            ))),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct MirrorConfig {
+    /// The file the state of the feeds is kept in.
+    pub state_path: String,
+    /// The root directory of the file backend of the TAXII 2.1 server.
+    pub file_backend: String,
+    /// The directory the responses of the feeds are archived in, if any.
+    #[serde(default)]
+    pub archive_dir: Option<String>,
+    #[serde(default)]
+    pub feeds: Vec<FeedConfig>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct MirrorConfigFile {
+    mirror: MirrorConfig,
+}
+
+impl MirrorConfig {
+    pub fn load_toml(path: &Path) -> Result<MirrorConfig, TaxiiError> {
+        let cfg = match std::fs::read_to_string(path) {
+            Ok(v) => v,
+            Err(err) => return Err(TaxiiError::Storage(err.to_string())),
+        };
+        match toml::from_str::<MirrorConfigFile>(cfg.as_str()) {
+            Ok(v) => Ok(v.mirror),
+            Err(err) => Err(TaxiiError::Protocol(err.to_string())),
+        }
+    }
+}
+
+/// The most content blocks kept for each feed to be converted again. The oldest are dropped
+/// beyond it.
+const MAX_FAILED_BLOCKS: usize = 1000;
+
+/// A content block that could not be converted, kept to be converted again by the next polls of
+/// the feed, e.g. once the converter handles it.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct FailedBlock {
+    pub binding_id: String,
+    pub content: String,
+    pub timestamp_label: Option<DateTime<Utc>>,
+    pub error: String,
+}
+
+impl FailedBlock {
+    fn content_block(&self) -> ContentBlock {
+        let mut content_block = ContentBlock::new(self.binding_id.as_str(), self.content.as_str());
+        content_block.timestamp_label = self.timestamp_label;
+        content_block
+    }
+}
+
+/// How far a feed has been mirrored.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct FeedState {
+    /// The end of the last time range that was written to the TAXII 2.1 collection, the
+    /// Exclusive_Begin_Timestamp of the next poll.
+    pub last_timestamp_label: Option<DateTime<Utc>>,
+    pub last_poll: Option<DateTime<Utc>>,
+    /// The content blocks of the polled time ranges that could not be converted.
+    #[serde(default)]
+    pub failed_blocks: Vec<FailedBlock>,
+}
+
+/// The state of all feeds, persisted across restarts.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct MirrorState {
+    pub feeds: BTreeMap<String, FeedState>,
+}
+
+impl MirrorState {
+    /// Read the state from a file. A missing file is an empty state: nothing was mirrored yet.
+    pub fn load(path: &Path) -> Result<MirrorState, TaxiiError> {
+        let data = match std::fs::read_to_string(path) {
+            Ok(v) => v,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(MirrorState::default())
+            }
+            Err(err) => return Err(TaxiiError::Storage(err.to_string())),
+        };
+        match serde_json::from_str::<MirrorState>(data.as_str()) {
+            Ok(v) => Ok(v),
+            Err(err) => Err(TaxiiError::Storage(format!(
+                "bad mirror state {}: {}",
+                path.display(),
+                err
+            ))),
+        }
+    }
+
+    /// Write the state to a file, replacing it atomically.
+    pub fn save(&self, path: &Path) -> Result<(), TaxiiError> {
+        let data = match serde_json::to_string_pretty(self) {
+            Ok(v) => v,
+            Err(err) => return Err(TaxiiError::Storage(err.to_string())),
+        };
+        match write_file_atomically(path, data.as_str()) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(TaxiiError::Storage(err.to_string())),
+        }
+    }
+}
+
+/// What one poll of a feed mirrored.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MirrorReport {
+    pub content_blocks: usize,
+    /// Content blocks that are not STIX.
+    pub skipped_blocks: usize,
+    /// Content blocks that could not be converted, kept in the state of the feed.
+    pub failed_blocks: usize,
+    /// Content blocks kept by earlier polls that were converted by this one.
+    pub replayed_blocks: usize,
+    pub objects: usize,
+    pub success_count: u32,
+    /// Objects the collection already holds at the same version.
+    pub failure_count: u32,
+    /// Messages about the STIX 1.x content that has no STIX 2.1 equivalent.
+    pub unmapped: Vec<String>,
+}
+
+/// Convert a content block to STIX 2.1 objects: STIX 1.x packages are converted, STIX 2.1
+/// content is taken as is. None when the block holds other content.
+fn convert_content_block(
+    content_block: &ContentBlock,
+    report: &mut MirrorReport,
+) -> Result<Option<Vec<Object>>, TaxiiError> {
+    let binding_id = content_block.content_binding.binding_id.as_str();
+    if STIX_1_TARGETING_EXPRESSION_IDS.contains(&binding_id) {
+        let package = StixPackage::parse(content_block.content.trim().as_bytes())?;
+        let conversion = convert_package(&package);
+        report
+            .unmapped
+            .extend(conversion.unmapped.into_iter().map(|v| v.message));
+        Ok(Some(conversion.objects))
+    } else if binding_id == STIX_JSON_21_BINDING {
+        Ok(Some(parse_stix_objects(content_block.content.as_str())?))
+    } else {
+        Ok(None)
+    }
+}
+
+struct Feed {
+    config: FeedConfig,
+    client: AsyncClient,
+}
+
+/// Mirrors TAXII 1.x collections into TAXII 2.1 collections. Each poll asks for the content
+/// after the Timestamp_Label the state holds for the feed, and the state is only advanced, and
+/// saved, once the content is written to the backend: a failed poll is retried from the same
+/// point, and a restart resumes where the last successful poll ended. Content blocks that cannot
+/// be converted are kept in the state and converted again by the next polls.
+#[derive(Clone)]
+pub struct Mirror {
+    feeds: Arc<Vec<Feed>>,
+    backend: Arc<Mutex<dyn Backend + Send>>,
+    state: Arc<Mutex<MirrorState>>,
+    state_path: PathBuf,
+}
+
+impl Mirror {
+    pub fn new(
+        feeds: &[FeedConfig],
+        backend: Arc<Mutex<dyn Backend + Send>>,
+        state_path: &Path,
+    ) -> Result<Mirror, TaxiiError> {
+        let feeds = feeds
+            .iter()
+            .map(|config| {
+                Ok(Feed {
+                    config: config.clone(),
+                    client: AsyncClient::new(
+                        config.username.as_str(),
+                        config.password.as_str(),
+                        config.taxii_version()?,
+                    ),
+                })
+            })
+            .collect::<Result<Vec<_>, TaxiiError>>()?;
+        Ok(Mirror {
+            feeds: Arc::new(feeds),
+            backend,
+            state: Arc::new(Mutex::new(MirrorState::load(state_path)?)),
+            state_path: state_path.to_path_buf(),
+        })
+    }
+
+    /// Archive the responses of the feeds, or not when None, the default.
+    pub fn with_archive(self, archive: Option<Archive>) -> Mirror {
+        let feeds = self
+            .feeds
+            .iter()
+            .map(|v| Feed {
+                config: v.config.clone(),
+                client: v.client.clone().with_archive(archive.clone()),
+            })
+            .collect();
+        Mirror {
+            feeds: Arc::new(feeds),
+            ..self
+        }
+    }
+
+    pub fn state(&self) -> MirrorState {
+        self.state.lock().unwrap().clone()
+    }
+
+    /// Poll the content of a feed since its last poll, including all parts of a multi-part
+    /// response. Returns the content blocks and the end of the polled time range.
+    async fn poll_content(
+        &self,
+        feed: &Feed,
+        exclusive_begin: Option<DateTime<Utc>>,
+    ) -> Result<(Vec<ContentBlock>, Option<DateTime<Utc>>), TaxiiError> {
+        let config = &feed.config;
+        let poll_parameters = PollParameters {
+            allow_asynch: false,
+            response_type: ResponseType::Full,
+            content_bindings: vec![],
+            query: String::from(""),
+            query_format_id: String::from(""),
+        };
+        let time_range = TimeRange {
+            exclusive_begin,
+            inclusive_end: None,
+        };
+        let mut poll_response = feed
+            .client
+            .poll(
+                config.poll_url.as_str(),
+                config.collection_name.as_str(),
+                Some(time_range),
+                "",
+                Some(poll_parameters),
+            )
+            .await?;
+        let inclusive_end = poll_response.time_range.inclusive_end;
+        let content_blocks = std::mem::take(&mut poll_response.content_blocks);
+        // servers must give the end of the range, but fall back to the newest content
+        let inclusive_end = inclusive_end.or(content_blocks
+            .iter()
+            .filter_map(|v| v.timestamp_label)
+            .max());
+        Ok((content_blocks, inclusive_end))
+    }
+
+    async fn poll_feed(&self, index: usize) -> Result<MirrorReport, TaxiiError> {
+        let feed = &self.feeds[index];
+        let name = feed.config.name.clone();
+        let feed_state = self.state().feeds.get(&name).cloned().unwrap_or_default();
+        let (content_blocks, inclusive_end) = self
+            .poll_content(feed, feed_state.last_timestamp_label)
+            .await?;
+
+        let mut report = MirrorReport {
+            content_blocks: content_blocks.len(),
+            ..MirrorReport::default()
+        };
+        let mut objects = Vec::<Object>::new();
+        let mut failed_blocks = Vec::<FailedBlock>::new();
+        for failed_block in feed_state.failed_blocks.iter() {
+            match convert_content_block(&failed_block.content_block(), &mut report) {
+                Ok(Some(v)) => {
+                    objects.extend(v);
+                    report.replayed_blocks += 1;
+                }
+                Ok(None) => report.skipped_blocks += 1,
+                Err(err) => failed_blocks.push(FailedBlock {
+                    error: err.to_string(),
+                    ..failed_block.clone()
+                }),
+            }
+        }
+        for content_block in content_blocks.iter() {
+            match convert_content_block(content_block, &mut report) {
+                Ok(Some(v)) => objects.extend(v),
+                Ok(None) => report.skipped_blocks += 1,
+                // a block that cannot be converted is kept rather than blocking the feed
+                Err(err) => {
+                    warn!("feed {}: kept content block: {}", name, err);
+                    report.failed_blocks += 1;
+                    failed_blocks.push(FailedBlock {
+                        binding_id: content_block.content_binding.binding_id.clone(),
+                        content: content_block.content.clone(),
+                        timestamp_label: content_block.timestamp_label,
+                        error: err.to_string(),
+                    });
+                }
+            }
+        }
+        if failed_blocks.len() > MAX_FAILED_BLOCKS {
+            let dropped = failed_blocks.len() - MAX_FAILED_BLOCKS;
+            warn!("feed {}: dropped {} kept content blocks", name, dropped);
+            failed_blocks.drain(..dropped);
+        }
+        report.objects = objects.len();
+        if !objects.is_empty() {
+            let mut backend = match self.backend.lock() {
+                Ok(v) => v,
+                Err(err) => return Err(TaxiiError::Storage(err.to_string())),
+            };
+            let status = match backend.add_objects(feed.config.collection_id.as_str(), &objects) {
+                Ok(v) => v,
+                Err(err) => return Err(TaxiiError::Storage(err.0)),
+            };
+            report.success_count = status.success_count;
+            report.failure_count = status.failure_count;
+        }
+
+        let feed_state = FeedState {
+            last_timestamp_label: inclusive_end.or(feed_state.last_timestamp_label),
+            last_poll: Some(Utc::now()),
+            failed_blocks,
+        };
+        // the feeds are polled concurrently, their states are saved one at a time
+        let mut state = self.state.lock().unwrap();
+        state.feeds.insert(name, feed_state);
+        state.save(self.state_path.as_path())?;
+        Ok(report)
+    }
+
+    /// Poll a feed by name, write its content to its TAXII 2.1 collection and save the state.
+    pub async fn poll(&self, feed_name: &str) -> Result<MirrorReport, TaxiiError> {
+        match self.feeds.iter().position(|v| v.config.name == feed_name) {
+            Some(index) => self.poll_feed(index).await,
+            None => Err(TaxiiError::Protocol(format!("unknown feed: {}", feed_name))),
+        }
+    }
+
+    /// Poll a feed on its interval, forever. Failed polls are logged and retried at the next
+    /// interval.
+    async fn run_feed(&self, index: usize) {
+        let config = &self.feeds[index].config;
+        loop {
+            match self.poll_feed(index).await {
+                Ok(report) => info!(
+                    "mirrored feed {}: content_blocks={} skipped={} failed={} replayed={} objects={} successes={} failures={} unmapped={}",
+                    config.name,
+                    report.content_blocks,
+                    report.skipped_blocks,
+                    report.failed_blocks,
+                    report.replayed_blocks,
+                    report.objects,
+                    report.success_count,
+                    report.failure_count,
+                    report.unmapped.len()
+                ),
+                Err(err) => warn!("could not mirror feed {}: {}", config.name, err),
+            }
+            tokio::time::sleep(Duration::from_secs(config.interval)).await;
+        }
+    }
+
+    /// Poll each feed on its interval, forever, each in its own task so that a slow feed does
+    /// not delay the others.
+    pub async fn run(&self) {
+        let tasks = (0..self.feeds.len())
+            .map(|index| {
+                let mirror = self.clone();
+                tokio::spawn(async move { mirror.run_feed(index).await })
+            })
+            .collect::<Vec<_>>();
+        for result in futures::future::join_all(tasks).await {
+            if let Err(err) = result {
+                warn!("mirror task failed: {}", err);
+            }
+        }
+    }
+}
+
+/// Run the mirror configured by a toml file, writing to the file backend of the TAXII 2.1
+/// server.
+pub fn main(path: &Path) -> Result<(), TaxiiError> {
+    let cfg = MirrorConfig::load_toml(path)?;
+    let backend: Arc<Mutex<dyn Backend + Send>> =
+        Arc::new(Mutex::new(FileBackend::new(cfg.file_backend.as_str())));
+    let archive = cfg
+        .archive_dir
+        .as_ref()
+        .map(|v| Archive::new(Path::new(v.as_str())));
+    let mirror =
+        Mirror::new(&cfg.feeds, backend, Path::new(cfg.state_path.as_str()))?.with_archive(archive);
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(v) => v,
+        Err(err) => return Err(TaxiiError::Transport(err.to_string())),
+    };
+    info!("mirroring {} feeds", cfg.feeds.len());
+    runtime.block_on(mirror.run());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env, fs,
+        net::TcpListener,
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex},
+    };
+
+    use actix_web::HttpServer;
+    use uuid::Uuid;
+
+    use super::{FeedConfig, Mirror, MirrorConfig, MirrorState};
+    use crate::{
+        taxii::{
+            collections::{Collection, CollectionType},
+            content_block::ContentBlock,
+            poll::parse_poll_response,
+            server::new_app,
+            store::{MemoryStore, Store},
+        },
+        taxii21::{
+            backend::{Backend, Filtering},
+            memory_backend::MemoryBackend,
+        },
+    };
+
+    fn sample_content_blocks() -> Vec<ContentBlock> {
+        let path = format!(
+            "{}/test/sample-poll-response.xml",
+            env::var("CARGO_MANIFEST_DIR").unwrap()
+        );
+        let doc = fs::read(path).unwrap();
+        parse_poll_response(&doc).unwrap().content_blocks
+    }
+
+    fn temp_state_path() -> PathBuf {
+        env::temp_dir().join(format!("mirror-state-{}.json", Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_load_config() {
+        let path = format!(
+            "{}/test/sample-mirror.toml",
+            env::var("CARGO_MANIFEST_DIR").unwrap()
+        );
+        let cfg = MirrorConfig::load_toml(Path::new(path.as_str())).unwrap();
+        assert_eq!(Some(String::from("archive/")), cfg.archive_dir);
+        assert_eq!(1, cfg.feeds.len());
+        assert_eq!("taxiistand", cfg.feeds[0].name);
+        assert_eq!("1.1", cfg.feeds[0].version);
+        assert_eq!(3600, cfg.feeds[0].interval);
+        assert_eq!("aaaabbbb", cfg.feeds[0].collection_id);
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let path = temp_state_path();
+        assert_eq!(MirrorState::default(), MirrorState::load(&path).unwrap());
+        let mut state = MirrorState::default();
+        state
+            .feeds
+            .entry(String::from("feed"))
+            .or_default()
+            .last_timestamp_label = Some(chrono::Utc::now());
+        state.save(&path).unwrap();
+        assert_eq!(state, MirrorState::load(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+    }
+
+    /// Serve a TAXII 1.1 `stix-data` collection and return the store holding its content and the
+    /// feed mirroring it into the `mirrored` collection.
+    fn start_feed() -> (Arc<Mutex<MemoryStore>>, FeedConfig) {
+        let mut collection = Collection::new_empty();
+        collection.collection_name = String::from("stix-data");
+        collection.collection_type = CollectionType::DataFeed;
+        collection.available = true;
+        let mut store = MemoryStore::new();
+        store.add_collection(collection);
+        let store = Arc::new(Mutex::new(store));
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let app_store: Arc<Mutex<dyn Store + Send>> = store.clone();
+        let app_base_url = base_url.clone();
+        let server = HttpServer::new(move || new_app(app_base_url.as_str(), app_store.clone()))
+            .workers(1)
+            .listen(listener)
+            .unwrap();
+        actix_web::rt::spawn(server.run());
+
+        let feed = FeedConfig {
+            name: String::from("stix-data"),
+            poll_url: format!("{}/services/poll", base_url),
+            collection_name: String::from("stix-data"),
+            username: String::from("guest"),
+            password: String::from("guest"),
+            version: String::from("1.1"),
+            interval: 60,
+            collection_id: String::from("mirrored"),
+        };
+        (store, feed)
+    }
+
+    #[actix_web::test]
+    async fn test_mirror() {
+        let (store, feed) = start_feed();
+        let mut backend = MemoryBackend::new();
+        backend.add_collection("mirrored");
+        let backend = Arc::new(Mutex::new(backend));
+        let feeds = vec![feed];
+        let state_path = temp_state_path();
+        let object_count = || {
+            backend
+                .lock()
+                .unwrap()
+                .get_objects("mirrored", &Filtering::no_filter())
+                .unwrap()
+                .len()
+        };
+
+        let content_blocks = sample_content_blocks();
+        let (first, rest) = content_blocks.split_at(2);
+        for content_block in first.iter() {
+            store
+                .lock()
+                .unwrap()
+                .add_content("stix-data", content_block)
+                .unwrap();
+        }
+        let mirror = Mirror::new(&feeds, backend.clone(), &state_path).unwrap();
+        let report = mirror.poll("stix-data").await.unwrap();
+        assert_eq!(2, report.content_blocks);
+        assert!(report.objects > 0);
+        assert_eq!(report.objects as u32, report.success_count);
+        assert_eq!(report.objects, object_count());
+        let last_timestamp_label = mirror.state().feeds["stix-data"].last_timestamp_label;
+        assert!(last_timestamp_label.is_some());
+
+        // nothing new: nothing is written again
+        let report = mirror.poll("stix-data").await.unwrap();
+        assert_eq!(0, report.content_blocks);
+        assert_eq!(0, report.objects);
+
+        // a restarted mirror resumes from the saved state and only gets the new content
+        for content_block in rest.iter() {
+            store
+                .lock()
+                .unwrap()
+                .add_content("stix-data", content_block)
+                .unwrap();
+        }
+        let objects_before = object_count();
+        let mirror = Mirror::new(&feeds, backend.clone(), &state_path).unwrap();
+        assert!(mirror.state().feeds["stix-data"].last_timestamp_label >= last_timestamp_label);
+        let report = mirror.poll("stix-data").await.unwrap();
+        assert_eq!(rest.len(), report.content_blocks);
+        assert_eq!(
+            report.objects as u32,
+            report.success_count + report.failure_count
+        );
+        assert!(object_count() > objects_before);
+        assert!(mirror.poll("unknown").await.is_err());
+
+        // a failing backend leaves the state as it was, so that the window is polled again
+        let state = mirror.state().clone();
+        for content_block in first.iter() {
+            store
+                .lock()
+                .unwrap()
+                .add_content("stix-data", content_block)
+                .unwrap();
+        }
+        let feeds = vec![FeedConfig {
+            collection_id: String::from("unknown"),
+            ..feeds[0].clone()
+        }];
+        let mirror = Mirror::new(&feeds, backend.clone(), &state_path).unwrap();
+        assert!(mirror.poll("stix-data").await.is_err());
+        assert_eq!(state, mirror.state());
+        assert_eq!(state, MirrorState::load(&state_path).unwrap());
+
+        fs::remove_file(&state_path).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_mirror_failed_blocks() {
+        let (store, feed) = start_feed();
+        let mut backend = MemoryBackend::new();
+        backend.add_collection("mirrored");
+        let backend = Arc::new(Mutex::new(backend));
+        let state_path = temp_state_path();
+
+        let content_blocks = sample_content_blocks();
+        let mut broken = content_blocks[1].clone();
+        broken.content = String::from("not a STIX package");
+        for content_block in [&broken, &content_blocks[2]] {
+            store
+                .lock()
+                .unwrap()
+                .add_content("stix-data", content_block)
+                .unwrap();
+        }
+        let mirror =
+            Mirror::new(std::slice::from_ref(&feed), backend.clone(), &state_path).unwrap();
+        let report = mirror.poll("stix-data").await.unwrap();
+        assert_eq!(2, report.content_blocks);
+        assert_eq!(1, report.failed_blocks);
+        assert!(report.objects > 0);
+
+        // the feed moves on, the block that could not be converted is kept
+        let feed_state = mirror.state().feeds["stix-data"].clone();
+        assert!(feed_state.last_timestamp_label.is_some());
+        assert_eq!(1, feed_state.failed_blocks.len());
+        assert_eq!(broken.content, feed_state.failed_blocks[0].content);
+        let report = mirror.poll("stix-data").await.unwrap();
+        assert_eq!(0, report.content_blocks);
+        assert_eq!(0, report.replayed_blocks);
+        assert_eq!(1, mirror.state().feeds["stix-data"].failed_blocks.len());
+
+        // once it can be converted, e.g. by a newer mirror, it is written
+        let mut state = MirrorState::load(&state_path).unwrap();
+        let failed_block = &mut state.feeds.get_mut("stix-data").unwrap().failed_blocks[0];
+        failed_block.content = content_blocks[1].content.clone();
+        state.save(&state_path).unwrap();
+        let mirror = Mirror::new(&[feed], backend.clone(), &state_path).unwrap();
+        let report = mirror.poll("stix-data").await.unwrap();
+        assert_eq!(1, report.replayed_blocks);
+        assert!(report.success_count > 0);
+        assert!(mirror.state().feeds["stix-data"].failed_blocks.is_empty());
+
+        fs::remove_file(&state_path).unwrap();
+    }
+}
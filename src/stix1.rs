@@ -0,0 +1,5 @@
+pub mod common;
+pub mod convert;
+pub mod cybox;
+pub mod marking;
+pub mod package;
@@ -0,0 +1,270 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+use crate::taxii::{
+    errors::TaxiiError,
+    message::{Element, FromElement},
+};
+
+static NAMESPACE_XSI: &str = "http://www.w3.org/2001/XMLSchema-instance";
+
+/// Parse a STIX 1.x timestamp. These are xs:dateTime, which may leave out the time zone: those
+/// are read as UTC.
+pub fn parse_datetime(v: &str) -> Option<DateTime<Utc>> {
+    let v = v.trim();
+    match DateTime::parse_from_rfc3339(v) {
+        Ok(v) => Some(v.with_timezone(&Utc)),
+        Err(_) => NaiveDateTime::parse_from_str(v, "%Y-%m-%dT%H:%M:%S%.f")
+            .ok()
+            .map(|v| DateTime::<Utc>::from_utc(v, Utc)),
+    }
+}
+
+pub fn parse_stix_timestamp(name: &str, v: &str) -> Result<DateTime<Utc>, TaxiiError> {
+    match parse_datetime(v) {
+        Some(v) => Ok(v),
+        None => Err(TaxiiError::Protocol(format!("bad {}: {}", name, v.trim()))),
+    }
+}
+
+/// The timestamp attribute of a STIX component, e.g. of an Indicator.
+pub fn timestamp_attr(element: &Element) -> Result<Option<DateTime<Utc>>, TaxiiError> {
+    match element.attr("timestamp") {
+        Some(v) => Ok(Some(parse_stix_timestamp("timestamp", v)?)),
+        None => Ok(None),
+    }
+}
+
+/// The xsi:type of an element without its namespace prefix, e.g. `AddressObjectType` for
+/// `xsi:type="AddressObj:AddressObjectType"`.
+pub fn xsi_type(element: &Element) -> Option<&str> {
+    element
+        .attributes
+        .iter()
+        .find(|a| a.name.local_name == "type" && a.name.namespace.as_deref() == Some(NAMESPACE_XSI))
+        .map(|a| match a.value.split_once(':') {
+            Some((_, v)) => v,
+            None => a.value.as_str(),
+        })
+}
+
+/// The texts of the child elements with a local name, e.g. the Types of an Indicator.
+pub fn element_texts(element: &Element, local_name: &str) -> Vec<String> {
+    element
+        .elements_named(local_name)
+        .map(|e| e.text())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+/// Read the child elements with a local name, e.g. the Indicators of an Indicators element.
+pub fn read_all<T: FromElement>(element: &Element, local_name: &str) -> Result<Vec<T>, TaxiiError> {
+    element
+        .elements_named(local_name)
+        .map(T::from_element)
+        .collect::<Result<Vec<_>, _>>()
+}
+
+/// Read a child element, None when there is none.
+pub fn read_optional<T: FromElement>(
+    element: &Element,
+    local_name: &str,
+) -> Result<Option<T>, TaxiiError> {
+    match element.element(local_name) {
+        Some(v) => Ok(Some(T::from_element(v)?)),
+        None => Ok(None),
+    }
+}
+
+/// A value of a controlled vocabulary with a timestamp, e.g. the Confidence of an Indicator.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Statement {
+    pub value: Option<String>,
+    pub description: Option<String>,
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+impl FromElement for Statement {
+    fn from_element(element: &Element) -> Result<Statement, TaxiiError> {
+        Ok(Statement {
+            value: element.element_text("Value"),
+            description: element.element_text("Description"),
+            timestamp: timestamp_attr(element)?,
+        })
+    }
+}
+
+/// The party names and address of a CIQ identity specification.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CiqIdentity {
+    pub organisation_names: Vec<String>,
+    pub person_names: Vec<String>,
+    pub countries: Vec<String>,
+    pub administrative_areas: Vec<String>,
+    pub industry_type: Option<String>,
+}
+
+// CIQ names are held by NameElements, which are either text or carry a NameCode.
+fn name_elements(element: &Element) -> Vec<String> {
+    element
+        .elements_named("NameElement")
+        .map(|e| match e.attr("NameCode") {
+            Some(v) => String::from(v),
+            None => e.text(),
+        })
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+impl FromElement for CiqIdentity {
+    fn from_element(element: &Element) -> Result<CiqIdentity, TaxiiError> {
+        element.expect("Specification")?;
+        let mut identity = CiqIdentity::default();
+        if let Some(party_name) = element.element("PartyName") {
+            for name in party_name.elements_named("OrganisationName") {
+                identity.organisation_names.extend(name_elements(name));
+            }
+            for name in party_name.elements_named("PersonName") {
+                identity.person_names.push(name_elements(name).join(" "));
+            }
+        }
+        let addresses = element
+            .elements_named("Addresses")
+            .flat_map(|e| e.elements_named("Address"));
+        for address in addresses {
+            if let Some(country) = address.element("Country") {
+                identity.countries.extend(name_elements(country));
+            }
+            if let Some(area) = address.element("AdministrativeArea") {
+                identity.administrative_areas.extend(name_elements(area));
+            }
+        }
+        identity.industry_type = element
+            .element("OrganisationInfo")
+            .and_then(|e| e.attr("IndustryType"))
+            .map(String::from);
+        Ok(identity)
+    }
+}
+
+/// An identity, e.g. of the source of a package or the producer of an indicator.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Identity {
+    pub id: Option<String>,
+    pub idref: Option<String>,
+    pub name: Option<String>,
+    pub ciq: Option<CiqIdentity>,
+}
+
+impl Identity {
+    /// The name to show for the identity: its Name, else the first name of its CIQ
+    /// specification.
+    pub fn display_name(&self) -> Option<&str> {
+        match (&self.name, &self.ciq) {
+            (Some(name), _) => Some(name.as_str()),
+            (None, Some(ciq)) => ciq
+                .organisation_names
+                .first()
+                .or(ciq.person_names.first())
+                .map(|v| v.as_str()),
+            (None, None) => None,
+        }
+    }
+}
+
+impl FromElement for Identity {
+    fn from_element(element: &Element) -> Result<Identity, TaxiiError> {
+        Ok(Identity {
+            id: element.attr("id").map(String::from),
+            idref: element.attr("idref").map(String::from),
+            name: element.element_text("Name"),
+            ciq: read_optional(element, "Specification")?,
+        })
+    }
+}
+
+/// A tool that produced content.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tool {
+    pub name: Option<String>,
+    pub vendor: Option<String>,
+    pub version: Option<String>,
+}
+
+impl FromElement for Tool {
+    fn from_element(element: &Element) -> Result<Tool, TaxiiError> {
+        Ok(Tool {
+            name: element.element_text("Name"),
+            vendor: element.element_text("Vendor"),
+            version: element.element_text("Version"),
+        })
+    }
+}
+
+/// Who produced content, when and with which tools.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InformationSource {
+    pub description: Option<String>,
+    pub identity: Option<Identity>,
+    pub produced_time: Option<DateTime<Utc>>,
+    pub tools: Vec<Tool>,
+}
+
+impl FromElement for InformationSource {
+    fn from_element(element: &Element) -> Result<InformationSource, TaxiiError> {
+        let produced_time = match element
+            .element("Time")
+            .and_then(|e| e.element_text("Produced_Time"))
+        {
+            Some(v) => Some(parse_stix_timestamp("Produced_Time", v.as_str())?),
+            None => None,
+        };
+        Ok(InformationSource {
+            description: element.element_text("Description"),
+            identity: read_optional(element, "Identity")?,
+            produced_time,
+            tools: match element.element("Tools") {
+                Some(v) => read_all(v, "Tool")?,
+                None => Vec::<Tool>::new(),
+            },
+        })
+    }
+}
+
+/// A relationship to another component, e.g. a Related_Indicator of an Incident. The component
+/// is referenced by its id, whether it is given inline or by idref.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RelatedReference {
+    pub idref: Option<String>,
+    pub relationship: Option<String>,
+    pub confidence: Option<Statement>,
+}
+
+impl FromElement for RelatedReference {
+    fn from_element(element: &Element) -> Result<RelatedReference, TaxiiError> {
+        let idref = element
+            .elements()
+            .filter(|e| {
+                !["Confidence", "Information_Source", "Relationship"].contains(&e.local_name())
+            })
+            .find_map(|e| e.attr("idref").or(e.attr("id")))
+            .map(String::from);
+        Ok(RelatedReference {
+            idref,
+            relationship: element.element_text("Relationship"),
+            confidence: read_optional(element, "Confidence")?,
+        })
+    }
+}
+
+/// Read the relationships held by a list element, e.g. the Related_Indicator elements of
+/// Related_Indicators.
+pub fn related_references(
+    element: &Element,
+    list_name: &str,
+    local_name: &str,
+) -> Result<Vec<RelatedReference>, TaxiiError> {
+    match element.element(list_name) {
+        Some(v) => read_all(v, local_name),
+        None => Ok(Vec::<RelatedReference>::new()),
+    }
+}
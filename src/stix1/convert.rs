@@ -0,0 +1,1415 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::taxii21::server::Object;
+
+use super::{
+    common::{Identity, InformationSource, Statement},
+    cybox::{EmailHeader, Object as CyboxObject, ObjectProperties, Observable, PropertyValue},
+    marking::{Marking, MarkingStructure},
+    package::{ExploitTarget, Incident, Indicator, KillChainPhase, StixPackage, Ttp},
+};
+
+/// The marking definitions STIX 2.1 defines for the TLP colors.
+pub static TLP_WHITE: &str = "marking-definition--613f2e26-407d-48c7-9eca-b8e91df99dc9";
+pub static TLP_GREEN: &str = "marking-definition--34098fce-860f-48ae-8e50-ebd3cc5e41da";
+pub static TLP_AMBER: &str = "marking-definition--f88d31f6-486f-44da-b317-01333bde0b82";
+pub static TLP_RED: &str = "marking-definition--5e57c739-391a-4eb3-b6be-7d15ca92d5ed";
+
+/// Something in a STIX 1.x package that the conversion left out, e.g. a property STIX 2.1 has
+/// no equivalent for.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Unmapped {
+    /// The id of the STIX 1.x component, e.g. of the indicator, when it has one.
+    pub source: Option<String>,
+    pub message: String,
+}
+
+/// The STIX 2.1 objects converted from a STIX 1.x package, and a report of what could not be
+/// mapped.
+#[derive(Clone, Debug)]
+pub struct Conversion {
+    pub objects: Vec<Object>,
+    pub unmapped: Vec<Unmapped>,
+}
+
+impl Conversion {
+    /// The objects as a STIX 2.1 bundle.
+    pub fn bundle(&self) -> Value {
+        json!({
+            "type": "bundle",
+            "id": format!("bundle--{}", Uuid::new_v4()),
+            "objects": self.objects,
+        })
+    }
+
+    /// The objects of a type, e.g. `indicator`.
+    pub fn objects_of_type(&self, typ: &str) -> Vec<&Object> {
+        self.objects.iter().filter(|v| v.typ == typ).collect()
+    }
+}
+
+/// Convert a STIX 1.x package to STIX 2.1 objects: indicators with patterns from their CybOX
+/// observables, SCOs for the observables of the package, malware and attack patterns for TTPs,
+/// vulnerabilities for exploit targets, incidents, and the identities and markings they
+/// reference. Objects keep the UUID of the STIX 1.x id they are converted from, when it has one.
+pub fn convert_package(package: &StixPackage) -> Conversion {
+    let mut converter = Converter::new(package);
+    converter.convert(package);
+    Conversion {
+        objects: converter.objects,
+        unmapped: converter.unmapped,
+    }
+}
+
+fn format_timestamp(v: &DateTime<Utc>) -> String {
+    v.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// A STIX 2.1 vocabulary value from a STIX 1.x one, e.g. `ip-watchlist` from `IP Watchlist`.
+fn vocabulary_value(v: &str) -> String {
+    v.trim()
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|v| !v.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// A STIX 2.1 confidence from a STIX 1.x HighMediumLow value, using the scale of the STIX 2.1
+/// specification.
+fn confidence(statement: &Statement) -> Option<u8> {
+    match statement
+        .value
+        .as_deref()
+        .map(|v| v.to_lowercase())
+        .as_deref()
+    {
+        Some("none") => Some(0),
+        Some("low") => Some(15),
+        Some("medium") => Some(50),
+        Some("high") => Some(85),
+        _ => None,
+    }
+}
+
+fn quote(v: &str) -> String {
+    format!("'{}'", v.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// Escape the wildcards of a LIKE pattern with a backslash, so that `%` and `_` in a value
+/// match themselves rather than any characters.
+fn escape_like(v: &str) -> String {
+    v.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// A comparison expression of a STIX pattern for a property value, e.g.
+/// `ipv4-addr:value = '1.2.3.4'`. Values listing several with apply_condition ALL must all
+/// match, others any of them. The substring conditions become LIKE with the value escaped.
+fn comparison(path: &str, value: &PropertyValue) -> Result<String, String> {
+    let condition = value.condition.as_deref().unwrap_or("Equals");
+    let comparisons = value
+        .values()
+        .iter()
+        .map(|v| match condition {
+            "Equals" => Ok(format!("{} = {}", path, quote(v))),
+            "DoesNotEqual" => Ok(format!("{} != {}", path, quote(v))),
+            "Contains" => Ok(format!(
+                "{} LIKE {}",
+                path,
+                quote(format!("%{}%", escape_like(v)).as_str())
+            )),
+            "DoesNotContain" => Ok(format!(
+                "{} NOT LIKE {}",
+                path,
+                quote(format!("%{}%", escape_like(v)).as_str())
+            )),
+            "StartsWith" => Ok(format!(
+                "{} LIKE {}",
+                path,
+                quote(format!("{}%", escape_like(v)).as_str())
+            )),
+            "EndsWith" => Ok(format!(
+                "{} LIKE {}",
+                path,
+                quote(format!("%{}", escape_like(v)).as_str())
+            )),
+            "FitsPattern" => Ok(format!("{} MATCHES {}", path, quote(v))),
+            _ => Err(format!("condition {} of {}", condition, path)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let operator = match value.apply_condition.as_deref() {
+        Some("ALL") => " AND ",
+        _ => " OR ",
+    };
+    match comparisons.len() {
+        1 => Ok(comparisons[0].clone()),
+        _ => Ok(format!("({})", comparisons.join(operator))),
+    }
+}
+
+/// The STIX 2.1 hash algorithm name of a CybOX one.
+fn hash_algorithm(v: &str) -> String {
+    match v.to_uppercase().replace('-', "").as_str() {
+        "MD5" => String::from("MD5"),
+        "SHA1" => String::from("SHA-1"),
+        "SHA224" => String::from("SHA-224"),
+        "SHA256" => String::from("SHA-256"),
+        "SHA384" => String::from("SHA-384"),
+        "SHA512" => String::from("SHA-512"),
+        "SSDEEP" => String::from("SSDEEP"),
+        v => String::from(v),
+    }
+}
+
+/// The SCO type of an address, by its category or else its value.
+fn address_type(category: Option<&str>, value: &str) -> Option<&'static str> {
+    match category {
+        Some("ipv4-addr") => Some("ipv4-addr"),
+        Some("ipv6-addr") => Some("ipv6-addr"),
+        Some("e-mail") => Some("email-addr"),
+        Some("mac") => Some("mac-addr"),
+        Some(_) => None,
+        None if value.contains('@') => Some("email-addr"),
+        None if value.contains(':') => Some("ipv6-addr"),
+        None => Some("ipv4-addr"),
+    }
+}
+
+struct Converter {
+    created: String,
+    ids: HashMap<String, String>,
+    /// The objects shared by the components, e.g. identities, by a key of their content.
+    shared: HashMap<String, String>,
+    objects: Vec<Object>,
+    unmapped: Vec<Unmapped>,
+    package_markings: Vec<String>,
+    package_source: Option<String>,
+}
+
+impl Converter {
+    fn new(package: &StixPackage) -> Converter {
+        Converter {
+            created: format_timestamp(&package.timestamp.unwrap_or_else(Utc::now)),
+            ids: HashMap::new(),
+            shared: HashMap::new(),
+            objects: Vec::<Object>::new(),
+            unmapped: Vec::<Unmapped>::new(),
+            package_markings: Vec::<String>::new(),
+            package_source: None,
+        }
+    }
+
+    fn report(&mut self, source: Option<&String>, message: &str) {
+        self.unmapped.push(Unmapped {
+            source: source.cloned(),
+            message: String::from(message),
+        });
+    }
+
+    /// The id of the STIX 2.1 object converted from a STIX 1.x component, keeping the UUID of
+    /// its id when it has one.
+    fn new_id(&mut self, typ: &str, source: Option<&String>) -> String {
+        lazy_static! {
+            static ref UUID: Regex = Regex::new(
+                "[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}$"
+            )
+            .unwrap();
+        }
+        let uuid = match source.and_then(|v| UUID.find(v)) {
+            Some(v) => v.as_str().to_lowercase(),
+            None => Uuid::new_v4().to_string(),
+        };
+        let id = format!("{}--{}", typ, uuid);
+        if let Some(source) = source {
+            self.ids.entry(source.clone()).or_insert(id.clone());
+        }
+        id
+    }
+
+    fn new_object(&self, typ: &str, id: &str, timestamp: Option<&DateTime<Utc>>) -> Object {
+        let mut object = Object::new(typ, id);
+        let created = match timestamp {
+            Some(v) => format_timestamp(v),
+            None => self.created.clone(),
+        };
+        object.created = Some(created.clone());
+        object.modified = Some(created);
+        object
+    }
+
+    /// Add an SDO with the markings and source of the package, and those of the component.
+    fn add_sdo(&mut self, mut object: Object, markings: &[String], created_by_ref: Option<String>) {
+        let mut marking_refs = self.package_markings.clone();
+        for marking in markings.iter() {
+            if !marking_refs.contains(marking) {
+                marking_refs.push(marking.clone());
+            }
+        }
+        if !marking_refs.is_empty() {
+            object
+                .properties
+                .insert(String::from("object_marking_refs"), json!(marking_refs));
+        }
+        if let Some(v) = created_by_ref.or(self.package_source.clone()) {
+            object
+                .properties
+                .insert(String::from("created_by_ref"), json!(v));
+        }
+        self.objects.push(object);
+    }
+
+    fn add_relationship(&mut self, source_ref: &str, relationship_type: &str, target_ref: &str) {
+        let id = self.new_id("relationship", None);
+        let mut object = self.new_object("relationship", id.as_str(), None);
+        object
+            .properties
+            .insert(String::from("relationship_type"), json!(relationship_type));
+        object
+            .properties
+            .insert(String::from("source_ref"), json!(source_ref));
+        object
+            .properties
+            .insert(String::from("target_ref"), json!(target_ref));
+        self.add_sdo(object, &[], None);
+    }
+
+    fn convert(&mut self, package: &StixPackage) {
+        if let Some(header) = &package.header {
+            self.package_markings = self.convert_markings(package.id.as_ref(), &header.handling);
+            self.package_source = header
+                .information_source
+                .as_ref()
+                .and_then(|v| self.convert_source(package.id.as_ref(), v));
+        }
+        let mut object_refs = Vec::<String>::new();
+        for ttp in package.ttps.iter() {
+            object_refs.extend(self.convert_ttp(ttp));
+        }
+        for exploit_target in package.exploit_targets.iter() {
+            object_refs.extend(self.convert_exploit_target(exploit_target));
+        }
+        for indicator in package.indicators.iter() {
+            object_refs.extend(self.convert_indicator(indicator));
+        }
+        for observable in package.observables.iter() {
+            object_refs.extend(self.convert_observable(observable));
+        }
+        for incident in package.incidents.iter() {
+            object_refs.extend(self.convert_incident(incident));
+        }
+        self.convert_relationships(package);
+        self.convert_header(package, object_refs);
+    }
+
+    /// The package becomes a report of the objects converted from it.
+    fn convert_header(&mut self, package: &StixPackage, object_refs: Vec<String>) {
+        let header = match &package.header {
+            Some(v) => v,
+            None => return,
+        };
+        if object_refs.is_empty() {
+            return;
+        }
+        let name = match header.title.as_ref().or(package.id.as_ref()) {
+            Some(v) => v.clone(),
+            None => String::from("STIX package"),
+        };
+        let id = self.new_id("report", package.id.as_ref());
+        let mut object = self.new_object("report", id.as_str(), package.timestamp.as_ref());
+        object.properties.insert(String::from("name"), json!(name));
+        if let Some(v) = header
+            .description
+            .as_ref()
+            .or(header.short_description.as_ref())
+        {
+            object
+                .properties
+                .insert(String::from("description"), json!(v));
+        }
+        let report_types: Vec<String> = header
+            .package_intents
+            .iter()
+            .map(|v| vocabulary_value(v))
+            .collect();
+        if !report_types.is_empty() {
+            object
+                .properties
+                .insert(String::from("report_types"), json!(report_types));
+        }
+        object
+            .properties
+            .insert(String::from("published"), json!(self.created));
+        object
+            .properties
+            .insert(String::from("object_refs"), json!(object_refs));
+        self.add_sdo(object, &[], None);
+    }
+
+    /// Convert markings to marking definitions, returning their ids.
+    fn convert_markings(&mut self, source: Option<&String>, markings: &[Marking]) -> Vec<String> {
+        let mut marking_refs = Vec::<String>::new();
+        for marking in markings.iter() {
+            let created_by_ref = marking
+                .information_source
+                .as_ref()
+                .and_then(|v| self.convert_source(source, v));
+            for structure in marking.marking_structures.iter() {
+                let statement = match structure {
+                    MarkingStructure::Tlp { color } => {
+                        self.convert_tlp(source, color, &mut marking_refs);
+                        continue;
+                    }
+                    MarkingStructure::Ais {
+                        proprietary,
+                        consent,
+                        tlp,
+                        cisa_proprietary,
+                    } => {
+                        if let Some(color) = tlp {
+                            self.convert_tlp(source, color, &mut marking_refs);
+                        }
+                        let mut statement = format!(
+                            "AIS: proprietary={} consent={}",
+                            proprietary,
+                            consent.as_deref().unwrap_or("NONE")
+                        );
+                        if let Some(v) = cisa_proprietary {
+                            statement.push_str(format!(" cisa_proprietary={}", v).as_str());
+                        }
+                        statement
+                    }
+                    MarkingStructure::Statement { statement } => statement.clone(),
+                    MarkingStructure::TermsOfUse { terms_of_use } => terms_of_use.clone(),
+                    MarkingStructure::Other { xsi_type } => {
+                        let message = format!(
+                            "marking structure {}",
+                            xsi_type.as_deref().unwrap_or("without xsi:type")
+                        );
+                        self.report(source, message.as_str());
+                        continue;
+                    }
+                };
+                let id = self.statement_marking(statement.as_str(), created_by_ref.as_ref());
+                if !marking_refs.contains(&id) {
+                    marking_refs.push(id);
+                }
+            }
+        }
+        marking_refs
+    }
+
+    fn convert_tlp(
+        &mut self,
+        source: Option<&String>,
+        color: &str,
+        marking_refs: &mut Vec<String>,
+    ) {
+        let id = match color.to_uppercase().as_str() {
+            "WHITE" | "CLEAR" => TLP_WHITE,
+            "GREEN" => TLP_GREEN,
+            "AMBER" => TLP_AMBER,
+            "RED" => TLP_RED,
+            _ => {
+                self.report(source, format!("TLP color {}", color).as_str());
+                return;
+            }
+        };
+        if !marking_refs.iter().any(|v| v == id) {
+            marking_refs.push(String::from(id));
+        }
+    }
+
+    /// A statement marking definition, shared by the objects with the same statement.
+    fn statement_marking(&mut self, statement: &str, created_by_ref: Option<&String>) -> String {
+        let key = format!("statement:{}", statement);
+        if let Some(id) = self.shared.get(&key) {
+            return id.clone();
+        }
+        let id = self.new_id("marking-definition", None);
+        let mut object = Object::new("marking-definition", id.as_str());
+        object.created = Some(self.created.clone());
+        object
+            .properties
+            .insert(String::from("definition_type"), json!("statement"));
+        object.properties.insert(
+            String::from("definition"),
+            json!({ "statement": statement }),
+        );
+        if let Some(v) = created_by_ref {
+            object
+                .properties
+                .insert(String::from("created_by_ref"), json!(v));
+        }
+        self.objects.push(object);
+        self.shared.insert(key, id.clone());
+        id
+    }
+
+    /// Convert the identity of an information source, returning the id of the identity.
+    fn convert_source(
+        &mut self,
+        source: Option<&String>,
+        information_source: &InformationSource,
+    ) -> Option<String> {
+        if !information_source.tools.is_empty() {
+            self.report(source, "tools of an information source");
+        }
+        information_source
+            .identity
+            .as_ref()
+            .and_then(|v| self.convert_identity(source, v))
+    }
+
+    /// Convert an identity, shared by the objects with the same source. CIQ addresses become
+    /// locations the identity is located at.
+    fn convert_identity(&mut self, source: Option<&String>, identity: &Identity) -> Option<String> {
+        if let Some(id) = identity.idref.as_ref().and_then(|v| self.ids.get(v)) {
+            return Some(id.clone());
+        }
+        let name = match identity.display_name() {
+            Some(v) => String::from(v),
+            None => {
+                self.report(source, "identity without name");
+                return None;
+            }
+        };
+        let key = format!("identity:{}", name);
+        if let Some(id) = self.shared.get(&key) {
+            return Some(id.clone());
+        }
+        let id = self.new_id("identity", identity.id.as_ref());
+        let mut object = self.new_object("identity", id.as_str(), None);
+        object.properties.insert(String::from("name"), json!(name));
+        let ciq = identity.ciq.as_ref();
+        let identity_class = match ciq {
+            Some(v) if v.organisation_names.is_empty() && !v.person_names.is_empty() => {
+                "individual"
+            }
+            Some(_) => "organization",
+            None => "unknown",
+        };
+        object
+            .properties
+            .insert(String::from("identity_class"), json!(identity_class));
+        if let Some(v) = ciq.and_then(|v| v.industry_type.as_ref()) {
+            object
+                .properties
+                .insert(String::from("sectors"), json!([vocabulary_value(v)]));
+        }
+        self.objects.push(object);
+        self.shared.insert(key, id.clone());
+        if let Some(ciq) = ciq {
+            let count = ciq.countries.len().max(ciq.administrative_areas.len());
+            for i in 0..count {
+                let location_id = self.new_id("location", None);
+                let mut location = self.new_object("location", location_id.as_str(), None);
+                if let Some(v) = ciq.countries.get(i) {
+                    location
+                        .properties
+                        .insert(String::from("country"), json!(v));
+                }
+                if let Some(v) = ciq.administrative_areas.get(i) {
+                    location
+                        .properties
+                        .insert(String::from("administrative_area"), json!(v));
+                }
+                self.objects.push(location);
+                self.add_relationship(id.as_str(), "located-at", location_id.as_str());
+            }
+        }
+        Some(id)
+    }
+
+    fn convert_kill_chain_phases(&self, kill_chain_phases: &[KillChainPhase]) -> Option<Value> {
+        let phases: Vec<Value> = kill_chain_phases
+            .iter()
+            .filter_map(|v| match (&v.kill_chain_name, &v.name) {
+                (Some(kill_chain_name), Some(name)) => Some(json!({
+                    "kill_chain_name": vocabulary_value(kill_chain_name),
+                    "phase_name": vocabulary_value(name),
+                })),
+                _ => None,
+            })
+            .collect();
+        match phases.is_empty() {
+            true => None,
+            false => Some(json!(phases)),
+        }
+    }
+
+    fn convert_indicator(&mut self, indicator: &Indicator) -> Vec<String> {
+        let source = indicator.id.as_ref();
+        if indicator.idref.is_some() {
+            return Vec::new();
+        }
+        let pattern = match (&indicator.observable, &indicator.composite_expression) {
+            (Some(observable), _) => self.observable_pattern(source, observable),
+            (None, Some(_)) => {
+                self.report(source, "composite indicator expression");
+                None
+            }
+            (None, None) => None,
+        };
+        let pattern = match pattern {
+            Some(v) => v,
+            None => {
+                self.report(
+                    source,
+                    "indicator without an observable to convert to a pattern",
+                );
+                return Vec::new();
+            }
+        };
+        let id = self.new_id("indicator", source);
+        let mut object = self.new_object("indicator", id.as_str(), indicator.timestamp.as_ref());
+        let properties = &mut object.properties;
+        if let Some(v) = &indicator.title {
+            properties.insert(String::from("name"), json!(v));
+        }
+        if let Some(v) = indicator
+            .description
+            .as_ref()
+            .or(indicator.short_description.as_ref())
+        {
+            properties.insert(String::from("description"), json!(v));
+        }
+        if !indicator.indicator_types.is_empty() {
+            let indicator_types: Vec<String> = indicator
+                .indicator_types
+                .iter()
+                .map(|v| vocabulary_value(v))
+                .collect();
+            properties.insert(String::from("indicator_types"), json!(indicator_types));
+        }
+        let pattern = match indicator.negate {
+            true => format!("NOT ({})", pattern),
+            false => pattern,
+        };
+        properties.insert(String::from("pattern"), json!(pattern));
+        properties.insert(String::from("pattern_type"), json!("stix"));
+        let valid_time = indicator.valid_time_positions.first();
+        let valid_from = match valid_time.and_then(|v| v.start_time.as_ref()) {
+            Some(v) => format_timestamp(v),
+            None => object.created.clone().unwrap_or_default(),
+        };
+        let properties = &mut object.properties;
+        properties.insert(String::from("valid_from"), json!(valid_from));
+        if let Some(v) = valid_time.and_then(|v| v.end_time.as_ref()) {
+            properties.insert(String::from("valid_until"), json!(format_timestamp(v)));
+        }
+        if let Some(v) = self.convert_kill_chain_phases(&indicator.kill_chain_phases) {
+            properties.insert(String::from("kill_chain_phases"), v);
+        }
+        if let Some(v) = indicator.confidence.as_ref().and_then(confidence) {
+            properties.insert(String::from("confidence"), json!(v));
+        }
+        if indicator.likely_impact.is_some() {
+            self.report(source, "likely impact of an indicator");
+        }
+        let created_by_ref = indicator
+            .producer
+            .as_ref()
+            .and_then(|v| self.convert_source(source, v));
+        let markings = self.convert_markings(source, &indicator.handling);
+        self.add_sdo(object, &markings, created_by_ref);
+        vec![id]
+    }
+
+    /// The pattern of an observable: an observation expression for an object, or the
+    /// expressions of a composition combined with its operator. Observables without objects,
+    /// e.g. those only holding a title, are left out.
+    fn observable_pattern(
+        &mut self,
+        source: Option<&String>,
+        observable: &Observable,
+    ) -> Option<String> {
+        if let Some(object) = &observable.object {
+            return self
+                .object_comparison(source, object)
+                .map(|v| format!("[{}]", v));
+        }
+        let composition = observable.composition.as_ref()?;
+        let patterns: Vec<String> = composition
+            .observables
+            .iter()
+            .filter_map(|v| self.observable_pattern(source, v))
+            .collect();
+        let operator = match composition.operator.to_uppercase().as_str() {
+            "AND" => " AND ",
+            _ => " OR ",
+        };
+        match patterns.len() {
+            0 => None,
+            1 => Some(patterns[0].clone()),
+            _ => Some(format!("({})", patterns.join(operator))),
+        }
+    }
+
+    /// The comparison expression of the properties of an object, reporting those it cannot map.
+    fn object_comparison(
+        &mut self,
+        source: Option<&String>,
+        object: &CyboxObject,
+    ) -> Option<String> {
+        let properties = object.properties.as_ref()?;
+        let mut comparisons = Vec::<Result<String, String>>::new();
+        match properties {
+            ObjectProperties::Address {
+                category,
+                value: Some(value),
+            } => match address_type(category.as_deref(), value.value.as_str()) {
+                Some(typ) => comparisons.push(comparison(format!("{}:value", typ).as_str(), value)),
+                None => comparisons.push(Err(format!(
+                    "address category {}",
+                    category.as_deref().unwrap_or_default()
+                ))),
+            },
+            ObjectProperties::DomainName {
+                value: Some(value), ..
+            } => comparisons.push(comparison("domain-name:value", value)),
+            ObjectProperties::Uri {
+                value: Some(value), ..
+            } => comparisons.push(comparison("url:value", value)),
+            ObjectProperties::File {
+                file_name,
+                size,
+                hashes,
+            } => {
+                if let Some(v) = file_name {
+                    comparisons.push(comparison("file:name", v));
+                }
+                if let Some(v) = size {
+                    comparisons.push(match v.value.trim().parse::<u64>() {
+                        Ok(size) => Ok(format!("file:size = {}", size)),
+                        Err(_) => Err(String::from("file size")),
+                    });
+                }
+                for hash in hashes.iter() {
+                    let path = format!(
+                        "file:hashes.{}",
+                        quote(hash_algorithm(&hash.hash_type).as_str())
+                    );
+                    comparisons.push(comparison(path.as_str(), &hash.value));
+                }
+            }
+            ObjectProperties::EmailMessage {
+                header,
+                email_server,
+            } => {
+                if let Some(header) = header {
+                    comparisons.extend(email_comparisons(header));
+                }
+                if email_server.is_some() {
+                    comparisons.push(Err(String::from("email server")));
+                }
+            }
+            ObjectProperties::Other { xsi_type } => comparisons.push(Err(format!(
+                "object type {}",
+                xsi_type.as_deref().unwrap_or("without xsi:type")
+            ))),
+            _ => (),
+        }
+        let mut mapped = Vec::<String>::new();
+        for comparison in comparisons.into_iter() {
+            match comparison {
+                Ok(v) => mapped.push(v),
+                Err(message) => self.report(source, message.as_str()),
+            }
+        }
+        match mapped.is_empty() {
+            true => None,
+            false => Some(mapped.join(" AND ")),
+        }
+    }
+
+    /// Convert the observables of the package to SCOs, returning their ids.
+    fn convert_observable(&mut self, observable: &Observable) -> Vec<String> {
+        let mut ids = Vec::<String>::new();
+        for object in observable.objects() {
+            let source = object.id.as_ref().or(observable.id.as_ref());
+            match object.properties.as_ref().and_then(|v| self.sco(source, v)) {
+                Some(sco) => {
+                    ids.push(sco.id.clone());
+                    self.objects.push(sco);
+                }
+                None => self.report(source, "observable without an object to convert to an SCO"),
+            }
+        }
+        ids
+    }
+
+    fn sco(&mut self, source: Option<&String>, properties: &ObjectProperties) -> Option<Object> {
+        let (typ, mut values) = match properties {
+            ObjectProperties::Address {
+                category,
+                value: Some(value),
+            } => (
+                address_type(category.as_deref(), value.value.as_str())?,
+                vec![("value", json!(value.value))],
+            ),
+            ObjectProperties::DomainName {
+                value: Some(value), ..
+            } => ("domain-name", vec![("value", json!(value.value))]),
+            ObjectProperties::Uri {
+                value: Some(value), ..
+            } => ("url", vec![("value", json!(value.value))]),
+            ObjectProperties::File {
+                file_name, hashes, ..
+            } => {
+                let mut values = Vec::new();
+                if let Some(v) = file_name {
+                    values.push(("name", json!(v.value)));
+                }
+                if !hashes.is_empty() {
+                    let hashes: serde_json::Map<String, Value> = hashes
+                        .iter()
+                        .map(|v| (hash_algorithm(&v.hash_type), json!(v.value.value)))
+                        .collect();
+                    values.push(("hashes", json!(hashes)));
+                }
+                ("file", values)
+            }
+            ObjectProperties::EmailMessage {
+                header: Some(header),
+                ..
+            } => {
+                let mut values = vec![("is_multipart", json!(false))];
+                if let Some(v) = &header.subject {
+                    values.push(("subject", json!(v)));
+                }
+                ("email-message", values)
+            }
+            _ => return None,
+        };
+        if values.is_empty() {
+            return None;
+        }
+        let id = self.new_id(typ, source);
+        let mut object = Object::new(typ, id.as_str());
+        for (name, value) in values.drain(..) {
+            object.properties.insert(String::from(name), value);
+        }
+        Some(object)
+    }
+
+    fn convert_ttp(&mut self, ttp: &Ttp) -> Vec<String> {
+        let source = ttp.id.as_ref();
+        if ttp.idref.is_some() {
+            return Vec::new();
+        }
+        let markings = self.convert_markings(source, &ttp.handling);
+        let kill_chain_phases = self.convert_kill_chain_phases(&ttp.kill_chain_phases);
+        let mut ids = Vec::<String>::new();
+        for malware in ttp.malware.iter() {
+            let id = self.new_id("malware", if ids.is_empty() { source } else { None });
+            let mut object = self.new_object("malware", id.as_str(), ttp.timestamp.as_ref());
+            let name = malware
+                .names
+                .first()
+                .or(malware.title.as_ref())
+                .or(ttp.title.as_ref());
+            if let Some(v) = name {
+                object.properties.insert(String::from("name"), json!(v));
+            }
+            if let Some(v) = malware.description.as_ref().or(ttp.description.as_ref()) {
+                object
+                    .properties
+                    .insert(String::from("description"), json!(v));
+            }
+            if !malware.malware_types.is_empty() {
+                let malware_types: Vec<String> = malware
+                    .malware_types
+                    .iter()
+                    .map(|v| vocabulary_value(v))
+                    .collect();
+                object
+                    .properties
+                    .insert(String::from("malware_types"), json!(malware_types));
+            }
+            object
+                .properties
+                .insert(String::from("is_family"), json!(false));
+            if let Some(v) = &kill_chain_phases {
+                object
+                    .properties
+                    .insert(String::from("kill_chain_phases"), v.clone());
+            }
+            ids.push(id);
+            self.add_sdo(object, &markings, None);
+        }
+        for attack_pattern in ttp.attack_patterns.iter() {
+            let id = self.new_id("attack-pattern", if ids.is_empty() { source } else { None });
+            let mut object = self.new_object("attack-pattern", id.as_str(), ttp.timestamp.as_ref());
+            let name = attack_pattern
+                .title
+                .as_ref()
+                .or(ttp.title.as_ref())
+                .or(attack_pattern.capec_id.as_ref());
+            if let Some(v) = name {
+                object.properties.insert(String::from("name"), json!(v));
+            }
+            if let Some(v) = &attack_pattern.description {
+                object
+                    .properties
+                    .insert(String::from("description"), json!(v));
+            }
+            if let Some(v) = &attack_pattern.capec_id {
+                object.properties.insert(
+                    String::from("external_references"),
+                    json!([{ "source_name": "capec", "external_id": v }]),
+                );
+            }
+            if let Some(v) = &kill_chain_phases {
+                object
+                    .properties
+                    .insert(String::from("kill_chain_phases"), v.clone());
+            }
+            ids.push(id);
+            self.add_sdo(object, &markings, None);
+        }
+        if ids.is_empty() {
+            self.report(source, "TTP without malware or attack patterns");
+        }
+        if !ttp.intended_effects.is_empty() {
+            self.report(source, "intended effects of a TTP");
+        }
+        ids
+    }
+
+    fn convert_exploit_target(&mut self, exploit_target: &ExploitTarget) -> Vec<String> {
+        let source = exploit_target.id.as_ref();
+        if exploit_target.idref.is_some() {
+            return Vec::new();
+        }
+        let markings = self.convert_markings(source, &exploit_target.handling);
+        let mut ids = Vec::<String>::new();
+        for vulnerability in exploit_target.vulnerabilities.iter() {
+            let name = match vulnerability
+                .cve_id
+                .as_ref()
+                .or(vulnerability.title.as_ref())
+                .or(exploit_target.title.as_ref())
+            {
+                Some(v) => v.clone(),
+                None => {
+                    self.report(source, "vulnerability without CVE id or title");
+                    continue;
+                }
+            };
+            let id = self.new_id("vulnerability", if ids.is_empty() { source } else { None });
+            let mut object = self.new_object(
+                "vulnerability",
+                id.as_str(),
+                exploit_target.timestamp.as_ref(),
+            );
+            object.properties.insert(String::from("name"), json!(name));
+            if let Some(v) = vulnerability
+                .description
+                .as_ref()
+                .or(exploit_target.description.as_ref())
+            {
+                object
+                    .properties
+                    .insert(String::from("description"), json!(v));
+            }
+            if let Some(v) = &vulnerability.cve_id {
+                object.properties.insert(
+                    String::from("external_references"),
+                    json!([{ "source_name": "cve", "external_id": v }]),
+                );
+            }
+            if vulnerability.cvss_base_score.is_some() {
+                self.report(source, "CVSS score of a vulnerability");
+            }
+            ids.push(id);
+            self.add_sdo(object, &markings, None);
+        }
+        for weakness in exploit_target.weaknesses.iter() {
+            self.report(source, format!("weakness {}", weakness).as_str());
+        }
+        ids
+    }
+
+    fn convert_incident(&mut self, incident: &Incident) -> Vec<String> {
+        let source = incident.id.as_ref();
+        if incident.idref.is_some() {
+            return Vec::new();
+        }
+        let id = self.new_id("incident", source);
+        let mut object = self.new_object("incident", id.as_str(), incident.timestamp.as_ref());
+        let name = match incident.title.as_ref().or(incident.id.as_ref()) {
+            Some(v) => v.clone(),
+            None => String::from("Incident"),
+        };
+        object.properties.insert(String::from("name"), json!(name));
+        if let Some(v) = incident
+            .description
+            .as_ref()
+            .or(incident.short_description.as_ref())
+        {
+            object
+                .properties
+                .insert(String::from("description"), json!(v));
+        }
+        if let Some(v) = incident.confidence.as_ref().and_then(confidence) {
+            object
+                .properties
+                .insert(String::from("confidence"), json!(v));
+        }
+        if !incident.categories.is_empty() {
+            self.report(source, "categories of an incident");
+        }
+        if !incident.victims.is_empty() {
+            self.report(source, "victims of an incident");
+        }
+        let created_by_ref = incident
+            .reporter
+            .as_ref()
+            .and_then(|v| self.convert_source(source, v));
+        let markings = self.convert_markings(source, &incident.handling);
+        self.add_sdo(object, &markings, created_by_ref);
+        vec![id]
+    }
+
+    /// Convert the relationships between components once they all have ids. References to
+    /// components that were not converted are reported.
+    fn convert_relationships(&mut self, package: &StixPackage) {
+        let mut relationships = Vec::<(Option<String>, &str, Option<String>)>::new();
+        for indicator in package.indicators.iter() {
+            for ttp in indicator.indicated_ttps.iter() {
+                relationships.push((indicator.id.clone(), "indicates", ttp.idref.clone()));
+            }
+        }
+        for ttp in package.ttps.iter() {
+            for exploit_target in ttp.exploit_targets.iter() {
+                relationships.push((ttp.id.clone(), "targets", exploit_target.idref.clone()));
+            }
+        }
+        for incident in package.incidents.iter() {
+            for indicator in incident.related_indicators.iter() {
+                relationships.push((incident.id.clone(), "related-to", indicator.idref.clone()));
+            }
+            for observable in incident.related_observables.iter() {
+                relationships.push((incident.id.clone(), "related-to", observable.idref.clone()));
+            }
+            for ttp in incident.leveraged_ttps.iter() {
+                relationships.push((incident.id.clone(), "uses", ttp.idref.clone()));
+            }
+        }
+        for (source, relationship_type, target) in relationships.into_iter() {
+            let source_ref = source.as_ref().and_then(|v| self.ids.get(v)).cloned();
+            let target_ref = target.as_ref().and_then(|v| self.ids.get(v)).cloned();
+            match (source_ref, target_ref) {
+                (Some(source_ref), Some(target_ref)) => self.add_relationship(
+                    source_ref.as_str(),
+                    relationship_type,
+                    target_ref.as_str(),
+                ),
+                _ => {
+                    let message = format!(
+                        "{} relationship to {}",
+                        relationship_type,
+                        target.as_deref().unwrap_or("a component without id")
+                    );
+                    self.report(source.as_ref(), message.as_str());
+                }
+            }
+        }
+    }
+}
+
+fn email_comparisons(header: &EmailHeader) -> Vec<Result<String, String>> {
+    let mut comparisons = Vec::<Result<String, String>>::new();
+    let value = |v: &String| PropertyValue {
+        value: v.clone(),
+        condition: None,
+        apply_condition: None,
+    };
+    let fields = [
+        ("email-message:from_ref.value", &header.from),
+        ("email-message:sender_ref.value", &header.sender),
+        ("email-message:subject", &header.subject),
+        ("email-message:message_id", &header.message_id),
+        (
+            "email-message:additional_header_fields.'Reply-To'",
+            &header.reply_to,
+        ),
+        (
+            "email-message:additional_header_fields.'X-Mailer'",
+            &header.x_mailer,
+        ),
+    ];
+    for (path, field) in fields.iter() {
+        if let Some(v) = field {
+            comparisons.push(comparison(path, &value(v)));
+        }
+    }
+    for to in header.to.iter() {
+        comparisons.push(comparison("email-message:to_refs[*].value", &value(to)));
+    }
+    if header.date.is_some() {
+        comparisons.push(Err(String::from("email date")));
+    }
+    if header.x_originating_ip.is_some() {
+        comparisons.push(Err(String::from("email X-Originating-IP")));
+    }
+    comparisons
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, env, fs};
+
+    use serde_json::json;
+
+    use super::{convert_package, Conversion, TLP_AMBER, TLP_GREEN, TLP_RED, TLP_WHITE};
+    use crate::{stix1::package::StixPackage, taxii::poll::parse_poll_response};
+
+    fn convert_sample() -> Vec<Conversion> {
+        let path = format!(
+            "{}/test/sample-poll-response.xml",
+            env::var("CARGO_MANIFEST_DIR").unwrap()
+        );
+        let doc = fs::read(path).unwrap();
+        parse_poll_response(&doc)
+            .unwrap()
+            .content_blocks
+            .iter()
+            .filter(|v| v.content.starts_with('<'))
+            .map(|v| convert_package(&StixPackage::parse(v.content.as_bytes()).unwrap()))
+            .collect()
+    }
+
+    fn property<'a>(conversion: &'a Conversion, id: &str, name: &str) -> &'a serde_json::Value {
+        let object = conversion.objects.iter().find(|v| v.id == id).unwrap();
+        &object.properties[name]
+    }
+
+    // The properties STIX 2.1 requires, and references to objects of the bundle.
+    fn check_conformance(conversion: &Conversion) {
+        let ids: HashSet<&str> = conversion.objects.iter().map(|v| v.id.as_str()).collect();
+        assert_eq!(conversion.objects.len(), ids.len());
+        for object in conversion.objects.iter() {
+            assert!(object.id.starts_with(format!("{}--", object.typ).as_str()));
+            assert_eq!(Some("2.1"), object.spec_version.as_deref());
+            let required: &[&str] = match object.typ.as_str() {
+                "indicator" => &["pattern", "pattern_type", "valid_from"],
+                "identity" | "malware" | "attack-pattern" | "vulnerability" | "incident" => {
+                    &["name"]
+                }
+                "relationship" => &["relationship_type", "source_ref", "target_ref"],
+                "report" => &["name", "published", "object_refs"],
+                "marking-definition" => &["definition_type", "definition"],
+                _ => &[],
+            };
+            for name in required.iter() {
+                assert!(
+                    object.properties.contains_key(*name),
+                    "{} without {}",
+                    object.id,
+                    name
+                );
+            }
+            let mut refs = Vec::<&str>::new();
+            for name in ["source_ref", "target_ref", "created_by_ref"] {
+                if let Some(v) = object.properties.get(name).and_then(|v| v.as_str()) {
+                    refs.push(v);
+                }
+            }
+            for name in ["object_refs", "object_marking_refs"] {
+                if let Some(v) = object.properties.get(name).and_then(|v| v.as_array()) {
+                    refs.extend(v.iter().filter_map(|v| v.as_str()));
+                }
+            }
+            for v in refs {
+                assert!(
+                    ids.contains(v) || [TLP_WHITE, TLP_GREEN, TLP_AMBER, TLP_RED].contains(&v),
+                    "{} references {}",
+                    object.id,
+                    v
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_convert_sample_packages() {
+        let conversions = convert_sample();
+        assert_eq!(9, conversions.len());
+        for conversion in conversions.iter() {
+            check_conformance(conversion);
+            let bundle = conversion.bundle();
+            assert_eq!("bundle", bundle["type"]);
+            assert_eq!(
+                conversion.objects.len(),
+                bundle["objects"].as_array().unwrap().len()
+            );
+        }
+        let indicators: usize = conversions
+            .iter()
+            .map(|v| v.objects_of_type("indicator").len())
+            .sum();
+        assert_eq!(381, indicators);
+
+        // AIS marking with TLP:WHITE and a CIQ identity
+        let conversion = &conversions[0];
+        let id = "indicator--720cc55d-9120-4eae-91b3-fdde9b2995ea";
+        assert_eq!(
+            json!("[ipv4-addr:value = '104.12.241.179']"),
+            *property(conversion, id, "pattern")
+        );
+        assert_eq!(
+            json!(["ip-watchlist"]),
+            *property(conversion, id, "indicator_types")
+        );
+        assert_eq!(
+            json!("2016-12-09T16:58:46.974Z"),
+            *property(conversion, id, "valid_from")
+        );
+        let marking_refs = property(conversion, id, "object_marking_refs")
+            .as_array()
+            .unwrap();
+        assert_eq!(json!(TLP_WHITE), marking_refs[0]);
+        let marking = marking_refs[1].as_str().unwrap();
+        assert_eq!(
+            json!({"statement": "AIS: proprietary=false consent=EVERYONE cisa_proprietary=false"}),
+            *property(conversion, marking, "definition")
+        );
+        let identity = &conversion.objects_of_type("identity")[0];
+        assert_eq!(
+            json!(identity.id),
+            *property(conversion, marking, "created_by_ref")
+        );
+        assert_eq!(json!("JJohnson Industries"), identity.properties["name"]);
+        assert_eq!(json!("organization"), identity.properties["identity_class"]);
+        assert_eq!(
+            json!(["information-technology"]),
+            identity.properties["sectors"]
+        );
+        let location = &conversion.objects_of_type("location")[0];
+        assert_eq!(json!("US"), location.properties["country"]);
+        assert_eq!(json!("US-DC"), location.properties["administrative_area"]);
+    }
+
+    #[test]
+    fn test_convert_incident_package() {
+        let conversions = convert_sample();
+        let conversion = &conversions[1];
+        let email = "indicator--f4f192b1-7dba-49d5-b790-ad00dd63b2a2";
+        let pattern = property(conversion, email, "pattern").as_str().unwrap();
+        assert!(pattern.starts_with(
+            "[email-message:from_ref.value = 'jessantt@gmail.com' AND \
+            email-message:sender_ref.value = 'badguy@badguys.com' AND \
+            email-message:subject = 'The Silent Treatment - 10/10/2014'"
+        ));
+        assert_eq!(json!(0), *property(conversion, email, "confidence"));
+        let source = Some(String::from(
+            "INTINC-Dev:indicator-f4f192b1-7dba-49d5-b790-ad00dd63b2a2",
+        ));
+        let unmapped: Vec<&str> = conversion
+            .unmapped
+            .iter()
+            .filter(|v| v.source == source)
+            .map(|v| v.message.as_str())
+            .collect();
+        assert_eq!(
+            vec!["email date", "email X-Originating-IP", "email server"],
+            unmapped
+        );
+        assert!(conversion
+            .unmapped
+            .iter()
+            .any(|v| v.message == "likely impact of an indicator"));
+
+        let identities = conversion.objects_of_type("identity");
+        assert_eq!(1, identities.len());
+        assert_eq!(json!("Wapack Labs"), identities[0].properties["name"]);
+        assert_eq!(
+            json!(identities[0].id),
+            *property(conversion, email, "created_by_ref")
+        );
+
+        let incident = "incident--7db51424-5eba-421a-8106-0d04c1fcb142";
+        assert_eq!(
+            json!("Aviation One Spear-phising Attack"),
+            *property(conversion, incident, "name")
+        );
+        let related: Vec<_> = conversion
+            .objects_of_type("relationship")
+            .into_iter()
+            .filter(|v| v.properties["source_ref"] == json!(incident))
+            .collect();
+        assert_eq!(5, related.len());
+        assert_eq!(
+            json!("related-to"),
+            related[0].properties["relationship_type"]
+        );
+        assert_eq!(
+            json!("indicator--2831a42f-eca1-46fe-acd9-41d59b792e05"),
+            related[0].properties["target_ref"]
+        );
+
+        let report = &conversion.objects_of_type("report")[0];
+        assert_eq!(
+            json!("CRITs Generated STIX Package"),
+            report.properties["name"]
+        );
+        assert_eq!(
+            json!(["collective-threat-intelligence"]),
+            report.properties["report_types"]
+        );
+        assert_eq!(
+            6,
+            report.properties["object_refs"].as_array().unwrap().len()
+        );
+    }
+
+    #[test]
+    fn test_convert_patterns() {
+        let conversions = convert_sample();
+        let pattern = property(
+            &conversions[2],
+            "indicator--db4a6ffe-61f0-488d-85a1-20bd5e360f37",
+            "pattern",
+        );
+        assert_eq!(
+            json!(
+                "[(url:value = 'http://example.com/foo/malicious1.html' OR \
+                url:value = 'http://example.com/foo/malicious2.html' OR \
+                url:value = 'http://example.com/foo/malicious3.html')]"
+            ),
+            *pattern
+        );
+        let patterns: Vec<&str> = conversions
+            .iter()
+            .flat_map(|v| v.objects_of_type("indicator"))
+            .filter_map(|v| v.properties["pattern"].as_str())
+            .collect();
+        assert!(patterns.contains(&"[domain-name:value = 'really.badguys.com']"));
+        assert!(patterns.contains(
+            &"[file:hashes.'SHA-256' = 'a685cf4dca6a58213e67d041bba637dca9cb3ea6bb9ad3eae3ba85229118bce0']"
+        ));
+    }
+
+    #[test]
+    fn test_convert_components() {
+        let doc = r#"<stix:STIX_Package xmlns:stix="http://stix.mitre.org/stix-1"
+            xmlns:stixCommon="http://stix.mitre.org/common-1"
+            xmlns:indicator="http://stix.mitre.org/Indicator-2"
+            xmlns:ttp="http://stix.mitre.org/TTP-1" xmlns:et="http://stix.mitre.org/ExploitTarget-1"
+            xmlns:cybox="http://cybox.mitre.org/cybox-2"
+            xmlns:AddressObj="http://cybox.mitre.org/objects#AddressObject-2"
+            xmlns:marking="http://data-marking.mitre.org/Marking-1"
+            xmlns:tlpMarking="http://data-marking.mitre.org/extensions/MarkingStructure#TLP-1"
+            xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"
+            id="example:Package-1" version="1.2" timestamp="2015-03-01T10:00:00Z">
+            <stix:Observables cybox_major_version="2" cybox_minor_version="1">
+                <cybox:Observable id="example:Observable-5e5d5a0c-6c1e-4d1c-a7b8-5f0b8f1e2a11">
+                    <cybox:Object><cybox:Properties xsi:type="AddressObj:AddressObjectType" category="ipv4-addr">
+                        <AddressObj:Address_Value>10.0.0.1</AddressObj:Address_Value>
+                    </cybox:Properties></cybox:Object>
+                </cybox:Observable>
+            </stix:Observables>
+            <stix:Indicators>
+                <stix:Indicator id="example:indicator-8b0c0b67-4d4f-4b83-8a8c-2d6a1b7e3c01" negate="true">
+                    <indicator:Observable><cybox:Object>
+                        <cybox:Properties xsi:type="AddressObj:AddressObjectType">
+                            <AddressObj:Address_Value condition="StartsWith">10.</AddressObj:Address_Value>
+                        </cybox:Properties>
+                    </cybox:Object></indicator:Observable>
+                    <indicator:Indicated_TTP><stixCommon:TTP idref="example:ttp-0b4b7f5e-5c9c-4d8e-9f6a-7b1c2d3e4f50"/></indicator:Indicated_TTP>
+                    <indicator:Indicated_TTP><stixCommon:TTP idref="example:ttp-unknown"/></indicator:Indicated_TTP>
+                </stix:Indicator>
+                <stix:Indicator id="example:indicator-8b0c0b67-4d4f-4b83-8a8c-2d6a1b7e3c02">
+                    <indicator:Observable><cybox:Object>
+                        <cybox:Properties xsi:type="AddressObj:AddressObjectType">
+                            <AddressObj:Address_Value condition="Contains">100%_</AddressObj:Address_Value>
+                        </cybox:Properties>
+                    </cybox:Object></indicator:Observable>
+                </stix:Indicator>
+            </stix:Indicators>
+            <stix:TTPs>
+                <stix:TTP id="example:ttp-0b4b7f5e-5c9c-4d8e-9f6a-7b1c2d3e4f50">
+                    <ttp:Title>Poison Ivy</ttp:Title>
+                    <ttp:Behavior><ttp:Malware><ttp:Malware_Instance>
+                        <ttp:Type>Remote Access Trojan</ttp:Type><ttp:Name>Poison Ivy</ttp:Name>
+                    </ttp:Malware_Instance></ttp:Malware></ttp:Behavior>
+                    <ttp:Handling><marking:Marking>
+                        <marking:Marking_Structure xsi:type="tlpMarking:TLPMarkingStructureType" color="AMBER"/>
+                    </marking:Marking></ttp:Handling>
+                </stix:TTP>
+            </stix:TTPs>
+            <stix:Exploit_Targets>
+                <stixCommon:Exploit_Target id="example:et-1">
+                    <et:Vulnerability><et:CVE_ID>CVE-2011-2462</et:CVE_ID></et:Vulnerability>
+                    <et:Weakness><et:CWE_ID>CWE-119</et:CWE_ID></et:Weakness>
+                </stixCommon:Exploit_Target>
+            </stix:Exploit_Targets>
+        </stix:STIX_Package>"#;
+        let conversion = convert_package(&StixPackage::parse(doc.as_bytes()).unwrap());
+        check_conformance(&conversion);
+
+        let sco = &conversion.objects_of_type("ipv4-addr")[0];
+        assert_eq!("ipv4-addr--5e5d5a0c-6c1e-4d1c-a7b8-5f0b8f1e2a11", sco.id);
+        assert_eq!(json!("10.0.0.1"), sco.properties["value"]);
+
+        let indicator = "indicator--8b0c0b67-4d4f-4b83-8a8c-2d6a1b7e3c01";
+        assert_eq!(
+            json!("NOT ([ipv4-addr:value LIKE '10.%'])"),
+            *property(&conversion, indicator, "pattern")
+        );
+        assert_eq!(
+            json!(r"[ipv4-addr:value LIKE '%100\\%\\_%']"),
+            *property(
+                &conversion,
+                "indicator--8b0c0b67-4d4f-4b83-8a8c-2d6a1b7e3c02",
+                "pattern"
+            )
+        );
+        let malware = "malware--0b4b7f5e-5c9c-4d8e-9f6a-7b1c2d3e4f50";
+        assert_eq!(json!("Poison Ivy"), *property(&conversion, malware, "name"));
+        assert_eq!(
+            json!(["remote-access-trojan"]),
+            *property(&conversion, malware, "malware_types")
+        );
+        assert_eq!(
+            json!([TLP_AMBER]),
+            *property(&conversion, malware, "object_marking_refs")
+        );
+        let relationship = &conversion.objects_of_type("relationship")[0];
+        assert_eq!(
+            json!("indicates"),
+            relationship.properties["relationship_type"]
+        );
+        assert_eq!(json!(indicator), relationship.properties["source_ref"]);
+        assert_eq!(json!(malware), relationship.properties["target_ref"]);
+
+        let vulnerability = &conversion.objects_of_type("vulnerability")[0];
+        assert_eq!(json!("CVE-2011-2462"), vulnerability.properties["name"]);
+        assert_eq!(
+            json!([{"source_name": "cve", "external_id": "CVE-2011-2462"}]),
+            vulnerability.properties["external_references"]
+        );
+
+        let unmapped: Vec<&str> = conversion
+            .unmapped
+            .iter()
+            .map(|v| v.message.as_str())
+            .collect();
+        assert_eq!(
+            vec![
+                "weakness CWE-119",
+                "indicates relationship to example:ttp-unknown"
+            ],
+            unmapped
+        );
+    }
+}
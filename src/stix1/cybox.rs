@@ -0,0 +1,243 @@
+use crate::taxii::{
+    errors::TaxiiError,
+    message::{Element, FromElement},
+};
+
+use super::common::{read_all, read_optional, xsi_type};
+
+/// CybOX lists several values in one property, e.g. the URIs of a watchlist, with this
+/// delimiter.
+pub static LIST_DELIMITER: &str = "##comma##";
+
+/// The value of an object property, with the condition of a pattern, e.g. `Equals`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PropertyValue {
+    pub value: String,
+    pub condition: Option<String>,
+    pub apply_condition: Option<String>,
+}
+
+impl PropertyValue {
+    /// The values of the property, which lists several when it has an apply_condition.
+    pub fn values(&self) -> Vec<&str> {
+        match self.apply_condition {
+            Some(_) => self.value.split(LIST_DELIMITER).collect(),
+            None => vec![self.value.as_str()],
+        }
+    }
+}
+
+impl FromElement for PropertyValue {
+    fn from_element(element: &Element) -> Result<PropertyValue, TaxiiError> {
+        Ok(PropertyValue {
+            value: element.text(),
+            condition: element.attr("condition").map(String::from),
+            apply_condition: element.attr("apply_condition").map(String::from),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hash {
+    /// The hash algorithm, e.g. `SHA256`.
+    pub hash_type: String,
+    pub value: PropertyValue,
+}
+
+impl FromElement for Hash {
+    fn from_element(element: &Element) -> Result<Hash, TaxiiError> {
+        element.expect("Hash")?;
+        let value = match element.element("Simple_Hash_Value") {
+            Some(v) => PropertyValue::from_element(v)?,
+            None => {
+                return Err(TaxiiError::Protocol(
+                    "missing Simple_Hash_Value".to_string(),
+                ))
+            }
+        };
+        Ok(Hash {
+            hash_type: element.element_text("Type").unwrap_or_default(),
+            value,
+        })
+    }
+}
+
+/// The header fields of an email message. Addresses are given by their values.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EmailHeader {
+    pub from: Option<String>,
+    pub sender: Option<String>,
+    pub reply_to: Option<String>,
+    pub to: Vec<String>,
+    pub subject: Option<String>,
+    pub date: Option<String>,
+    pub message_id: Option<String>,
+    pub x_mailer: Option<String>,
+    pub x_originating_ip: Option<String>,
+}
+
+fn address_value(element: &Element) -> Option<String> {
+    element.element_text("Address_Value")
+}
+
+impl FromElement for EmailHeader {
+    fn from_element(element: &Element) -> Result<EmailHeader, TaxiiError> {
+        element.expect("Header")?;
+        let address = |local_name: &str| element.element(local_name).and_then(address_value);
+        Ok(EmailHeader {
+            from: address("From"),
+            sender: address("Sender"),
+            reply_to: address("Reply_To"),
+            to: element
+                .elements_named("To")
+                .flat_map(|e| e.elements_named("Recipient"))
+                .filter_map(address_value)
+                .collect(),
+            subject: element.element_text("Subject"),
+            date: element.element_text("Date"),
+            message_id: element.element_text("Message_ID"),
+            x_mailer: element.element_text("X_Mailer"),
+            x_originating_ip: address("X_Originating_IP"),
+        })
+    }
+}
+
+/// The properties of a CybOX object, by the object type given in their xsi:type.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ObjectProperties {
+    Address {
+        /// The kind of address, e.g. `ipv4-addr` or `e-mail`.
+        category: Option<String>,
+        value: Option<PropertyValue>,
+    },
+    DomainName {
+        domain_type: Option<String>,
+        value: Option<PropertyValue>,
+    },
+    Uri {
+        uri_type: Option<String>,
+        value: Option<PropertyValue>,
+    },
+    File {
+        file_name: Option<PropertyValue>,
+        size: Option<PropertyValue>,
+        hashes: Vec<Hash>,
+    },
+    EmailMessage {
+        header: Option<EmailHeader>,
+        email_server: Option<String>,
+    },
+    /// Properties of another object type, with their xsi:type.
+    Other { xsi_type: Option<String> },
+}
+
+impl FromElement for ObjectProperties {
+    fn from_element(element: &Element) -> Result<ObjectProperties, TaxiiError> {
+        element.expect("Properties")?;
+        let properties = match xsi_type(element) {
+            Some("AddressObjectType") => ObjectProperties::Address {
+                category: element.attr("category").map(String::from),
+                value: read_optional(element, "Address_Value")?,
+            },
+            Some("DomainNameObjectType") => ObjectProperties::DomainName {
+                domain_type: element.attr("type").map(String::from),
+                value: read_optional(element, "Value")?,
+            },
+            Some("URIObjectType") => ObjectProperties::Uri {
+                uri_type: element.attr("type").map(String::from),
+                value: read_optional(element, "Value")?,
+            },
+            Some("FileObjectType") => ObjectProperties::File {
+                file_name: read_optional(element, "File_Name")?,
+                size: read_optional(element, "Size_In_Bytes")?,
+                hashes: match element.element("Hashes") {
+                    Some(v) => read_all(v, "Hash")?,
+                    None => Vec::<Hash>::new(),
+                },
+            },
+            Some("EmailMessageObjectType") => ObjectProperties::EmailMessage {
+                header: read_optional(element, "Header")?,
+                email_server: element.element_text("Email_Server"),
+            },
+            v => ObjectProperties::Other {
+                xsi_type: v.map(String::from),
+            },
+        };
+        Ok(properties)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Object {
+    pub id: Option<String>,
+    pub idref: Option<String>,
+    pub properties: Option<ObjectProperties>,
+}
+
+impl FromElement for Object {
+    fn from_element(element: &Element) -> Result<Object, TaxiiError> {
+        element.expect("Object")?;
+        Ok(Object {
+            id: element.attr("id").map(String::from),
+            idref: element.attr("idref").map(String::from),
+            properties: read_optional(element, "Properties")?,
+        })
+    }
+}
+
+/// Observables combined with an operator, `AND` or `OR`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObservableComposition {
+    pub operator: String,
+    pub observables: Vec<Observable>,
+}
+
+impl FromElement for ObservableComposition {
+    fn from_element(element: &Element) -> Result<ObservableComposition, TaxiiError> {
+        element.expect("Observable_Composition")?;
+        Ok(ObservableComposition {
+            operator: String::from(element.required_attr("operator")?),
+            observables: read_all(element, "Observable")?,
+        })
+    }
+}
+
+/// A CybOX observable: an object, or a composition of observables.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Observable {
+    pub id: Option<String>,
+    pub idref: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub object: Option<Object>,
+    pub composition: Option<ObservableComposition>,
+}
+
+impl Observable {
+    /// The objects of the observable, including those of the observables it is composed of.
+    pub fn objects(&self) -> Vec<&Object> {
+        let mut objects = Vec::<&Object>::new();
+        if let Some(object) = &self.object {
+            objects.push(object);
+        }
+        if let Some(composition) = &self.composition {
+            for observable in composition.observables.iter() {
+                objects.extend(observable.objects());
+            }
+        }
+        objects
+    }
+}
+
+impl FromElement for Observable {
+    fn from_element(element: &Element) -> Result<Observable, TaxiiError> {
+        Ok(Observable {
+            id: element.attr("id").map(String::from),
+            idref: element.attr("idref").map(String::from),
+            title: element.element_text("Title"),
+            description: element.element_text("Description"),
+            object: read_optional(element, "Object")?,
+            composition: read_optional(element, "Observable_Composition")?,
+        })
+    }
+}
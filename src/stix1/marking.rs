@@ -0,0 +1,117 @@
+use crate::taxii::{
+    errors::TaxiiError,
+    message::{parse_bool, Element, FromElement},
+};
+
+use super::common::{read_all, read_optional, xsi_type, InformationSource};
+
+/// A marking structure, e.g. the TLP color of a Marking. The structure is known by its xsi:type.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MarkingStructure {
+    Tlp {
+        color: String,
+    },
+    /// A simple marking statement.
+    Statement {
+        statement: String,
+    },
+    TermsOfUse {
+        terms_of_use: String,
+    },
+    /// The consent and proprietary markings of the DHS Automated Indicator Sharing program.
+    Ais {
+        proprietary: bool,
+        consent: Option<String>,
+        tlp: Option<String>,
+        cisa_proprietary: Option<bool>,
+    },
+    /// A structure of another type, with its xsi:type.
+    Other {
+        xsi_type: Option<String>,
+    },
+}
+
+impl FromElement for MarkingStructure {
+    fn from_element(element: &Element) -> Result<MarkingStructure, TaxiiError> {
+        element.expect("Marking_Structure")?;
+        let structure = match xsi_type(element) {
+            Some("TLPMarkingStructureType") => MarkingStructure::Tlp {
+                color: String::from(element.required_attr("color")?),
+            },
+            Some("SimpleMarkingStructureType") => MarkingStructure::Statement {
+                statement: element.element_text("Statement").unwrap_or_default(),
+            },
+            Some("TermsOfUseMarkingStructureType") => MarkingStructure::TermsOfUse {
+                terms_of_use: element.element_text("Terms_Of_Use").unwrap_or_default(),
+            },
+            Some("AISMarkingStructure") => {
+                let (proprietary, marking) = match (
+                    element.element("Is_Proprietary"),
+                    element.element("Not_Proprietary"),
+                ) {
+                    (Some(v), _) => (true, v),
+                    (None, Some(v)) => (false, v),
+                    (None, None) => {
+                        return Err(TaxiiError::Protocol(
+                            "missing Is_Proprietary or Not_Proprietary in AIS marking".to_string(),
+                        ))
+                    }
+                };
+                MarkingStructure::Ais {
+                    proprietary,
+                    consent: marking
+                        .element("AISConsent")
+                        .and_then(|e| e.attr("consent"))
+                        .map(String::from),
+                    tlp: marking
+                        .element("TLPMarking")
+                        .and_then(|e| e.attr("color"))
+                        .map(String::from),
+                    cisa_proprietary: marking.attr("CISA_Proprietary").map(parse_bool),
+                }
+            }
+            v => MarkingStructure::Other {
+                xsi_type: v.map(String::from),
+            },
+        };
+        Ok(structure)
+    }
+}
+
+/// A data marking, e.g. of the Handling of a package, and the nodes it applies to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Marking {
+    pub controlled_structure: Option<String>,
+    pub marking_structures: Vec<MarkingStructure>,
+    pub information_source: Option<InformationSource>,
+}
+
+impl Marking {
+    /// The TLP color of the marking, from a TLP or AIS marking structure.
+    pub fn tlp(&self) -> Option<&str> {
+        self.marking_structures.iter().find_map(|v| match v {
+            MarkingStructure::Tlp { color } => Some(color.as_str()),
+            MarkingStructure::Ais { tlp: Some(v), .. } => Some(v.as_str()),
+            _ => None,
+        })
+    }
+}
+
+impl FromElement for Marking {
+    fn from_element(element: &Element) -> Result<Marking, TaxiiError> {
+        element.expect("Marking")?;
+        Ok(Marking {
+            controlled_structure: element.element_text("Controlled_Structure"),
+            marking_structures: read_all(element, "Marking_Structure")?,
+            information_source: read_optional(element, "Information_Source")?,
+        })
+    }
+}
+
+/// Read the markings of a Handling element.
+pub fn handling(element: &Element) -> Result<Vec<Marking>, TaxiiError> {
+    match element.element("Handling") {
+        Some(v) => read_all(v, "Marking"),
+        None => Ok(Vec::<Marking>::new()),
+    }
+}
@@ -0,0 +1,691 @@
+use chrono::{DateTime, Utc};
+
+use crate::taxii::{
+    errors::TaxiiError,
+    message::{parse_bool, Element, FromElement},
+};
+
+use super::{
+    common::{
+        element_texts, parse_stix_timestamp, read_all, read_optional, related_references,
+        timestamp_attr, Identity, InformationSource, RelatedReference, Statement,
+    },
+    cybox::Observable,
+    marking::{handling, Marking},
+};
+
+// The components of a package are held by a list element, e.g. Indicators, which is left out
+// when there are none.
+fn components<T: FromElement>(
+    element: &Element,
+    list_name: &str,
+    local_name: &str,
+) -> Result<Vec<T>, TaxiiError> {
+    match element.element(list_name) {
+        Some(v) => read_all(v, local_name),
+        None => Ok(Vec::<T>::new()),
+    }
+}
+
+fn id(element: &Element) -> Option<String> {
+    element.attr("id").map(String::from)
+}
+
+fn idref(element: &Element) -> Option<String> {
+    element.attr("idref").map(String::from)
+}
+
+/// A phase of a kill chain, e.g. of the Lockheed Martin Cyber Kill Chain.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KillChainPhase {
+    pub phase_id: Option<String>,
+    pub name: Option<String>,
+    pub kill_chain_id: Option<String>,
+    pub kill_chain_name: Option<String>,
+}
+
+impl FromElement for KillChainPhase {
+    fn from_element(element: &Element) -> Result<KillChainPhase, TaxiiError> {
+        element.expect("Kill_Chain_Phase")?;
+        Ok(KillChainPhase {
+            phase_id: element.attr("phase_id").map(String::from),
+            name: element.attr("name").map(String::from),
+            kill_chain_id: element.attr("kill_chain_id").map(String::from),
+            kill_chain_name: element.attr("kill_chain_name").map(String::from),
+        })
+    }
+}
+
+/// When an indicator is valid.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidTime {
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+}
+
+impl FromElement for ValidTime {
+    fn from_element(element: &Element) -> Result<ValidTime, TaxiiError> {
+        let time = |local_name: &str| match element.element_text(local_name) {
+            Some(v) => parse_stix_timestamp(local_name, v.as_str()).map(Some),
+            None => Ok(None),
+        };
+        Ok(ValidTime {
+            start_time: time("Start_Time")?,
+            end_time: time("End_Time")?,
+        })
+    }
+}
+
+/// Indicators combined with an operator, `AND` or `OR`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompositeIndicatorExpression {
+    pub operator: String,
+    pub indicators: Vec<Indicator>,
+}
+
+impl FromElement for CompositeIndicatorExpression {
+    fn from_element(element: &Element) -> Result<CompositeIndicatorExpression, TaxiiError> {
+        element.expect("Composite_Indicator_Expression")?;
+        Ok(CompositeIndicatorExpression {
+            operator: String::from(element.required_attr("operator")?),
+            indicators: read_all(element, "Indicator")?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Indicator {
+    pub id: Option<String>,
+    pub idref: Option<String>,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub negate: bool,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub short_description: Option<String>,
+    /// The types of the indicator, e.g. `IP Watchlist`.
+    pub indicator_types: Vec<String>,
+    pub valid_time_positions: Vec<ValidTime>,
+    pub observable: Option<Observable>,
+    pub composite_expression: Option<CompositeIndicatorExpression>,
+    pub indicated_ttps: Vec<RelatedReference>,
+    pub kill_chain_phases: Vec<KillChainPhase>,
+    pub likely_impact: Option<Statement>,
+    pub confidence: Option<Statement>,
+    pub producer: Option<InformationSource>,
+    pub handling: Vec<Marking>,
+}
+
+impl FromElement for Indicator {
+    fn from_element(element: &Element) -> Result<Indicator, TaxiiError> {
+        element.expect("Indicator")?;
+        Ok(Indicator {
+            id: id(element),
+            idref: idref(element),
+            timestamp: timestamp_attr(element)?,
+            negate: element.attr("negate").is_some_and(parse_bool),
+            title: element.element_text("Title"),
+            description: element.element_text("Description"),
+            short_description: element.element_text("Short_Description"),
+            indicator_types: element_texts(element, "Type"),
+            valid_time_positions: read_all(element, "Valid_Time_Position")?,
+            observable: read_optional(element, "Observable")?,
+            composite_expression: read_optional(element, "Composite_Indicator_Expression")?,
+            indicated_ttps: read_all(element, "Indicated_TTP")?,
+            kill_chain_phases: components(element, "Kill_Chain_Phases", "Kill_Chain_Phase")?,
+            likely_impact: read_optional(element, "Likely_Impact")?,
+            confidence: read_optional(element, "Confidence")?,
+            producer: read_optional(element, "Producer")?,
+            handling: handling(element)?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MalwareInstance {
+    pub names: Vec<String>,
+    /// The types of the malware, e.g. `Remote Access Trojan`.
+    pub malware_types: Vec<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+}
+
+impl FromElement for MalwareInstance {
+    fn from_element(element: &Element) -> Result<MalwareInstance, TaxiiError> {
+        element.expect("Malware_Instance")?;
+        Ok(MalwareInstance {
+            names: element_texts(element, "Name"),
+            malware_types: element_texts(element, "Type"),
+            title: element.element_text("Title"),
+            description: element.element_text("Description"),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttackPattern {
+    pub capec_id: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+}
+
+impl FromElement for AttackPattern {
+    fn from_element(element: &Element) -> Result<AttackPattern, TaxiiError> {
+        element.expect("Attack_Pattern")?;
+        Ok(AttackPattern {
+            capec_id: element.attr("capec_id").map(String::from),
+            title: element.element_text("Title"),
+            description: element.element_text("Description"),
+        })
+    }
+}
+
+/// A tactic, technique or procedure: the behavior of an adversary.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ttp {
+    pub id: Option<String>,
+    pub idref: Option<String>,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub short_description: Option<String>,
+    pub intended_effects: Vec<Statement>,
+    pub malware: Vec<MalwareInstance>,
+    pub attack_patterns: Vec<AttackPattern>,
+    pub kill_chain_phases: Vec<KillChainPhase>,
+    pub exploit_targets: Vec<RelatedReference>,
+    pub handling: Vec<Marking>,
+}
+
+impl FromElement for Ttp {
+    fn from_element(element: &Element) -> Result<Ttp, TaxiiError> {
+        element.expect("TTP")?;
+        let (malware, attack_patterns) = match element.element("Behavior") {
+            Some(v) => (
+                components(v, "Malware", "Malware_Instance")?,
+                components(v, "Attack_Patterns", "Attack_Pattern")?,
+            ),
+            None => (Vec::new(), Vec::new()),
+        };
+        Ok(Ttp {
+            id: id(element),
+            idref: idref(element),
+            timestamp: timestamp_attr(element)?,
+            title: element.element_text("Title"),
+            description: element.element_text("Description"),
+            short_description: element.element_text("Short_Description"),
+            intended_effects: read_all(element, "Intended_Effect")?,
+            malware,
+            attack_patterns,
+            kill_chain_phases: components(element, "Kill_Chain_Phases", "Kill_Chain_Phase")?,
+            exploit_targets: related_references(element, "Exploit_Targets", "Exploit_Target")?,
+            handling: handling(element)?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vulnerability {
+    pub cve_id: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub cvss_base_score: Option<String>,
+}
+
+impl FromElement for Vulnerability {
+    fn from_element(element: &Element) -> Result<Vulnerability, TaxiiError> {
+        element.expect("Vulnerability")?;
+        Ok(Vulnerability {
+            cve_id: element.element_text("CVE_ID"),
+            title: element.element_text("Title"),
+            description: element.element_text("Description"),
+            cvss_base_score: element
+                .element("CVSS_Score")
+                .and_then(|e| e.element_text("Base_Score")),
+        })
+    }
+}
+
+/// A vulnerability, weakness or configuration an adversary may exploit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExploitTarget {
+    pub id: Option<String>,
+    pub idref: Option<String>,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub short_description: Option<String>,
+    pub vulnerabilities: Vec<Vulnerability>,
+    /// The CWE identifiers of the weaknesses, e.g. `CWE-79`.
+    pub weaknesses: Vec<String>,
+    pub handling: Vec<Marking>,
+}
+
+impl FromElement for ExploitTarget {
+    fn from_element(element: &Element) -> Result<ExploitTarget, TaxiiError> {
+        element.expect("Exploit_Target")?;
+        Ok(ExploitTarget {
+            id: id(element),
+            idref: idref(element),
+            timestamp: timestamp_attr(element)?,
+            title: element.element_text("Title"),
+            description: element.element_text("Description"),
+            short_description: element.element_text("Short_Description"),
+            vulnerabilities: read_all(element, "Vulnerability")?,
+            weaknesses: element
+                .elements_named("Weakness")
+                .filter_map(|e| e.element_text("CWE_ID"))
+                .collect(),
+            handling: handling(element)?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Incident {
+    pub id: Option<String>,
+    pub idref: Option<String>,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub short_description: Option<String>,
+    pub categories: Vec<String>,
+    pub reporter: Option<InformationSource>,
+    pub victims: Vec<Identity>,
+    pub related_indicators: Vec<RelatedReference>,
+    pub related_observables: Vec<RelatedReference>,
+    pub leveraged_ttps: Vec<RelatedReference>,
+    pub confidence: Option<Statement>,
+    pub handling: Vec<Marking>,
+}
+
+impl FromElement for Incident {
+    fn from_element(element: &Element) -> Result<Incident, TaxiiError> {
+        element.expect("Incident")?;
+        Ok(Incident {
+            id: id(element),
+            idref: idref(element),
+            timestamp: timestamp_attr(element)?,
+            title: element.element_text("Title"),
+            description: element.element_text("Description"),
+            short_description: element.element_text("Short_Description"),
+            categories: match element.element("Categories") {
+                Some(v) => element_texts(v, "Category"),
+                None => Vec::<String>::new(),
+            },
+            reporter: read_optional(element, "Reporter")?,
+            victims: read_all(element, "Victim")?,
+            related_indicators: related_references(
+                element,
+                "Related_Indicators",
+                "Related_Indicator",
+            )?,
+            related_observables: related_references(
+                element,
+                "Related_Observables",
+                "Related_Observable",
+            )?,
+            leveraged_ttps: related_references(element, "Leveraged_TTPs", "Leveraged_TTP")?,
+            confidence: read_optional(element, "Confidence")?,
+            handling: handling(element)?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StixHeader {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub short_description: Option<String>,
+    /// The intents of the package, e.g. `Indicators - Watchlist`.
+    pub package_intents: Vec<String>,
+    pub information_source: Option<InformationSource>,
+    pub handling: Vec<Marking>,
+}
+
+impl FromElement for StixHeader {
+    fn from_element(element: &Element) -> Result<StixHeader, TaxiiError> {
+        element.expect("STIX_Header")?;
+        Ok(StixHeader {
+            title: element.element_text("Title"),
+            description: element.element_text("Description"),
+            short_description: element.element_text("Short_Description"),
+            package_intents: element_texts(element, "Package_Intent"),
+            information_source: read_optional(element, "Information_Source")?,
+            handling: handling(element)?,
+        })
+    }
+}
+
+/// A STIX 1.x package, e.g. the content of a content block with a STIX 1.x binding. Components
+/// other than observables, indicators, TTPs, exploit targets and incidents are left out.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StixPackage {
+    pub id: Option<String>,
+    pub idref: Option<String>,
+    pub version: Option<String>,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub header: Option<StixHeader>,
+    pub observables: Vec<Observable>,
+    pub indicators: Vec<Indicator>,
+    pub ttps: Vec<Ttp>,
+    pub exploit_targets: Vec<ExploitTarget>,
+    pub incidents: Vec<Incident>,
+}
+
+impl StixPackage {
+    pub fn parse(doc: &[u8]) -> Result<StixPackage, TaxiiError> {
+        StixPackage::from_element(&Element::parse(doc)?)
+    }
+
+    /// The markings of the package, from its header.
+    pub fn handling(&self) -> &[Marking] {
+        match &self.header {
+            Some(v) => v.handling.as_slice(),
+            None => &[],
+        }
+    }
+}
+
+impl FromElement for StixPackage {
+    fn from_element(element: &Element) -> Result<StixPackage, TaxiiError> {
+        element.expect("STIX_Package")?;
+        Ok(StixPackage {
+            id: id(element),
+            idref: idref(element),
+            version: element.attr("version").map(String::from),
+            timestamp: timestamp_attr(element)?,
+            header: read_optional(element, "STIX_Header")?,
+            observables: components(element, "Observables", "Observable")?,
+            indicators: components(element, "Indicators", "Indicator")?,
+            ttps: components(element, "TTPs", "TTP")?,
+            exploit_targets: components(element, "Exploit_Targets", "Exploit_Target")?,
+            incidents: components(element, "Incidents", "Incident")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use chrono::{TimeZone, Utc};
+
+    use super::StixPackage;
+    use crate::{
+        stix1::{
+            cybox::{ObjectProperties, PropertyValue},
+            marking::MarkingStructure,
+        },
+        taxii::poll::parse_poll_response,
+    };
+
+    fn sample_packages() -> Vec<StixPackage> {
+        let path = format!(
+            "{}/test/sample-poll-response.xml",
+            env::var("CARGO_MANIFEST_DIR").unwrap()
+        );
+        let doc = fs::read(path).unwrap();
+        parse_poll_response(&doc)
+            .unwrap()
+            .content_blocks
+            .iter()
+            .filter(|v| v.content.starts_with('<'))
+            .map(|v| StixPackage::parse(v.content.as_bytes()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_sample_packages() {
+        let packages = sample_packages();
+        assert_eq!(9, packages.len());
+
+        let package = &packages[0];
+        let header = package.header.as_ref().unwrap();
+        assert!(header
+            .description
+            .as_ref()
+            .unwrap()
+            .starts_with("STIX Package containing project tags"));
+        let marking = &header.handling[0];
+        assert_eq!(Some("//node()"), marking.controlled_structure.as_deref());
+        assert_eq!(
+            MarkingStructure::Ais {
+                proprietary: false,
+                consent: Some(String::from("EVERYONE")),
+                tlp: Some(String::from("WHITE")),
+                cisa_proprietary: Some(false),
+            },
+            marking.marking_structures[0]
+        );
+        assert_eq!(Some("WHITE"), marking.tlp());
+        let identity = marking
+            .information_source
+            .as_ref()
+            .unwrap()
+            .identity
+            .as_ref()
+            .unwrap();
+        let ciq = identity.ciq.as_ref().unwrap();
+        assert_eq!(Some("JJohnson Industries"), identity.display_name());
+        assert_eq!(vec!["US"], ciq.countries);
+        assert_eq!(vec!["US-DC"], ciq.administrative_areas);
+        assert_eq!(Some("Information Technology"), ciq.industry_type.as_deref());
+
+        let indicator = &package.indicators[0];
+        assert_eq!(
+            Some("example:indicator-720cc55d-9120-4eae-91b3-fdde9b2995ea"),
+            indicator.id.as_deref()
+        );
+        assert_eq!(vec!["IP Watchlist"], indicator.indicator_types);
+        let observable = indicator.observable.as_ref().unwrap();
+        let composition = observable.composition.as_ref().unwrap();
+        assert_eq!("OR", composition.operator);
+        assert_eq!(
+            Some("[JJIndustries] Mirai"),
+            composition.observables[1].description.as_deref()
+        );
+        let objects = observable.objects();
+        assert_eq!(1, objects.len());
+        match &objects[0].properties {
+            Some(ObjectProperties::Address {
+                value: Some(v),
+                category: None,
+            }) => assert_eq!("104.12.241.179", v.value),
+            v => panic!("unexpected properties: {:?}", v),
+        }
+    }
+
+    #[test]
+    fn test_parse_incident_package() {
+        let packages = sample_packages();
+        let package = &packages[1];
+        assert_eq!(Some("1.2"), package.version.as_deref());
+        let header = package.header.as_ref().unwrap();
+        assert_eq!(
+            vec!["Collective Threat Intelligence"],
+            header.package_intents
+        );
+        let source = header.information_source.as_ref().unwrap();
+        assert_eq!(
+            Some("Wapack Labs"),
+            source.identity.as_ref().unwrap().display_name()
+        );
+        assert_eq!(
+            Some(String::from("2017-01-17T11:38:49.724038+00:00")),
+            source.produced_time.map(|v| v.to_rfc3339())
+        );
+        assert_eq!(Some("CRITs"), source.tools[0].name.as_deref());
+
+        assert_eq!(5, package.indicators.len());
+        let email = &package.indicators[0];
+        assert_eq!(
+            Some("None"),
+            email.confidence.as_ref().unwrap().value.as_deref()
+        );
+        match &email.observable.as_ref().unwrap().objects()[0].properties {
+            Some(ObjectProperties::EmailMessage {
+                header: Some(header),
+                email_server,
+            }) => {
+                assert_eq!(Some("jessantt@gmail.com"), header.from.as_deref());
+                assert_eq!(
+                    Some("The Silent Treatment - 10/10/2014"),
+                    header.subject.as_deref()
+                );
+                assert_eq!(Some("1.2.3.4"), header.x_originating_ip.as_deref());
+                assert_eq!(Some("o3.grid1.badguys.com"), email_server.as_deref());
+            }
+            v => panic!("unexpected properties: {:?}", v),
+        }
+        let indicator = &package.indicators[1];
+        assert_eq!(
+            Some("Wapack Labs"),
+            indicator
+                .producer
+                .as_ref()
+                .and_then(|v| v.identity.as_ref())
+                .and_then(|v| v.display_name())
+        );
+        assert_eq!(
+            Some("Unknown"),
+            indicator.likely_impact.as_ref().unwrap().value.as_deref()
+        );
+
+        let incident = &package.incidents[0];
+        assert_eq!(
+            Some("Aviation One Spear-phising Attack"),
+            incident.title.as_deref()
+        );
+        assert_eq!(Some("Intel Sharing"), incident.short_description.as_deref());
+        assert_eq!(5, incident.related_indicators.len());
+        assert_eq!(
+            Some("INTINC-Dev:indicator-2831a42f-eca1-46fe-acd9-41d59b792e05"),
+            incident.related_indicators[0].idref.as_deref()
+        );
+        assert_eq!(
+            Some("Related To"),
+            incident.related_indicators[0].relationship.as_deref()
+        );
+    }
+
+    #[test]
+    fn test_parse_observable_patterns() {
+        let packages = sample_packages();
+        match &packages[2].indicators[0]
+            .observable
+            .as_ref()
+            .unwrap()
+            .objects()[0]
+            .properties
+        {
+            Some(ObjectProperties::Uri {
+                value: Some(value), ..
+            }) => {
+                assert_eq!(Some("Equals"), value.condition.as_deref());
+                assert_eq!(
+                    vec![
+                        "http://example.com/foo/malicious1.html",
+                        "http://example.com/foo/malicious2.html",
+                        "http://example.com/foo/malicious3.html"
+                    ],
+                    value.values()
+                );
+            }
+            v => panic!("unexpected properties: {:?}", v),
+        }
+        let hashes: Vec<_> = packages
+            .iter()
+            .flat_map(|v| v.indicators.iter())
+            .filter_map(|v| v.observable.as_ref())
+            .flat_map(|v| v.objects())
+            .filter_map(|v| match &v.properties {
+                Some(ObjectProperties::File { hashes, .. }) => Some(hashes),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        assert_eq!(182, hashes.len());
+        assert_eq!("SHA256", hashes[0].hash_type);
+        assert_eq!(
+            PropertyValue {
+                value: String::from(
+                    "a685cf4dca6a58213e67d041bba637dca9cb3ea6bb9ad3eae3ba85229118bce0"
+                ),
+                condition: None,
+                apply_condition: None,
+            },
+            hashes[0].value
+        );
+    }
+
+    #[test]
+    fn test_parse_components() {
+        let doc = r#"<stix:STIX_Package xmlns:stix="http://stix.mitre.org/stix-1"
+            xmlns:stixCommon="http://stix.mitre.org/common-1"
+            xmlns:ttp="http://stix.mitre.org/TTP-1" xmlns:et="http://stix.mitre.org/ExploitTarget-1"
+            xmlns:marking="http://data-marking.mitre.org/Marking-1"
+            xmlns:tlpMarking="http://data-marking.mitre.org/extensions/MarkingStructure#TLP-1"
+            xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"
+            id="example:Package-1" version="1.2" timestamp="2015-03-01T10:00:00Z">
+            <stix:TTPs>
+                <stix:TTP id="example:ttp-1" timestamp="2015-03-01T10:00:00Z">
+                    <ttp:Title>Poison Ivy</ttp:Title>
+                    <ttp:Behavior>
+                        <ttp:Malware><ttp:Malware_Instance>
+                            <ttp:Type>Remote Access Trojan</ttp:Type><ttp:Name>Poison Ivy</ttp:Name>
+                        </ttp:Malware_Instance></ttp:Malware>
+                        <ttp:Attack_Patterns>
+                            <ttp:Attack_Pattern capec_id="CAPEC-98"><ttp:Title>Phishing</ttp:Title></ttp:Attack_Pattern>
+                        </ttp:Attack_Patterns>
+                    </ttp:Behavior>
+                    <ttp:Exploit_Targets><ttp:Exploit_Target>
+                        <stixCommon:Exploit_Target idref="example:et-1"/>
+                    </ttp:Exploit_Target></ttp:Exploit_Targets>
+                    <ttp:Handling><marking:Marking>
+                        <marking:Marking_Structure xsi:type="tlpMarking:TLPMarkingStructureType" color="AMBER"/>
+                    </marking:Marking></ttp:Handling>
+                </stix:TTP>
+            </stix:TTPs>
+            <stix:Exploit_Targets>
+                <stixCommon:Exploit_Target id="example:et-1">
+                    <et:Vulnerability><et:CVE_ID>CVE-2011-2462</et:CVE_ID>
+                        <et:CVSS_Score><et:Base_Score>9.3</et:Base_Score></et:CVSS_Score>
+                    </et:Vulnerability>
+                    <et:Weakness><et:CWE_ID>CWE-119</et:CWE_ID></et:Weakness>
+                </stixCommon:Exploit_Target>
+            </stix:Exploit_Targets>
+        </stix:STIX_Package>"#;
+        let package = StixPackage::parse(doc.as_bytes()).unwrap();
+        assert_eq!(
+            Some(Utc.with_ymd_and_hms(2015, 3, 1, 10, 0, 0).unwrap()),
+            package.timestamp
+        );
+        let ttp = &package.ttps[0];
+        assert_eq!(Some("Poison Ivy"), ttp.title.as_deref());
+        assert_eq!(vec!["Poison Ivy"], ttp.malware[0].names);
+        assert_eq!(vec!["Remote Access Trojan"], ttp.malware[0].malware_types);
+        assert_eq!(Some("CAPEC-98"), ttp.attack_patterns[0].capec_id.as_deref());
+        assert_eq!(
+            Some("example:et-1"),
+            ttp.exploit_targets[0].idref.as_deref()
+        );
+        assert_eq!(Some("AMBER"), ttp.handling[0].tlp());
+        let exploit_target = &package.exploit_targets[0];
+        assert_eq!(
+            Some("CVE-2011-2462"),
+            exploit_target.vulnerabilities[0].cve_id.as_deref()
+        );
+        assert_eq!(
+            Some("9.3"),
+            exploit_target.vulnerabilities[0].cvss_base_score.as_deref()
+        );
+        assert_eq!(vec!["CWE-119"], exploit_target.weaknesses);
+
+        assert!(StixPackage::parse(
+            b"<stix:Indicator xmlns:stix=\"http://stix.mitre.org/stix-1\"/>"
+        )
+        .is_err());
+        let bad_timestamp = "<STIX_Package timestamp=\"yesterday\"/>";
+        assert!(StixPackage::parse(bad_timestamp.as_bytes()).is_err());
+    }
+}
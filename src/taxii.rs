@@ -1,9 +1,17 @@
+pub mod archive;
+pub mod client;
 pub mod collections;
+pub mod content_block;
 pub mod errors;
 pub mod files;
+pub mod inbox;
+pub mod message;
 pub mod poll;
+pub mod query;
+pub mod server;
 pub mod services;
 pub mod status_message;
+pub mod store;
 pub mod subscriptions;
 pub mod types;
 pub mod version;
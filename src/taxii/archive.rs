@@ -0,0 +1,533 @@
+use std::{
+    collections::HashSet,
+    fs::{File, OpenOptions},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Duration, Utc};
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{
+    errors::TaxiiError,
+    files::write_file_atomically,
+    version::{parse_message_root, Version},
+};
+
+/// The environment variable the blocking requests take the archive directory from. Nothing is
+/// archived when it is not set.
+pub static ARCHIVE_DIR_VAR: &str = "TAXII_ARCHIVE_DIR";
+
+const OBJECTS_DIR: &str = "objects";
+const ENTRIES_DIR: &str = "entries";
+const LOCK_FILE: &str = "lock";
+
+fn storage_error(err: std::io::Error) -> TaxiiError {
+    TaxiiError::Storage(err.to_string())
+}
+
+/// The hex SHA-256 of a document, the name it is stored under. Archives written with SHA-1 names
+/// are still read, their entries refer to the documents by name.
+fn content_hash(data: &str) -> String {
+    Sha256::digest(data.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// How many archived responses to keep. The oldest are removed first; no limit is set by
+/// default. The limits are applied by `prune`, which `store` runs once every prune interval.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Retention {
+    pub max_entries: Option<usize>,
+    pub max_age: Option<Duration>,
+}
+
+/// An archived exchange: the request that was sent and the response that came back. The
+/// documents themselves are stored by their hash, so a response that does not change is only
+/// stored once.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ArchiveEntry {
+    pub id: String,
+    pub url: String,
+    /// The TAXII version of the exchange, e.g. `1.1`.
+    pub version: String,
+    pub requested_at: DateTime<Utc>,
+    /// The HTTP status of the response.
+    pub status: u16,
+    /// The tag of the root element of the response, e.g. `Poll_Response`, if it is XML.
+    pub message: Option<String>,
+    pub request_hash: String,
+    pub response_hash: String,
+    pub response_length: usize,
+}
+
+/// An archived exchange and what parsing its response gave.
+pub type Replayed<T> = (ArchiveEntry, Result<T, TaxiiError>);
+
+/// A directory of archived TAXII responses, for looking back at what a server sent and for
+/// reprocessing it offline with `replay`.
+///
+/// ```text
+/// <dir>/objects/<sha256>        request and response documents
+/// <dir>/entries/<id>.json       one ArchiveEntry per exchange
+/// <dir>/lock                    taken to store, and exclusively to prune
+/// ```
+#[derive(Clone, Debug)]
+pub struct Archive {
+    dir: PathBuf,
+    retention: Retention,
+    prune_interval: Duration,
+    /// When `store` last pruned, shared by the clones of the archive.
+    last_prune: Arc<Mutex<Option<DateTime<Utc>>>>,
+}
+
+impl Archive {
+    pub fn new(dir: &Path) -> Archive {
+        Archive {
+            dir: dir.to_path_buf(),
+            retention: Retention::default(),
+            prune_interval: Duration::hours(1),
+            last_prune: Arc::new(Mutex::new(None)),
+        }
+    }
+    /// The archive in the directory named by TAXII_ARCHIVE_DIR, None when it is not set.
+    pub fn from_env() -> Option<Archive> {
+        match std::env::var(ARCHIVE_DIR_VAR) {
+            Ok(v) if !v.is_empty() => Some(Archive::new(Path::new(v.as_str()))),
+            _ => None,
+        }
+    }
+    pub fn with_retention(mut self, retention: Retention) -> Archive {
+        self.retention = retention;
+        self
+    }
+    /// Set how often `store` prunes the archive, an hour by default.
+    pub fn with_prune_interval(mut self, prune_interval: Duration) -> Archive {
+        self.prune_interval = prune_interval;
+        self
+    }
+    pub fn dir(&self) -> &Path {
+        self.dir.as_path()
+    }
+
+    fn object_path(&self, hash: &str) -> PathBuf {
+        self.dir.join(OBJECTS_DIR).join(hash)
+    }
+    fn entry_path(&self, id: &str) -> PathBuf {
+        self.dir.join(ENTRIES_DIR).join(format!("{}.json", id))
+    }
+
+    /// Lock the archive against other stores and prunes, in this process or another. Stores
+    /// share the lock, a prune takes it exclusively so that it does not remove the documents of
+    /// an entry being stored. The lock is released when the file is dropped.
+    fn lock(&self, exclusive: bool) -> Result<File, TaxiiError> {
+        std::fs::create_dir_all(self.dir.as_path()).map_err(storage_error)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.dir.join(LOCK_FILE))
+            .map_err(storage_error)?;
+        let locked = if exclusive {
+            file.lock_exclusive()
+        } else {
+            file.lock_shared()
+        };
+        locked.map_err(storage_error)?;
+        Ok(file)
+    }
+
+    /// Whether the prune interval has passed since `store` last pruned, in which case it is
+    /// restarted.
+    fn prune_due(&self, now: DateTime<Utc>) -> bool {
+        let mut last_prune = self.last_prune.lock().unwrap();
+        match *last_prune {
+            Some(v) if now - v < self.prune_interval => false,
+            _ => {
+                *last_prune = Some(now);
+                true
+            }
+        }
+    }
+
+    fn write_object(&self, data: &str) -> Result<String, TaxiiError> {
+        let hash = content_hash(data);
+        let path = self.object_path(hash.as_str());
+        if !path.exists() {
+            write_file_atomically(path.as_path(), data).map_err(storage_error)?;
+        }
+        Ok(hash)
+    }
+
+    /// Archive a response to a request, then apply the retention limits if the prune interval
+    /// has passed since they last were.
+    pub fn store(
+        &self,
+        url: &str,
+        ver: Version,
+        request_body: &str,
+        status: u16,
+        response_body: &str,
+    ) -> Result<ArchiveEntry, TaxiiError> {
+        self.store_at(url, ver, request_body, status, response_body, Utc::now())
+    }
+
+    fn store_at(
+        &self,
+        url: &str,
+        ver: Version,
+        request_body: &str,
+        status: u16,
+        response_body: &str,
+        requested_at: DateTime<Utc>,
+    ) -> Result<ArchiveEntry, TaxiiError> {
+        let entry =
+            self.write_entry(url, ver, request_body, status, response_body, requested_at)?;
+        if self.prune_due(Utc::now()) {
+            self.prune()?;
+        }
+        Ok(entry)
+    }
+
+    fn write_entry(
+        &self,
+        url: &str,
+        ver: Version,
+        request_body: &str,
+        status: u16,
+        response_body: &str,
+        requested_at: DateTime<Utc>,
+    ) -> Result<ArchiveEntry, TaxiiError> {
+        let _lock = self.lock(false)?;
+        std::fs::create_dir_all(self.dir.join(OBJECTS_DIR)).map_err(storage_error)?;
+        std::fs::create_dir_all(self.dir.join(ENTRIES_DIR)).map_err(storage_error)?;
+        let request_hash = self.write_object(request_body)?;
+        let response_hash = self.write_object(response_body)?;
+        // ids sort by time; the hash tells apart exchanges within the same microsecond
+        let id = format!(
+            "{}-{}",
+            requested_at.format("%Y%m%dT%H%M%S%.6fZ"),
+            &content_hash(format!("{}\n{}\n{}", url, request_hash, response_hash).as_str())[..8]
+        );
+        let entry = ArchiveEntry {
+            id,
+            url: String::from(url),
+            version: String::from(ver.to_str()),
+            requested_at,
+            status,
+            message: parse_message_root(response_body.as_bytes())
+                .ok()
+                .map(|(name, _)| name),
+            request_hash,
+            response_hash,
+            response_length: response_body.len(),
+        };
+        let data = match serde_json::to_string_pretty(&entry) {
+            Ok(v) => v,
+            Err(err) => return Err(TaxiiError::Storage(err.to_string())),
+        };
+        write_file_atomically(self.entry_path(entry.id.as_str()).as_path(), data.as_str())
+            .map_err(storage_error)?;
+        Ok(entry)
+    }
+
+    /// The archived exchanges, oldest first.
+    pub fn entries(&self) -> Result<Vec<ArchiveEntry>, TaxiiError> {
+        let dir = match std::fs::read_dir(self.dir.join(ENTRIES_DIR)) {
+            Ok(v) => v,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Vec::<ArchiveEntry>::new())
+            }
+            Err(err) => return Err(storage_error(err)),
+        };
+        let mut entries = Vec::<ArchiveEntry>::new();
+        for dir_entry in dir {
+            let path = dir_entry.map_err(storage_error)?.path();
+            if path.extension().is_none_or(|v| v != "json") {
+                continue;
+            }
+            let data = std::fs::read_to_string(&path).map_err(storage_error)?;
+            match serde_json::from_str::<ArchiveEntry>(data.as_str()) {
+                Ok(v) => entries.push(v),
+                Err(err) => {
+                    return Err(TaxiiError::Storage(format!(
+                        "bad archive entry {}: {}",
+                        path.display(),
+                        err
+                    )))
+                }
+            }
+        }
+        entries.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(entries)
+    }
+
+    fn read_object(&self, hash: &str) -> Result<String, TaxiiError> {
+        std::fs::read_to_string(self.object_path(hash)).map_err(storage_error)
+    }
+    pub fn request_body(&self, entry: &ArchiveEntry) -> Result<String, TaxiiError> {
+        self.read_object(entry.request_hash.as_str())
+    }
+    pub fn response_body(&self, entry: &ArchiveEntry) -> Result<String, TaxiiError> {
+        self.read_object(entry.response_hash.as_str())
+    }
+
+    /// Remove the entries beyond the retention limits, and the documents no entry refers to any
+    /// more. Returns how many entries were removed.
+    pub fn prune(&self) -> Result<usize, TaxiiError> {
+        let _lock = self.lock(true)?;
+        let entries = self.entries()?;
+        let now = Utc::now();
+        let too_many = match self.retention.max_entries {
+            Some(v) => entries.len().saturating_sub(v),
+            None => 0,
+        };
+        let (removed, kept): (Vec<_>, Vec<_>) =
+            entries.into_iter().enumerate().partition(|(i, entry)| {
+                *i < too_many
+                    || self
+                        .retention
+                        .max_age
+                        .is_some_and(|v| now - entry.requested_at > v)
+            });
+        if removed.is_empty() {
+            return Ok(0);
+        }
+        for (_, entry) in removed.iter() {
+            std::fs::remove_file(self.entry_path(entry.id.as_str())).map_err(storage_error)?;
+        }
+        let referenced: HashSet<&str> = kept
+            .iter()
+            .flat_map(|(_, v)| [v.request_hash.as_str(), v.response_hash.as_str()])
+            .collect();
+        for dir_entry in std::fs::read_dir(self.dir.join(OBJECTS_DIR)).map_err(storage_error)? {
+            let dir_entry = dir_entry.map_err(storage_error)?;
+            let name = dir_entry.file_name();
+            if !referenced.contains(name.to_string_lossy().as_ref()) {
+                std::fs::remove_file(dir_entry.path()).map_err(storage_error)?;
+            }
+        }
+        Ok(removed.len())
+    }
+
+    /// Parse the archived responses with a message tag, e.g. every `Poll_Response` with
+    /// `parse_poll_response`, oldest first. A response that does not parse gives its error
+    /// rather than stopping the replay.
+    pub fn replay<T, F>(&self, message: &str, parse: F) -> Result<Vec<Replayed<T>>, TaxiiError>
+    where
+        F: Fn(&[u8]) -> Result<T, TaxiiError>,
+    {
+        let mut results = Vec::<Replayed<T>>::new();
+        for entry in self.entries()? {
+            if entry.message.as_deref() != Some(message) {
+                continue;
+            }
+            let response_body = self.response_body(&entry)?;
+            let result = parse(response_body.as_bytes());
+            results.push((entry, result));
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    use super::{Archive, Retention};
+    use crate::taxii::{
+        poll::parse_poll_response, services::parse_discovery_response, version::Version,
+    };
+
+    fn temp_archive() -> Archive {
+        Archive::new(&env::temp_dir().join(format!("archive-{}", Uuid::new_v4())))
+    }
+
+    fn sample(name: &str) -> String {
+        let path = format!("{}/test/{}", env::var("CARGO_MANIFEST_DIR").unwrap(), name);
+        fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn test_store_and_replay() {
+        let archive = temp_archive();
+        assert!(archive.entries().unwrap().is_empty());
+        let poll_response = sample("sample-poll-response.xml");
+        let discovery_response = sample("sample-discovery-response.xml");
+        let url = "https://example.com/services/poll";
+        let entry = archive
+            .store(
+                url,
+                Version::V11,
+                "<Poll_Request/>",
+                200,
+                poll_response.as_str(),
+            )
+            .unwrap();
+        assert_eq!(Some(String::from("Poll_Response")), entry.message);
+        assert_eq!("1.1", entry.version);
+        assert_eq!(poll_response.len(), entry.response_length);
+        archive
+            .store(
+                url,
+                Version::V11,
+                "<Poll_Request/>",
+                200,
+                poll_response.as_str(),
+            )
+            .unwrap();
+        archive
+            .store(
+                url,
+                Version::V11,
+                "<Discovery_Request/>",
+                200,
+                discovery_response.as_str(),
+            )
+            .unwrap();
+        archive
+            .store(url, Version::V11, "", 502, "Bad Gateway")
+            .unwrap();
+
+        let entries = archive.entries().unwrap();
+        assert_eq!(4, entries.len());
+        assert_eq!(entry, entries[0]);
+        assert!(entries
+            .windows(2)
+            .all(|v| v[0].requested_at <= v[1].requested_at));
+        assert_eq!(None, entries[3].message);
+        assert_eq!(502, entries[3].status);
+        // the same response is stored once
+        assert_eq!(entries[0].response_hash, entries[1].response_hash);
+        let objects = fs::read_dir(archive.dir().join("objects")).unwrap().count();
+        assert_eq!(6, objects);
+        assert_eq!(poll_response, archive.response_body(&entries[1]).unwrap());
+        assert_eq!(
+            "<Poll_Request/>",
+            archive.request_body(&entries[1]).unwrap()
+        );
+
+        let poll_responses = archive
+            .replay("Poll_Response", parse_poll_response)
+            .unwrap();
+        assert_eq!(2, poll_responses.len());
+        for (_, poll_response) in poll_responses.iter() {
+            assert!(!poll_response.as_ref().unwrap().content_blocks.is_empty());
+        }
+        let discovery_responses = archive
+            .replay("Discovery_Response", parse_discovery_response)
+            .unwrap();
+        assert_eq!(1, discovery_responses.len());
+        // responses that do not parse are reported, not skipped
+        let results = archive
+            .replay("Poll_Response", parse_discovery_response)
+            .unwrap();
+        assert!(results.iter().all(|(_, v)| v.is_err()));
+
+        fs::remove_dir_all(archive.dir()).unwrap();
+    }
+
+    #[test]
+    fn test_retention() {
+        let archive = temp_archive()
+            .with_retention(Retention {
+                max_entries: Some(3),
+                max_age: Some(Duration::days(7)),
+            })
+            .with_prune_interval(Duration::zero());
+        let url = "https://example.com/services/poll";
+        let now = Utc::now();
+        archive
+            .store_at(
+                url,
+                Version::V11,
+                "old",
+                200,
+                "old",
+                now - Duration::days(8),
+            )
+            .unwrap();
+        archive
+            .store_at(url, Version::V11, "a", 200, "1", now - Duration::days(1))
+            .unwrap();
+        assert_eq!(1, archive.entries().unwrap().len());
+        for i in 2..6 {
+            archive
+                .store(url, Version::V11, "a", 200, i.to_string().as_str())
+                .unwrap();
+        }
+        let entries = archive.entries().unwrap();
+        assert_eq!(3, entries.len());
+        let responses: Vec<String> = entries
+            .iter()
+            .map(|v| archive.response_body(v).unwrap())
+            .collect();
+        assert_eq!(vec!["3", "4", "5"], responses);
+        // documents of removed entries are removed with them, shared ones are kept
+        let objects = fs::read_dir(archive.dir().join("objects")).unwrap().count();
+        assert_eq!(4, objects);
+        assert_eq!(0, archive.prune().unwrap());
+
+        fs::remove_dir_all(archive.dir()).unwrap();
+    }
+
+    #[test]
+    fn test_prune_interval() {
+        let archive = temp_archive().with_retention(Retention {
+            max_entries: Some(1),
+            max_age: None,
+        });
+        let url = "https://example.com/services/poll";
+        for i in 0..3 {
+            archive
+                .store(url, Version::V11, "a", 200, i.to_string().as_str())
+                .unwrap();
+        }
+        // only the first store pruned, the next ones are within the prune interval
+        assert_eq!(3, archive.entries().unwrap().len());
+        assert_eq!(2, archive.prune().unwrap());
+        assert_eq!(1, archive.entries().unwrap().len());
+
+        fs::remove_dir_all(archive.dir()).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_store_and_prune() {
+        let archive = temp_archive()
+            .with_retention(Retention {
+                max_entries: Some(2),
+                max_age: None,
+            })
+            .with_prune_interval(Duration::zero());
+        let url = "https://example.com/services/poll";
+        let threads = (0..4)
+            .map(|i| {
+                let archive = archive.clone();
+                std::thread::spawn(move || {
+                    for j in 0..20 {
+                        let response = format!("{}-{}", i, j % 3);
+                        archive
+                            .store(url, Version::V11, "a", 200, response.as_str())
+                            .unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        // a prune never removed the documents of an entry being stored
+        for entry in archive.entries().unwrap() {
+            assert!(archive.response_body(&entry).is_ok());
+            assert!(archive.request_body(&entry).is_ok());
+        }
+
+        fs::remove_dir_all(archive.dir()).unwrap();
+    }
+}
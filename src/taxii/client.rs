@@ -0,0 +1,623 @@
+use std::time::Duration;
+
+use tracing::warn;
+
+use super::{
+    archive::Archive,
+    collections::{parse_collection_information_response, CollectionSet},
+    errors::TaxiiError,
+    poll::{
+        create_poll_fulfillment_request_body, create_poll_request_body, parse_poll_response,
+        PollParameters, PollResponse, TimeRange,
+    },
+    services::{parse_discovery_response, ServiceSet},
+    status_message::{parse_status_message, StatusMessage, StatusType},
+    subscriptions::{
+        create_subscribe_request_body, parse_subscription_management_response, PushParameters,
+        SubscribeAction, SubscriptionParameters, SubscriptionResponse,
+    },
+    version::{
+        create_collection_information_request_body, create_discovery_request_body, taxii_headers,
+        Version,
+    },
+};
+
+/// An async TAXII 1.x client. It builds requests and parses responses with the same functions as
+/// the blocking helpers, but sends them on a shared `reqwest::Client` so that many feeds can be
+/// polled concurrently from a single tokio runtime.
+///
+/// RETRY status messages are handled by re-sending the request, and PENDING answers to a poll by
+/// fetching the result with a Poll_Fulfillment request, waiting the ESTIMATED_WAIT given by the
+/// server in between, but never longer than the configured maximum wait. Any other status message
+/// is reported as an error.
+#[derive(Clone)]
+pub struct AsyncClient {
+    client: reqwest::Client,
+    username: String,
+    password: String,
+    ver: Version,
+    max_retries: u32,
+    default_wait: Duration,
+    max_wait: Duration,
+    archive: Option<Archive>,
+}
+
+impl AsyncClient {
+    pub fn new(username: &str, password: &str, ver: Version) -> AsyncClient {
+        AsyncClient::with_client(reqwest::Client::new(), username, password, ver)
+    }
+
+    /// Create a client on top of an existing `reqwest::Client`, e.g. to share its connection pool
+    /// or to configure timeouts and proxies.
+    pub fn with_client(
+        client: reqwest::Client,
+        username: &str,
+        password: &str,
+        ver: Version,
+    ) -> AsyncClient {
+        AsyncClient {
+            client,
+            username: String::from(username),
+            password: String::from(password),
+            ver,
+            max_retries: 3,
+            default_wait: Duration::from_secs(30),
+            max_wait: Duration::from_secs(300),
+            archive: None,
+        }
+    }
+
+    /// Set how many times a request is re-sent after a RETRY or PENDING status message, and how
+    /// long to wait when the server does not give an ESTIMATED_WAIT.
+    pub fn with_retry_policy(mut self, max_retries: u32, default_wait: Duration) -> AsyncClient {
+        self.max_retries = max_retries;
+        self.default_wait = default_wait;
+        self
+    }
+
+    /// Set the longest the client waits before re-sending a request, whatever ESTIMATED_WAIT the
+    /// server asks for.
+    pub fn with_max_wait(mut self, max_wait: Duration) -> AsyncClient {
+        self.max_wait = max_wait;
+        self
+    }
+
+    /// Archive the responses the client receives, or not when None, the default.
+    pub fn with_archive(mut self, archive: Option<Archive>) -> AsyncClient {
+        self.archive = archive;
+        self
+    }
+
+    pub fn version(&self) -> Version {
+        self.ver
+    }
+
+    async fn send(&self, url: &str, request_body: &str) -> Result<String, TaxiiError> {
+        let headers = taxii_headers(self.ver)?;
+        let response = match self
+            .client
+            .post(url)
+            .basic_auth(self.username.as_str(), Some(self.password.as_str()))
+            .headers(headers)
+            .body(String::from(request_body))
+            .send()
+            .await
+        {
+            Ok(v) => v,
+            Err(err) => return Err(TaxiiError::Transport(err.to_string())),
+        };
+        let status = response.status();
+        let response_body = match response.text().await {
+            Ok(v) => v,
+            Err(err) => return Err(TaxiiError::Transport(err.to_string())),
+        };
+        if let Some(archive) = self.archive.clone() {
+            // the archive is written with blocking file operations
+            let ver = self.ver;
+            let (url, request_body) = (String::from(url), String::from(request_body));
+            let response_body = response_body.clone();
+            let stored = tokio::task::spawn_blocking(move || {
+                archive.store(
+                    url.as_str(),
+                    ver,
+                    request_body.as_str(),
+                    status.as_u16(),
+                    response_body.as_str(),
+                )
+            })
+            .await;
+            match stored {
+                Ok(Ok(_)) => (),
+                Ok(Err(err)) => warn!("could not archive response: {}", err),
+                Err(err) => warn!("could not archive response: {}", err),
+            }
+        }
+        if !status.is_success() {
+            return Err(TaxiiError::HttpStatus(status.as_u16()));
+        }
+        Ok(response_body)
+    }
+
+    async fn wait(&self, estimated_wait: Option<u32>) {
+        let duration = match estimated_wait {
+            Some(v) => Duration::from_secs(v as u64),
+            None => self.default_wait,
+        };
+        tokio::time::sleep(duration.min(self.max_wait)).await;
+    }
+
+    /// Send a TAXII message, re-sending it while the server answers RETRY. Returns either the
+    /// response body or the status message the server answered with.
+    async fn exchange(
+        &self,
+        url: &str,
+        request_body: &str,
+    ) -> Result<Result<String, StatusMessage>, TaxiiError> {
+        let mut attempt = 0;
+        loop {
+            let response_body = self.send(url, request_body).await?;
+            let status_message = match parse_status_message(response_body.as_bytes()) {
+                Ok(v) => v,
+                Err(_) => return Ok(Ok(response_body)),
+            };
+            match status_message.status_type {
+                StatusType::Retry { estimated_wait } if attempt < self.max_retries => {
+                    attempt += 1;
+                    self.wait(estimated_wait).await;
+                }
+                _ => return Ok(Err(status_message)),
+            }
+        }
+    }
+
+    /// POST a TAXII message to `url` and return the response body. A `Status_Message` returned in
+    /// place of the expected response is reported as an error.
+    pub async fn request(&self, url: &str, request_body: String) -> Result<String, TaxiiError> {
+        match self.exchange(url, request_body.as_str()).await? {
+            Ok(v) => Ok(v),
+            Err(status_message) => Err(TaxiiError::Protocol(status_message.to_string())),
+        }
+    }
+
+    pub async fn discovery(&self, url: &str) -> Result<ServiceSet, TaxiiError> {
+        let request_body = create_discovery_request_body(self.ver)?;
+        let response_body = self.request(url, request_body).await?;
+        parse_discovery_response(response_body.as_bytes())
+    }
+
+    pub async fn collection_information(&self, url: &str) -> Result<CollectionSet, TaxiiError> {
+        let request_body = create_collection_information_request_body(self.ver)?;
+        let response_body = self.request(url, request_body).await?;
+        parse_collection_information_response(response_body.as_bytes())
+    }
+
+    /// Poll a collection. When the server answers PENDING, the result is fetched with
+    /// Poll_Fulfillment requests unless the server said it will push it, in which case the status
+    /// message, with its RESULT_ID, is the error. The parts of a multipart result are all fetched
+    /// and returned as a single `Poll_Response` holding the content blocks of every part.
+    pub async fn poll(
+        &self,
+        url: &str,
+        collection_name: &str,
+        time_range: Option<TimeRange>,
+        subscription_id: &str,
+        poll_parameters: Option<PollParameters>,
+    ) -> Result<PollResponse, TaxiiError> {
+        let response_body = self
+            .poll_result(
+                url,
+                collection_name,
+                time_range,
+                subscription_id,
+                poll_parameters,
+            )
+            .await?;
+        let mut poll_response = parse_poll_response(response_body.as_bytes())?;
+        while poll_response.more {
+            let result_id = match &poll_response.result_id {
+                Some(v) => v.clone(),
+                None => {
+                    return Err(TaxiiError::Protocol(String::from(
+                        "missing Result_ID in a poll response with more parts",
+                    )))
+                }
+            };
+            let result_part_number = poll_response.result_part_number + 1;
+            let request_body = create_poll_fulfillment_request_body(
+                self.ver,
+                collection_name,
+                result_id.as_str(),
+                result_part_number,
+            )?;
+            let response_body = self.request(url, request_body).await?;
+            let mut part = parse_poll_response(response_body.as_bytes())?;
+            if part.result_part_number != result_part_number {
+                return Err(TaxiiError::Protocol(format!(
+                    "expected part {} of result {} but got part {}",
+                    result_part_number, result_id, part.result_part_number
+                )));
+            }
+            poll_response
+                .content_blocks
+                .append(&mut part.content_blocks);
+            poll_response.more = part.more;
+            poll_response.result_part_number = part.result_part_number;
+        }
+        Ok(poll_response)
+    }
+
+    /// Poll a collection and return the raw `Poll_Response` document of the first part of the
+    /// result, waiting for it while the server answers PENDING.
+    async fn poll_result(
+        &self,
+        url: &str,
+        collection_name: &str,
+        time_range: Option<TimeRange>,
+        subscription_id: &str,
+        poll_parameters: Option<PollParameters>,
+    ) -> Result<String, TaxiiError> {
+        let request_body = create_poll_request_body(
+            self.ver,
+            collection_name,
+            time_range,
+            subscription_id,
+            poll_parameters,
+        )?;
+        let mut response = self.exchange(url, request_body.as_str()).await?;
+        let mut attempt = 0;
+        loop {
+            let status_message = match response {
+                Ok(v) => return Ok(v),
+                Err(v) => v,
+            };
+            match status_message.status_type {
+                StatusType::Pending {
+                    estimated_wait,
+                    result_id: Some(ref result_id),
+                    will_push,
+                } if will_push != Some(true) && attempt < self.max_retries => {
+                    attempt += 1;
+                    self.wait(estimated_wait).await;
+                    let request_body = create_poll_fulfillment_request_body(
+                        self.ver,
+                        collection_name,
+                        result_id,
+                        1,
+                    )?;
+                    response = self.exchange(url, request_body.as_str()).await?;
+                }
+                _ => return Err(TaxiiError::Protocol(status_message.to_string())),
+            }
+        }
+    }
+
+    async fn subscription_management(
+        &self,
+        url: &str,
+        action: SubscribeAction,
+        collection_name: &str,
+        subscription_id: Option<&str>,
+        subscription_parameters: Option<&SubscriptionParameters>,
+        push_parameters: Option<&PushParameters>,
+    ) -> Result<SubscriptionResponse, TaxiiError> {
+        let request_body = create_subscribe_request_body(
+            self.ver,
+            action,
+            collection_name,
+            subscription_id,
+            subscription_parameters,
+            push_parameters,
+        )?;
+        let response_body = self.request(url, request_body).await?;
+        parse_subscription_management_response(response_body.as_bytes())
+    }
+
+    pub async fn subscribe(
+        &self,
+        url: &str,
+        collection_name: &str,
+    ) -> Result<SubscriptionResponse, TaxiiError> {
+        self.subscribe_with(url, collection_name, None, None).await
+    }
+
+    /// Subscribe with explicit subscription parameters, and push parameters to have the server
+    /// deliver content to an inbox rather than wait to be polled.
+    pub async fn subscribe_with(
+        &self,
+        url: &str,
+        collection_name: &str,
+        subscription_parameters: Option<&SubscriptionParameters>,
+        push_parameters: Option<&PushParameters>,
+    ) -> Result<SubscriptionResponse, TaxiiError> {
+        self.subscription_management(
+            url,
+            SubscribeAction::Subscribe,
+            collection_name,
+            None,
+            subscription_parameters,
+            push_parameters,
+        )
+        .await
+    }
+
+    pub async fn unsubscribe(
+        &self,
+        url: &str,
+        collection_name: &str,
+        subscription_id: &str,
+    ) -> Result<SubscriptionResponse, TaxiiError> {
+        self.subscription_management(
+            url,
+            SubscribeAction::Unsubscribe,
+            collection_name,
+            Some(subscription_id),
+            None,
+            None,
+        )
+        .await
+    }
+
+    pub async fn pause(
+        &self,
+        url: &str,
+        collection_name: &str,
+        subscription_id: &str,
+    ) -> Result<SubscriptionResponse, TaxiiError> {
+        self.subscription_management(
+            url,
+            SubscribeAction::Pause,
+            collection_name,
+            Some(subscription_id),
+            None,
+            None,
+        )
+        .await
+    }
+
+    pub async fn resume(
+        &self,
+        url: &str,
+        collection_name: &str,
+        subscription_id: &str,
+    ) -> Result<SubscriptionResponse, TaxiiError> {
+        self.subscription_management(
+            url,
+            SubscribeAction::Resume,
+            collection_name,
+            Some(subscription_id),
+            None,
+            None,
+        )
+        .await
+    }
+
+    /// Get the status of one subscription, or of all the requester's subscriptions on the
+    /// collection when `subscription_id` is `None`.
+    pub async fn status(
+        &self,
+        url: &str,
+        collection_name: &str,
+        subscription_id: Option<&str>,
+    ) -> Result<SubscriptionResponse, TaxiiError> {
+        self.subscription_management(
+            url,
+            SubscribeAction::Status,
+            collection_name,
+            subscription_id,
+            None,
+            None,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env,
+        fs::read_to_string,
+        net::SocketAddr,
+        path::Path,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::{Duration, Instant},
+    };
+
+    use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+
+    use super::AsyncClient;
+    use crate::taxii::{errors::TaxiiError, services::ServiceType, version::Version};
+
+    fn read_sample(name: &str) -> String {
+        let path = env::var("CARGO_MANIFEST_DIR").unwrap();
+        let path = Path::new(path.as_str()).join("test").join(name);
+        read_to_string(path).unwrap()
+    }
+
+    /// Answer with the sample matching the request. Under `/busy/` the first request is answered
+    /// RETRY, under `/async/` polls are answered PENDING and under `/error/` requests fail. Poll
+    /// results have two parts, the sample poll response being the first one.
+    async fn handle_sample(
+        req: HttpRequest,
+        body: String,
+        requests: web::Data<AtomicUsize>,
+    ) -> HttpResponse {
+        let binding = req.headers().get("X-TAXII-Content-Type");
+        if binding.is_none() || binding.unwrap() != "urn:taxii.mitre.org:message:xml:1.1" {
+            return HttpResponse::BadRequest().finish();
+        }
+        if req.path().starts_with("/error/") {
+            return HttpResponse::InternalServerError().finish();
+        }
+        let count = requests.fetch_add(1, Ordering::SeqCst);
+        let sample = if req.path().starts_with("/busy/") && count == 0 {
+            "sample-status-message-response-retry.xml"
+        } else if req.path().starts_with("/async/") && body.contains("Poll_Request") {
+            "sample-status-message-response-pending.xml"
+        } else if body.contains("Poll_Fulfillment") {
+            "sample-poll-response.xml"
+        } else if body.contains("Discovery_Request") {
+            "sample-discovery-response.xml"
+        } else if body.contains("Collection_Information_Request") {
+            "sample-collection-information-response.xml"
+        } else if body.contains("action=\"STATUS\"") {
+            "sample-subscription-management-response-status.xml"
+        } else if body.contains("Subscription_Management_Request") {
+            "sample-subscription-management-response-subscribe.xml"
+        } else {
+            "sample-status-message-response-failure.xml"
+        };
+        let mut response = read_sample(sample);
+        if body.contains("Poll_Fulfillment") && body.contains("result_part_number=\"2\"") {
+            response = response.replace(
+                "more=\"true\" result_part_number=\"1\"",
+                "more=\"false\" result_part_number=\"2\"",
+            );
+        }
+        HttpResponse::Ok()
+            .append_header(("Content-Type", "application/xml"))
+            .body(response)
+    }
+
+    fn start_sample_server() -> SocketAddr {
+        let requests = web::Data::new(AtomicUsize::new(0));
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(requests.clone())
+                .default_service(web::post().to(handle_sample))
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let addr = server.addrs()[0];
+        actix_web::rt::spawn(server.run());
+        addr
+    }
+
+    #[actix_web::test]
+    async fn test_async_client_concurrent_requests() {
+        let addr = start_sample_server();
+        let client = AsyncClient::new("guest", "guest", Version::V11);
+        let discovery_url = format!("http://{}/services/discovery", addr);
+        let collection_url = format!("http://{}/services/collection-management", addr);
+        let (service_set, collection_set, subscription_response) = futures::join!(
+            client.discovery(discovery_url.as_str()),
+            client.collection_information(collection_url.as_str()),
+            client.subscribe(collection_url.as_str(), "stix-data"),
+        );
+        let service_set = service_set.unwrap();
+        assert_eq!(8, service_set.services.len());
+        assert_eq!(ServiceType::Discovery, service_set.services[7].service_type);
+        let collection_set = collection_set.unwrap();
+        assert_eq!(5, collection_set.collections.len());
+        assert_eq!("stix-data", collection_set.collections[1].collection_name);
+        assert!(subscription_response.is_ok());
+    }
+
+    #[actix_web::test]
+    async fn test_async_client_status_message() {
+        let addr = start_sample_server();
+        let client = AsyncClient::new("guest", "guest", Version::V11);
+        let poll_url = format!("http://{}/services/poll", addr);
+        let result = client
+            .poll(poll_url.as_str(), "stix-data", None, "1234", None)
+            .await;
+        match result {
+            Ok(_) => panic!("expected the status message to be reported as an error"),
+            Err(err) => {
+                assert!(matches!(err, TaxiiError::Protocol(_)));
+                assert!(err.to_string().contains("FAILURE"));
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_async_client_errors() {
+        let addr = start_sample_server();
+        let client = AsyncClient::new("guest", "guest", Version::V11);
+        let url = format!("http://{}/error/discovery", addr);
+        match client.discovery(url.as_str()).await {
+            Err(TaxiiError::HttpStatus(500)) => (),
+            result => panic!("expected an HTTP status error: {:?}", result.err()),
+        }
+
+        // nothing listens on port 1
+        match client
+            .discovery("http://127.0.0.1:1/services/discovery")
+            .await
+        {
+            Err(TaxiiError::Transport(_)) => (),
+            result => panic!("expected a transport error: {:?}", result.err()),
+        }
+
+        let client = AsyncClient::new("guest", "guest", Version::V21);
+        let url = format!("http://{}/services/discovery", addr);
+        match client.discovery(url.as_str()).await {
+            Err(TaxiiError::UnsupportedVersion { version, .. }) => {
+                assert_eq!(Version::V21, version)
+            }
+            result => panic!("expected an unsupported version error: {:?}", result.err()),
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_async_client_retry() {
+        let addr = start_sample_server();
+        let discovery_url = format!("http://{}/busy/discovery", addr);
+        let client = AsyncClient::new("guest", "guest", Version::V11)
+            .with_retry_policy(0, Duration::from_millis(10));
+        match client.discovery(discovery_url.as_str()).await {
+            Ok(_) => panic!("expected the RETRY status message to be reported as an error"),
+            Err(err) => assert!(err.to_string().contains("RETRY ESTIMATED_WAIT=1")),
+        }
+        // the server only answers RETRY to its first request
+        let addr = start_sample_server();
+        let discovery_url = format!("http://{}/busy/discovery", addr);
+        let client = AsyncClient::new("guest", "guest", Version::V11);
+        let service_set = client.discovery(discovery_url.as_str()).await.unwrap();
+        assert_eq!(8, service_set.services.len());
+
+        // the ESTIMATED_WAIT of a second is clamped to the maximum wait
+        let addr = start_sample_server();
+        let discovery_url = format!("http://{}/busy/discovery", addr);
+        let client = AsyncClient::new("guest", "guest", Version::V11)
+            .with_max_wait(Duration::from_millis(10));
+        let start = Instant::now();
+        client.discovery(discovery_url.as_str()).await.unwrap();
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[actix_web::test]
+    async fn test_async_client_pending_poll() {
+        let addr = start_sample_server();
+        let poll_url = format!("http://{}/async/poll", addr);
+        let client = AsyncClient::new("guest", "guest", Version::V11);
+        let poll_response = client
+            .poll(poll_url.as_str(), "stix-data", None, "1234", None)
+            .await
+            .unwrap();
+        // both parts of the result were fetched
+        assert!(!poll_response.more);
+        assert_eq!(2, poll_response.result_part_number);
+        assert_eq!(20, poll_response.content_blocks.len());
+    }
+
+    #[actix_web::test]
+    async fn test_async_client_subscription_status() {
+        let addr = start_sample_server();
+        let client = AsyncClient::new("guest", "guest", Version::V11);
+        let url = format!("http://{}/services/collection-management", addr);
+        let subscription_response = client
+            .status(url.as_str(), "stix-data", None)
+            .await
+            .unwrap();
+        assert_eq!(2, subscription_response.subscriptions.len());
+        let subscription_response = client
+            .pause(url.as_str(), "stix-data", "8954140241256270840")
+            .await
+            .unwrap();
+        assert_eq!(
+            "8954140241256270840",
+            subscription_response.subscriptions[0].id
+        );
+    }
+}
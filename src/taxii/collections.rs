@@ -1,30 +1,69 @@
-use xml::reader::{EventReader, XmlEvent};
-
-use super::errors::MyError;
+use super::{
+    errors::TaxiiError,
+    message::{
+        collection_name_attr, from_xml, message_id, parse_bool, to_xml, Element, FromElement,
+        ToElement,
+    },
+    types::ContentBinding,
+    version::Version,
+};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum CollectionType {
     Unknown,
     DataFeed,
+    DataSet,
 }
 
 impl CollectionType {
-    pub fn parse(v: &str) -> Result<CollectionType, MyError> {
+    pub fn parse(v: &str) -> Result<CollectionType, TaxiiError> {
         match v {
             "DATA_FEED" => Ok(CollectionType::DataFeed),
-            _ => Err(MyError(format!("could not parse: {}", v))),
+            "DATA_SET" => Ok(CollectionType::DataSet),
+            _ => Err(TaxiiError::Protocol(format!("could not parse: {}", v))),
+        }
+    }
+    pub fn to_str(&self) -> &str {
+        match self {
+            CollectionType::Unknown => "UNKNOWN",
+            CollectionType::DataFeed => "DATA_FEED",
+            CollectionType::DataSet => "DATA_SET",
         }
     }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum CollectionServiceType {
+    PushMethod,
     PollingService,
     SubscriptionService,
     ReceivingInboxService,
 }
 
-#[derive(Clone)]
+impl CollectionServiceType {
+    pub fn parse(v: &str) -> Result<CollectionServiceType, TaxiiError> {
+        match v {
+            "Push_Method" => Ok(CollectionServiceType::PushMethod),
+            "Polling_Service" => Ok(CollectionServiceType::PollingService),
+            "Subscription_Service" => Ok(CollectionServiceType::SubscriptionService),
+            "Receiving_Inbox_Service" => Ok(CollectionServiceType::ReceivingInboxService),
+            _ => Err(TaxiiError::Protocol(format!("could not parse: {}", v))),
+        }
+    }
+    /// The tag of the service in a Collection_Information_Response.
+    pub fn to_str(&self) -> &str {
+        match self {
+            CollectionServiceType::PushMethod => "Push_Method",
+            CollectionServiceType::PollingService => "Polling_Service",
+            CollectionServiceType::SubscriptionService => "Subscription_Service",
+            CollectionServiceType::ReceivingInboxService => "Receiving_Inbox_Service",
+        }
+    }
+}
+
+/// A service of a collection. A Push_Method has no address: it is a way the server can push
+/// content to subscribers.
+#[derive(Clone, Debug, PartialEq)]
 pub struct CollectionService {
     pub collection_service_type: CollectionServiceType,
     pub protocol_binding: String,
@@ -35,17 +74,17 @@ pub struct CollectionService {
 
 impl CollectionService {
     pub fn new(collection_service_type: CollectionServiceType) -> CollectionService {
-        return CollectionService {
+        CollectionService {
             collection_service_type,
             protocol_binding: String::from(""),
             address: String::from(""),
             message_bindings: Vec::<String>::new(),
             content_bindings: Vec::<String>::new(),
-        };
+        }
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Collection {
     pub collection_name: String,
     pub collection_type: CollectionType,
@@ -70,229 +109,274 @@ impl Collection {
     }
 }
 
+/// A Collection_Information_Response, or the Feed_Information_Response of TAXII 1.0, whose feeds
+/// are read as DATA_FEED collections.
+#[derive(Clone, Debug, PartialEq)]
 pub struct CollectionSet {
-    collections: Vec<Collection>,
+    pub message_id: String,
+    pub in_response_to: String,
+    pub collections: Vec<Collection>,
+}
+
+impl Default for CollectionSet {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CollectionSet {
     pub fn new() -> CollectionSet {
-        return CollectionSet {
+        CollectionSet {
+            message_id: String::from(""),
+            in_response_to: String::from(""),
             collections: Vec::<Collection>::new(),
-        };
+        }
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
-enum CollectionTags {
-    CollectionInformationResponse,
-    Collection,
-    Description,
-    CollectionVolume,
-    ContentBinding,
-    MessageBinding,
-    Address,
-    ProtocolBinding,
-    PollingService,
-    SubscriptionService,
-    ReceivingInboxService,
+impl FromElement for CollectionService {
+    fn from_element(element: &Element) -> Result<CollectionService, TaxiiError> {
+        let collection_service_type = CollectionServiceType::parse(element.local_name())?;
+        element.check_elements(&[
+            "Protocol_Binding",
+            "Address",
+            "Message_Binding",
+            "Content_Binding",
+        ])?;
+        Ok(CollectionService {
+            collection_service_type,
+            protocol_binding: element.element_text("Protocol_Binding").unwrap_or_default(),
+            address: element.element_text("Address").unwrap_or_default(),
+            message_bindings: element
+                .elements_named("Message_Binding")
+                .map(|e| e.text())
+                .collect(),
+            content_bindings: content_bindings(element)?,
+        })
+    }
 }
 
-impl CollectionTags {
-    fn parse(tag: &str) -> Result<CollectionTags, MyError> {
-        match tag {
-            "Collection_Information_Response" => Ok(CollectionTags::CollectionInformationResponse),
-            "Collection" => Ok(CollectionTags::Collection),
-            "Description" => Ok(CollectionTags::Description),
-            "Collection_Volume" => Ok(CollectionTags::CollectionVolume),
-            "Content_Binding" => Ok(CollectionTags::ContentBinding),
-            "Message_Binding" => Ok(CollectionTags::MessageBinding),
-            "Address" => Ok(CollectionTags::Address),
-            "Protocol_Binding" => Ok(CollectionTags::ProtocolBinding),
-            "Polling_Service" => Ok(CollectionTags::PollingService),
-            "Subscription_Service" => Ok(CollectionTags::SubscriptionService),
-            "Receiving_Inbox_Service" => Ok(CollectionTags::ReceivingInboxService),
-            _ => Err(MyError(format!("could not parse tag: {}", tag))),
+impl ToElement for CollectionService {
+    fn to_element(&self, ver: Version) -> Result<Element, TaxiiError> {
+        let mut element = Element::new(ver, self.collection_service_type.to_str()).with_element(
+            Element::text_element(ver, "Protocol_Binding", self.protocol_binding.as_str()),
+        );
+        if self.collection_service_type != CollectionServiceType::PushMethod {
+            element.push(Element::text_element(ver, "Address", self.address.as_str()));
         }
-    }
-    fn matches_expected_depth(&self, depth: usize) -> bool {
-        match self {
-            CollectionTags::CollectionInformationResponse => depth == 0,
-            CollectionTags::Collection => depth == 1,
-            CollectionTags::Description => depth == 2,
-            CollectionTags::CollectionVolume => depth == 2,
-            CollectionTags::ContentBinding => depth == 2 || depth == 3,
-            CollectionTags::MessageBinding => depth == 3,
-            CollectionTags::Address => depth == 3,
-            CollectionTags::ProtocolBinding => depth == 3,
-            CollectionTags::PollingService => depth == 2,
-            CollectionTags::SubscriptionService => depth == 2,
-            CollectionTags::ReceivingInboxService => depth == 2,
+        for message_binding in self.message_bindings.iter() {
+            element.push(Element::text_element(
+                ver,
+                "Message_Binding",
+                message_binding.as_str(),
+            ));
         }
-    }
-    fn to_str(&self) -> &str {
-        match self {
-            CollectionTags::CollectionInformationResponse => "Collection_Information_Response",
-            CollectionTags::Collection => "Collection",
-            CollectionTags::Description => "Description",
-            CollectionTags::CollectionVolume => "Collection_Volume",
-            CollectionTags::ContentBinding => "Content_Binding",
-            CollectionTags::MessageBinding => "Message_Binding",
-            CollectionTags::Address => "Address",
-            CollectionTags::ProtocolBinding => "Protocol_Binding",
-            CollectionTags::PollingService => "Polling_Service",
-            CollectionTags::SubscriptionService => "Subscription_Service",
-            CollectionTags::ReceivingInboxService => "Receiving_DataFeed_Service",
+        if self.collection_service_type == CollectionServiceType::ReceivingInboxService {
+            for content_binding in self.content_bindings.iter() {
+                element.push(content_binding_element(ver, content_binding));
+            }
         }
+        Ok(element)
     }
 }
 
-pub fn parse_collection_information_response(doc: &[u8]) -> Result<CollectionSet, MyError> {
-    let mut tag_stack = Vec::<CollectionTags>::new();
-    let mut collection_set = CollectionSet::new();
-    let mut cur_collection = Collection::new_empty();
-    let mut cur_service: Option<CollectionService> = None;
-    let mut last_value: String = String::new();
-    let xml_parser = EventReader::new(doc);
-    for e in xml_parser {
-        match e {
-            Ok(XmlEvent::StartElement {
-                name, attributes, ..
-            }) => {
-                let tag = match CollectionTags::parse(name.local_name.as_str()) {
-                    Ok(v) => v,
-                    Err(err) => return Err(err),
-                };
-                if !tag.matches_expected_depth(tag_stack.len()) {
-                    return Err(MyError(format!(
-                        "tag at unexpected depth of {}: {}",
-                        tag_stack.len(),
-                        name.local_name.as_str()
-                    )));
-                }
-                tag_stack.push(tag);
-                match tag {
-                    CollectionTags::Collection => {
-                        for attr in attributes {
-                            match attr.name.local_name.as_str() {
-                                "collection_name" => {
-                                    cur_collection.collection_name = attr.value.clone()
-                                }
-                                "collection_type" => {
-                                    cur_collection.collection_type =
-                                        CollectionType::parse(attr.value.as_str())?
-                                }
-                                "available" => {
-                                    cur_collection.available = attr.value.to_lowercase().eq("true")
-                                }
-                                _ => {
-                                    return Err(MyError(format!(
-                                        "unrecogized attribute: {}",
-                                        attr.name.local_name
-                                    )))
-                                }
-                            }
-                        }
-                    }
-                    CollectionTags::ContentBinding => {
-                        for attr in attributes {
-                            match attr.name.local_name.as_str() {
-                                "binding_id" => match cur_service {
-                                    Some(ref mut v) => v.content_bindings.push(attr.value.clone()),
-                                    None => {
-                                        cur_collection.content_bindings.push(attr.value.clone())
-                                    }
-                                },
-                                _ => {
-                                    return Err(MyError(format!(
-                                        "unrecogized attribute: {}",
-                                        attr.name.local_name
-                                    )))
-                                }
-                            }
-                        }
-                    }
-                    CollectionTags::PollingService => {
-                        cur_service = Some(CollectionService::new(
-                            CollectionServiceType::PollingService,
-                        ));
-                    }
-                    CollectionTags::SubscriptionService => {
-                        cur_service = Some(CollectionService::new(
-                            CollectionServiceType::SubscriptionService,
-                        ));
-                    }
-                    CollectionTags::ReceivingInboxService => {
-                        cur_service = Some(CollectionService::new(
-                            CollectionServiceType::ReceivingInboxService,
-                        ));
-                    }
-                    // We only match on tags that we need to parse attributes from. This default
-                    // match is therefore: keep calm and carry on.
-                    _ => (),
-                }
-            }
-            Ok(XmlEvent::EndElement { name }) => {
-                let end_tag = CollectionTags::parse(name.local_name.as_str())?;
-                let tag = tag_stack.pop();
-                if tag.is_none() || tag.unwrap() != end_tag {
-                    return Err(MyError(format!("malformed XML response")));
-                }
-                match end_tag {
-                    CollectionTags::CollectionInformationResponse => {}
-                    CollectionTags::Collection => {
-                        collection_set.collections.push(cur_collection.clone());
-                        cur_collection = Collection::new_empty();
-                    }
-                    CollectionTags::Description => {
-                        cur_collection.description = last_value.clone();
-                    }
-                    CollectionTags::CollectionVolume => {
-                        cur_collection.collection_volume = last_value.clone();
-                    }
-                    CollectionTags::ContentBinding => {
-                        // Nothing to do, that attributes are extracted in the StartElement handlers
-                    }
-                    CollectionTags::MessageBinding => match cur_service {
-                        Some(ref mut v) => v.message_bindings.push(last_value.clone()),
-                        None => return Err(MyError(format!("unexpected Address tag"))),
-                    },
-                    CollectionTags::Address => match cur_service {
-                        Some(ref mut v) => v.address = last_value.clone(),
-                        None => return Err(MyError(format!("unexpected Address tag"))),
-                    },
-                    CollectionTags::ProtocolBinding => match cur_service {
-                        Some(ref mut v) => v.protocol_binding = last_value.clone(),
-                        None => return Err(MyError(format!("unexpected Protocol_Binding tag"))),
-                    },
-                    CollectionTags::PollingService
-                    | CollectionTags::SubscriptionService
-                    | CollectionTags::ReceivingInboxService => {
-                        match cur_service {
-                            Some(v) => cur_collection.collection_services.push(v.clone()),
-                            None => return Err(MyError(format!("unexpected end tag for service"))),
-                        }
-                        cur_service = None
-                    }
-                }
-            }
-            Ok(XmlEvent::Characters(ref data)) => {
-                last_value = data.clone();
+pub(crate) fn content_bindings(element: &Element) -> Result<Vec<String>, TaxiiError> {
+    element
+        .elements_named("Content_Binding")
+        .map(|e| ContentBinding::from_element(e).map(|v| v.binding_id))
+        .collect()
+}
+
+pub(crate) fn content_binding_element(ver: Version, binding_id: &str) -> Element {
+    match ver {
+        Version::V10 => Element::text_element(ver, "Content_Binding", binding_id),
+        _ => Element::new(ver, "Content_Binding").with_attr("binding_id", binding_id),
+    }
+}
+
+// TAXII 1.0 has feeds where 1.1 has collections
+fn collection_tag(ver: Version) -> &'static str {
+    match ver {
+        Version::V10 => "Feed",
+        _ => "Collection",
+    }
+}
+
+fn collection_information_tag(ver: Version, message: &str) -> String {
+    match ver {
+        Version::V10 => format!("Feed_Information_{}", message),
+        _ => format!("Collection_Information_{}", message),
+    }
+}
+
+impl FromElement for Collection {
+    fn from_element(element: &Element) -> Result<Collection, TaxiiError> {
+        let ver = element.version();
+        element.expect(collection_tag(ver))?;
+        element.check_elements(&[
+            "Description",
+            "Collection_Volume",
+            "Content_Binding",
+            "Push_Method",
+            "Polling_Service",
+            "Subscription_Service",
+            "Receiving_Inbox_Service",
+        ])?;
+        let collection_type = match (ver, element.attr("collection_type")) {
+            (Version::V10, _) => CollectionType::DataFeed,
+            (_, Some(v)) => CollectionType::parse(v)?,
+            (_, None) => CollectionType::Unknown,
+        };
+        Ok(Collection {
+            collection_name: String::from(
+                element.attr(collection_name_attr(ver)).unwrap_or_default(),
+            ),
+            collection_type,
+            available: element.attr("available").is_some_and(parse_bool),
+            description: element.element_text("Description").unwrap_or_default(),
+            collection_volume: element
+                .element_text("Collection_Volume")
+                .unwrap_or_default(),
+            content_bindings: content_bindings(element)?,
+            collection_services: element
+                .elements()
+                .filter(|e| CollectionServiceType::parse(e.local_name()).is_ok())
+                .map(CollectionService::from_element)
+                .collect::<Result<Vec<_>, _>>()?,
+        })
+    }
+}
+
+impl ToElement for Collection {
+    fn to_element(&self, ver: Version) -> Result<Element, TaxiiError> {
+        let mut element = Element::new(ver, collection_tag(ver))
+            .with_attr(collection_name_attr(ver), self.collection_name.as_str());
+        if ver != Version::V10 {
+            element.set_attr("collection_type", self.collection_type.to_str());
+        }
+        element.set_attr("available", self.available.to_string().as_str());
+        element.push(Element::text_element(
+            ver,
+            "Description",
+            self.description.as_str(),
+        ));
+        if ver != Version::V10 && !self.collection_volume.is_empty() {
+            element.push(Element::text_element(
+                ver,
+                "Collection_Volume",
+                self.collection_volume.as_str(),
+            ));
+        }
+        for content_binding in self.content_bindings.iter() {
+            element.push(content_binding_element(ver, content_binding));
+        }
+        // the schema orders the services by type
+        let service_types = [
+            CollectionServiceType::PushMethod,
+            CollectionServiceType::PollingService,
+            CollectionServiceType::SubscriptionService,
+            CollectionServiceType::ReceivingInboxService,
+        ];
+        for service_type in service_types.iter() {
+            // TAXII 1.0 feeds have no inbox services
+            if ver == Version::V10 && *service_type == CollectionServiceType::ReceivingInboxService
+            {
+                continue;
             }
-            Err(e) => {
-                return Err(MyError(e.to_string()));
+            for service in self
+                .collection_services
+                .iter()
+                .filter(|v| v.collection_service_type == *service_type)
+            {
+                element.push(service.to_element(ver)?);
             }
-            _ => {}
         }
+        Ok(element)
+    }
+}
+
+impl FromElement for CollectionSet {
+    fn from_element(element: &Element) -> Result<CollectionSet, TaxiiError> {
+        let ver = element.version();
+        element.expect(collection_information_tag(ver, "Response").as_str())?;
+        element.check_elements(&[collection_tag(ver)])?;
+        Ok(CollectionSet {
+            message_id: String::from(element.attr("message_id").unwrap_or_default()),
+            in_response_to: String::from(element.attr("in_response_to").unwrap_or_default()),
+            collections: element
+                .elements()
+                .map(Collection::from_element)
+                .collect::<Result<Vec<_>, _>>()?,
+        })
+    }
+}
+
+impl ToElement for CollectionSet {
+    fn to_element(&self, ver: Version) -> Result<Element, TaxiiError> {
+        let mut element = Element::new(ver, collection_information_tag(ver, "Response").as_str())
+            .with_attr(
+                "message_id",
+                message_id(ver, self.message_id.as_str())?.as_str(),
+            )
+            .with_attr("in_response_to", self.in_response_to.as_str());
+        for collection in self.collections.iter() {
+            element.push(collection.to_element(ver)?);
+        }
+        Ok(element)
+    }
+}
+
+pub fn parse_collection_information_response(doc: &[u8]) -> Result<CollectionSet, TaxiiError> {
+    from_xml(doc)
+}
+
+pub fn create_collection_information_response_body(
+    ver: Version,
+    in_response_to: &str,
+    collection_set: &CollectionSet,
+) -> Result<String, TaxiiError> {
+    let mut collection_set = collection_set.clone();
+    collection_set.in_response_to = String::from(in_response_to);
+    to_xml(ver, &collection_set)
+}
+
+/// A Collection_Information_Request, asking a collection management service for its
+/// collections, or a Feed_Information_Request to a TAXII 1.0 feed management service.
+#[derive(Clone, Debug)]
+pub struct CollectionInformationRequest {
+    pub message_id: String,
+}
+
+impl FromElement for CollectionInformationRequest {
+    fn from_element(element: &Element) -> Result<CollectionInformationRequest, TaxiiError> {
+        element.expect(collection_information_tag(element.version(), "Request").as_str())?;
+        Ok(CollectionInformationRequest {
+            message_id: String::from(element.attr("message_id").unwrap_or_default()),
+        })
+    }
+}
+
+impl ToElement for CollectionInformationRequest {
+    fn to_element(&self, ver: Version) -> Result<Element, TaxiiError> {
+        Ok(
+            Element::new(ver, "Collection_Information_Request").with_attr(
+                "message_id",
+                message_id(ver, self.message_id.as_str())?.as_str(),
+            ),
+        )
     }
-    Ok(collection_set)
 }
 
 #[cfg(test)]
 mod tests {
     use std::{env, fs::read_to_string, path::Path};
 
-    use crate::taxii::collections::{
-        parse_collection_information_response, CollectionServiceType, CollectionType,
+    use crate::taxii::{
+        collections::{
+            create_collection_information_response_body, parse_collection_information_response,
+            CollectionServiceType, CollectionType,
+        },
+        version::Version,
     };
 
     #[test]
@@ -306,7 +390,7 @@ mod tests {
         };
         assert_eq!(5, collection_set.collections.len());
         for collection in collection_set.collections.iter() {
-            assert_eq!(collection.available, true);
+            assert!(collection.available);
             assert_eq!(collection.collection_type, CollectionType::DataFeed);
         }
         // collection[0]
@@ -375,11 +459,10 @@ mod tests {
                 "urn:stix.mitre.org:xml:1.1.1",
                 "urn:stix.mitre.org:xml:1.2",
             ];
-            for pos in 0..expected_collection1_content_bindings.len() {
-                assert_eq!(
-                    expected_collection1_content_bindings[pos],
-                    collection1.content_bindings[pos]
-                );
+            for (pos, expected_content_binding) in
+                expected_collection1_content_bindings.iter().enumerate()
+            {
+                assert_eq!(*expected_content_binding, collection1.content_bindings[pos]);
             }
             let expected1_service_types = [
                 CollectionServiceType::PollingService,
@@ -421,13 +504,15 @@ mod tests {
                     collection1.collection_services[pos].message_bindings[1]
                 );
             }
-            for pos in 0..expected_collection1_content_bindings.len() {
+            for (pos, expected_content_binding) in
+                expected_collection1_content_bindings.iter().enumerate()
+            {
                 assert_eq!(
-                    expected_collection1_content_bindings[pos],
+                    *expected_content_binding,
                     collection1.collection_services[4].content_bindings[pos]
                 );
                 assert_eq!(
-                    expected_collection1_content_bindings[pos],
+                    *expected_content_binding,
                     collection1.collection_services[5].content_bindings[pos]
                 );
             }
@@ -445,11 +530,10 @@ mod tests {
                 "urn:oasis:names:tc:emergency:cap:1.1",
                 "urn:oasis:names:tc:emergency:cap:1.2",
             ];
-            for pos in 0..expected_collection2_content_bindings.len() {
-                assert_eq!(
-                    expected_collection2_content_bindings[pos],
-                    collection2.content_bindings[pos]
-                );
+            for (pos, expected_content_binding) in
+                expected_collection2_content_bindings.iter().enumerate()
+            {
+                assert_eq!(*expected_content_binding, collection2.content_bindings[pos]);
             }
             let expected2_service_types = [
                 CollectionServiceType::PollingService,
@@ -491,13 +575,15 @@ mod tests {
                     collection2.collection_services[pos].message_bindings[1]
                 );
             }
-            for pos in 0..expected_collection2_content_bindings.len() {
+            for (pos, expected_content_binding) in
+                expected_collection2_content_bindings.iter().enumerate()
+            {
                 assert_eq!(
-                    expected_collection2_content_bindings[pos],
+                    *expected_content_binding,
                     collection2.collection_services[4].content_bindings[pos]
                 );
                 assert_eq!(
-                    expected_collection2_content_bindings[pos],
+                    *expected_content_binding,
                     collection2.collection_services[5].content_bindings[pos]
                 );
             }
@@ -512,11 +598,10 @@ mod tests {
             );
             assert_eq!(1, collection3.content_bindings.len());
             let expected_collection3_content_bindings = ["http://www.w3.org/2001/04/xmlenc#"];
-            for pos in 0..expected_collection3_content_bindings.len() {
-                assert_eq!(
-                    expected_collection3_content_bindings[pos],
-                    collection3.content_bindings[pos]
-                );
+            for (pos, expected_content_binding) in
+                expected_collection3_content_bindings.iter().enumerate()
+            {
+                assert_eq!(*expected_content_binding, collection3.content_bindings[pos]);
             }
             let expected3_service_types = [
                 CollectionServiceType::PollingService,
@@ -558,13 +643,15 @@ mod tests {
                     collection3.collection_services[pos].message_bindings[1]
                 );
             }
-            for pos in 0..expected_collection3_content_bindings.len() {
+            for (pos, expected_content_binding) in
+                expected_collection3_content_bindings.iter().enumerate()
+            {
                 assert_eq!(
-                    expected_collection3_content_bindings[pos],
+                    *expected_content_binding,
                     collection3.collection_services[4].content_bindings[pos]
                 );
                 assert_eq!(
-                    expected_collection3_content_bindings[pos],
+                    *expected_content_binding,
                     collection3.collection_services[5].content_bindings[pos]
                 );
             }
@@ -579,11 +666,10 @@ mod tests {
             );
             assert_eq!(1, collection4.content_bindings.len());
             let expected_collection4_content_bindings = ["application/pkcs7-mime"];
-            for pos in 0..expected_collection4_content_bindings.len() {
-                assert_eq!(
-                    expected_collection4_content_bindings[pos],
-                    collection4.content_bindings[pos]
-                );
+            for (pos, expected_content_binding) in
+                expected_collection4_content_bindings.iter().enumerate()
+            {
+                assert_eq!(*expected_content_binding, collection4.content_bindings[pos]);
             }
             let expected4_service_types = [
                 CollectionServiceType::PollingService,
@@ -625,16 +711,97 @@ mod tests {
                     collection4.collection_services[pos].message_bindings[1]
                 );
             }
-            for pos in 0..expected_collection4_content_bindings.len() {
+            for (pos, expected_content_binding) in
+                expected_collection4_content_bindings.iter().enumerate()
+            {
                 assert_eq!(
-                    expected_collection4_content_bindings[pos],
+                    *expected_content_binding,
                     collection4.collection_services[4].content_bindings[pos]
                 );
                 assert_eq!(
-                    expected_collection4_content_bindings[pos],
+                    *expected_content_binding,
                     collection4.collection_services[5].content_bindings[pos]
                 );
             }
         }
     }
+
+    #[test]
+    fn test_create_collection_information_response_body() {
+        let path = env::var("CARGO_MANIFEST_DIR").unwrap();
+        let path = Path::new(path.as_str()).join("test/sample-collection-information-response.xml");
+        let doc = read_to_string(path).unwrap();
+        let collection_set = parse_collection_information_response(doc.as_bytes()).unwrap();
+
+        let doc =
+            create_collection_information_response_body(Version::V11, "1234", &collection_set)
+                .unwrap();
+        let round_trip = parse_collection_information_response(doc.as_bytes()).unwrap();
+        assert_eq!(
+            collection_set.collections.len(),
+            round_trip.collections.len()
+        );
+        for (collection, expected) in round_trip
+            .collections
+            .iter()
+            .zip(collection_set.collections.iter())
+        {
+            assert_eq!(expected.collection_name, collection.collection_name);
+            assert_eq!(expected.collection_type, collection.collection_type);
+            assert_eq!(expected.description, collection.description);
+            assert_eq!(expected.collection_volume, collection.collection_volume);
+            assert_eq!(expected.content_bindings, collection.content_bindings);
+            assert_eq!(
+                expected.collection_services.len(),
+                collection.collection_services.len()
+            );
+            for (service, expected) in collection
+                .collection_services
+                .iter()
+                .zip(expected.collection_services.iter())
+            {
+                assert_eq!(
+                    expected.collection_service_type,
+                    service.collection_service_type
+                );
+                assert_eq!(expected.address, service.address);
+                assert_eq!(expected.message_bindings, service.message_bindings);
+                assert_eq!(expected.content_bindings, service.content_bindings);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_feed_information_response() {
+        let path = env::var("CARGO_MANIFEST_DIR").unwrap();
+        let path = Path::new(path.as_str()).join("test/sample-10-feed-information-response.xml");
+        let doc = read_to_string(path).unwrap();
+        let collection_set = parse_collection_information_response(doc.as_bytes()).unwrap();
+        assert_eq!(1, collection_set.collections.len());
+        let feed = &collection_set.collections[0];
+        assert_eq!("stix-data", feed.collection_name);
+        assert_eq!(CollectionType::DataFeed, feed.collection_type);
+        assert_eq!(
+            vec!["urn:stix.mitre.org:xml:1.0", "urn:stix.mitre.org:xml:1.0.1"],
+            feed.content_bindings
+        );
+        let service_types: Vec<_> = feed
+            .collection_services
+            .iter()
+            .map(|s| s.collection_service_type)
+            .collect();
+        assert_eq!(
+            vec![
+                CollectionServiceType::PushMethod,
+                CollectionServiceType::PollingService,
+                CollectionServiceType::SubscriptionService,
+            ],
+            service_types
+        );
+
+        let doc = create_collection_information_response_body(Version::V10, "1", &collection_set)
+            .unwrap();
+        assert!(doc.starts_with("<taxii:Feed_Information_Response"));
+        assert!(doc.contains("<taxii:Feed feed_name=\"stix-data\""));
+    }
 }
@@ -0,0 +1,91 @@
+use chrono::{DateTime, Utc};
+
+use super::{
+    errors::TaxiiError,
+    message::{parse_timestamp, Element, FromElement, ToElement},
+    types::ContentBinding,
+    version::Version,
+};
+
+/// A Content_Block of an Inbox_Message or Poll_Response. `content` holds the block's content as
+/// sent: the serialized XML when the content is a document, e.g. a STIX package, and the text
+/// otherwise.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContentBlock {
+    pub content_binding: ContentBinding,
+    pub content: String,
+    pub timestamp_label: Option<DateTime<Utc>>,
+    pub message: Option<String>,
+    pub padding: Option<String>,
+}
+
+impl ContentBlock {
+    pub fn new(binding_id: &str, content: &str) -> ContentBlock {
+        ContentBlock {
+            content_binding: ContentBinding {
+                binding_id: String::from(binding_id),
+                subtype_id: None,
+            },
+            content: String::from(content),
+            timestamp_label: None,
+            message: None,
+            padding: None,
+        }
+    }
+}
+
+impl FromElement for ContentBlock {
+    fn from_element(element: &Element) -> Result<ContentBlock, TaxiiError> {
+        element.expect("Content_Block")?;
+        element.check_elements(&[
+            "Content_Binding",
+            "Content",
+            "Timestamp_Label",
+            "Message",
+            "Padding",
+            "Signature",
+        ])?;
+        let content_binding = match element.element("Content_Binding") {
+            Some(v) => ContentBinding::from_element(v)?,
+            None => return Err(TaxiiError::Protocol("missing Content_Binding".to_string())),
+        };
+        let content = match element.element("Content") {
+            Some(v) => v.content()?,
+            None => return Err(TaxiiError::Protocol("missing Content".to_string())),
+        };
+        let timestamp_label = match element.element_text("Timestamp_Label") {
+            Some(v) => Some(parse_timestamp("Timestamp_Label", v.as_str())?),
+            None => None,
+        };
+        Ok(ContentBlock {
+            content_binding,
+            content,
+            timestamp_label,
+            message: element.element_text("Message"),
+            padding: element.element_text("Padding"),
+        })
+    }
+}
+
+impl ToElement for ContentBlock {
+    fn to_element(&self, ver: Version) -> Result<Element, TaxiiError> {
+        let mut element = Element::new(ver, "Content_Block")
+            .with_element(self.content_binding.to_element(ver)?)
+            .with_element(Element::new(ver, "Content").with_content(self.content.as_str())?);
+        if let Some(timestamp_label) = self.timestamp_label {
+            element.push(Element::text_element(
+                ver,
+                "Timestamp_Label",
+                timestamp_label.to_rfc3339().as_str(),
+            ));
+        }
+        // TAXII 1.0 content blocks do not have a Message
+        if let (Some(message), false) = (&self.message, ver == Version::V10) {
+            element.push(Element::text_element(ver, "Message", message.as_str()));
+        }
+        if let Some(padding) = &self.padding {
+            element.push(Element::text_element(ver, "Padding", padding.as_str()));
+        }
+        Ok(element)
+    }
+}
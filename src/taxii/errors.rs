@@ -1,10 +1,45 @@
+use super::version::Version;
+
+/// The errors of the TAXII 1.x messages, client and server.
 #[derive(Debug)]
-pub struct MyError(pub String);
+pub enum TaxiiError {
+    /// Sending a request or reading the response failed, e.g. the server could not be reached.
+    Transport(String),
+    /// The server answered with an HTTP status other than a success.
+    HttpStatus(u16),
+    /// A document is not well-formed XML, or could not be written.
+    Xml(String),
+    /// A message is not a valid TAXII message, or not the one expected, including the status
+    /// messages a server answers with in place of a response.
+    Protocol(String),
+    /// The version of TAXII does not support what was asked, e.g. XML messages for TAXII 2.1.
+    UnsupportedVersion { version: Version, feature: String },
+    /// The store or inbox sink behind a server failed.
+    Storage(String),
+}
+
+impl TaxiiError {
+    pub fn unsupported(version: Version, feature: &str) -> TaxiiError {
+        TaxiiError::UnsupportedVersion {
+            version,
+            feature: String::from(feature),
+        }
+    }
+}
 
-impl std::fmt::Display for MyError {
+impl std::fmt::Display for TaxiiError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        match self {
+            TaxiiError::Transport(v)
+            | TaxiiError::Xml(v)
+            | TaxiiError::Protocol(v)
+            | TaxiiError::Storage(v) => write!(f, "{}", v),
+            TaxiiError::HttpStatus(v) => write!(f, "unexpected HTTP status: {}", v),
+            TaxiiError::UnsupportedVersion { version, feature } => {
+                write!(f, "TAXII {} does not support {}", version.to_str(), feature)
+            }
+        }
     }
 }
 
-impl std::error::Error for MyError {}
+impl std::error::Error for TaxiiError {}
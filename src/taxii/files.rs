@@ -1,16 +1,53 @@
-use std::{self, io::Write};
+/// Write a file by writing it next to `path` and renaming it over it, so that a crash leaves
+/// either the old or the new content, e.g. of the state of a long-running mirror. Each writer
+/// has its own temporary file, named after the process and a random suffix, so concurrent
+/// writers leave the content of one of them.
+pub fn write_file_atomically(path: &std::path::Path, data: &str) -> std::io::Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(format!(
+        ".{}.{:016x}.tmp",
+        std::process::id(),
+        rand::random::<u64>()
+    ));
+    let tmp_path = std::path::PathBuf::from(tmp_path);
+    std::fs::write(&tmp_path, data)?;
+    match std::fs::rename(&tmp_path, path) {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            let _ = std::fs::remove_file(&tmp_path);
+            Err(err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, thread};
+
+    use uuid::Uuid;
 
-use chrono::Utc;
+    use super::write_file_atomically;
 
-pub fn write_cache_file_with_filestamp(stub_name: &str, data: &str) -> std::io::Result<()> {
-    let path = std::env::var("HOME").unwrap();
-    let filestamp = Utc::now()
-        .format(format!("%Y-%m-%d-%H-%M-%S-{}", stub_name).as_str())
-        .to_string();
-    let path = std::path::Path::new(path.as_str())
-        .join(".rkcache")
-        .join(filestamp);
-    let mut file = std::fs::File::create(path)?;
-    file.write_all(data.as_bytes())?;
-    Ok(())
+    #[test]
+    fn test_concurrent_writes() {
+        let dir = env::temp_dir().join(format!("files-{}", Uuid::new_v4()));
+        fs::create_dir(&dir).unwrap();
+        let path = dir.join("state.json");
+        let writers: Vec<_> = (0..8)
+            .map(|i| {
+                let path = path.clone();
+                thread::spawn(move || {
+                    for _ in 0..20 {
+                        write_file_atomically(&path, format!("writer {}", i).as_str()).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert!(fs::read_to_string(&path).unwrap().starts_with("writer "));
+        assert_eq!(1, fs::read_dir(&dir).unwrap().count());
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
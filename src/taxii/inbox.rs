@@ -0,0 +1,260 @@
+use chrono::{DateTime, Utc};
+
+use super::{
+    content_block::ContentBlock,
+    errors::TaxiiError,
+    message::{
+        collection_name_attr, from_xml, message_id, parse_timestamp, to_xml, Element, FromElement,
+        ToElement,
+    },
+    types::RecordCount,
+    version::Version,
+};
+
+/// The subscription an Inbox_Message was pushed for. For TAXII 1.0, `exclusive_begin` holds
+/// the Inclusive_Begin_Timestamp of the content.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceSubscription {
+    pub collection_name: String,
+    pub subscription_id: String,
+    pub exclusive_begin: Option<DateTime<Utc>>,
+    pub inclusive_end: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct InboxMessage {
+    pub message_id: String,
+    pub result_id: Option<String>,
+    pub destination_collection_names: Vec<String>,
+    pub message: Option<String>,
+    pub source_subscription: Option<SourceSubscription>,
+    pub record_count: Option<RecordCount>,
+    pub content_blocks: Vec<ContentBlock>,
+}
+
+impl InboxMessage {
+    pub fn new_empty() -> InboxMessage {
+        InboxMessage {
+            message_id: String::from(""),
+            result_id: None,
+            destination_collection_names: Vec::<String>::new(),
+            message: None,
+            source_subscription: None,
+            record_count: None,
+            content_blocks: Vec::<ContentBlock>::new(),
+        }
+    }
+}
+
+// TAXII 1.0 has an Inclusive_Begin_Timestamp where 1.1 has an Exclusive_Begin_Timestamp
+fn begin_timestamp(ver: Version) -> &'static str {
+    match ver {
+        Version::V10 => "Inclusive_Begin_Timestamp",
+        _ => "Exclusive_Begin_Timestamp",
+    }
+}
+
+impl FromElement for SourceSubscription {
+    fn from_element(element: &Element) -> Result<SourceSubscription, TaxiiError> {
+        element.expect("Source_Subscription")?;
+        let ver = element.version();
+        element.check_elements(&[
+            "Subscription_ID",
+            begin_timestamp(ver),
+            "Inclusive_End_Timestamp",
+        ])?;
+        let timestamp = |name: &str| -> Result<Option<DateTime<Utc>>, TaxiiError> {
+            match element.element_text(name) {
+                Some(v) => Ok(Some(parse_timestamp(name, v.as_str())?)),
+                None => Ok(None),
+            }
+        };
+        Ok(SourceSubscription {
+            collection_name: String::from(
+                element.attr(collection_name_attr(ver)).unwrap_or_default(),
+            ),
+            subscription_id: element.element_text("Subscription_ID").unwrap_or_default(),
+            exclusive_begin: timestamp(begin_timestamp(ver))?,
+            inclusive_end: timestamp("Inclusive_End_Timestamp")?,
+        })
+    }
+}
+
+impl ToElement for SourceSubscription {
+    fn to_element(&self, ver: Version) -> Result<Element, TaxiiError> {
+        let mut element = Element::new(ver, "Source_Subscription")
+            .with_attr(collection_name_attr(ver), self.collection_name.as_str())
+            .with_element(Element::text_element(
+                ver,
+                "Subscription_ID",
+                self.subscription_id.as_str(),
+            ));
+        if let Some(exclusive_begin) = self.exclusive_begin {
+            element.push(Element::text_element(
+                ver,
+                begin_timestamp(ver),
+                exclusive_begin.to_rfc3339().as_str(),
+            ));
+        }
+        if let Some(inclusive_end) = self.inclusive_end {
+            element.push(Element::text_element(
+                ver,
+                "Inclusive_End_Timestamp",
+                inclusive_end.to_rfc3339().as_str(),
+            ));
+        }
+        Ok(element)
+    }
+}
+
+impl FromElement for InboxMessage {
+    fn from_element(element: &Element) -> Result<InboxMessage, TaxiiError> {
+        element.expect("Inbox_Message")?;
+        element.check_elements(&[
+            "Destination_Collection_Name",
+            "Message",
+            "Source_Subscription",
+            "Record_Count",
+            "Content_Block",
+        ])?;
+        let source_subscription = match element.element("Source_Subscription") {
+            Some(v) => Some(SourceSubscription::from_element(v)?),
+            None => None,
+        };
+        let record_count = match element.element("Record_Count") {
+            Some(v) => Some(RecordCount::from_element(v)?),
+            None => None,
+        };
+        Ok(InboxMessage {
+            message_id: String::from(element.required_attr("message_id")?),
+            result_id: element.attr("result_id").map(String::from),
+            destination_collection_names: element
+                .elements_named("Destination_Collection_Name")
+                .map(|e| e.text())
+                .collect(),
+            message: element.element_text("Message"),
+            source_subscription,
+            record_count,
+            content_blocks: element
+                .elements_named("Content_Block")
+                .map(ContentBlock::from_element)
+                .collect::<Result<Vec<_>, _>>()?,
+        })
+    }
+}
+
+// A TAXII 1.0 Inbox_Message only has a Message, the Source_Subscription and the content: the
+// server decides which feeds it goes to.
+impl ToElement for InboxMessage {
+    fn to_element(&self, ver: Version) -> Result<Element, TaxiiError> {
+        let msg_id = message_id(ver, self.message_id.as_str())?;
+        let mut element =
+            Element::new(ver, "Inbox_Message").with_attr("message_id", msg_id.as_str());
+        let v11 = ver != Version::V10;
+        if let (Some(result_id), true) = (&self.result_id, v11) {
+            element.set_attr("result_id", result_id.as_str());
+        }
+        if v11 {
+            for collection_name in self.destination_collection_names.iter() {
+                element.push(Element::text_element(
+                    ver,
+                    "Destination_Collection_Name",
+                    collection_name.as_str(),
+                ));
+            }
+        }
+        if let Some(message) = &self.message {
+            element.push(Element::text_element(ver, "Message", message.as_str()));
+        }
+        if let Some(source_subscription) = &self.source_subscription {
+            element.push(source_subscription.to_element(ver)?);
+        }
+        if let (Some(record_count), true) = (&self.record_count, v11) {
+            element.push(record_count.to_element(ver)?);
+        }
+        for content_block in self.content_blocks.iter() {
+            element.push(content_block.to_element(ver)?);
+        }
+        Ok(element)
+    }
+}
+
+pub fn parse_inbox_message(doc: &[u8]) -> Result<InboxMessage, TaxiiError> {
+    from_xml(doc)
+}
+
+pub fn create_inbox_message_body(
+    ver: Version,
+    inbox_message: &InboxMessage,
+) -> Result<String, TaxiiError> {
+    to_xml(ver, inbox_message)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::{create_inbox_message_body, parse_inbox_message, InboxMessage, SourceSubscription};
+    use crate::taxii::{content_block::ContentBlock, types::RecordCount, version::Version};
+
+    #[test]
+    fn test_inbox_message_round_trip() {
+        let mut inbox_message = InboxMessage::new_empty();
+        inbox_message.destination_collection_names = vec![String::from("stix-data")];
+        inbox_message.source_subscription = Some(SourceSubscription {
+            collection_name: String::from("stix-data"),
+            subscription_id: String::from("8954140241256270840"),
+            exclusive_begin: Some(Utc.with_ymd_and_hms(2016, 12, 8, 0, 0, 0).unwrap()),
+            inclusive_end: None,
+        });
+        inbox_message.record_count = Some(RecordCount {
+            count: 2,
+            partial_count: false,
+        });
+        let mut content_block = ContentBlock::new(
+            "urn:stix.mitre.org:xml:1.1.1",
+            "<stix:STIX_Package xmlns:stix=\"http://stix.mitre.org/stix-1\" id=\"example:Package-1\">\
+             <stix:STIX_Header><stix:Title>a &amp; b</stix:Title></stix:STIX_Header>\
+             </stix:STIX_Package>",
+        );
+        content_block.timestamp_label =
+            Some(Utc.with_ymd_and_hms(2016, 12, 8, 21, 29, 56).unwrap());
+        inbox_message.content_blocks.push(content_block);
+        inbox_message
+            .content_blocks
+            .push(ContentBlock::new("urn:example:text", "a < b"));
+
+        let doc = create_inbox_message_body(Version::V11, &inbox_message).unwrap();
+        let parsed = parse_inbox_message(doc.as_bytes()).unwrap();
+        assert!(!parsed.message_id.is_empty());
+        assert_eq!(
+            inbox_message.destination_collection_names,
+            parsed.destination_collection_names
+        );
+        assert_eq!(
+            inbox_message.source_subscription,
+            parsed.source_subscription
+        );
+        assert_eq!(inbox_message.record_count, parsed.record_count);
+        assert_eq!(2, parsed.content_blocks.len());
+        let content_block = &parsed.content_blocks[0];
+        assert_eq!(
+            "urn:stix.mitre.org:xml:1.1.1",
+            content_block.content_binding.binding_id
+        );
+        assert!(content_block.content.starts_with("<stix:STIX_Package"));
+        assert!(content_block
+            .content
+            .contains("xmlns:stix=\"http://stix.mitre.org/stix-1\""));
+        assert!(content_block
+            .content
+            .contains("<stix:Title>a &amp; b</stix:Title>"));
+        assert_eq!(
+            inbox_message.content_blocks[0].timestamp_label,
+            content_block.timestamp_label
+        );
+        assert_eq!("a < b", parsed.content_blocks[1].content);
+
+        assert!(parse_inbox_message(b"<Inbox_Message><Content/></Inbox_Message>").is_err());
+    }
+}
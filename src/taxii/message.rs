@@ -0,0 +1,469 @@
+use std::{borrow::Cow, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use xml::{
+    attribute::{Attribute, OwnedAttribute},
+    name::OwnedName,
+    namespace::Namespace,
+    reader::{self, EventReader},
+    writer::{self, EmitterConfig, EventWriter},
+};
+
+use super::{errors::TaxiiError, version::Version};
+
+/// A node of a parsed XML document: an element or the text between elements.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Node {
+    Element(Element),
+    Text(String),
+}
+
+/// An XML element with its attributes and children. TAXII messages are read from and written to
+/// elements, which keeps the message types free of the details of parsing and emitting XML.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Element {
+    pub name: OwnedName,
+    pub attributes: Vec<OwnedAttribute>,
+    /// The namespace mappings in scope for the element. Elements built for a message only carry
+    /// the ones they declare.
+    pub namespace: Namespace,
+    pub children: Vec<Node>,
+}
+
+/// A TAXII message, or part of one, that can be read from an element.
+pub trait FromElement: Sized {
+    fn from_element(element: &Element) -> Result<Self, TaxiiError>;
+}
+
+/// A TAXII message, or part of one, that can be written as an element of the version's binding.
+pub trait ToElement {
+    fn to_element(&self, ver: Version) -> Result<Element, TaxiiError>;
+}
+
+/// Parse a TAXII message.
+pub fn from_xml<T: FromElement>(doc: &[u8]) -> Result<T, TaxiiError> {
+    T::from_element(&Element::parse(doc)?)
+}
+
+/// Write a TAXII message, declaring the namespace of the version's binding on its root element.
+pub fn to_xml<T: ToElement>(ver: Version, message: &T) -> Result<String, TaxiiError> {
+    let (prefix, namespace) = (ver.xml_prefix()?, ver.xml_namespace()?);
+    let mut root = message.to_element(ver)?;
+    root.namespace.put(prefix, namespace);
+    root.to_xml(true)
+}
+
+/// The message_id to write for a message, a new one when the message does not have one yet.
+pub fn message_id(ver: Version, message_id: &str) -> Result<String, TaxiiError> {
+    match message_id {
+        "" => ver.message_id(),
+        v => Ok(String::from(v)),
+    }
+}
+
+/// The attribute naming the collection a message is about: TAXII 1.0 calls collections feeds.
+pub fn collection_name_attr(ver: Version) -> &'static str {
+    match ver {
+        Version::V10 => "feed_name",
+        _ => "collection_name",
+    }
+}
+
+pub fn parse_bool(v: &str) -> bool {
+    v.trim().eq_ignore_ascii_case("true") || v.trim() == "1"
+}
+
+pub fn parse_number<T: FromStr>(name: &str, v: &str) -> Result<T, TaxiiError>
+where
+    T::Err: std::fmt::Display,
+{
+    match v.trim().parse::<T>() {
+        Ok(v) => Ok(v),
+        Err(err) => Err(TaxiiError::Protocol(format!("bad {}: {}", name, err))),
+    }
+}
+
+pub fn parse_timestamp(name: &str, v: &str) -> Result<DateTime<Utc>, TaxiiError> {
+    match DateTime::parse_from_rfc3339(v.trim()) {
+        Ok(v) => Ok(v.with_timezone(&Utc)),
+        Err(err) => Err(TaxiiError::Protocol(format!("bad {}: {}", name, err))),
+    }
+}
+
+impl Element {
+    /// A new element of the version's binding, e.g. `Element::new(Version::V11, "Message")`.
+    /// The element has no namespace for versions without an XML binding, which `to_xml` rejects.
+    pub fn new(ver: Version, local_name: &str) -> Element {
+        Element {
+            name: OwnedName {
+                local_name: String::from(local_name),
+                namespace: ver.xml_namespace().ok().map(String::from),
+                prefix: ver.xml_prefix().ok().map(String::from),
+            },
+            attributes: Vec::<OwnedAttribute>::new(),
+            namespace: Namespace::empty(),
+            children: Vec::<Node>::new(),
+        }
+    }
+
+    /// A new element holding text, e.g. an Address.
+    pub fn text_element(ver: Version, local_name: &str, text: &str) -> Element {
+        Element::new(ver, local_name).with_text(text)
+    }
+
+    pub fn parse(doc: &[u8]) -> Result<Element, TaxiiError> {
+        let mut stack = Vec::<Element>::new();
+        for e in EventReader::new(doc) {
+            match e {
+                Ok(reader::XmlEvent::StartElement {
+                    name,
+                    attributes,
+                    namespace,
+                }) => stack.push(Element {
+                    name,
+                    attributes,
+                    namespace,
+                    children: Vec::<Node>::new(),
+                }),
+                Ok(reader::XmlEvent::EndElement { .. }) => {
+                    let element = match stack.pop() {
+                        Some(v) => v,
+                        None => return Err(TaxiiError::Xml("malformed XML".to_string())),
+                    };
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(Node::Element(element)),
+                        None => return Ok(element),
+                    }
+                }
+                Ok(reader::XmlEvent::Characters(data))
+                | Ok(reader::XmlEvent::CData(data))
+                | Ok(reader::XmlEvent::Whitespace(data)) => {
+                    if let Some(parent) = stack.last_mut() {
+                        parent.children.push(Node::Text(data))
+                    }
+                }
+                Ok(_) => (),
+                Err(err) => return Err(TaxiiError::Xml(err.to_string())),
+            }
+        }
+        Err(TaxiiError::Xml("missing root element".to_string()))
+    }
+
+    pub fn local_name(&self) -> &str {
+        self.name.local_name.as_str()
+    }
+
+    /// The version of the TAXII XML binding the element belongs to. Elements outside the 1.0
+    /// namespace are read as TAXII 1.1.
+    pub fn version(&self) -> Version {
+        match self.name.namespace.as_deref() {
+            Some(v) if Version::from_xml_namespace(v) == Some(Version::V10) => Version::V10,
+            _ => Version::V11,
+        }
+    }
+
+    /// Check the element is the expected one, e.g. the root of the message being parsed.
+    pub fn expect(&self, local_name: &str) -> Result<(), TaxiiError> {
+        if self.local_name() != local_name {
+            return Err(TaxiiError::Protocol(format!(
+                "expected {}, found {}",
+                local_name,
+                self.local_name()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check that the child elements are all expected ones.
+    pub fn check_elements(&self, local_names: &[&str]) -> Result<(), TaxiiError> {
+        match self
+            .elements()
+            .find(|e| !local_names.contains(&e.local_name()))
+        {
+            Some(e) => Err(TaxiiError::Protocol(format!(
+                "unexpected {} tag in {}",
+                e.local_name(),
+                self.local_name()
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|a| a.name.local_name == name)
+            .map(|a| a.value.as_str())
+    }
+
+    pub fn required_attr(&self, name: &str) -> Result<&str, TaxiiError> {
+        match self.attr(name) {
+            Some(v) => Ok(v),
+            None => Err(TaxiiError::Protocol(format!(
+                "missing {} attribute on {}",
+                name,
+                self.local_name()
+            ))),
+        }
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|node| match node {
+            Node::Element(v) => Some(v),
+            Node::Text(_) => None,
+        })
+    }
+
+    pub fn elements_named<'a>(&'a self, local_name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.elements()
+            .filter(move |e| e.local_name() == local_name)
+    }
+
+    pub fn element(&self, local_name: &str) -> Option<&Element> {
+        self.elements().find(|e| e.local_name() == local_name)
+    }
+
+    /// The text of the element, without surrounding whitespace.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for node in self.children.iter() {
+            if let Node::Text(v) = node {
+                text.push_str(v)
+            }
+        }
+        String::from(text.trim())
+    }
+
+    pub fn element_text(&self, local_name: &str) -> Option<String> {
+        self.element(local_name).map(|e| e.text())
+    }
+
+    /// The content of the element as sent: the serialized XML when it holds elements, e.g. a
+    /// STIX package, and the text otherwise.
+    pub fn content(&self) -> Result<String, TaxiiError> {
+        if self.elements().next().is_none() {
+            return Ok(self.text());
+        }
+        let mut writer = EmitterConfig::new()
+            .write_document_declaration(false)
+            .create_writer(Vec::<u8>::new());
+        for node in self.children.iter() {
+            write_node(&mut writer, node, false)?;
+        }
+        match String::from_utf8(writer.into_inner()) {
+            Ok(v) => Ok(String::from(v.trim())),
+            Err(err) => Err(TaxiiError::Xml(err.to_string())),
+        }
+    }
+
+    pub fn with_attr(mut self, name: &str, value: &str) -> Element {
+        self.set_attr(name, value);
+        self
+    }
+
+    pub fn set_attr(&mut self, name: &str, value: &str) {
+        match self
+            .attributes
+            .iter_mut()
+            .find(|a| a.name.local_name == name)
+        {
+            Some(attr) => attr.value = String::from(value),
+            None => self
+                .attributes
+                .push(OwnedAttribute::new(OwnedName::local(name), value)),
+        }
+    }
+
+    pub fn with_element(mut self, element: Element) -> Element {
+        self.push(element);
+        self
+    }
+
+    pub fn push(&mut self, element: Element) {
+        self.children.push(Node::Element(element))
+    }
+
+    pub fn with_text(mut self, text: &str) -> Element {
+        self.children.push(Node::Text(String::from(text)));
+        self
+    }
+
+    /// Set the content of the element, parsing it as XML when it is a document.
+    pub fn with_content(self, content: &str) -> Result<Element, TaxiiError> {
+        if content.trim_start().starts_with('<') {
+            Ok(self.with_element(Element::parse(content.as_bytes())?))
+        } else {
+            Ok(self.with_text(content))
+        }
+    }
+
+    pub fn write<W: std::io::Write>(&self, writer: &mut EventWriter<W>) -> Result<(), TaxiiError> {
+        write_element(writer, self, true)
+    }
+
+    pub fn to_xml(&self, indent: bool) -> Result<String, TaxiiError> {
+        let mut writer = EmitterConfig::new()
+            .write_document_declaration(false)
+            .perform_indent(indent)
+            .create_writer(Vec::<u8>::new());
+        write_element(&mut writer, self, indent)?;
+        match String::from_utf8(writer.into_inner()) {
+            Ok(v) => Ok(v),
+            Err(err) => Err(TaxiiError::Xml(err.to_string())),
+        }
+    }
+}
+
+fn write_event<'a, W, E>(writer: &mut EventWriter<W>, event: E) -> Result<(), TaxiiError>
+where
+    W: std::io::Write,
+    E: Into<writer::XmlEvent<'a>>,
+{
+    match writer.write(event) {
+        Ok(_) => Ok(()),
+        Err(err) => Err(TaxiiError::Xml(err.to_string())),
+    }
+}
+
+// When indenting, the whitespace between elements is left to the writer. Content of other
+// namespaces, e.g. a STIX package, is written as it is: its text is kept and empty text before
+// its markup stops the writer from indenting it.
+fn write_element<W: std::io::Write>(
+    writer: &mut EventWriter<W>,
+    element: &Element,
+    indent: bool,
+) -> Result<(), TaxiiError> {
+    let attributes: Vec<Attribute> = element.attributes.iter().map(|a| a.borrow()).collect();
+    write_event(
+        writer,
+        writer::XmlEvent::StartElement {
+            name: element.name.borrow(),
+            attributes: Cow::Owned(attributes),
+            namespace: Cow::Borrowed(&element.namespace),
+        },
+    )?;
+    let indent = indent && is_taxii(element);
+    let has_elements = element.elements().next().is_some();
+    for node in element.children.iter() {
+        match node {
+            Node::Text(v) if indent && has_elements && v.trim().is_empty() => (),
+            Node::Element(v) if !indent => {
+                write_event(writer, writer::XmlEvent::characters(""))?;
+                write_element(writer, v, false)?
+            }
+            node => write_node(writer, node, indent)?,
+        }
+    }
+    if !indent && has_elements {
+        write_event(writer, writer::XmlEvent::characters(""))?;
+    }
+    write_event(writer, writer::XmlEvent::end_element())
+}
+
+fn is_taxii(element: &Element) -> bool {
+    element
+        .name
+        .namespace
+        .as_deref()
+        .and_then(Version::from_xml_namespace)
+        .is_some()
+}
+
+fn write_node<W: std::io::Write>(
+    writer: &mut EventWriter<W>,
+    node: &Node,
+    indent: bool,
+) -> Result<(), TaxiiError> {
+    match node {
+        Node::Element(v) => write_element(writer, v, indent),
+        Node::Text(v) => write_event(writer, writer::XmlEvent::characters(v.as_str())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fmt::Debug, fs, path::Path};
+
+    use super::{from_xml, to_xml, Element, FromElement, ToElement};
+    use crate::taxii::{
+        collections::CollectionSet,
+        poll::PollResponse,
+        services::ServiceSet,
+        status_message::StatusMessage,
+        subscriptions::SubscriptionResponse,
+        version::{parse_message_root, Version},
+    };
+
+    // Writing a parsed message and parsing it again must give the same message.
+    fn round_trip<T: FromElement + ToElement + PartialEq + Debug>(doc: &str) -> String {
+        let ver = Element::parse(doc.as_bytes()).unwrap().version();
+        let message = from_xml::<T>(doc.as_bytes()).unwrap();
+        let written = to_xml(ver, &message).unwrap();
+        assert_eq!(message, from_xml::<T>(written.as_bytes()).unwrap());
+        written
+    }
+
+    #[test]
+    fn test_element() {
+        let doc = "<taxii_11:Message_Root xmlns:taxii_11=\"urn:example\" message_id=\"1\">\n  \
+            <taxii_11:Content>\n    <stix:Package xmlns:stix=\"urn:stix\"><stix:A>a</stix:A>\
+            </stix:Package>\n  </taxii_11:Content>\n  <taxii_11:Message> text </taxii_11:Message>\
+            </taxii_11:Message_Root>";
+        let root = Element::parse(doc.as_bytes()).unwrap();
+        assert!(root.expect("Message_Root").is_ok());
+        assert!(root.expect("Message").is_err());
+        assert_eq!(Some("1"), root.attr("message_id"));
+        assert!(root.required_attr("in_response_to").is_err());
+        assert!(root.check_elements(&["Content", "Message"]).is_ok());
+        assert!(root.check_elements(&["Content"]).is_err());
+        assert_eq!(Some(String::from("text")), root.element_text("Message"));
+        let content = root.element("Content").unwrap().content().unwrap();
+        assert!(content.starts_with("<stix:Package xmlns:stix=\"urn:stix\""));
+        assert!(content.ends_with("<stix:A>a</stix:A></stix:Package>"));
+
+        let element = Element::new(Version::V11, "Content")
+            .with_attr("binding_id", "urn:stix")
+            .with_content(content.as_str())
+            .unwrap();
+        assert_eq!(content, element.content().unwrap());
+        let text = Element::new(Version::V11, "Content")
+            .with_content("plain text")
+            .unwrap();
+        assert_eq!("plain text", text.content().unwrap());
+        assert!(Element::new(Version::V11, "Content")
+            .with_content("<unclosed>")
+            .is_err());
+    }
+
+    #[test]
+    fn test_round_trip_samples() {
+        let path = env::var("CARGO_MANIFEST_DIR").unwrap();
+        let mut count = 0;
+        for entry in fs::read_dir(Path::new(path.as_str()).join("test")).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|v| v != "xml") {
+                continue;
+            }
+            let doc = fs::read_to_string(&path).unwrap();
+            let (name, message_id) = parse_message_root(doc.as_bytes()).unwrap();
+            let written = match name.as_str() {
+                "Collection_Information_Response" | "Feed_Information_Response" => {
+                    round_trip::<CollectionSet>(&doc)
+                }
+                "Discovery_Response" => round_trip::<ServiceSet>(&doc),
+                "Poll_Response" => round_trip::<PollResponse>(&doc),
+                "Status_Message" => round_trip::<StatusMessage>(&doc),
+                "Subscription_Management_Response" | "Manage_Feed_Subscription_Response" => {
+                    round_trip::<SubscriptionResponse>(&doc)
+                }
+                name => panic!("no round trip for {}: {}", name, path.display()),
+            };
+            assert_eq!(
+                (name, message_id),
+                parse_message_root(written.as_bytes()).unwrap()
+            );
+            count += 1;
+        }
+        assert!(count > 0);
+    }
+}
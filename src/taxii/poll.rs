@@ -1,14 +1,57 @@
 use chrono::{DateTime, Utc};
-use xml::writer;
 
 use super::{
-    errors::MyError,
-    types::{ContentBinding, ResponseType},
-    version::{taxii_request, write_xml, write_xml_tag_with_data, Version},
+    content_block::ContentBlock,
+    errors::TaxiiError,
+    message::{
+        collection_name_attr, from_xml, message_id, parse_bool, parse_number, parse_timestamp,
+        to_xml, Element, FromElement, ToElement,
+    },
+    query::{parse_query, DefaultQuery, DEFAULT_QUERY_FORMAT_ID},
+    types::{ContentBinding, RecordCount, ResponseType},
+    version::{taxii_request, Version},
 };
-struct TimeRange {
-    exclusive_begin: Option<DateTime<Utc>>,
-    inclusive_end: Option<DateTime<Utc>>,
+
+/// The range of Timestamp_Labels of a poll. For a TAXII 1.0 Poll_Response, `exclusive_begin`
+/// holds the Inclusive_Begin_Timestamp.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TimeRange {
+    pub exclusive_begin: Option<DateTime<Utc>>,
+    pub inclusive_end: Option<DateTime<Utc>>,
+}
+
+impl TimeRange {
+    // `begin` is the tag of the begin timestamp: TAXII 1.0 responses have an inclusive one.
+    fn from_element(element: &Element, begin: &str) -> Result<TimeRange, TaxiiError> {
+        let timestamp = |name: &str| -> Result<Option<DateTime<Utc>>, TaxiiError> {
+            match element.element_text(name) {
+                Some(v) => Ok(Some(parse_timestamp(name, v.as_str())?)),
+                None => Ok(None),
+            }
+        };
+        Ok(TimeRange {
+            exclusive_begin: timestamp(begin)?,
+            inclusive_end: timestamp("Inclusive_End_Timestamp")?,
+        })
+    }
+
+    /// Add the begin and Inclusive_End_Timestamp of the range to a message.
+    fn push_to(&self, ver: Version, element: &mut Element, begin: &str) {
+        if let Some(exclusive_begin) = self.exclusive_begin {
+            element.push(Element::text_element(
+                ver,
+                begin,
+                exclusive_begin.to_rfc3339().as_str(),
+            ));
+        }
+        if let Some(inclusive_end) = self.inclusive_end {
+            element.push(Element::text_element(
+                ver,
+                "Inclusive_End_Timestamp",
+                inclusive_end.to_rfc3339().as_str(),
+            ));
+        }
+    }
 }
 
 struct DeliveryParameters {
@@ -17,61 +60,165 @@ struct DeliveryParameters {
     message_binding: String,
 }
 
-struct PollParameters {
-    allow_asynch: bool,
-    response_type: ResponseType,
-    content_bindings: Vec<ContentBinding>,
-    query: String,
-    query_format_id: String,
+#[derive(Clone, Debug)]
+pub struct PollParameters {
+    pub allow_asynch: bool,
+    pub response_type: ResponseType,
+    pub content_bindings: Vec<ContentBinding>,
+    pub query: String,
+    pub query_format_id: String,
 }
 
-fn create_poll_request_body(
+impl PollParameters {
+    /// Set the query to a TAXII Default Query.
+    pub fn set_default_query(&mut self, query: &DefaultQuery) -> Result<(), TaxiiError> {
+        self.query = query.to_xml()?;
+        self.query_format_id = String::from(DEFAULT_QUERY_FORMAT_ID);
+        Ok(())
+    }
+
+    /// The TAXII Default Query of the poll, None when there is no query.
+    pub fn default_query(&self) -> Result<Option<DefaultQuery>, TaxiiError> {
+        parse_query(self.query.as_str(), self.query_format_id.as_str())
+    }
+}
+
+impl FromElement for PollParameters {
+    fn from_element(element: &Element) -> Result<PollParameters, TaxiiError> {
+        element.expect("Poll_Parameters")?;
+        element.check_elements(&[
+            "Response_Type",
+            "Content_Binding",
+            "Query",
+            "Delivery_Parameters",
+        ])?;
+        let response_type = match element.element_text("Response_Type") {
+            Some(v) => ResponseType::parse(v.as_str())?,
+            None => ResponseType::Full,
+        };
+        let (query, query_format_id) = match element.element("Query") {
+            Some(v) => (
+                v.content()?,
+                String::from(v.attr("format_id").unwrap_or_default()),
+            ),
+            None => (String::from(""), String::from("")),
+        };
+        Ok(PollParameters {
+            allow_asynch: element.attr("allow_asynch").is_some_and(parse_bool),
+            response_type,
+            content_bindings: element
+                .elements_named("Content_Binding")
+                .map(ContentBinding::from_element)
+                .collect::<Result<Vec<_>, _>>()?,
+            query,
+            query_format_id,
+        })
+    }
+}
+
+impl ToElement for PollParameters {
+    fn to_element(&self, ver: Version) -> Result<Element, TaxiiError> {
+        let mut element = Element::new(ver, "Poll_Parameters")
+            .with_attr("allow_asynch", self.allow_asynch.to_string().as_str())
+            .with_element(Element::text_element(
+                ver,
+                "Response_Type",
+                self.response_type.to_str(),
+            ));
+        for content_binding in self.content_bindings.iter() {
+            element.push(content_binding.to_element(ver)?);
+        }
+        if !self.query.is_empty() {
+            element.push(
+                Element::new(ver, "Query")
+                    .with_attr("format_id", self.query_format_id.as_str())
+                    .with_content(self.query.as_str())?,
+            );
+        }
+        Ok(element)
+    }
+}
+
+pub fn create_poll_request_body(
     ver: Version,
     collection_name: &str,
     time_range: Option<TimeRange>,
     subscription_id: &str,
     poll_paramters: Option<PollParameters>,
-) -> Result<String, MyError> {
-    let mut buf_writer: Vec<u8> = Vec::with_capacity(128);
-    let mut writer = writer::EmitterConfig::new()
-        .write_document_declaration(false)
-        .perform_indent(true)
-        .create_writer(&mut buf_writer);
-
-    let msg_id = ver.message_id();
-    let tag = format!("taxii_11:Poll_Request");
-    let elem = writer::XmlEvent::start_element(tag.as_str())
-        .attr("message_id", msg_id.as_str())
-        .attr("collection_name", collection_name)
-        .ns("taxii_11", ver.xml_namespace());
-
-    // <Poll_Request>
-    write_xml(&mut writer, elem)?;
-
-    match time_range {
-        Some(time_range) => match time_range.exclusive_begin {
-            Some(exclusive_begin) => write_xml_tag_with_data(
-                &mut writer,
-                "taxii_11:Exclusive_Begin_Timestamp",
-                exclusive_begin.to_rfc3339().as_str(),
-            )?,
-            _ => (),
-        },
-        None => (),
+) -> Result<String, TaxiiError> {
+    let mut poll_request = PollRequest::new_empty();
+    poll_request.collection_name = String::from(collection_name);
+    poll_request.time_range = time_range.unwrap_or_default();
+    if !subscription_id.is_empty() {
+        poll_request.subscription_id = Some(String::from(subscription_id));
     }
+    poll_request.poll_parameters = poll_paramters;
+    to_xml(ver, &poll_request)
+}
 
-    // TODO: time_range
+/// A Poll_Fulfillment request, used to collect the results of an asynchronous poll once the
+/// server has answered with a PENDING status message, or the further parts of a result.
+#[derive(Clone, Debug)]
+pub struct PollFulfillment {
+    pub message_id: String,
+    pub collection_name: String,
+    pub result_id: String,
+    pub result_part_number: u32,
+}
 
-    // <Subscription_ID></Subscription_ID>
-    write_xml_tag_with_data(&mut writer, "taxii_11:Subscription_ID", subscription_id)?;
+impl FromElement for PollFulfillment {
+    fn from_element(element: &Element) -> Result<PollFulfillment, TaxiiError> {
+        element.expect("Poll_Fulfillment")?;
+        Ok(PollFulfillment {
+            message_id: String::from(element.attr("message_id").unwrap_or_default()),
+            collection_name: String::from(element.required_attr("collection_name")?),
+            result_id: String::from(element.required_attr("result_id")?),
+            result_part_number: parse_number(
+                "result_part_number",
+                element.required_attr("result_part_number")?,
+            )?,
+        })
+    }
+}
 
-    // TODO: PollParameters
+impl ToElement for PollFulfillment {
+    fn to_element(&self, ver: Version) -> Result<Element, TaxiiError> {
+        if ver == Version::V10 {
+            return Err(TaxiiError::unsupported(ver, "Poll_Fulfillment"));
+        }
+        Ok(Element::new(ver, "Poll_Fulfillment")
+            .with_attr(
+                "message_id",
+                message_id(ver, self.message_id.as_str())?.as_str(),
+            )
+            .with_attr("collection_name", self.collection_name.as_str())
+            .with_attr("result_id", self.result_id.as_str())
+            .with_attr(
+                "result_part_number",
+                self.result_part_number.to_string().as_str(),
+            ))
+    }
+}
 
-    // </PollRequest>
-    write_xml(&mut writer, writer::XmlEvent::end_element())?;
+/// Build a Poll_Fulfillment request, used to collect the results of an asynchronous poll once
+/// the server has answered with a PENDING status message.
+pub fn create_poll_fulfillment_request_body(
+    ver: Version,
+    collection_name: &str,
+    result_id: &str,
+    result_part_number: u32,
+) -> Result<String, TaxiiError> {
+    let poll_fulfillment = PollFulfillment {
+        message_id: String::from(""),
+        collection_name: String::from(collection_name),
+        result_id: String::from(result_id),
+        result_part_number,
+    };
+    to_xml(ver, &poll_fulfillment)
+}
 
-    // TODO: better check on conversion than unwrap
-    return Ok(String::from_utf8(buf_writer).unwrap());
+pub fn parse_poll_fulfillment(doc: &[u8]) -> Result<PollFulfillment, TaxiiError> {
+    from_xml(doc)
 }
 
 pub fn poll_request(
@@ -81,14 +228,477 @@ pub fn poll_request(
     ver: Version,
     collection_name: &str,
     subscription_id: &str,
-) {
+) -> Result<String, TaxiiError> {
     // e.g.
     // let time_range = Some(TimeRange {
     // exclusive_begin: Utc::now().checked_sub_days(Days::new(1)),
     // inclusive_end: Some(Utc::now()),
     // });
-    match create_poll_request_body(ver, collection_name, None, subscription_id, None) {
-        Ok(request_body) => taxii_request(url, username, password, &request_body, ver),
-        Err(err) => panic!("{}", err),
+    let request_body = create_poll_request_body(ver, collection_name, None, subscription_id, None)?;
+    taxii_request(url, username, password, &request_body, ver)
+}
+
+/// A parsed Poll_Request, as received by a poll service. A request names either a subscription
+/// or the poll parameters to use.
+#[derive(Clone, Debug)]
+pub struct PollRequest {
+    pub message_id: String,
+    pub collection_name: String,
+    pub time_range: TimeRange,
+    pub subscription_id: Option<String>,
+    pub poll_parameters: Option<PollParameters>,
+}
+
+impl PollRequest {
+    pub fn new_empty() -> PollRequest {
+        PollRequest {
+            message_id: String::from(""),
+            collection_name: String::from(""),
+            time_range: TimeRange::default(),
+            subscription_id: None,
+            poll_parameters: None,
+        }
+    }
+}
+
+impl FromElement for PollRequest {
+    fn from_element(element: &Element) -> Result<PollRequest, TaxiiError> {
+        element.expect("Poll_Request")?;
+        let ver = element.version();
+        if ver == Version::V10 {
+            return poll_request_10(element);
+        }
+        element.check_elements(&[
+            "Exclusive_Begin_Timestamp",
+            "Inclusive_End_Timestamp",
+            "Subscription_ID",
+            "Poll_Parameters",
+        ])?;
+        let poll_parameters = match element.element("Poll_Parameters") {
+            Some(v) => Some(PollParameters::from_element(v)?),
+            None => None,
+        };
+        Ok(PollRequest {
+            message_id: String::from(element.attr("message_id").unwrap_or_default()),
+            collection_name: String::from(element.required_attr("collection_name")?),
+            time_range: TimeRange::from_element(element, "Exclusive_Begin_Timestamp")?,
+            subscription_id: element.element_text("Subscription_ID"),
+            poll_parameters,
+        })
+    }
+}
+
+// A TAXII 1.0 Poll_Request has the subscription as an attribute and the content bindings
+// directly in the request. Without a subscription, they are read as the poll parameters.
+fn poll_request_10(element: &Element) -> Result<PollRequest, TaxiiError> {
+    element.check_elements(&[
+        "Exclusive_Begin_Timestamp",
+        "Inclusive_End_Timestamp",
+        "Content_Binding",
+    ])?;
+    let subscription_id = element.attr("subscription_id").map(String::from);
+    let poll_parameters = match subscription_id {
+        Some(_) => None,
+        None => Some(PollParameters {
+            allow_asynch: false,
+            response_type: ResponseType::Full,
+            content_bindings: element
+                .elements_named("Content_Binding")
+                .map(ContentBinding::from_element)
+                .collect::<Result<Vec<_>, _>>()?,
+            query: String::from(""),
+            query_format_id: String::from(""),
+        }),
+    };
+    Ok(PollRequest {
+        message_id: String::from(element.attr("message_id").unwrap_or_default()),
+        collection_name: String::from(element.required_attr("feed_name")?),
+        time_range: TimeRange::from_element(element, "Exclusive_Begin_Timestamp")?,
+        subscription_id,
+        poll_parameters,
+    })
+}
+
+impl ToElement for PollRequest {
+    fn to_element(&self, ver: Version) -> Result<Element, TaxiiError> {
+        let mut element = Element::new(ver, "Poll_Request")
+            .with_attr(
+                "message_id",
+                message_id(ver, self.message_id.as_str())?.as_str(),
+            )
+            .with_attr(collection_name_attr(ver), self.collection_name.as_str());
+        if ver == Version::V10 {
+            if let Some(subscription_id) = &self.subscription_id {
+                element.set_attr("subscription_id", subscription_id.as_str());
+            }
+        }
+        self.time_range
+            .push_to(ver, &mut element, "Exclusive_Begin_Timestamp");
+        if ver == Version::V10 {
+            if let Some(poll_parameters) = &self.poll_parameters {
+                if !poll_parameters.query.is_empty() {
+                    return Err(TaxiiError::unsupported(ver, "queries"));
+                }
+                if poll_parameters.response_type != ResponseType::Full {
+                    return Err(TaxiiError::unsupported(
+                        ver,
+                        format!("{} polls", poll_parameters.response_type.to_str()).as_str(),
+                    ));
+                }
+                for content_binding in poll_parameters.content_bindings.iter() {
+                    element.push(content_binding.to_element(ver)?);
+                }
+            }
+            return Ok(element);
+        }
+        if let Some(subscription_id) = &self.subscription_id {
+            element.push(Element::text_element(
+                ver,
+                "Subscription_ID",
+                subscription_id.as_str(),
+            ));
+        }
+        if let Some(poll_parameters) = &self.poll_parameters {
+            element.push(poll_parameters.to_element(ver)?);
+        }
+        Ok(element)
+    }
+}
+
+pub fn parse_poll_request(doc: &[u8]) -> Result<PollRequest, TaxiiError> {
+    from_xml(doc)
+}
+
+/// A Poll_Response. `more` is set when the result has further parts, to be fetched with
+/// Poll_Fulfillment requests for `result_id`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PollResponse {
+    pub message_id: String,
+    pub in_response_to: String,
+    pub collection_name: String,
+    pub more: bool,
+    pub result_id: Option<String>,
+    pub result_part_number: u32,
+    pub subscription_id: Option<String>,
+    pub time_range: TimeRange,
+    pub record_count: Option<RecordCount>,
+    pub message: Option<String>,
+    pub content_blocks: Vec<ContentBlock>,
+}
+
+impl PollResponse {
+    pub fn new_empty() -> PollResponse {
+        PollResponse {
+            message_id: String::from(""),
+            in_response_to: String::from(""),
+            collection_name: String::from(""),
+            more: false,
+            result_id: None,
+            result_part_number: 1,
+            subscription_id: None,
+            time_range: TimeRange::default(),
+            record_count: None,
+            message: None,
+            content_blocks: Vec::<ContentBlock>::new(),
+        }
+    }
+}
+
+impl FromElement for PollResponse {
+    fn from_element(element: &Element) -> Result<PollResponse, TaxiiError> {
+        element.expect("Poll_Response")?;
+        if element.version() == Version::V10 {
+            return poll_response_10(element);
+        }
+        element.check_elements(&[
+            "Subscription_ID",
+            "Exclusive_Begin_Timestamp",
+            "Inclusive_End_Timestamp",
+            "Record_Count",
+            "Message",
+            "Content_Block",
+        ])?;
+        let result_part_number = match element.attr("result_part_number") {
+            Some(v) => parse_number("result_part_number", v)?,
+            None => 1,
+        };
+        let record_count = match element.element("Record_Count") {
+            Some(v) => Some(RecordCount::from_element(v)?),
+            None => None,
+        };
+        Ok(PollResponse {
+            message_id: String::from(element.attr("message_id").unwrap_or_default()),
+            in_response_to: String::from(element.attr("in_response_to").unwrap_or_default()),
+            collection_name: String::from(element.attr("collection_name").unwrap_or_default()),
+            more: element.attr("more").is_some_and(parse_bool),
+            result_id: element.attr("result_id").map(String::from),
+            result_part_number,
+            subscription_id: element.element_text("Subscription_ID"),
+            time_range: TimeRange::from_element(element, "Exclusive_Begin_Timestamp")?,
+            record_count,
+            message: element.element_text("Message"),
+            content_blocks: content_blocks(element)?,
+        })
+    }
+}
+
+// A TAXII 1.0 Poll_Response has the subscription as an attribute, an inclusive begin and
+// always the whole result.
+fn poll_response_10(element: &Element) -> Result<PollResponse, TaxiiError> {
+    element.check_elements(&[
+        "Message",
+        "Inclusive_Begin_Timestamp",
+        "Inclusive_End_Timestamp",
+        "Content_Block",
+    ])?;
+    let mut poll_response = PollResponse::new_empty();
+    poll_response.message_id = String::from(element.attr("message_id").unwrap_or_default());
+    poll_response.in_response_to = String::from(element.attr("in_response_to").unwrap_or_default());
+    poll_response.collection_name = String::from(element.attr("feed_name").unwrap_or_default());
+    poll_response.subscription_id = element.attr("subscription_id").map(String::from);
+    poll_response.time_range = TimeRange::from_element(element, "Inclusive_Begin_Timestamp")?;
+    poll_response.message = element.element_text("Message");
+    poll_response.content_blocks = content_blocks(element)?;
+    Ok(poll_response)
+}
+
+fn content_blocks(element: &Element) -> Result<Vec<ContentBlock>, TaxiiError> {
+    element
+        .elements_named("Content_Block")
+        .map(ContentBlock::from_element)
+        .collect()
+}
+
+impl ToElement for PollResponse {
+    fn to_element(&self, ver: Version) -> Result<Element, TaxiiError> {
+        let mut element = Element::new(ver, "Poll_Response")
+            .with_attr(
+                "message_id",
+                message_id(ver, self.message_id.as_str())?.as_str(),
+            )
+            .with_attr("in_response_to", self.in_response_to.as_str())
+            .with_attr(collection_name_attr(ver), self.collection_name.as_str());
+        if ver == Version::V10 {
+            if let Some(subscription_id) = &self.subscription_id {
+                element.set_attr("subscription_id", subscription_id.as_str());
+            }
+            if let Some(message) = &self.message {
+                element.push(Element::text_element(ver, "Message", message.as_str()));
+            }
+            self.time_range
+                .push_to(ver, &mut element, "Inclusive_Begin_Timestamp");
+        } else {
+            element.set_attr("more", self.more.to_string().as_str());
+            element.set_attr(
+                "result_part_number",
+                self.result_part_number.to_string().as_str(),
+            );
+            if let Some(result_id) = &self.result_id {
+                element.set_attr("result_id", result_id.as_str());
+            }
+            if let Some(subscription_id) = &self.subscription_id {
+                element.push(Element::text_element(
+                    ver,
+                    "Subscription_ID",
+                    subscription_id.as_str(),
+                ));
+            }
+            self.time_range
+                .push_to(ver, &mut element, "Exclusive_Begin_Timestamp");
+            if let Some(record_count) = &self.record_count {
+                element.push(record_count.to_element(ver)?);
+            }
+            if let Some(message) = &self.message {
+                element.push(Element::text_element(ver, "Message", message.as_str()));
+            }
+        }
+        for content_block in self.content_blocks.iter() {
+            element.push(content_block.to_element(ver)?);
+        }
+        Ok(element)
+    }
+}
+
+pub fn create_poll_response_body(
+    ver: Version,
+    poll_response: &PollResponse,
+) -> Result<String, TaxiiError> {
+    to_xml(ver, poll_response)
+}
+
+pub fn parse_poll_response(doc: &[u8]) -> Result<PollResponse, TaxiiError> {
+    from_xml(doc)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs::read_to_string, path::Path};
+
+    use chrono::{TimeZone, Utc};
+
+    use super::{
+        create_poll_fulfillment_request_body, create_poll_request_body, create_poll_response_body,
+        parse_poll_request, parse_poll_response, PollParameters, TimeRange,
+    };
+    use crate::taxii::{
+        types::{ContentBinding, ResponseType},
+        version::Version,
+    };
+
+    #[test]
+    fn test_parse_poll_response() {
+        let path = env::var("CARGO_MANIFEST_DIR").unwrap();
+        let path = Path::new(path.as_str()).join("test/sample-poll-response.xml");
+        let doc = read_to_string(path).unwrap();
+        let poll_response = parse_poll_response(doc.as_bytes()).unwrap();
+        assert_eq!("4702984631239378997", poll_response.message_id);
+        assert_eq!("stix-data", poll_response.collection_name);
+        assert!(poll_response.more);
+        assert_eq!(
+            Some(String::from("5743921917948702777")),
+            poll_response.result_id
+        );
+        assert_eq!(1, poll_response.result_part_number);
+        assert_eq!(
+            Some(String::from("2326864292141172358")),
+            poll_response.subscription_id
+        );
+        let record_count = poll_response.record_count.as_ref().unwrap();
+        assert_eq!(100, record_count.count);
+        assert!(record_count.partial_count);
+        assert_eq!(10, poll_response.content_blocks.len());
+        assert_eq!("None", poll_response.content_blocks[0].content);
+        let content_block = &poll_response.content_blocks[1];
+        assert_eq!(
+            "urn:stix.mitre.org:xml:1.1",
+            content_block.content_binding.binding_id
+        );
+        assert!(content_block.content.starts_with("<stix:STIX_Package"));
+        assert!(content_block.content.ends_with("</stix:STIX_Package>"));
+        assert!(content_block.timestamp_label.is_some());
+
+        // writing the response back keeps the content
+        let doc = create_poll_response_body(Version::V11, &poll_response).unwrap();
+        let round_trip = parse_poll_response(doc.as_bytes()).unwrap();
+        assert_eq!(poll_response.result_id, round_trip.result_id);
+        assert_eq!(poll_response.record_count, round_trip.record_count);
+        assert_eq!(poll_response.content_blocks, round_trip.content_blocks);
+    }
+
+    #[test]
+    fn test_poll_request_round_trip() {
+        let time_range = TimeRange {
+            exclusive_begin: Some(Utc.with_ymd_and_hms(2016, 12, 1, 0, 0, 0).unwrap()),
+            inclusive_end: Some(Utc.with_ymd_and_hms(2016, 12, 8, 0, 0, 0).unwrap()),
+        };
+        let poll_parameters = PollParameters {
+            allow_asynch: true,
+            response_type: ResponseType::CountOnly,
+            content_bindings: vec![ContentBinding {
+                binding_id: String::from("urn:stix.mitre.org:xml:1.1.1"),
+                subtype_id: None,
+            }],
+            query: String::from(""),
+            query_format_id: String::from(""),
+        };
+        let doc = create_poll_request_body(
+            Version::V11,
+            "stix-data",
+            Some(time_range.clone()),
+            "",
+            Some(poll_parameters),
+        )
+        .unwrap();
+        let poll_request = parse_poll_request(doc.as_bytes()).unwrap();
+        assert_eq!("stix-data", poll_request.collection_name);
+        assert_eq!(time_range, poll_request.time_range);
+        assert_eq!(None, poll_request.subscription_id);
+        let poll_parameters = poll_request.poll_parameters.unwrap();
+        assert!(poll_parameters.allow_asynch);
+        assert_eq!(ResponseType::CountOnly, poll_parameters.response_type);
+        assert_eq!(
+            "urn:stix.mitre.org:xml:1.1.1",
+            poll_parameters.content_bindings[0].binding_id
+        );
+
+        let doc = create_poll_request_body(Version::V11, "stix-data", None, "1234", None).unwrap();
+        let poll_request = parse_poll_request(doc.as_bytes()).unwrap();
+        assert_eq!(Some(String::from("1234")), poll_request.subscription_id);
+        assert!(poll_request.poll_parameters.is_none());
+
+        assert!(parse_poll_request(b"<Poll_Request message_id=\"1\"/>").is_err());
+    }
+
+    #[test]
+    fn test_poll_request_10() {
+        let time_range = TimeRange {
+            exclusive_begin: Some(Utc.with_ymd_and_hms(2014, 1, 1, 0, 0, 0).unwrap()),
+            inclusive_end: None,
+        };
+        let poll_parameters = PollParameters {
+            allow_asynch: false,
+            response_type: ResponseType::Full,
+            content_bindings: vec![ContentBinding {
+                binding_id: String::from("urn:stix.mitre.org:xml:1.0.1"),
+                subtype_id: None,
+            }],
+            query: String::from(""),
+            query_format_id: String::from(""),
+        };
+        let doc = create_poll_request_body(
+            Version::V10,
+            "stix-data",
+            Some(time_range.clone()),
+            "",
+            Some(poll_parameters.clone()),
+        )
+        .unwrap();
+        assert!(doc.starts_with(
+            "<taxii:Poll_Request xmlns:taxii=\"http://taxii.mitre.org/messages/taxii_xml_binding-1\""
+        ));
+        assert!(doc.contains("feed_name=\"stix-data\""));
+        assert!(doc.contains(
+            "<taxii:Content_Binding>urn:stix.mitre.org:xml:1.0.1</taxii:Content_Binding>"
+        ));
+        let poll_request = parse_poll_request(doc.as_bytes()).unwrap();
+        assert_eq!("stix-data", poll_request.collection_name);
+        assert_eq!(time_range, poll_request.time_range);
+        assert_eq!(
+            "urn:stix.mitre.org:xml:1.0.1",
+            poll_request.poll_parameters.unwrap().content_bindings[0].binding_id
+        );
+
+        let doc = create_poll_request_body(Version::V10, "stix-data", None, "1234", None).unwrap();
+        assert!(doc.contains("subscription_id=\"1234\""));
+        let poll_request = parse_poll_request(doc.as_bytes()).unwrap();
+        assert_eq!(Some(String::from("1234")), poll_request.subscription_id);
+
+        let mut count_only = poll_parameters;
+        count_only.response_type = ResponseType::CountOnly;
+        assert!(
+            create_poll_request_body(Version::V10, "stix-data", None, "", Some(count_only))
+                .is_err()
+        );
+        assert!(create_poll_fulfillment_request_body(Version::V10, "stix-data", "1", 1).is_err());
+    }
+
+    #[test]
+    fn test_parse_poll_response_10() {
+        let path = env::var("CARGO_MANIFEST_DIR").unwrap();
+        let path = Path::new(path.as_str()).join("test/sample-10-poll-response.xml");
+        let doc = read_to_string(path).unwrap();
+        let poll_response = parse_poll_response(doc.as_bytes()).unwrap();
+        assert_eq!("stix-data", poll_response.collection_name);
+        assert_eq!(
+            Some(Utc.with_ymd_and_hms(2014, 1, 1, 0, 0, 0).unwrap()),
+            poll_response.time_range.exclusive_begin
+        );
+        assert_eq!(1, poll_response.content_blocks.len());
+        let content_block = &poll_response.content_blocks[0];
+        assert_eq!(
+            "urn:stix.mitre.org:xml:1.0.1",
+            content_block.content_binding.binding_id
+        );
+        assert!(content_block.content.starts_with("<stix:STIX_Package"));
     }
 }
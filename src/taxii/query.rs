@@ -0,0 +1,1032 @@
+use chrono::{DateTime, Utc};
+use regex::RegexBuilder;
+use xml::{attribute::OwnedAttribute, name::OwnedName, namespace::Namespace};
+
+use crate::stix1::common::parse_datetime;
+
+use super::{
+    content_block::ContentBlock,
+    errors::TaxiiError,
+    message::{parse_bool, Element, FromElement, Node},
+};
+
+/// The format_id of a Query holding a TAXII Default Query.
+pub static DEFAULT_QUERY_FORMAT_ID: &str = "urn:taxii.mitre.org:query:default:1.0";
+pub static NAMESPACE_TDQ: &str = "http://taxii.mitre.org/query/taxii_default_query-1";
+
+pub static CAPABILITY_CORE: &str = "urn:taxii.mitre.org:query:capability:core-1";
+pub static CAPABILITY_REGEX: &str = "urn:taxii.mitre.org:query:capability:regex-1";
+pub static CAPABILITY_TIMESTAMP: &str = "urn:taxii.mitre.org:query:capability:timestamp-1";
+
+/// The targeting expressions the evaluator supports: the content bindings of STIX 1.x, whose
+/// documents are targeted by the local names of their elements and attributes.
+pub static STIX_1_TARGETING_EXPRESSION_IDS: [&str; 5] = [
+    "urn:stix.mitre.org:xml:1.0",
+    "urn:stix.mitre.org:xml:1.0.1",
+    "urn:stix.mitre.org:xml:1.1",
+    "urn:stix.mitre.org:xml:1.1.1",
+    "urn:stix.mitre.org:xml:1.2",
+];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Operator {
+    And,
+    Or,
+}
+
+impl Operator {
+    pub fn parse(v: &str) -> Result<Operator, TaxiiError> {
+        match v {
+            "AND" => Ok(Operator::And),
+            "OR" => Ok(Operator::Or),
+            _ => Err(TaxiiError::Protocol(format!(
+                "could not parse operator: {}",
+                v
+            ))),
+        }
+    }
+    pub fn to_str(&self) -> &str {
+        match self {
+            Operator::And => "AND",
+            Operator::Or => "OR",
+        }
+    }
+}
+
+/// The capability modules of the TAXII Default Query, which define the relationships a test can
+/// use.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Capability {
+    Core,
+    Regex,
+    Timestamp,
+}
+
+impl Capability {
+    pub fn parse(v: &str) -> Result<Capability, TaxiiError> {
+        match v {
+            v if v == CAPABILITY_CORE => Ok(Capability::Core),
+            v if v == CAPABILITY_REGEX => Ok(Capability::Regex),
+            v if v == CAPABILITY_TIMESTAMP => Ok(Capability::Timestamp),
+            _ => Err(TaxiiError::Protocol(format!(
+                "unsupported capability module: {}",
+                v
+            ))),
+        }
+    }
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            Capability::Core => CAPABILITY_CORE,
+            Capability::Regex => CAPABILITY_REGEX,
+            Capability::Timestamp => CAPABILITY_TIMESTAMP,
+        }
+    }
+    fn relationships(&self) -> &'static [Relationship] {
+        match self {
+            Capability::Core => &[
+                Relationship::Equals,
+                Relationship::NotEquals,
+                Relationship::GreaterThan,
+                Relationship::GreaterThanOrEqual,
+                Relationship::LessThan,
+                Relationship::LessThanOrEqual,
+                Relationship::DoesNotExist,
+                Relationship::Exists,
+                Relationship::BeginsWith,
+                Relationship::EndsWith,
+                Relationship::Contains,
+            ],
+            Capability::Regex => &[Relationship::Matches],
+            Capability::Timestamp => &[
+                Relationship::Equals,
+                Relationship::GreaterThan,
+                Relationship::GreaterThanOrEqual,
+                Relationship::LessThan,
+                Relationship::LessThanOrEqual,
+            ],
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Relationship {
+    Equals,
+    NotEquals,
+    GreaterThan,
+    GreaterThanOrEqual,
+    LessThan,
+    LessThanOrEqual,
+    DoesNotExist,
+    Exists,
+    BeginsWith,
+    EndsWith,
+    Contains,
+    Matches,
+}
+
+impl Relationship {
+    pub fn parse(v: &str) -> Result<Relationship, TaxiiError> {
+        match v {
+            "equals" => Ok(Relationship::Equals),
+            "not_equals" => Ok(Relationship::NotEquals),
+            "greater_than" => Ok(Relationship::GreaterThan),
+            "greater_than_or_equal" => Ok(Relationship::GreaterThanOrEqual),
+            "less_than" => Ok(Relationship::LessThan),
+            "less_than_or_equal" => Ok(Relationship::LessThanOrEqual),
+            "does_not_exist" => Ok(Relationship::DoesNotExist),
+            "exists" => Ok(Relationship::Exists),
+            "begins_with" => Ok(Relationship::BeginsWith),
+            "ends_with" => Ok(Relationship::EndsWith),
+            "contains" => Ok(Relationship::Contains),
+            "matches" => Ok(Relationship::Matches),
+            _ => Err(TaxiiError::Protocol(format!(
+                "could not parse relationship: {}",
+                v
+            ))),
+        }
+    }
+    pub fn to_str(&self) -> &str {
+        match self {
+            Relationship::Equals => "equals",
+            Relationship::NotEquals => "not_equals",
+            Relationship::GreaterThan => "greater_than",
+            Relationship::GreaterThanOrEqual => "greater_than_or_equal",
+            Relationship::LessThan => "less_than",
+            Relationship::LessThanOrEqual => "less_than_or_equal",
+            Relationship::DoesNotExist => "does_not_exist",
+            Relationship::Exists => "exists",
+            Relationship::BeginsWith => "begins_with",
+            Relationship::EndsWith => "ends_with",
+            Relationship::Contains => "contains",
+            Relationship::Matches => "matches",
+        }
+    }
+}
+
+/// How the equals and not_equals relationships of the core module compare values.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MatchType {
+    CaseSensitiveString,
+    CaseInsensitiveString,
+    Number,
+}
+
+impl MatchType {
+    pub fn parse(v: &str) -> Result<MatchType, TaxiiError> {
+        match v {
+            "case_sensitive_string" => Ok(MatchType::CaseSensitiveString),
+            "case_insensitive_string" => Ok(MatchType::CaseInsensitiveString),
+            "number" => Ok(MatchType::Number),
+            _ => Err(TaxiiError::Protocol(format!(
+                "could not parse match_type: {}",
+                v
+            ))),
+        }
+    }
+    pub fn to_str(&self) -> &str {
+        match self {
+            MatchType::CaseSensitiveString => "case_sensitive_string",
+            MatchType::CaseInsensitiveString => "case_insensitive_string",
+            MatchType::Number => "number",
+        }
+    }
+}
+
+/// The test of a Criterion: a relationship of a capability module and its parameters. Use the
+/// constructors, which check that the relationship belongs to the module and has the parameters
+/// it needs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Test {
+    pub capability: Capability,
+    pub relationship: Relationship,
+    pub value: Option<String>,
+    pub match_type: Option<MatchType>,
+    pub case_sensitive: Option<bool>,
+}
+
+impl Test {
+    pub fn new(
+        capability: Capability,
+        relationship: Relationship,
+        value: Option<&str>,
+        match_type: Option<MatchType>,
+        case_sensitive: Option<bool>,
+    ) -> Result<Test, TaxiiError> {
+        let test = Test {
+            capability,
+            relationship,
+            value: value.map(String::from),
+            match_type,
+            case_sensitive,
+        };
+        test.validate()?;
+        Ok(test)
+    }
+
+    /// A core equals test, e.g. of an `@id`.
+    pub fn equals(value: &str, match_type: MatchType) -> Test {
+        Test {
+            capability: Capability::Core,
+            relationship: Relationship::Equals,
+            value: Some(String::from(value)),
+            match_type: Some(match_type),
+            case_sensitive: None,
+        }
+    }
+
+    /// A core test of the presence of the target, exists or does_not_exist.
+    pub fn exists(exists: bool) -> Test {
+        Test {
+            capability: Capability::Core,
+            relationship: match exists {
+                true => Relationship::Exists,
+                false => Relationship::DoesNotExist,
+            },
+            value: None,
+            match_type: None,
+            case_sensitive: None,
+        }
+    }
+
+    /// A core string test: begins_with, ends_with or contains.
+    pub fn string(
+        relationship: Relationship,
+        value: &str,
+        case_sensitive: bool,
+    ) -> Result<Test, TaxiiError> {
+        Test::new(
+            Capability::Core,
+            relationship,
+            Some(value),
+            None,
+            Some(case_sensitive),
+        )
+    }
+
+    /// A core numeric comparison: greater_than, greater_than_or_equal, less_than or
+    /// less_than_or_equal.
+    pub fn number(relationship: Relationship, value: f64) -> Result<Test, TaxiiError> {
+        Test::new(
+            Capability::Core,
+            relationship,
+            Some(value.to_string().as_str()),
+            None,
+            None,
+        )
+    }
+
+    /// A regex matches test.
+    pub fn matches(pattern: &str, case_sensitive: bool) -> Result<Test, TaxiiError> {
+        Test::new(
+            Capability::Regex,
+            Relationship::Matches,
+            Some(pattern),
+            None,
+            Some(case_sensitive),
+        )
+    }
+
+    /// A timestamp comparison, e.g. of a `Timestamp` element or `@timestamp`.
+    pub fn timestamp(relationship: Relationship, value: DateTime<Utc>) -> Result<Test, TaxiiError> {
+        Test::new(
+            Capability::Timestamp,
+            relationship,
+            Some(value.to_rfc3339().as_str()),
+            None,
+            None,
+        )
+    }
+
+    fn validate(&self) -> Result<(), TaxiiError> {
+        let invalid = |message: &str| -> Result<(), TaxiiError> {
+            Err(TaxiiError::Protocol(format!(
+                "{} test: {}",
+                self.relationship.to_str(),
+                message
+            )))
+        };
+        if !self.capability.relationships().contains(&self.relationship) {
+            return invalid(format!("not in {}", self.capability.to_str()).as_str());
+        }
+        let value = self.value.as_deref();
+        match (self.capability, self.relationship) {
+            (_, Relationship::Exists | Relationship::DoesNotExist) => return Ok(()),
+            _ if value.is_none() => return invalid("missing value"),
+            (Capability::Timestamp, _) => {
+                if value.and_then(parse_datetime).is_none() {
+                    return invalid("value is not a timestamp");
+                }
+            }
+            (Capability::Core, Relationship::Equals | Relationship::NotEquals) => {
+                match self.match_type {
+                    Some(MatchType::Number) if value.and_then(parse_float).is_none() => {
+                        return invalid("value is not a number")
+                    }
+                    Some(_) => (),
+                    None => return invalid("missing match_type"),
+                }
+            }
+            (
+                Capability::Core,
+                Relationship::BeginsWith | Relationship::EndsWith | Relationship::Contains,
+            )
+            | (Capability::Regex, _) => {
+                if self.case_sensitive.is_none() {
+                    return invalid("missing case_sensitive");
+                }
+            }
+            (Capability::Core, _) => {
+                if value.and_then(parse_float).is_none() {
+                    return invalid("value is not a number");
+                }
+            }
+        }
+        if self.capability == Capability::Regex {
+            self.regex()?;
+        }
+        Ok(())
+    }
+
+    fn regex(&self) -> Result<regex::Regex, TaxiiError> {
+        match RegexBuilder::new(self.value.as_deref().unwrap_or_default())
+            .case_insensitive(self.case_sensitive == Some(false))
+            .build()
+        {
+            Ok(v) => Ok(v),
+            Err(err) => Err(TaxiiError::Protocol(format!("bad regex: {}", err))),
+        }
+    }
+
+    /// Whether the values selected by the target of a criterion pass the test: any of them has
+    /// to, except for the exists and does_not_exist relationships.
+    pub fn evaluate(&self, values: &[String]) -> Result<bool, TaxiiError> {
+        match self.relationship {
+            Relationship::Exists => return Ok(!values.is_empty()),
+            Relationship::DoesNotExist => return Ok(values.is_empty()),
+            _ => (),
+        }
+        let expected = self.value.as_deref().unwrap_or_default();
+        match self.capability {
+            Capability::Regex => {
+                let regex = self.regex()?;
+                Ok(values.iter().any(|v| regex.is_match(v)))
+            }
+            Capability::Timestamp => {
+                let expected = match parse_datetime(expected) {
+                    Some(v) => v,
+                    None => return Err(TaxiiError::Protocol("bad timestamp value".to_string())),
+                };
+                Ok(values
+                    .iter()
+                    .filter_map(|v| parse_datetime(v))
+                    .any(|v| compare(self.relationship, v.partial_cmp(&expected))))
+            }
+            Capability::Core => Ok(values.iter().any(|v| self.evaluate_core(v, expected))),
+        }
+    }
+
+    fn evaluate_core(&self, value: &str, expected: &str) -> bool {
+        let case_sensitive = self.case_sensitive != Some(false);
+        let fold = |v: &str| match case_sensitive {
+            true => String::from(v),
+            false => v.to_lowercase(),
+        };
+        match self.relationship {
+            Relationship::Equals | Relationship::NotEquals => {
+                let equal = match self.match_type {
+                    Some(MatchType::Number) => match (parse_float(value), parse_float(expected)) {
+                        (Some(v), Some(expected)) => v == expected,
+                        _ => return false,
+                    },
+                    Some(MatchType::CaseInsensitiveString) => {
+                        value.to_lowercase() == expected.to_lowercase()
+                    }
+                    _ => value == expected,
+                };
+                equal == (self.relationship == Relationship::Equals)
+            }
+            Relationship::BeginsWith => fold(value).starts_with(fold(expected).as_str()),
+            Relationship::EndsWith => fold(value).ends_with(fold(expected).as_str()),
+            Relationship::Contains => fold(value).contains(fold(expected).as_str()),
+            relationship => match (parse_float(value), parse_float(expected)) {
+                (Some(v), Some(expected)) => compare(relationship, v.partial_cmp(&expected)),
+                _ => false,
+            },
+        }
+    }
+}
+
+fn compare(relationship: Relationship, ordering: Option<std::cmp::Ordering>) -> bool {
+    use std::cmp::Ordering;
+    match (relationship, ordering) {
+        (_, None) => false,
+        (Relationship::Equals, Some(v)) => v == Ordering::Equal,
+        (Relationship::GreaterThan, Some(v)) => v == Ordering::Greater,
+        (Relationship::GreaterThanOrEqual, Some(v)) => v != Ordering::Less,
+        (Relationship::LessThan, Some(v)) => v == Ordering::Less,
+        (Relationship::LessThanOrEqual, Some(v)) => v != Ordering::Greater,
+        _ => false,
+    }
+}
+
+fn parse_float(v: &str) -> Option<f64> {
+    v.trim().parse::<f64>().ok()
+}
+
+/// A test of the nodes selected by a targeting expression, e.g. `STIX_Package/Indicators/*/@id`.
+/// `*` selects any element or attribute at its level and `**` any number of levels of elements.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Criterion {
+    pub negate: bool,
+    pub target: String,
+    pub test: Test,
+}
+
+impl Criterion {
+    pub fn new(target: &str, test: Test) -> Criterion {
+        Criterion {
+            negate: false,
+            target: String::from(target),
+            test,
+        }
+    }
+
+    pub fn negated(mut self) -> Criterion {
+        self.negate = !self.negate;
+        self
+    }
+
+    pub fn evaluate(&self, root: &Element) -> Result<bool, TaxiiError> {
+        let values = select(root, self.target.as_str())?;
+        Ok(self.test.evaluate(&values)? != self.negate)
+    }
+}
+
+/// Criteria and criterion combined with an operator.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Criteria {
+    pub operator: Operator,
+    pub criteria: Vec<Criteria>,
+    pub criterion: Vec<Criterion>,
+}
+
+impl Criteria {
+    pub fn new(operator: Operator) -> Criteria {
+        Criteria {
+            operator,
+            criteria: Vec::<Criteria>::new(),
+            criterion: Vec::<Criterion>::new(),
+        }
+    }
+
+    pub fn with_criteria(mut self, criteria: Criteria) -> Criteria {
+        self.criteria.push(criteria);
+        self
+    }
+
+    pub fn with_criterion(mut self, criterion: Criterion) -> Criteria {
+        self.criterion.push(criterion);
+        self
+    }
+
+    pub fn evaluate(&self, root: &Element) -> Result<bool, TaxiiError> {
+        for criteria in self.criteria.iter() {
+            match (self.operator, criteria.evaluate(root)?) {
+                (Operator::And, false) => return Ok(false),
+                (Operator::Or, true) => return Ok(true),
+                _ => (),
+            }
+        }
+        for criterion in self.criterion.iter() {
+            match (self.operator, criterion.evaluate(root)?) {
+                (Operator::And, false) => return Ok(false),
+                (Operator::Or, true) => return Ok(true),
+                _ => (),
+            }
+        }
+        Ok(self.operator == Operator::And)
+    }
+}
+
+/// A TAXII Default Query, sent as the Query of Poll_Parameters or Subscription_Parameters with
+/// the `DEFAULT_QUERY_FORMAT_ID` format.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DefaultQuery {
+    pub targeting_expression_id: String,
+    pub criteria: Criteria,
+}
+
+impl DefaultQuery {
+    pub fn new(targeting_expression_id: &str, criteria: Criteria) -> DefaultQuery {
+        DefaultQuery {
+            targeting_expression_id: String::from(targeting_expression_id),
+            criteria,
+        }
+    }
+
+    pub fn parse(query: &str) -> Result<DefaultQuery, TaxiiError> {
+        DefaultQuery::from_element(&Element::parse(query.as_bytes())?)
+    }
+
+    /// The query as the content of a Query element.
+    pub fn to_xml(&self) -> Result<String, TaxiiError> {
+        let mut root = self.to_element();
+        root.namespace.put("tdq", NAMESPACE_TDQ);
+        root.to_xml(false)
+    }
+
+    /// Whether a document matches the query, e.g. a STIX package.
+    pub fn evaluate(&self, doc: &str) -> Result<bool, TaxiiError> {
+        self.criteria.evaluate(&Element::parse(doc.as_bytes())?)
+    }
+
+    /// Whether a content block matches the query. Only content in a binding the query targets
+    /// can, and content that is not well-formed XML does not.
+    pub fn matches(&self, content_block: &ContentBlock) -> bool {
+        STIX_1_TARGETING_EXPRESSION_IDS.contains(&self.targeting_expression_id.as_str())
+            && STIX_1_TARGETING_EXPRESSION_IDS
+                .contains(&content_block.content_binding.binding_id.as_str())
+            && self
+                .evaluate(content_block.content.as_str())
+                .unwrap_or(false)
+    }
+
+    fn to_element(&self) -> Element {
+        tdq_element("Default_Query")
+            .with_attr(
+                "targeting_expression_id",
+                self.targeting_expression_id.as_str(),
+            )
+            .with_element(self.criteria.to_element())
+    }
+}
+
+/// Read the query of Poll_Parameters or Subscription_Parameters, None when there is none.
+pub fn parse_query(query: &str, query_format_id: &str) -> Result<Option<DefaultQuery>, TaxiiError> {
+    match (query.trim(), query_format_id) {
+        ("", _) => Ok(None),
+        (query, v) if v == DEFAULT_QUERY_FORMAT_ID => Ok(Some(DefaultQuery::parse(query)?)),
+        _ => Err(TaxiiError::Protocol(format!(
+            "unsupported query format: {}",
+            query_format_id
+        ))),
+    }
+}
+
+fn tdq_element(local_name: &str) -> Element {
+    Element {
+        name: OwnedName {
+            local_name: String::from(local_name),
+            namespace: Some(String::from(NAMESPACE_TDQ)),
+            prefix: Some(String::from("tdq")),
+        },
+        attributes: Vec::<OwnedAttribute>::new(),
+        namespace: Namespace::empty(),
+        children: Vec::<Node>::new(),
+    }
+}
+
+impl Criteria {
+    fn to_element(&self) -> Element {
+        let mut element = tdq_element("Criteria").with_attr("operator", self.operator.to_str());
+        for criteria in self.criteria.iter() {
+            element.push(criteria.to_element());
+        }
+        for criterion in self.criterion.iter() {
+            element.push(criterion.to_element());
+        }
+        element
+    }
+}
+
+impl Criterion {
+    fn to_element(&self) -> Element {
+        tdq_element("Criterion")
+            .with_attr("negate", self.negate.to_string().as_str())
+            .with_element(tdq_element("Target").with_text(self.target.as_str()))
+            .with_element(self.test.to_element())
+    }
+}
+
+impl Test {
+    fn to_element(&self) -> Element {
+        let mut element = tdq_element("Test")
+            .with_attr("capability_id", self.capability.to_str())
+            .with_attr("relationship", self.relationship.to_str());
+        let parameter = |name: &str, value: &str| {
+            tdq_element("Parameter")
+                .with_attr("name", name)
+                .with_text(value)
+        };
+        if let Some(value) = &self.value {
+            element.push(parameter("value", value.as_str()));
+        }
+        if let Some(match_type) = self.match_type {
+            element.push(parameter("match_type", match_type.to_str()));
+        }
+        if let Some(case_sensitive) = self.case_sensitive {
+            element.push(parameter(
+                "case_sensitive",
+                case_sensitive.to_string().as_str(),
+            ));
+        }
+        element
+    }
+}
+
+impl FromElement for DefaultQuery {
+    fn from_element(element: &Element) -> Result<DefaultQuery, TaxiiError> {
+        element.expect("Default_Query")?;
+        element.check_elements(&["Criteria"])?;
+        let criteria = match element.element("Criteria") {
+            Some(v) => Criteria::from_element(v)?,
+            None => return Err(TaxiiError::Protocol("missing Criteria".to_string())),
+        };
+        Ok(DefaultQuery {
+            targeting_expression_id: String::from(
+                element.required_attr("targeting_expression_id")?,
+            ),
+            criteria,
+        })
+    }
+}
+
+impl FromElement for Criteria {
+    fn from_element(element: &Element) -> Result<Criteria, TaxiiError> {
+        element.expect("Criteria")?;
+        element.check_elements(&["Criteria", "Criterion"])?;
+        Ok(Criteria {
+            operator: Operator::parse(element.required_attr("operator")?)?,
+            criteria: element
+                .elements_named("Criteria")
+                .map(Criteria::from_element)
+                .collect::<Result<Vec<_>, _>>()?,
+            criterion: element
+                .elements_named("Criterion")
+                .map(Criterion::from_element)
+                .collect::<Result<Vec<_>, _>>()?,
+        })
+    }
+}
+
+impl FromElement for Criterion {
+    fn from_element(element: &Element) -> Result<Criterion, TaxiiError> {
+        element.expect("Criterion")?;
+        element.check_elements(&["Target", "Test"])?;
+        let target = match element.element_text("Target") {
+            Some(v) => v,
+            None => return Err(TaxiiError::Protocol("missing Target".to_string())),
+        };
+        check_target(target.as_str())?;
+        let test = match element.element("Test") {
+            Some(v) => Test::from_element(v)?,
+            None => return Err(TaxiiError::Protocol("missing Test".to_string())),
+        };
+        Ok(Criterion {
+            negate: element.attr("negate").is_some_and(parse_bool),
+            target,
+            test,
+        })
+    }
+}
+
+impl FromElement for Test {
+    fn from_element(element: &Element) -> Result<Test, TaxiiError> {
+        element.expect("Test")?;
+        element.check_elements(&["Parameter"])?;
+        let parameter = |name: &str| {
+            element
+                .elements_named("Parameter")
+                .find(|v| v.attr("name") == Some(name))
+                .map(|v| v.text())
+        };
+        let match_type = match parameter("match_type") {
+            Some(v) => Some(MatchType::parse(v.as_str())?),
+            None => None,
+        };
+        Test::new(
+            Capability::parse(element.required_attr("capability_id")?)?,
+            Relationship::parse(element.required_attr("relationship")?)?,
+            parameter("value").as_deref(),
+            match_type,
+            parameter("case_sensitive").map(|v| parse_bool(v.as_str())),
+        )
+    }
+}
+
+fn check_target(target: &str) -> Result<(), TaxiiError> {
+    let nodes: Vec<&str> = target.split('/').collect();
+    let invalid = || TaxiiError::Protocol(format!("unsupported targeting expression: {}", target));
+    if nodes.iter().any(|v| v.is_empty() || *v == "@") || nodes[0].starts_with('@') {
+        return Err(invalid());
+    }
+    // attributes can only be the last node
+    if nodes[..nodes.len() - 1].iter().any(|v| v.starts_with('@')) {
+        return Err(invalid());
+    }
+    Ok(())
+}
+
+// A node a targeting expression is applied to: the document, whose only element is the root, or
+// an element.
+#[derive(Copy, Clone)]
+enum Context<'a> {
+    Document(&'a Element),
+    Element(&'a Element),
+}
+
+impl<'a> Context<'a> {
+    fn elements(&self) -> Vec<&'a Element> {
+        match self {
+            Context::Document(root) => vec![*root],
+            Context::Element(element) => element.elements().collect(),
+        }
+    }
+}
+
+/// The values of the elements and attributes a targeting expression selects in a document: the
+/// text of the elements, and the values of the attributes.
+pub fn select(root: &Element, target: &str) -> Result<Vec<String>, TaxiiError> {
+    check_target(target)?;
+    let nodes: Vec<&str> = target.split('/').collect();
+    let mut values = Vec::<String>::new();
+    select_nodes(Context::Document(root), &nodes, &mut values);
+    Ok(values)
+}
+
+fn select_nodes(context: Context, nodes: &[&str], values: &mut Vec<String>) {
+    let (node, rest) = match nodes.split_first() {
+        Some(v) => v,
+        None => {
+            if let Context::Element(element) = context {
+                values.push(element.text());
+            }
+            return;
+        }
+    };
+    if let Some(name) = node.strip_prefix('@') {
+        if let Context::Element(element) = context {
+            values.extend(
+                element
+                    .attributes
+                    .iter()
+                    .filter(|a| name == "*" || a.name.local_name == name)
+                    .map(|a| a.value.clone()),
+            );
+        }
+        return;
+    }
+    if *node == "**" {
+        select_nodes(context, rest, values);
+        for element in context.elements() {
+            select_nodes(Context::Element(element), nodes, values);
+        }
+        return;
+    }
+    for element in context.elements() {
+        if *node == "*" || element.local_name() == *node {
+            select_nodes(Context::Element(element), rest, values);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::{
+        parse_query, select, Capability, Criteria, Criterion, DefaultQuery, MatchType, Operator,
+        Relationship, Test, DEFAULT_QUERY_FORMAT_ID,
+    };
+    use crate::taxii::{content_block::ContentBlock, message::Element};
+
+    static STIX_11_BINDING: &str = "urn:stix.mitre.org:xml:1.1.1";
+
+    static PACKAGE: &str = "<stix:STIX_Package xmlns:stix=\"http://stix.mitre.org/stix-1\" \
+        xmlns:indicator=\"http://stix.mitre.org/Indicator-2\" id=\"example:Package-1\" \
+        timestamp=\"2014-05-08T09:00:00.000000Z\">\
+        <stix:STIX_Header><stix:Title>Phishing campaign</stix:Title></stix:STIX_Header>\
+        <stix:Indicators>\
+        <stix:Indicator id=\"example:Indicator-1\" timestamp=\"2014-05-08T09:00:00\">\
+        <indicator:Title>Malicious URL</indicator:Title>\
+        <indicator:Confidence><stix:Value>85</stix:Value></indicator:Confidence>\
+        </stix:Indicator>\
+        <stix:Indicator id=\"example:Indicator-2\"><indicator:Title>Sender</indicator:Title>\
+        </stix:Indicator>\
+        </stix:Indicators>\
+        </stix:STIX_Package>";
+
+    #[test]
+    fn test_select() {
+        let root = Element::parse(PACKAGE.as_bytes()).unwrap();
+        assert_eq!(
+            vec!["example:Indicator-1", "example:Indicator-2"],
+            select(&root, "STIX_Package/Indicators/Indicator/@id").unwrap()
+        );
+        assert_eq!(
+            vec![
+                "example:Package-1",
+                "example:Indicator-1",
+                "example:Indicator-2"
+            ],
+            select(&root, "**/@id").unwrap()
+        );
+        assert_eq!(
+            vec!["Malicious URL", "Sender"],
+            select(&root, "STIX_Package/*/*/Title").unwrap()
+        );
+        assert_eq!(vec!["85"], select(&root, "**/Confidence/Value").unwrap());
+        assert!(select(&root, "Indicators/Indicator").unwrap().is_empty());
+        assert!(select(&root, "STIX_Package/@id/Title").is_err());
+        assert!(select(&root, "STIX_Package//Title").is_err());
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let query = |criteria: Criteria| DefaultQuery::new(STIX_11_BINDING, criteria);
+        let criterion = |target: &str, test: Test| {
+            query(Criteria::new(Operator::And).with_criterion(Criterion::new(target, test)))
+                .evaluate(PACKAGE)
+                .unwrap()
+        };
+        let id = "**/@id";
+        assert!(criterion(
+            id,
+            Test::equals("example:Indicator-2", MatchType::CaseSensitiveString)
+        ));
+        assert!(!criterion(
+            id,
+            Test::equals("EXAMPLE:INDICATOR-2", MatchType::CaseSensitiveString)
+        ));
+        assert!(criterion(
+            id,
+            Test::equals("EXAMPLE:INDICATOR-2", MatchType::CaseInsensitiveString)
+        ));
+        let confidence = "**/Confidence/Value";
+        assert!(criterion(
+            confidence,
+            Test::equals("85.0", MatchType::Number)
+        ));
+        assert!(criterion(
+            confidence,
+            Test::number(Relationship::GreaterThan, 80.0).unwrap()
+        ));
+        assert!(!criterion(
+            confidence,
+            Test::number(Relationship::LessThanOrEqual, 84.0).unwrap()
+        ));
+        assert!(criterion("**/Confidence", Test::exists(true)));
+        assert!(!criterion("**/Kill_Chain", Test::exists(true)));
+        assert!(criterion("**/Kill_Chain", Test::exists(false)));
+        let title = "STIX_Package/STIX_Header/Title";
+        assert!(criterion(
+            title,
+            Test::string(Relationship::BeginsWith, "phishing", false).unwrap()
+        ));
+        assert!(!criterion(
+            title,
+            Test::string(Relationship::BeginsWith, "phishing", true).unwrap()
+        ));
+        assert!(criterion(
+            title,
+            Test::string(Relationship::Contains, "campaign", true).unwrap()
+        ));
+        assert!(criterion(
+            "**/Indicator/Title",
+            Test::matches("^mal.*url$", false).unwrap()
+        ));
+        let timestamp = "STIX_Package/Indicators/Indicator/@timestamp";
+        let may_1 = Utc.with_ymd_and_hms(2014, 5, 1, 0, 0, 0).unwrap();
+        assert!(criterion(
+            timestamp,
+            Test::timestamp(Relationship::GreaterThan, may_1).unwrap()
+        ));
+        assert!(!criterion(
+            timestamp,
+            Test::timestamp(Relationship::LessThan, may_1).unwrap()
+        ));
+
+        let indicator_1 = Criterion::new(
+            id,
+            Test::equals("example:Indicator-1", MatchType::CaseSensitiveString),
+        );
+        let other = Criterion::new(
+            id,
+            Test::equals("example:Other", MatchType::CaseSensitiveString),
+        );
+        let or = Criteria::new(Operator::Or)
+            .with_criterion(indicator_1.clone())
+            .with_criterion(other.clone());
+        assert!(query(or.clone()).evaluate(PACKAGE).unwrap());
+        let and = Criteria::new(Operator::And)
+            .with_criteria(or.clone())
+            .with_criterion(other.clone());
+        assert!(!query(and).evaluate(PACKAGE).unwrap());
+        let and = Criteria::new(Operator::And)
+            .with_criteria(or)
+            .with_criterion(other.negated());
+        assert!(query(and.clone()).evaluate(PACKAGE).unwrap());
+
+        let query = query(and);
+        assert!(query.matches(&ContentBlock::new(STIX_11_BINDING, PACKAGE)));
+        assert!(!query.matches(&ContentBlock::new("urn:example:text", PACKAGE)));
+        assert!(!query.matches(&ContentBlock::new(STIX_11_BINDING, "not xml")));
+    }
+
+    #[test]
+    fn test_default_query_round_trip() {
+        let query = DefaultQuery::new(
+            STIX_11_BINDING,
+            Criteria::new(Operator::Or)
+                .with_criteria(
+                    Criteria::new(Operator::And).with_criterion(
+                        Criterion::new(
+                            "**/@id",
+                            Test::equals("example:Indicator-1", MatchType::CaseSensitiveString),
+                        )
+                        .negated(),
+                    ),
+                )
+                .with_criterion(Criterion::new(
+                    "**/Title",
+                    Test::matches("phish", false).unwrap(),
+                )),
+        );
+        let doc = query.to_xml().unwrap();
+        assert!(doc.starts_with(
+            "<tdq:Default_Query xmlns:tdq=\"http://taxii.mitre.org/query/taxii_default_query-1\" \
+            targeting_expression_id=\"urn:stix.mitre.org:xml:1.1.1\">"
+        ));
+        assert!(doc.contains("<tdq:Parameter name=\"case_sensitive\">false</tdq:Parameter>"));
+        assert_eq!(query, DefaultQuery::parse(doc.as_str()).unwrap());
+        assert_eq!(
+            Some(query),
+            parse_query(doc.as_str(), DEFAULT_QUERY_FORMAT_ID).unwrap()
+        );
+        assert_eq!(None, parse_query("", "").unwrap());
+        assert!(parse_query(doc.as_str(), "urn:example:query").is_err());
+    }
+
+    #[test]
+    fn test_invalid_tests() {
+        // relationships of another capability module
+        assert!(Test::new(
+            Capability::Regex,
+            Relationship::Equals,
+            Some("a"),
+            None,
+            Some(true)
+        )
+        .is_err());
+        assert!(Test::new(
+            Capability::Timestamp,
+            Relationship::Contains,
+            Some("2014-05-08T09:00:00Z"),
+            None,
+            None
+        )
+        .is_err());
+        // missing or bad parameters
+        assert!(Test::new(
+            Capability::Core,
+            Relationship::Equals,
+            Some("a"),
+            None,
+            None
+        )
+        .is_err());
+        assert!(Test::new(
+            Capability::Core,
+            Relationship::Contains,
+            Some("a"),
+            None,
+            None
+        )
+        .is_err());
+        assert!(Test::new(
+            Capability::Core,
+            Relationship::GreaterThan,
+            Some("a"),
+            None,
+            None
+        )
+        .is_err());
+        assert!(Test::new(
+            Capability::Timestamp,
+            Relationship::Equals,
+            Some("yesterday"),
+            None,
+            None
+        )
+        .is_err());
+        assert!(Test::matches("(unclosed", true).is_err());
+
+        let doc =
+            "<tdq:Default_Query xmlns:tdq=\"http://taxii.mitre.org/query/taxii_default_query-1\" \
+            targeting_expression_id=\"urn:stix.mitre.org:xml:1.1.1\">\
+            <tdq:Criteria operator=\"AND\"><tdq:Criterion><tdq:Target>**/@id</tdq:Target>\
+            <tdq:Test capability_id=\"urn:example:capability\" relationship=\"equals\"/>\
+            </tdq:Criterion></tdq:Criteria></tdq:Default_Query>";
+        assert!(DefaultQuery::parse(doc).is_err());
+    }
+}
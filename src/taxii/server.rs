@@ -0,0 +1,1433 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use actix_web::{
+    body::BoxBody,
+    dev::{ServiceFactory, ServiceRequest, ServiceResponse},
+    web, App, Error, HttpRequest, HttpResponse, HttpServer,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use chrono::Utc;
+
+use super::{
+    collections::{
+        create_collection_information_response_body, Collection, CollectionService,
+        CollectionServiceType, CollectionSet, CollectionType,
+    },
+    errors::TaxiiError,
+    inbox::{parse_inbox_message, InboxMessage},
+    poll::{create_poll_response_body, parse_poll_request, PollResponse},
+    query::{parse_query, DefaultQuery, DEFAULT_QUERY_FORMAT_ID, STIX_1_TARGETING_EXPRESSION_IDS},
+    services::{create_discovery_response_body, ServiceInstance, ServiceSet, ServiceType},
+    status_message::{create_status_message_body, StatusType},
+    store::{MemoryStore, Store},
+    subscriptions::{
+        create_subscription_management_response_body, parse_subscription_management_request,
+        PollInstance, SubscribeAction, Subscription, SubscriptionResponse, SubscriptionStatus,
+    },
+    types::{RecordCount, ResponseType},
+    version::{parse_message_root, Version, XML_BINDING_HTTPS_10, XML_BINDING_HTTP_10},
+};
+use crate::taxii21::{
+    backend::Backend,
+    server::{Envelope, Object},
+};
+
+/// Receives the content of the Inbox_Messages accepted by the inbox service.
+pub trait InboxSink: Send + Sync {
+    /// The content bindings the sink accepts. Messages with other content are answered
+    /// UNSUPPORTED_CONTENT. All content is accepted when empty.
+    fn content_bindings(&self) -> Vec<String> {
+        Vec::<String>::new()
+    }
+    /// The collections the sink accepts content for. Messages for other destination collections
+    /// are answered DESTINATION_COLLECTION_ERROR. All collections are accepted when empty.
+    fn collection_names(&self) -> Vec<String> {
+        Vec::<String>::new()
+    }
+    /// Handle an accepted message. An error is answered with a FAILURE status message.
+    fn receive(&self, inbox_message: &InboxMessage) -> Result<(), TaxiiError>;
+}
+
+/// STIX 2.1 JSON content, either a bundle or a single object.
+pub static STIX_JSON_21_BINDING: &str = "application/stix+json;version=2.1";
+
+/// A sink that adds pushed STIX 2.1 content to a collection of a TAXII 2.1 backend.
+pub struct Taxii21Sink {
+    backend: Arc<Mutex<dyn Backend + Send>>,
+    collection_id: String,
+}
+
+impl Taxii21Sink {
+    pub fn new(backend: Arc<Mutex<dyn Backend + Send>>, collection_id: &str) -> Taxii21Sink {
+        Taxii21Sink {
+            backend,
+            collection_id: String::from(collection_id),
+        }
+    }
+}
+
+/// Read the STIX 2.1 objects of a content block: a bundle or envelope, or a single object.
+pub fn parse_stix_objects(content: &str) -> Result<Vec<Object>, TaxiiError> {
+    match serde_json::from_str::<Envelope>(content) {
+        Ok(Envelope {
+            objects: Some(objects),
+            ..
+        }) => return Ok(objects),
+        Ok(_) => (),
+        Err(err) => return Err(TaxiiError::Protocol(err.to_string())),
+    }
+    match serde_json::from_str::<Object>(content) {
+        Ok(v) => Ok(vec![v]),
+        Err(err) => Err(TaxiiError::Protocol(err.to_string())),
+    }
+}
+
+impl InboxSink for Taxii21Sink {
+    fn content_bindings(&self) -> Vec<String> {
+        vec![String::from(STIX_JSON_21_BINDING)]
+    }
+    fn receive(&self, inbox_message: &InboxMessage) -> Result<(), TaxiiError> {
+        let mut objects = Vec::<Object>::new();
+        for content_block in inbox_message.content_blocks.iter() {
+            objects.extend(parse_stix_objects(content_block.content.as_str())?);
+        }
+        let mut backend = match self.backend.lock() {
+            Ok(v) => v,
+            Err(err) => return Err(TaxiiError::Storage(err.to_string())),
+        };
+        let status = match backend.add_objects(self.collection_id.as_str(), &objects) {
+            Ok(v) => v,
+            Err(err) => return Err(TaxiiError::Storage(err.0)),
+        };
+        info!(
+            "inbox added objects: collection={} successes={} failures={}",
+            self.collection_id, status.success_count, status.failure_count
+        );
+        Ok(())
+    }
+}
+
+/// The content bindings accepted by all of the collections, empty when one of them accepts any
+/// content.
+fn accepted_content_bindings(collections: &[Collection]) -> Vec<String> {
+    if collections.iter().any(|c| c.content_bindings.is_empty()) {
+        return Vec::<String>::new();
+    }
+    let mut content_bindings = Vec::<String>::new();
+    for binding_id in collections.iter().flat_map(|c| c.content_bindings.iter()) {
+        if !content_bindings.contains(binding_id) {
+            content_bindings.push(binding_id.clone());
+        }
+    }
+    content_bindings
+}
+
+/// A sink that adds the content it receives to the destination collections of a store.
+pub struct StoreSink {
+    store: Arc<Mutex<dyn Store + Send>>,
+}
+
+impl StoreSink {
+    pub fn new(store: Arc<Mutex<dyn Store + Send>>) -> StoreSink {
+        StoreSink { store }
+    }
+    fn collections(&self) -> Vec<Collection> {
+        match self.store.lock() {
+            Ok(store) => store.collections(),
+            Err(_) => Vec::<Collection>::new(),
+        }
+    }
+}
+
+impl InboxSink for StoreSink {
+    fn content_bindings(&self) -> Vec<String> {
+        accepted_content_bindings(&self.collections())
+    }
+    fn collection_names(&self) -> Vec<String> {
+        self.collections()
+            .into_iter()
+            .map(|c| c.collection_name)
+            .collect()
+    }
+    fn receive(&self, inbox_message: &InboxMessage) -> Result<(), TaxiiError> {
+        if inbox_message.destination_collection_names.is_empty() {
+            return Err(TaxiiError::Protocol(
+                "missing destination collection".to_string(),
+            ));
+        }
+        let mut store = match self.store.lock() {
+            Ok(v) => v,
+            Err(err) => return Err(TaxiiError::Storage(err.to_string())),
+        };
+        for collection_name in inbox_message.destination_collection_names.iter() {
+            for content_block in inbox_message.content_blocks.iter() {
+                store.add_content(collection_name, content_block)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Check the headers required by the TAXII HTTP protocol binding, returning the status to answer
+/// with when they do not match what the service supports.
+pub fn check_taxii_headers(req: &HttpRequest, ver: Version) -> Result<(), StatusType> {
+    let header = |name: &str| -> Option<&str> {
+        req.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim())
+    };
+    match header("Content-Type") {
+        Some(v) if v.starts_with(ver.content_type()) => (),
+        _ => return Err(StatusType::BadMessage),
+    }
+    match header("X-TAXII-Content-Type") {
+        Some(v) if ver.xml_binding_urn().is_ok_and(|urn| v == urn) => (),
+        Some(_) => return Err(StatusType::UnsupportedMessage),
+        None => return Err(StatusType::BadMessage),
+    }
+    match header("X-TAXII-Services") {
+        Some(v) if ver.services_version_urn().is_ok_and(|urn| v == urn) => (),
+        Some(_) => return Err(StatusType::UnsupportedMessage),
+        None => return Err(StatusType::BadMessage),
+    }
+    let protocols = [XML_BINDING_HTTP_10, XML_BINDING_HTTPS_10];
+    match header("X-TAXII-Protocol") {
+        Some(v) if protocols.contains(&v) => (),
+        Some(_) => {
+            return Err(StatusType::UnsupportedProtocol {
+                supported_protocols: protocols.iter().map(|v| String::from(*v)).collect(),
+            })
+        }
+        None => return Err(StatusType::BadMessage),
+    }
+    if let Some(accept) = header("X-TAXII-Accept") {
+        if !accept
+            .split(',')
+            .any(|v| ver.xml_binding_urn().is_ok_and(|urn| v.trim() == urn))
+        {
+            return Err(StatusType::UnsupportedMessage);
+        }
+    }
+    Ok(())
+}
+
+/// Answer with a Status_Message. TAXII reports errors in the message, the HTTP status is 200.
+pub fn status_response(
+    ver: Version,
+    in_response_to: &str,
+    status_type: &StatusType,
+    message: Option<&str>,
+) -> HttpResponse {
+    taxii_response(
+        ver,
+        create_status_message_body(ver, in_response_to, status_type, message),
+    )
+}
+
+/// Answer with a TAXII message and the headers of the TAXII HTTP protocol binding.
+pub fn taxii_response(ver: Version, body: Result<String, TaxiiError>) -> HttpResponse {
+    let response =
+        body.and_then(|body| Ok((ver.xml_binding_urn()?, ver.services_version_urn()?, body)));
+    match response {
+        Ok((xml_binding_urn, services_version_urn, body)) => HttpResponse::Ok()
+            .append_header(("Content-Type", ver.content_type()))
+            .append_header(("X-TAXII-Content-Type", xml_binding_urn))
+            .append_header(("X-TAXII-Services", services_version_urn))
+            .append_header(("X-TAXII-Protocol", XML_BINDING_HTTP_10))
+            .body(body),
+        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+    }
+}
+
+#[derive(Clone)]
+struct InboxState {
+    sink: Arc<dyn InboxSink>,
+}
+
+async fn handle_inbox(
+    req: HttpRequest,
+    body: web::Bytes,
+    state: web::Data<InboxState>,
+) -> HttpResponse {
+    let ver = Version::V11;
+    if let Err(status_type) = check_taxii_headers(&req, ver) {
+        return status_response(ver, "0", &status_type, None);
+    }
+    let inbox_message = match parse_inbox_message(&body) {
+        Ok(v) => v,
+        Err(err) => {
+            return status_response(
+                ver,
+                "0",
+                &StatusType::BadMessage,
+                Some(err.to_string().as_str()),
+            )
+        }
+    };
+    let in_response_to = inbox_message.message_id.as_str();
+    let collection_names = state.sink.collection_names();
+    if !collection_names.is_empty()
+        && inbox_message
+            .destination_collection_names
+            .iter()
+            .any(|v| !collection_names.contains(v))
+    {
+        let status_type = StatusType::DestinationCollectionError {
+            acceptable_destinations: collection_names,
+        };
+        return status_response(ver, in_response_to, &status_type, None);
+    }
+    let content_bindings = state.sink.content_bindings();
+    if !content_bindings.is_empty()
+        && inbox_message
+            .content_blocks
+            .iter()
+            .any(|v| !content_bindings.contains(&v.content_binding.binding_id))
+    {
+        let status_type = StatusType::UnsupportedContent {
+            supported_content: content_bindings,
+        };
+        return status_response(ver, in_response_to, &status_type, None);
+    }
+    match state.sink.receive(&inbox_message) {
+        Ok(_) => status_response(ver, in_response_to, &StatusType::Success, None),
+        Err(err) => status_response(
+            ver,
+            in_response_to,
+            &StatusType::Failure,
+            Some(err.to_string().as_str()),
+        ),
+    }
+}
+
+/// An app serving a TAXII 1.1 inbox service at `/services/inbox`, e.g. to receive the content
+/// pushed for subscriptions made with push parameters. The binary does not run it: the caller
+/// provides the sink, which decides whether the content outlives the process.
+pub fn new_inbox_app(
+    sink: Arc<dyn InboxSink>,
+) -> actix_web::App<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<BoxBody>,
+        Error = Error,
+        InitError = (),
+    >,
+> {
+    App::new()
+        .app_data(web::Data::new(InboxState { sink }))
+        .service(web::resource(INBOX_PATH).route(web::post().to(handle_inbox)))
+}
+
+static DISCOVERY_PATH: &str = "/services/discovery";
+static COLLECTION_MANAGEMENT_PATH: &str = "/services/collection-management";
+static POLL_PATH: &str = "/services/poll";
+static INBOX_PATH: &str = "/services/inbox";
+
+struct AppState {
+    /// The URL the services are reached at, e.g. `https://taxii.example.com`, used for the
+    /// addresses of the services in responses.
+    base_url: String,
+    store: Arc<Mutex<dyn Store + Send>>,
+}
+
+impl AppState {
+    fn protocol_binding(&self) -> &'static str {
+        if self.base_url.starts_with("https:") {
+            XML_BINDING_HTTPS_10
+        } else {
+            XML_BINDING_HTTP_10
+        }
+    }
+    fn address(&self, path: &str) -> String {
+        format!("{}{}", self.base_url.trim_end_matches('/'), path)
+    }
+}
+
+/// The message type and ID of a request to a service, or the response when the request is not
+/// one the service can handle.
+fn read_request(
+    req: &HttpRequest,
+    ver: Version,
+    body: &[u8],
+    message_types: &[&str],
+) -> Result<(String, String), HttpResponse> {
+    if let Err(status_type) = check_taxii_headers(req, ver) {
+        return Err(status_response(ver, "0", &status_type, None));
+    }
+    let (message_type, message_id) = match parse_message_root(body) {
+        Ok(v) => v,
+        Err(err) => {
+            return Err(status_response(
+                ver,
+                "0",
+                &StatusType::BadMessage,
+                Some(err.to_string().as_str()),
+            ))
+        }
+    };
+    if !message_types.contains(&message_type.as_str()) {
+        return Err(status_response(
+            ver,
+            message_id.as_str(),
+            &StatusType::UnsupportedMessage,
+            Some(format!("{} not supported by this service", message_type).as_str()),
+        ));
+    }
+    Ok((message_type, message_id))
+}
+
+fn failure(ver: Version, in_response_to: &str, err: &dyn std::fmt::Display) -> HttpResponse {
+    status_response(
+        ver,
+        in_response_to,
+        &StatusType::Failure,
+        Some(err.to_string().as_str()),
+    )
+}
+
+/// The TAXII Default Query of a poll or subscription, or the status message to answer when the
+/// server cannot evaluate the query.
+fn read_query(
+    ver: Version,
+    in_response_to: &str,
+    query: &str,
+    query_format_id: &str,
+) -> Result<Option<DefaultQuery>, HttpResponse> {
+    if !query.trim().is_empty() && query_format_id != DEFAULT_QUERY_FORMAT_ID {
+        let status_type = StatusType::UnsupportedQuery {
+            supported_queries: vec![String::from(DEFAULT_QUERY_FORMAT_ID)],
+        };
+        return Err(status_response(ver, in_response_to, &status_type, None));
+    }
+    let query = match parse_query(query, query_format_id) {
+        Ok(v) => v,
+        Err(err) => {
+            return Err(status_response(
+                ver,
+                in_response_to,
+                &StatusType::BadMessage,
+                Some(err.to_string().as_str()),
+            ))
+        }
+    };
+    if let Some(query) = &query {
+        if !STIX_1_TARGETING_EXPRESSION_IDS.contains(&query.targeting_expression_id.as_str()) {
+            let status_type = StatusType::Other {
+                name: String::from("UNSUPPORTED_TARGETING_EXPRESSION_ID"),
+                details: STIX_1_TARGETING_EXPRESSION_IDS
+                    .iter()
+                    .map(|v| (String::from("TARGETING_EXPRESSION_ID"), String::from(*v)))
+                    .collect(),
+            };
+            return Err(status_response(ver, in_response_to, &status_type, None));
+        }
+    }
+    Ok(query)
+}
+
+fn not_found(ver: Version, in_response_to: &str, item: &str) -> HttpResponse {
+    let status_type = StatusType::NotFound {
+        item: Some(String::from(item)),
+    };
+    status_response(ver, in_response_to, &status_type, None)
+}
+
+async fn handle_discovery(
+    req: HttpRequest,
+    body: web::Bytes,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    let ver = Version::V11;
+    let (_, message_id) = match read_request(&req, ver, &body, &["Discovery_Request"]) {
+        Ok(v) => v,
+        Err(resp) => return resp,
+    };
+    let collections = match state.store.lock() {
+        Ok(store) => store.collections(),
+        Err(err) => return failure(ver, message_id.as_str(), &err),
+    };
+    taxii_response(
+        ver,
+        discovery_services(ver, &state, &collections).and_then(|service_set| {
+            create_discovery_response_body(ver, message_id.as_str(), &service_set)
+        }),
+    )
+}
+
+fn discovery_services(
+    ver: Version,
+    state: &AppState,
+    collections: &[Collection],
+) -> Result<ServiceSet, TaxiiError> {
+    let mut service_set = ServiceSet::new();
+    for (service_type, path) in [
+        (ServiceType::Discovery, DISCOVERY_PATH),
+        (
+            ServiceType::CollectionManagement,
+            COLLECTION_MANAGEMENT_PATH,
+        ),
+        (ServiceType::Poll, POLL_PATH),
+        (ServiceType::Inbox, INBOX_PATH),
+    ] {
+        let mut service = ServiceInstance::new_empty();
+        if service_type == ServiceType::Inbox {
+            service.content_bindings = accepted_content_bindings(collections);
+        }
+        service.service_type = service_type;
+        service.service_version = String::from(ver.services_version_urn()?);
+        service.available = true;
+        service.protocol_binding = String::from(state.protocol_binding());
+        service.address = state.address(path);
+        service.message_bindings = vec![String::from(ver.xml_binding_urn()?)];
+        service_set.services.push(service);
+    }
+    Ok(service_set)
+}
+
+async fn handle_collection_management(
+    req: HttpRequest,
+    body: web::Bytes,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    let ver = Version::V11;
+    let message_types = [
+        "Collection_Information_Request",
+        "Subscription_Management_Request",
+    ];
+    let (message_type, message_id) = match read_request(&req, ver, &body, &message_types) {
+        Ok(v) => v,
+        Err(resp) => return resp,
+    };
+    if message_type == "Subscription_Management_Request" {
+        return subscription_management(ver, &body, &state);
+    }
+    let collections = match state.store.lock() {
+        Ok(store) => store.collections(),
+        Err(err) => return failure(ver, message_id.as_str(), &err),
+    };
+    taxii_response(
+        ver,
+        collection_information(ver, &state, collections).and_then(|collection_set| {
+            create_collection_information_response_body(ver, message_id.as_str(), &collection_set)
+        }),
+    )
+}
+
+fn collection_information(
+    ver: Version,
+    state: &AppState,
+    collections: Vec<Collection>,
+) -> Result<CollectionSet, TaxiiError> {
+    let xml_binding_urn = ver.xml_binding_urn()?;
+    let mut collection_set = CollectionSet::new();
+    for mut collection in collections.into_iter() {
+        let services = [
+            (CollectionServiceType::PollingService, POLL_PATH),
+            (
+                CollectionServiceType::SubscriptionService,
+                COLLECTION_MANAGEMENT_PATH,
+            ),
+            (CollectionServiceType::ReceivingInboxService, INBOX_PATH),
+        ];
+        collection.collection_services = services
+            .iter()
+            .map(|(collection_service_type, path)| {
+                let mut service = CollectionService::new(*collection_service_type);
+                service.protocol_binding = String::from(state.protocol_binding());
+                service.address = state.address(path);
+                if *collection_service_type == CollectionServiceType::ReceivingInboxService {
+                    service.content_bindings = collection.content_bindings.clone();
+                }
+                service.message_bindings = vec![String::from(xml_binding_urn)];
+                service
+            })
+            .collect();
+        collection_set.collections.push(collection);
+    }
+    Ok(collection_set)
+}
+
+fn subscription_management(ver: Version, body: &[u8], state: &AppState) -> HttpResponse {
+    let request = match parse_subscription_management_request(body) {
+        Ok(v) => v,
+        Err(err) => {
+            return status_response(
+                ver,
+                "0",
+                &StatusType::BadMessage,
+                Some(err.to_string().as_str()),
+            )
+        }
+    };
+    let in_response_to = request.message_id.as_str();
+    let collection_name = request.collection_name.as_str();
+    let mut store = match state.store.lock() {
+        Ok(v) => v,
+        Err(err) => return failure(ver, in_response_to, &err),
+    };
+    if store.collection(collection_name).is_none() {
+        return not_found(ver, in_response_to, collection_name);
+    }
+    if let Some(subscription_parameters) = &request.subscription_parameters {
+        if let Err(resp) = read_query(
+            ver,
+            in_response_to,
+            subscription_parameters.query.as_deref().unwrap_or_default(),
+            subscription_parameters
+                .query_format_id
+                .as_deref()
+                .unwrap_or_default(),
+        ) {
+            return resp;
+        }
+    }
+    let subscription_id = request.subscription_id.as_deref();
+    let result = match (request.action, subscription_id) {
+        (SubscribeAction::Subscribe, _) => store
+            .subscribe(
+                collection_name,
+                request.subscription_parameters.as_ref(),
+                request.push_parameters.as_ref(),
+            )
+            .map(|v| Some(vec![v])),
+        (SubscribeAction::Status, None) => store.subscriptions(collection_name).map(Some),
+        (SubscribeAction::Status, Some(subscription_id)) => store
+            .subscription(collection_name, subscription_id)
+            .map(|v| v.map(|v| vec![v])),
+        (action, Some(subscription_id)) => {
+            let status = match action {
+                SubscribeAction::Unsubscribe => SubscriptionStatus::Unsubscribed,
+                SubscribeAction::Pause => SubscriptionStatus::Paused,
+                _ => SubscriptionStatus::Active,
+            };
+            store
+                .update_subscription(collection_name, subscription_id, status)
+                .map(|v| v.map(|v| vec![v]))
+        }
+        (action, None) => {
+            let message = format!("missing Subscription_ID for {}", action.to_str());
+            return status_response(
+                ver,
+                in_response_to,
+                &StatusType::BadMessage,
+                Some(message.as_str()),
+            );
+        }
+    };
+    let subscriptions = match result {
+        Ok(Some(v)) => v,
+        Ok(None) => return not_found(ver, in_response_to, subscription_id.unwrap_or_default()),
+        Err(err) => return failure(ver, in_response_to, &err),
+    };
+    let mut subscription_response = SubscriptionResponse::new_empty();
+    subscription_response.in_response_to = String::from(in_response_to);
+    subscription_response.collection_name = String::from(collection_name);
+    let response = subscriptions
+        .into_iter()
+        .map(|subscription| with_poll_instance(ver, state, subscription))
+        .collect::<Result<Vec<_>, _>>()
+        .and_then(|subscriptions| {
+            subscription_response.subscriptions = subscriptions;
+            create_subscription_management_response_body(ver, &subscription_response)
+        });
+    taxii_response(ver, response)
+}
+
+/// Subscriptions without push parameters are polled for, at the poll service.
+fn with_poll_instance(
+    ver: Version,
+    state: &AppState,
+    mut subscription: Subscription,
+) -> Result<Subscription, TaxiiError> {
+    if subscription.push_parameters.is_none() {
+        subscription.poll_instances = vec![PollInstance {
+            protocol_binding: String::from(state.protocol_binding()),
+            address: state.address(POLL_PATH),
+            message_bindings: vec![String::from(ver.xml_binding_urn()?)],
+        }];
+    }
+    Ok(subscription)
+}
+
+async fn handle_poll(
+    req: HttpRequest,
+    body: web::Bytes,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    let ver = Version::V11;
+    let message_id = match read_request(&req, ver, &body, &["Poll_Request"]) {
+        Ok((_, v)) => v,
+        Err(resp) => return resp,
+    };
+    let poll_request = match parse_poll_request(&body) {
+        Ok(v) => v,
+        Err(err) => {
+            return status_response(
+                ver,
+                message_id.as_str(),
+                &StatusType::BadMessage,
+                Some(err.to_string().as_str()),
+            )
+        }
+    };
+    let in_response_to = message_id.as_str();
+    let collection_name = poll_request.collection_name.as_str();
+    let store = match state.store.lock() {
+        Ok(v) => v,
+        Err(err) => return failure(ver, in_response_to, &err),
+    };
+    if store.collection(collection_name).is_none() {
+        return not_found(ver, in_response_to, collection_name);
+    }
+    let (response_type, content_bindings, query) =
+        match (&poll_request.subscription_id, &poll_request.poll_parameters) {
+            (Some(subscription_id), _) => {
+                let subscription = match store.subscription(collection_name, subscription_id) {
+                    Ok(Some(v)) => v,
+                    Ok(None) => return not_found(ver, in_response_to, subscription_id),
+                    Err(err) => return failure(ver, in_response_to, &err),
+                };
+                if subscription.status != SubscriptionStatus::Active {
+                    let message = format!("subscription is {}", subscription.status.to_str());
+                    return status_response(
+                        ver,
+                        in_response_to,
+                        &StatusType::Denied,
+                        Some(message.as_str()),
+                    );
+                }
+                let (content_bindings, query) = match &subscription.subscription_parameters {
+                    Some(v) => (
+                        v.content_bindings.clone(),
+                        read_query(
+                            ver,
+                            in_response_to,
+                            v.query.as_deref().unwrap_or_default(),
+                            v.query_format_id.as_deref().unwrap_or_default(),
+                        ),
+                    ),
+                    None => (Vec::new(), Ok(None)),
+                };
+                let query = match query {
+                    Ok(v) => v,
+                    Err(resp) => return resp,
+                };
+                (subscription.response_type(), content_bindings, query)
+            }
+            (None, Some(poll_parameters)) => {
+                let query = match read_query(
+                    ver,
+                    in_response_to,
+                    poll_parameters.query.as_str(),
+                    poll_parameters.query_format_id.as_str(),
+                ) {
+                    Ok(v) => v,
+                    Err(resp) => return resp,
+                };
+                (
+                    poll_parameters.response_type,
+                    poll_parameters.content_bindings.clone(),
+                    query,
+                )
+            }
+            (None, None) => {
+                return status_response(
+                    ver,
+                    in_response_to,
+                    &StatusType::BadMessage,
+                    Some("missing Subscription_ID or Poll_Parameters"),
+                )
+            }
+        };
+    let content_blocks = match store.poll(collection_name, &poll_request.time_range) {
+        Ok(v) => v,
+        Err(err) => return failure(ver, in_response_to, &err),
+    };
+    let content_blocks: Vec<_> = content_blocks
+        .into_iter()
+        .filter(|content_block| {
+            let binding = &content_block.content_binding;
+            content_bindings.is_empty()
+                || content_bindings.iter().any(|v| {
+                    v.binding_id == binding.binding_id
+                        && (v.subtype_id.is_none() || v.subtype_id == binding.subtype_id)
+                })
+        })
+        .filter(|content_block| query.as_ref().is_none_or(|v| v.matches(content_block)))
+        .collect();
+
+    let mut poll_response = PollResponse::new_empty();
+    poll_response.in_response_to = String::from(in_response_to);
+    poll_response.collection_name = String::from(collection_name);
+    poll_response.subscription_id = poll_request.subscription_id.clone();
+    poll_response.time_range.exclusive_begin = poll_request.time_range.exclusive_begin;
+    poll_response.time_range.inclusive_end = Some(
+        poll_request
+            .time_range
+            .inclusive_end
+            .unwrap_or_else(Utc::now),
+    );
+    poll_response.record_count = Some(RecordCount {
+        count: content_blocks.len() as u64,
+        partial_count: false,
+    });
+    if response_type == ResponseType::Full {
+        poll_response.content_blocks = content_blocks;
+    }
+    taxii_response(ver, create_poll_response_body(ver, &poll_response))
+}
+
+/// An app serving the TAXII 1.1 Discovery, Collection Management, Poll and Inbox services for
+/// the collections of `store`. `base_url` is the URL the app is reached at.
+pub fn new_app(
+    base_url: &str,
+    store: Arc<Mutex<dyn Store + Send>>,
+) -> actix_web::App<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<BoxBody>,
+        Error = Error,
+        InitError = (),
+    >,
+> {
+    let sink = Arc::new(StoreSink::new(store.clone()));
+    App::new()
+        .app_data(web::Data::new(AppState {
+            base_url: String::from(base_url),
+            store,
+        }))
+        .app_data(web::Data::new(InboxState { sink }))
+        .service(web::resource(DISCOVERY_PATH).route(web::post().to(handle_discovery)))
+        .service(
+            web::resource(COLLECTION_MANAGEMENT_PATH)
+                .route(web::post().to(handle_collection_management)),
+        )
+        .service(web::resource(POLL_PATH).route(web::post().to(handle_poll)))
+        .service(web::resource(INBOX_PATH).route(web::post().to(handle_inbox)))
+}
+
+fn default_collection_type() -> String {
+    String::from("DATA_FEED")
+}
+
+/// A collection served by the `taxii11` command.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CollectionConfig {
+    pub collection_name: String,
+    #[serde(default)]
+    pub description: String,
+    /// `DATA_FEED` or `DATA_SET`.
+    #[serde(default = "default_collection_type")]
+    pub collection_type: String,
+    /// The content bindings the collection accepts; any content when empty.
+    #[serde(default)]
+    pub content_bindings: Vec<String>,
+}
+
+/// The `[taxii11]` table of the configuration of the `taxii11` command, e.g.
+/// ```toml
+/// [taxii11]
+/// listen = "127.0.0.1:9000"
+/// base_url = "http://127.0.0.1:9000"
+///
+/// [[taxii11.collections]]
+/// collection_name = "stix-data"
+/// content_bindings = ["urn:stix.mitre.org:xml:1.1.1"]
+/// ```
+#[derive(Deserialize, Serialize)]
+pub struct ServerConfig {
+    /// The address to listen at.
+    pub listen: String,
+    /// The URL the services are reached at, used for their addresses in responses.
+    pub base_url: String,
+    #[serde(default)]
+    pub collections: Vec<CollectionConfig>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct ServerConfigFile {
+    taxii11: ServerConfig,
+}
+
+impl ServerConfig {
+    pub fn load_toml(path: &Path) -> Result<ServerConfig, TaxiiError> {
+        let cfg = match std::fs::read_to_string(path) {
+            Ok(v) => v,
+            Err(err) => return Err(TaxiiError::Storage(err.to_string())),
+        };
+        match toml::from_str::<ServerConfigFile>(cfg.as_str()) {
+            Ok(v) => Ok(v.taxii11),
+            Err(err) => Err(TaxiiError::Protocol(err.to_string())),
+        }
+    }
+
+    /// An empty store with the configured collections.
+    pub fn new_store(&self) -> Result<MemoryStore, TaxiiError> {
+        let mut store = MemoryStore::new();
+        for v in self.collections.iter() {
+            let mut collection = Collection::new_empty();
+            collection.collection_name = v.collection_name.clone();
+            collection.collection_type = CollectionType::parse(v.collection_type.as_str())?;
+            collection.available = true;
+            collection.description = v.description.clone();
+            collection.content_bindings = v.content_bindings.clone();
+            store.add_collection(collection);
+        }
+        Ok(store)
+    }
+}
+
+/// Serve the collections configured by a toml file with the TAXII 1.1 services. Their content
+/// is kept in a `MemoryStore`: what is pushed to the inbox is lost when the server stops.
+pub fn main(path: &Path) -> Result<(), TaxiiError> {
+    let cfg = ServerConfig::load_toml(path)?;
+    let store: Arc<Mutex<dyn Store + Send>> = Arc::new(Mutex::new(cfg.new_store()?));
+    let base_url = cfg.base_url.clone();
+    let server = match HttpServer::new(move || new_app(base_url.as_str(), store.clone()))
+        .bind(cfg.listen.as_str())
+    {
+        Ok(v) => v,
+        Err(err) => return Err(TaxiiError::Transport(format!("{}: {}", cfg.listen, err))),
+    };
+    info!(
+        "listening: {} for {} collections at {}",
+        cfg.listen,
+        cfg.collections.len(),
+        cfg.base_url
+    );
+    match actix_web::rt::System::new().block_on(server.run()) {
+        Ok(_) => Ok(()),
+        Err(err) => Err(TaxiiError::Transport(err.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::TcpListener,
+        sync::{Arc, Mutex},
+    };
+
+    use actix_web::{
+        body::to_bytes,
+        dev::{Service, ServiceResponse},
+        http, test, Error, HttpServer,
+    };
+    use uuid::Uuid;
+
+    use super::{
+        new_app, new_inbox_app, InboxSink, ServerConfig, Taxii21Sink, STIX_JSON_21_BINDING,
+    };
+    use crate::{
+        taxii::{
+            archive::Archive,
+            client::AsyncClient,
+            collections::{Collection, CollectionType},
+            content_block::ContentBlock,
+            errors::TaxiiError,
+            inbox::{create_inbox_message_body, InboxMessage},
+            poll::{create_poll_request_body, parse_poll_response, PollParameters},
+            query::{
+                Criteria, Criterion, DefaultQuery, MatchType, Operator, Test,
+                DEFAULT_QUERY_FORMAT_ID,
+            },
+            services::ServiceType,
+            status_message::{parse_status_message, StatusType},
+            store::{MemoryStore, Store},
+            subscriptions::SubscriptionStatus,
+            types::{ContentBinding, ResponseType},
+            version::{create_discovery_request_body, Version},
+        },
+        taxii21::{
+            backend::{Backend, Filtering},
+            memory_backend::MemoryBackend,
+        },
+    };
+
+    struct MemorySink {
+        messages: Mutex<Vec<InboxMessage>>,
+    }
+
+    impl InboxSink for MemorySink {
+        fn collection_names(&self) -> Vec<String> {
+            vec![String::from("stix-data")]
+        }
+        fn receive(&self, inbox_message: &InboxMessage) -> Result<(), TaxiiError> {
+            self.messages.lock().unwrap().push(inbox_message.clone());
+            Ok(())
+        }
+    }
+
+    fn inbox_request(body: String) -> test::TestRequest {
+        taxii_request("/services/inbox", body)
+    }
+
+    fn taxii_request(uri: &str, body: String) -> test::TestRequest {
+        test::TestRequest::post()
+            .uri(uri)
+            .append_header(("Content-Type", "application/xml"))
+            .append_header((
+                "X-TAXII-Content-Type",
+                "urn:taxii.mitre.org:message:xml:1.1",
+            ))
+            .append_header(("X-TAXII-Services", "urn:taxii.mitre.org:services:1.1"))
+            .append_header(("X-TAXII-Protocol", "urn:taxii.mitre.org:protocol:https:1.0"))
+            .set_payload(body)
+    }
+
+    fn new_inbox_message(collection_name: &str, content_block: ContentBlock) -> String {
+        let mut inbox_message = InboxMessage::new_empty();
+        inbox_message.message_id = String::from("inbox-1");
+        if !collection_name.is_empty() {
+            inbox_message.destination_collection_names = vec![String::from(collection_name)];
+        }
+        inbox_message.content_blocks.push(content_block);
+        create_inbox_message_body(Version::V11, &inbox_message).unwrap()
+    }
+
+    async fn read_status(resp: ServiceResponse) -> StatusType {
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let body = to_bytes(resp.into_body()).await.unwrap();
+        parse_status_message(&body).unwrap().status_type
+    }
+
+    #[actix_web::test]
+    async fn test_load_server_config() {
+        let path =
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("test/sample-taxii11.toml");
+        let cfg = ServerConfig::load_toml(&path).unwrap();
+        assert_eq!(cfg.listen, "127.0.0.1:9000");
+        let store = cfg.new_store().unwrap();
+        let collections = store.collections();
+        assert_eq!(collections.len(), 2);
+        let collection = store.collection("stix-data").unwrap();
+        assert_eq!(collection.collection_type, CollectionType::DataFeed);
+        assert!(collection.available);
+        assert_eq!(
+            collection.content_bindings,
+            vec!["urn:stix.mitre.org:xml:1.1.1"]
+        );
+        let collection = store.collection("archive").unwrap();
+        assert_eq!(collection.collection_type, CollectionType::DataSet);
+        assert!(collection.content_bindings.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_inbox_service() -> Result<(), Error> {
+        let sink = Arc::new(MemorySink {
+            messages: Mutex::new(Vec::<InboxMessage>::new()),
+        });
+        let app = test::init_service(new_inbox_app(sink.clone())).await;
+
+        let content_block = ContentBlock::new(
+            "urn:stix.mitre.org:xml:1.1.1",
+            "<stix:STIX_Package xmlns:stix=\"http://stix.mitre.org/stix-1\"/>",
+        );
+        let body = new_inbox_message("stix-data", content_block.clone());
+        let status_type =
+            read_status(app.call(inbox_request(body.clone()).to_request()).await?).await;
+        assert_eq!(StatusType::Success, status_type);
+        {
+            let messages = sink.messages.lock().unwrap();
+            assert_eq!(1, messages.len());
+            assert_eq!("inbox-1", messages[0].message_id);
+            assert!(messages[0].content_blocks[0]
+                .content
+                .starts_with("<stix:STIX_Package"));
+        }
+
+        let req = inbox_request(body.clone()).insert_header((
+            "X-TAXII-Content-Type",
+            "urn:taxii.mitre.org:message:xml:2.0",
+        ));
+        let resp = app.call(req.to_request()).await?;
+        let status_type = read_status(resp).await;
+        assert_eq!(StatusType::UnsupportedMessage, status_type);
+
+        let req = inbox_request(body.clone())
+            .insert_header(("X-TAXII-Protocol", "urn:taxii.mitre.org:protocol:ftp:1.0"));
+        let resp = app.call(req.to_request()).await?;
+        let status_type = read_status(resp).await;
+        assert_eq!("UNSUPPORTED_PROTOCOL", status_type.to_str());
+
+        let req = test::TestRequest::post()
+            .uri("/services/inbox")
+            .set_payload(body.clone());
+        let resp = app.call(req.to_request()).await?;
+        let status_type = read_status(resp).await;
+        assert_eq!(StatusType::BadMessage, status_type);
+
+        let status_type = read_status(
+            app.call(inbox_request(String::from("<Inbox_Message")).to_request())
+                .await?,
+        )
+        .await;
+        assert_eq!(StatusType::BadMessage, status_type);
+
+        let body = new_inbox_message("other-data", content_block);
+        let resp = app.call(inbox_request(body).to_request()).await?;
+        let status_type = read_status(resp).await;
+        assert_eq!(
+            StatusType::DestinationCollectionError {
+                acceptable_destinations: vec![String::from("stix-data")]
+            },
+            status_type
+        );
+        assert_eq!(1, sink.messages.lock().unwrap().len());
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_inbox_service_taxii21_sink() -> Result<(), Error> {
+        let mut backend = MemoryBackend::new();
+        backend.add_collection("collection1");
+        let backend = Arc::new(Mutex::new(backend));
+        let sink = Arc::new(Taxii21Sink::new(backend.clone(), "collection1"));
+        let app = test::init_service(new_inbox_app(sink)).await;
+
+        let bundle = r#"{"type": "bundle", "id": "bundle--5d0092c5-5f74-4287-9642-33f4c354e56d",
+            "objects": [{"type": "indicator", "spec_version": "2.1",
+            "id": "indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f",
+            "created": "2016-04-06T20:03:48.000Z", "modified": "2016-04-06T20:03:48.000Z",
+            "pattern": "[file:hashes.'SHA-256' = 'aec070645fe53ee3b3763059376134f058cc337247c978add178b6ccdfb0019f']",
+            "pattern_type": "stix", "valid_from": "2016-01-01T00:00:00Z"}]}"#;
+        let body = new_inbox_message("", ContentBlock::new(STIX_JSON_21_BINDING, bundle));
+        let resp = app.call(inbox_request(body).to_request()).await?;
+        let status_type = read_status(resp).await;
+        assert_eq!(StatusType::Success, status_type);
+        let objects = backend
+            .lock()
+            .unwrap()
+            .get_objects("collection1", &Filtering::no_filter())
+            .unwrap();
+        assert_eq!(1, objects.len());
+        assert_eq!("indicator", objects[0].typ);
+
+        let body = new_inbox_message(
+            "",
+            ContentBlock::new("urn:stix.mitre.org:xml:1.1.1", "<STIX_Package/>"),
+        );
+        let resp = app.call(inbox_request(body).to_request()).await?;
+        let status_type = read_status(resp).await;
+        assert_eq!(
+            StatusType::UnsupportedContent {
+                supported_content: vec![String::from(STIX_JSON_21_BINDING)]
+            },
+            status_type
+        );
+        Ok(())
+    }
+
+    static STIX_11_BINDING: &str = "urn:stix.mitre.org:xml:1.1.1";
+
+    fn new_store() -> Arc<Mutex<MemoryStore>> {
+        let mut collection = Collection::new_empty();
+        collection.collection_name = String::from("stix-data");
+        collection.collection_type = CollectionType::DataFeed;
+        collection.available = true;
+        collection.description = String::from("STIX 1.1.1 test data");
+        collection.content_bindings = vec![String::from(STIX_11_BINDING)];
+        let mut store = MemoryStore::new();
+        store.add_collection(collection);
+        Arc::new(Mutex::new(store))
+    }
+
+    fn new_poll_request(collection_name: &str, response_type: ResponseType) -> String {
+        let poll_parameters = PollParameters {
+            allow_asynch: false,
+            response_type,
+            content_bindings: vec![ContentBinding {
+                binding_id: String::from(STIX_11_BINDING),
+                subtype_id: None,
+            }],
+            query: String::from(""),
+            query_format_id: String::from(""),
+        };
+        create_poll_request_body(
+            Version::V11,
+            collection_name,
+            None,
+            "",
+            Some(poll_parameters),
+        )
+        .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn test_poll_and_inbox_services() -> Result<(), Error> {
+        let store = new_store();
+        let app = test::init_service(new_app("https://taxii.example.com", store.clone())).await;
+
+        let content_block = ContentBlock::new(
+            STIX_11_BINDING,
+            "<stix:STIX_Package xmlns:stix=\"http://stix.mitre.org/stix-1\"/>",
+        );
+        let body = new_inbox_message("stix-data", content_block);
+        let resp = app.call(inbox_request(body).to_request()).await?;
+        assert_eq!(StatusType::Success, read_status(resp).await);
+        let body = new_inbox_message("stix-data", ContentBlock::new(STIX_JSON_21_BINDING, "{}"));
+        let resp = app.call(inbox_request(body).to_request()).await?;
+        assert_eq!(
+            StatusType::UnsupportedContent {
+                supported_content: vec![String::from(STIX_11_BINDING)]
+            },
+            read_status(resp).await
+        );
+
+        let body = new_poll_request("stix-data", ResponseType::Full);
+        let resp = app
+            .call(taxii_request("/services/poll", body).to_request())
+            .await?;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let body = to_bytes(resp.into_body()).await.unwrap();
+        let poll_response = parse_poll_response(&body).unwrap();
+        assert_eq!("stix-data", poll_response.collection_name);
+        assert_eq!(1, poll_response.record_count.unwrap().count);
+        assert_eq!(1, poll_response.content_blocks.len());
+        assert!(poll_response.content_blocks[0]
+            .content
+            .starts_with("<stix:STIX_Package"));
+        assert!(poll_response.content_blocks[0].timestamp_label.is_some());
+
+        let body = new_poll_request("stix-data", ResponseType::CountOnly);
+        let resp = app
+            .call(taxii_request("/services/poll", body).to_request())
+            .await?;
+        let body = to_bytes(resp.into_body()).await.unwrap();
+        let poll_response = parse_poll_response(&body).unwrap();
+        assert_eq!(1, poll_response.record_count.unwrap().count);
+        assert!(poll_response.content_blocks.is_empty());
+
+        let body = new_poll_request("other-data", ResponseType::Full);
+        let resp = app
+            .call(taxii_request("/services/poll", body).to_request())
+            .await?;
+        assert_eq!(
+            StatusType::NotFound {
+                item: Some(String::from("other-data"))
+            },
+            read_status(resp).await
+        );
+
+        let body = create_discovery_request_body(Version::V11).unwrap();
+        let resp = app
+            .call(taxii_request("/services/poll", body).to_request())
+            .await?;
+        assert_eq!(StatusType::UnsupportedMessage, read_status(resp).await);
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_poll_with_default_query() -> Result<(), Error> {
+        let store = new_store();
+        for id in ["example:Package-1", "example:Package-2"] {
+            let package = format!(
+                "<stix:STIX_Package xmlns:stix=\"http://stix.mitre.org/stix-1\" id=\"{}\"/>",
+                id
+            );
+            store
+                .lock()
+                .unwrap()
+                .add_content(
+                    "stix-data",
+                    &ContentBlock::new(STIX_11_BINDING, package.as_str()),
+                )
+                .unwrap();
+        }
+        let app = test::init_service(new_app("https://taxii.example.com", store)).await;
+        let poll = |targeting_expression_id: &str| {
+            let query = DefaultQuery::new(
+                targeting_expression_id,
+                Criteria::new(Operator::And).with_criterion(Criterion::new(
+                    "STIX_Package/@id",
+                    Test::equals("example:package-2", MatchType::CaseInsensitiveString),
+                )),
+            );
+            let mut poll_parameters = PollParameters {
+                allow_asynch: false,
+                response_type: ResponseType::Full,
+                content_bindings: Vec::<ContentBinding>::new(),
+                query: String::from(""),
+                query_format_id: String::from(""),
+            };
+            poll_parameters.set_default_query(&query).unwrap();
+            let body = create_poll_request_body(
+                Version::V11,
+                "stix-data",
+                None,
+                "",
+                Some(poll_parameters),
+            )
+            .unwrap();
+            taxii_request("/services/poll", body).to_request()
+        };
+
+        let resp = app.call(poll(STIX_11_BINDING)).await?;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let body = to_bytes(resp.into_body()).await.unwrap();
+        let poll_response = parse_poll_response(&body).unwrap();
+        assert_eq!(1, poll_response.content_blocks.len());
+        assert!(poll_response.content_blocks[0]
+            .content
+            .contains("example:Package-2"));
+
+        let resp = app.call(poll("urn:example:cybox")).await?;
+        let status_type = read_status(resp).await;
+        assert_eq!("UNSUPPORTED_TARGETING_EXPRESSION_ID", status_type.to_str());
+
+        let mut poll_parameters = PollParameters {
+            allow_asynch: false,
+            response_type: ResponseType::Full,
+            content_bindings: Vec::<ContentBinding>::new(),
+            query: String::from("<Query/>"),
+            query_format_id: String::from("urn:example:query"),
+        };
+        let body = create_poll_request_body(
+            Version::V11,
+            "stix-data",
+            None,
+            "",
+            Some(poll_parameters.clone()),
+        )
+        .unwrap();
+        let resp = app
+            .call(taxii_request("/services/poll", body).to_request())
+            .await?;
+        assert_eq!(
+            StatusType::UnsupportedQuery {
+                supported_queries: vec![String::from(DEFAULT_QUERY_FORMAT_ID)]
+            },
+            read_status(resp).await
+        );
+
+        poll_parameters.query_format_id = String::from(DEFAULT_QUERY_FORMAT_ID);
+        let body =
+            create_poll_request_body(Version::V11, "stix-data", None, "", Some(poll_parameters))
+                .unwrap();
+        let resp = app
+            .call(taxii_request("/services/poll", body).to_request())
+            .await?;
+        assert_eq!(StatusType::BadMessage, read_status(resp).await);
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_server_with_async_client() {
+        let store = new_store();
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let base_url = format!("http://{}", addr);
+        let app_store: Arc<Mutex<dyn Store + Send>> = store.clone();
+        let app_base_url = base_url.clone();
+        let server = HttpServer::new(move || new_app(app_base_url.as_str(), app_store.clone()))
+            .workers(1)
+            .listen(listener)
+            .unwrap();
+        actix_web::rt::spawn(server.run());
+
+        let archive =
+            Archive::new(&std::env::temp_dir().join(format!("archive-{}", Uuid::new_v4())));
+        let client =
+            AsyncClient::new("guest", "guest", Version::V11).with_archive(Some(archive.clone()));
+        let discovery_url = format!("{}/services/discovery", base_url);
+        let collection_url = format!("{}/services/collection-management", base_url);
+        let poll_url = format!("{}/services/poll", base_url);
+
+        let service_set = client.discovery(discovery_url.as_str()).await.unwrap();
+        assert_eq!(4, service_set.services.len());
+        let poll_service = service_set
+            .services
+            .iter()
+            .find(|v| v.service_type == ServiceType::Poll)
+            .unwrap();
+        assert_eq!(poll_url, poll_service.address);
+        let collection_set = client
+            .collection_information(collection_url.as_str())
+            .await
+            .unwrap();
+        assert_eq!(1, collection_set.collections.len());
+        assert_eq!("stix-data", collection_set.collections[0].collection_name);
+        assert_eq!(3, collection_set.collections[0].collection_services.len());
+
+        store
+            .lock()
+            .unwrap()
+            .add_content(
+                "stix-data",
+                &ContentBlock::new(STIX_11_BINDING, "<STIX_Package/>"),
+            )
+            .unwrap();
+        let subscription_response = client
+            .subscribe(collection_url.as_str(), "stix-data")
+            .await
+            .unwrap();
+        let subscription = &subscription_response.subscriptions[0];
+        assert_eq!(SubscriptionStatus::Active, subscription.status);
+        assert_eq!(poll_url, subscription.poll_instances[0].address);
+        let subscription_id = subscription.id.clone();
+
+        let poll_response = client
+            .poll(
+                poll_url.as_str(),
+                "stix-data",
+                None,
+                subscription_id.as_str(),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(Some(subscription_id.clone()), poll_response.subscription_id);
+        assert_eq!(1, poll_response.content_blocks.len());
+
+        let subscription_response = client
+            .pause(
+                collection_url.as_str(),
+                "stix-data",
+                subscription_id.as_str(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            SubscriptionStatus::Paused,
+            subscription_response.subscriptions[0].status
+        );
+        let err = client
+            .poll(
+                poll_url.as_str(),
+                "stix-data",
+                None,
+                subscription_id.as_str(),
+                None,
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("DENIED"));
+        let err = client
+            .status(collection_url.as_str(), "stix-data", Some("unknown"))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("NOT_FOUND ITEM=unknown"));
+
+        // every exchange was archived and the poll responses can be read back
+        let entries = archive.entries().unwrap();
+        assert_eq!(7, entries.len());
+        assert!(entries.iter().all(|v| v.url.starts_with(base_url.as_str())));
+        let poll_responses = archive
+            .replay("Poll_Response", parse_poll_response)
+            .unwrap();
+        assert_eq!(1, poll_responses.len());
+        assert_eq!(
+            1,
+            poll_responses[0].1.as_ref().unwrap().content_blocks.len()
+        );
+        std::fs::remove_dir_all(archive.dir()).unwrap();
+    }
+}
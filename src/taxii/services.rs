@@ -1,6 +1,9 @@
-use xml::reader::{EventReader, XmlEvent};
-
-use super::errors::MyError;
+use super::{
+    collections::{content_binding_element, content_bindings},
+    errors::TaxiiError,
+    message::{from_xml, message_id, parse_bool, to_xml, Element, FromElement, ToElement},
+    version::Version,
+};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ServiceType {
@@ -12,18 +15,29 @@ pub enum ServiceType {
 }
 
 impl ServiceType {
-    pub fn parse(v: &str) -> Result<ServiceType, MyError> {
+    pub fn parse(v: &str) -> Result<ServiceType, TaxiiError> {
         match v {
             "COLLECTION_MANAGEMENT" => Ok(ServiceType::CollectionManagement),
+            // the TAXII 1.0 name of the collection management service
+            "FEED_MANAGEMENT" => Ok(ServiceType::CollectionManagement),
             "DISCOVERY" => Ok(ServiceType::Discovery),
             "INBOX" => Ok(ServiceType::Inbox),
             "POLL" => Ok(ServiceType::Poll),
-            _ => Err(MyError(format!("could not parse: {}", v))),
+            _ => Err(TaxiiError::Protocol(format!("could not parse: {}", v))),
+        }
+    }
+    pub fn to_str(&self) -> &str {
+        match self {
+            ServiceType::Undefined => "UNDEFINED",
+            ServiceType::CollectionManagement => "COLLECTION_MANAGEMENT",
+            ServiceType::Discovery => "DISCOVERY",
+            ServiceType::Inbox => "INBOX",
+            ServiceType::Poll => "POLL",
         }
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct ServiceInstance {
     pub service_type: ServiceType,
     pub service_version: String,
@@ -50,194 +64,189 @@ impl ServiceInstance {
     }
 }
 
+#[derive(Clone, Debug, PartialEq)]
 pub struct ServiceSet {
-    services: Vec<ServiceInstance>,
+    pub message_id: String,
+    pub in_response_to: String,
+    pub services: Vec<ServiceInstance>,
+}
+
+impl Default for ServiceSet {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ServiceSet {
     pub fn new() -> ServiceSet {
-        return ServiceSet {
+        ServiceSet {
+            message_id: String::from(""),
+            in_response_to: String::from(""),
             services: Vec::<ServiceInstance>::new(),
-        };
+        }
+    }
+}
+
+impl FromElement for ServiceInstance {
+    fn from_element(element: &Element) -> Result<ServiceInstance, TaxiiError> {
+        element.expect("Service_Instance")?;
+        element.check_elements(&[
+            "Protocol_Binding",
+            "Address",
+            "Message_Binding",
+            "Supported_Query",
+            "Content_Binding",
+            "Message",
+        ])?;
+        Ok(ServiceInstance {
+            service_type: ServiceType::parse(element.required_attr("service_type")?)?,
+            service_version: String::from(element.attr("service_version").unwrap_or_default()),
+            available: element.attr("available").is_some_and(parse_bool),
+            protocol_binding: element.element_text("Protocol_Binding").unwrap_or_default(),
+            address: element.element_text("Address").unwrap_or_default(),
+            message_bindings: element
+                .elements_named("Message_Binding")
+                .map(|e| e.text())
+                .collect(),
+            content_bindings: content_bindings(element)?,
+            message: element.element_text("Message"),
+        })
     }
 }
 
-fn indent(size: usize) -> String {
-    const INDENT: &'static str = "    ";
-    (0..size)
-        .map(|_| INDENT)
-        .fold(String::with_capacity(size * INDENT.len()), |r, s| r + s)
+impl ToElement for ServiceInstance {
+    fn to_element(&self, ver: Version) -> Result<Element, TaxiiError> {
+        let service_type = match (ver, &self.service_type) {
+            (Version::V10, ServiceType::CollectionManagement) => "FEED_MANAGEMENT",
+            (_, v) => v.to_str(),
+        };
+        let mut element = Element::new(ver, "Service_Instance")
+            .with_attr("service_type", service_type)
+            .with_attr("service_version", self.service_version.as_str())
+            .with_attr("available", self.available.to_string().as_str())
+            .with_element(Element::text_element(
+                ver,
+                "Protocol_Binding",
+                self.protocol_binding.as_str(),
+            ))
+            .with_element(Element::text_element(ver, "Address", self.address.as_str()));
+        for message_binding in self.message_bindings.iter() {
+            element.push(Element::text_element(
+                ver,
+                "Message_Binding",
+                message_binding.as_str(),
+            ));
+        }
+        for content_binding in self.content_bindings.iter() {
+            element.push(content_binding_element(ver, content_binding));
+        }
+        if let Some(message) = &self.message {
+            element.push(Element::text_element(ver, "Message", message.as_str()));
+        }
+        Ok(element)
+    }
 }
 
-enum InTag {
-    DiscoveryResponse,
-    ServiceInstance,
-    ProtocolBinding,
-    Address,
-    MessageBinding,
-    ContentBinding,
-    Message,
+impl FromElement for ServiceSet {
+    fn from_element(element: &Element) -> Result<ServiceSet, TaxiiError> {
+        element.expect("Discovery_Response")?;
+        element.check_elements(&["Service_Instance"])?;
+        Ok(ServiceSet {
+            message_id: String::from(element.attr("message_id").unwrap_or_default()),
+            in_response_to: String::from(element.attr("in_response_to").unwrap_or_default()),
+            services: element
+                .elements()
+                .map(ServiceInstance::from_element)
+                .collect::<Result<Vec<_>, _>>()?,
+        })
+    }
 }
 
-pub fn parse_discovery_response(doc: &[u8]) -> Result<ServiceSet, MyError> {
-    let mut tag_stack = Vec::<InTag>::new();
-    let mut service_set = ServiceSet::new();
-    let mut cur_service = ServiceInstance::new_empty();
-    let mut last_value: String = String::new();
-    let xml_parser = EventReader::new(doc);
-    for e in xml_parser {
-        match e {
-            Ok(XmlEvent::StartElement {
-                name, attributes, ..
-            }) => {
-                match name.local_name.as_str() {
-                    "Discovery_Response" => {
-                        if tag_stack.len() != 0 {
-                            return Err(MyError(format!(
-                                "unexpected tag preceeding Discovery_Response"
-                            )));
-                        }
-                        tag_stack.push(InTag::DiscoveryResponse)
-                    }
-                    "Service_Instance" => {
-                        if tag_stack.len() != 1 {
-                            return Err(MyError(format!(
-                                "unexpected tag depth for Service_Instance"
-                            )));
-                        }
-                        tag_stack.push(InTag::ServiceInstance);
-                        for attr in attributes {
-                            match attr.name.local_name.as_str() {
-                                "service_type" => {
-                                    cur_service.service_type =
-                                        match ServiceType::parse(attr.value.as_str()) {
-                                            Ok(v) => v,
-                                            Err(err) => panic!("{}", err), // TODO: return
-                                        }
-                                }
-                                "service_version" => {
-                                    cur_service.service_version = attr.value.clone()
-                                }
-                                "available" => {
-                                    cur_service.available = attr.value.to_lowercase().eq("true")
-                                }
-                                _ => {
-                                    return Err(MyError(format!(
-                                        "unrecogized attribute: {}",
-                                        attr.name.local_name
-                                    )))
-                                }
-                            }
-                        }
-                    }
-                    "Protocol_Binding" => {
-                        if tag_stack.len() != 2 {
-                            return Err(MyError(format!(
-                                "unexpected tag depth for Protocol_Binding"
-                            )));
-                        }
-                        tag_stack.push(InTag::ProtocolBinding);
-                    }
-                    "Address" => {
-                        if tag_stack.len() != 2 {
-                            return Err(MyError(format!("unexpected tag depth for Address")));
-                        }
-                        tag_stack.push(InTag::Address);
-                    }
-                    "Message_Binding" => {
-                        if tag_stack.len() != 2 {
-                            return Err(MyError(format!(
-                                "unexpected tag depth for Message_Binding"
-                            )));
-                        }
-                        tag_stack.push(InTag::MessageBinding);
-                    }
-                    "Content_Binding" => {
-                        if tag_stack.len() != 2 {
-                            return Err(MyError(format!(
-                                "unexpected tag depth for Content_Binding"
-                            )));
-                        }
-                        tag_stack.push(InTag::ContentBinding);
-                    }
-                    "Message" => {
-                        if tag_stack.len() != 2 {
-                            return Err(MyError(format!("unexpected tag depth for Message")));
-                        }
-                        tag_stack.push(InTag::Message);
-                    }
-                    tag => {
-                        return Err(MyError(format!("unexpected XML tag: {}", tag)));
-                    }
-                }
-            }
-            // TODO: seems like excessive cloning
-            Ok(XmlEvent::EndElement { name }) => match tag_stack.pop() {
-                // TODO: should we verify 'name' versus our tag stack?
-                Some(InTag::DiscoveryResponse) => {
-                    if name.local_name != "Discovery_Response" {
-                        return Err(MyError(format!("malformed XML response")));
-                    }
-                }
-                Some(InTag::ServiceInstance) => {
-                    if name.local_name != "Service_Instance" {
-                        return Err(MyError(format!("malformed XML response")));
-                    }
-                    service_set.services.push(cur_service.clone());
-                    cur_service = ServiceInstance::new_empty();
-                }
-                Some(InTag::ProtocolBinding) => {
-                    if name.local_name != "Protocol_Binding" {
-                        return Err(MyError(format!("malformed XML response")));
-                    }
-                    cur_service.protocol_binding = last_value.clone()
-                }
-                Some(InTag::Address) => {
-                    if name.local_name != "Address" {
-                        return Err(MyError(format!("malformed XML response")));
-                    }
-                    cur_service.address = last_value.clone()
-                }
-                Some(InTag::MessageBinding) => {
-                    if name.local_name != "Message_Binding" {
-                        return Err(MyError(format!("malformed XML response")));
-                    }
-                    cur_service.message_bindings.push(last_value.clone())
-                }
-                Some(InTag::ContentBinding) => {
-                    if name.local_name != "Content_Binding" {
-                        return Err(MyError(format!("malformed XML response")));
-                    }
-                    cur_service.content_bindings.push(last_value.clone())
-                }
-                Some(InTag::Message) => {
-                    if name.local_name != "Message" {
-                        return Err(MyError(format!("malformed XML response")));
-                    }
-                    cur_service.message = Some(last_value.clone())
-                }
-                None => return Err(MyError(format!("unexpected end tag: {}", name.local_name))),
-            },
-            Ok(XmlEvent::CData(ref data)) => {
-                last_value = data.clone();
-            }
-            Ok(XmlEvent::Characters(ref data)) => {
-                last_value = data.clone();
-            }
-            Err(e) => {
-                return Err(MyError(e.to_string()));
-            }
-            _ => {}
+impl ToElement for ServiceSet {
+    fn to_element(&self, ver: Version) -> Result<Element, TaxiiError> {
+        let mut element = Element::new(ver, "Discovery_Response")
+            .with_attr(
+                "message_id",
+                message_id(ver, self.message_id.as_str())?.as_str(),
+            )
+            .with_attr("in_response_to", self.in_response_to.as_str());
+        for service in self.services.iter() {
+            element.push(service.to_element(ver)?);
         }
+        Ok(element)
+    }
+}
+
+pub fn parse_discovery_response(doc: &[u8]) -> Result<ServiceSet, TaxiiError> {
+    from_xml(doc)
+}
+
+pub fn create_discovery_response_body(
+    ver: Version,
+    in_response_to: &str,
+    service_set: &ServiceSet,
+) -> Result<String, TaxiiError> {
+    let mut service_set = service_set.clone();
+    service_set.in_response_to = String::from(in_response_to);
+    to_xml(ver, &service_set)
+}
+
+/// A Discovery_Request, asking a discovery service for the services it knows of.
+#[derive(Clone, Debug)]
+pub struct DiscoveryRequest {
+    pub message_id: String,
+}
+
+impl FromElement for DiscoveryRequest {
+    fn from_element(element: &Element) -> Result<DiscoveryRequest, TaxiiError> {
+        element.expect("Discovery_Request")?;
+        Ok(DiscoveryRequest {
+            message_id: String::from(element.attr("message_id").unwrap_or_default()),
+        })
+    }
+}
+
+impl ToElement for DiscoveryRequest {
+    fn to_element(&self, ver: Version) -> Result<Element, TaxiiError> {
+        Ok(Element::new(ver, "Discovery_Request").with_attr(
+            "message_id",
+            message_id(ver, self.message_id.as_str())?.as_str(),
+        ))
     }
-    Ok(service_set)
 }
 
 #[cfg(test)]
 mod tests {
     use std::{env, fs::read_to_string, path::Path};
 
-    use crate::taxii::services::{parse_discovery_response, ServiceType};
+    use crate::taxii::{
+        errors::TaxiiError,
+        services::{create_discovery_response_body, parse_discovery_response, ServiceType},
+        version::Version,
+    };
+
+    #[test]
+    fn test_parse_discovery_response_errors() {
+        assert!(matches!(
+            parse_discovery_response(b"<taxii_11:Discovery_Response"),
+            Err(TaxiiError::Xml(_))
+        ));
+        assert!(matches!(
+            parse_discovery_response(b"<Poll_Response message_id=\"1\"/>"),
+            Err(TaxiiError::Protocol(_))
+        ));
+        let doc = "<Discovery_Response message_id=\"1\" in_response_to=\"2\">\
+            <Service_Instance service_type=\"NO_SUCH_SERVICE\" \
+            service_version=\"urn:taxii.mitre.org:services:1.1\">\
+            <Protocol_Binding>urn:taxii.mitre.org:protocol:https:1.0</Protocol_Binding>\
+            <Address>https://example.com/</Address>\
+            <Message_Binding>urn:taxii.mitre.org:message:xml:1.1</Message_Binding>\
+            </Service_Instance></Discovery_Response>";
+        match parse_discovery_response(doc.as_bytes()) {
+            Err(TaxiiError::Protocol(v)) => assert!(v.contains("NO_SUCH_SERVICE")),
+            result => panic!("expected a protocol error: {:?}", result.err()),
+        }
+    }
 
     #[test]
     fn test_parse_discovery_response() {
@@ -339,4 +348,33 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_create_discovery_response_body() {
+        let path = env::var("CARGO_MANIFEST_DIR").unwrap();
+        let path = Path::new(path.as_str()).join("test/sample-discovery-response.xml");
+        let doc = read_to_string(path).unwrap();
+        let service_set = parse_discovery_response(doc.as_bytes()).unwrap();
+        assert_eq!(
+            vec![
+                "urn:stix.mitre.org:xml:1.0",
+                "urn:stix.mitre.org:xml:1.0.1",
+                "urn:stix.mitre.org:xml:1.1",
+                "urn:stix.mitre.org:xml:1.1.1",
+                "urn:stix.mitre.org:xml:1.2",
+            ],
+            service_set.services[1].content_bindings
+        );
+
+        let doc = create_discovery_response_body(Version::V11, "1234", &service_set).unwrap();
+        let round_trip = parse_discovery_response(doc.as_bytes()).unwrap();
+        assert_eq!(service_set.services.len(), round_trip.services.len());
+        for (service, expected) in round_trip.services.iter().zip(service_set.services.iter()) {
+            assert_eq!(expected.service_type, service.service_type);
+            assert_eq!(expected.address, service.address);
+            assert_eq!(expected.message_bindings, service.message_bindings);
+            assert_eq!(expected.content_bindings, service.content_bindings);
+            assert_eq!(expected.message, service.message);
+        }
+    }
 }
@@ -1,12 +1,215 @@
-use xml::reader::{EventReader, XmlEvent};
+use std::fmt;
 
-use super::errors::MyError;
+use super::{
+    errors::TaxiiError,
+    message::{from_xml, message_id, to_xml, Element, FromElement, ToElement},
+    version::Version,
+};
 
-#[derive(Clone)]
+/// The status types of a TAXII 1.1 Status_Message. Status types that carry Status_Detail
+/// entries hold them as typed fields; all detail fields are optional on the wire, so missing
+/// ones are represented by `None` or an empty list rather than a parse error.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StatusType {
+    AsynchronousPollError,
+    BadMessage,
+    Denied,
+    DestinationCollectionError {
+        acceptable_destinations: Vec<String>,
+    },
+    Failure,
+    InvalidResponsePart {
+        max_part_number: Option<u32>,
+    },
+    NetworkError,
+    NotFound {
+        item: Option<String>,
+    },
+    Pending {
+        estimated_wait: Option<u32>,
+        result_id: Option<String>,
+        will_push: Option<bool>,
+    },
+    PollingUnsupported,
+    Retry {
+        estimated_wait: Option<u32>,
+    },
+    Success,
+    Unauthorized,
+    UnsupportedMessage,
+    UnsupportedContent {
+        supported_content: Vec<String>,
+    },
+    UnsupportedProtocol {
+        supported_protocols: Vec<String>,
+    },
+    UnsupportedQuery {
+        supported_queries: Vec<String>,
+    },
+    /// An extended status type, with its details as sent.
+    Other {
+        name: String,
+        details: Vec<(String, String)>,
+    },
+}
+
+impl StatusType {
+    /// Build a status type from its `status_type` attribute and the name/value pairs of its
+    /// Status_Detail. A detail may appear several times, e.g. SUPPORTED_CONTENT.
+    pub fn parse(v: &str, details: &[(String, String)]) -> Result<StatusType, TaxiiError> {
+        let values = |name: &str| -> Vec<String> {
+            details
+                .iter()
+                .filter(|(k, _)| k == name)
+                .map(|(_, v)| v.clone())
+                .collect()
+        };
+        let first = |name: &str| -> Option<String> { values(name).into_iter().next() };
+        let number = |name: &str| -> Result<Option<u32>, TaxiiError> {
+            match first(name) {
+                Some(v) => match v.trim().parse::<u32>() {
+                    Ok(v) => Ok(Some(v)),
+                    Err(err) => Err(TaxiiError::Protocol(format!("bad {}: {}", name, err))),
+                },
+                None => Ok(None),
+            }
+        };
+        let status_type = match v {
+            "ASYNCHRONOUS_POLL_ERROR" => StatusType::AsynchronousPollError,
+            "BAD_MESSAGE" => StatusType::BadMessage,
+            "DENIED" => StatusType::Denied,
+            "DESTINATION_COLLECTION_ERROR" => StatusType::DestinationCollectionError {
+                acceptable_destinations: values("ACCEPTABLE_DESTINATION"),
+            },
+            "FAILURE" => StatusType::Failure,
+            "INVALID_RESPONSE_PART" => StatusType::InvalidResponsePart {
+                max_part_number: number("MAX_PART_NUMBER")?,
+            },
+            "NETWORK_ERROR" => StatusType::NetworkError,
+            "NOT_FOUND" => StatusType::NotFound {
+                item: first("ITEM"),
+            },
+            "PENDING" => StatusType::Pending {
+                estimated_wait: number("ESTIMATED_WAIT")?,
+                result_id: first("RESULT_ID"),
+                will_push: match first("WILL_PUSH") {
+                    Some(v) => match v.trim() {
+                        "true" | "1" => Some(true),
+                        "false" | "0" => Some(false),
+                        _ => return Err(TaxiiError::Protocol(format!("bad WILL_PUSH: {}", v))),
+                    },
+                    None => None,
+                },
+            },
+            "POLLING_UNSUPPORTED" => StatusType::PollingUnsupported,
+            "RETRY" => StatusType::Retry {
+                estimated_wait: number("ESTIMATED_WAIT")?,
+            },
+            "SUCCESS" => StatusType::Success,
+            "UNAUTHORIZED" => StatusType::Unauthorized,
+            "UNSUPPORTED_MESSAGE" => StatusType::UnsupportedMessage,
+            "UNSUPPORTED_CONTENT" => StatusType::UnsupportedContent {
+                supported_content: values("SUPPORTED_CONTENT"),
+            },
+            "UNSUPPORTED_PROTOCOL" => StatusType::UnsupportedProtocol {
+                supported_protocols: values("SUPPORTED_PROTOCOL"),
+            },
+            "UNSUPPORTED_QUERY" => StatusType::UnsupportedQuery {
+                supported_queries: values("SUPPORTED_QUERY"),
+            },
+            "" => return Err(TaxiiError::Protocol("missing status_type".to_string())),
+            v => StatusType::Other {
+                name: String::from(v),
+                details: details.to_vec(),
+            },
+        };
+        Ok(status_type)
+    }
+    pub fn to_str(&self) -> &str {
+        match self {
+            StatusType::AsynchronousPollError => "ASYNCHRONOUS_POLL_ERROR",
+            StatusType::BadMessage => "BAD_MESSAGE",
+            StatusType::Denied => "DENIED",
+            StatusType::DestinationCollectionError { .. } => "DESTINATION_COLLECTION_ERROR",
+            StatusType::Failure => "FAILURE",
+            StatusType::InvalidResponsePart { .. } => "INVALID_RESPONSE_PART",
+            StatusType::NetworkError => "NETWORK_ERROR",
+            StatusType::NotFound { .. } => "NOT_FOUND",
+            StatusType::Pending { .. } => "PENDING",
+            StatusType::PollingUnsupported => "POLLING_UNSUPPORTED",
+            StatusType::Retry { .. } => "RETRY",
+            StatusType::Success => "SUCCESS",
+            StatusType::Unauthorized => "UNAUTHORIZED",
+            StatusType::UnsupportedMessage => "UNSUPPORTED_MESSAGE",
+            StatusType::UnsupportedContent { .. } => "UNSUPPORTED_CONTENT",
+            StatusType::UnsupportedProtocol { .. } => "UNSUPPORTED_PROTOCOL",
+            StatusType::UnsupportedQuery { .. } => "UNSUPPORTED_QUERY",
+            StatusType::Other { name, .. } => name.as_str(),
+        }
+    }
+    /// The Status_Detail entries of the status type as name/value pairs, in the order they are
+    /// written to XML.
+    pub fn details(&self) -> Vec<(String, String)> {
+        let pairs = |name: &str, values: &[String]| -> Vec<(String, String)> {
+            values
+                .iter()
+                .map(|v| (String::from(name), v.clone()))
+                .collect()
+        };
+        let mut details = Vec::<(String, String)>::new();
+        match self {
+            StatusType::DestinationCollectionError {
+                acceptable_destinations,
+            } => details = pairs("ACCEPTABLE_DESTINATION", acceptable_destinations),
+            StatusType::InvalidResponsePart {
+                max_part_number: Some(v),
+            } => details.push((String::from("MAX_PART_NUMBER"), v.to_string())),
+            StatusType::NotFound { item: Some(v) } => {
+                details.push((String::from("ITEM"), v.clone()))
+            }
+            StatusType::Pending {
+                estimated_wait,
+                result_id,
+                will_push,
+            } => {
+                if let Some(v) = estimated_wait {
+                    details.push((String::from("ESTIMATED_WAIT"), v.to_string()));
+                }
+                if let Some(v) = result_id {
+                    details.push((String::from("RESULT_ID"), v.clone()));
+                }
+                if let Some(v) = will_push {
+                    details.push((String::from("WILL_PUSH"), v.to_string()));
+                }
+            }
+            StatusType::Retry {
+                estimated_wait: Some(v),
+            } => details.push((String::from("ESTIMATED_WAIT"), v.to_string())),
+            StatusType::UnsupportedContent { supported_content } => {
+                details = pairs("SUPPORTED_CONTENT", supported_content)
+            }
+            StatusType::UnsupportedProtocol {
+                supported_protocols,
+            } => details = pairs("SUPPORTED_PROTOCOL", supported_protocols),
+            StatusType::UnsupportedQuery { supported_queries } => {
+                details = pairs("SUPPORTED_QUERY", supported_queries)
+            }
+            StatusType::Other { details: v, .. } => details = v.clone(),
+            _ => (),
+        }
+        details
+    }
+}
+
+/// The name the text of a TAXII 1.0 Status_Detail is kept under among the details of the status
+/// type. 1.0 details have no names, so only extended status types keep it.
+pub const DETAIL_10: &str = "DETAIL";
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct StatusMessage {
     pub message_id: String,
     pub in_response_to: String,
-    pub status_type: String, // TODO: this is probably an enum
+    pub status_type: StatusType,
     pub message: Option<String>,
 }
 
@@ -15,101 +218,209 @@ impl StatusMessage {
         StatusMessage {
             message_id: String::from(""),
             in_response_to: String::from(""),
-            status_type: String::from(""),
+            status_type: StatusType::Failure,
             message: None,
         }
     }
 }
 
-enum InTag {
-    StatusMessage,
-    Message,
+impl fmt::Display for StatusMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "status message: {}", self.status_type.to_str())?;
+        for (name, value) in self.status_type.details() {
+            write!(f, " {}={}", name, value)?;
+        }
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        Ok(())
+    }
 }
 
-pub fn parse_status_message(doc: &[u8]) -> Result<StatusMessage, MyError> {
-    let mut tag_stack = Vec::<InTag>::new();
-    let mut status_message = StatusMessage::new_empty();
-    let mut last_value: String = String::new();
-    let xml_parser = EventReader::new(doc);
-    for e in xml_parser {
-        match e {
-            Ok(XmlEvent::StartElement {
-                name, attributes, ..
-            }) => match name.local_name.as_str() {
-                "Status_Message" => {
-                    if tag_stack.len() != 0 {
-                        return Err(MyError(format!("unexpected tag preceeding Status_Message")));
-                    }
-                    tag_stack.push(InTag::StatusMessage);
-                    for attr in attributes {
-                        match attr.name.local_name.as_str() {
-                            "message_id" => status_message.message_id = attr.value.clone(),
-                            "in_response_to" => status_message.in_response_to = attr.value.clone(),
-                            "status_type" => status_message.status_type = attr.value.clone(),
-                            _ => {
-                                return Err(MyError(format!(
-                                    "unrecogized attribute: {}",
-                                    attr.name.local_name
-                                )))
-                            }
+impl FromElement for StatusMessage {
+    fn from_element(element: &Element) -> Result<StatusMessage, TaxiiError> {
+        element.expect("Status_Message")?;
+        element.check_elements(&["Status_Detail", "Message"])?;
+        let mut details = Vec::<(String, String)>::new();
+        if element.version() == Version::V10 {
+            // the Status_Detail of TAXII 1.0 is plain text
+            if let Some(v) = element.element_text("Status_Detail") {
+                details.push((String::from(DETAIL_10), v));
+            }
+        } else {
+            for status_detail in element.elements_named("Status_Detail") {
+                status_detail.check_elements(&["Detail"])?;
+                for detail in status_detail.elements() {
+                    let name = match detail.attr("name") {
+                        Some(v) => String::from(v),
+                        None => {
+                            return Err(TaxiiError::Protocol(
+                                "Detail is missing a name".to_string(),
+                            ))
                         }
-                    }
-                }
-                "Message" => {
-                    if tag_stack.len() != 1 {
-                        return Err(MyError(format!("unexpected tag depth for Message")));
-                    }
-                    tag_stack.push(InTag::Message);
-                }
-                tag => {
-                    return Err(MyError(format!("unexpected XML tag: {}", tag)));
-                }
-            },
-            // TODO: seems like excessive cloning
-            Ok(XmlEvent::EndElement { name }) => match tag_stack.pop() {
-                Some(InTag::StatusMessage) => {
-                    if name.local_name != "Status_Message" {
-                        return Err(MyError(format!("malformed XML response")));
-                    }
+                    };
+                    details.push((name, detail.text()));
                 }
-                Some(InTag::Message) => {
-                    if name.local_name != "Message" {
-                        return Err(MyError(format!("malformed XML response")));
-                    }
-                    status_message.message = Some(last_value.clone());
-                }
-                None => return Err(MyError(format!("unexpected end tag: {}", name.local_name))),
-            },
-            Ok(XmlEvent::CData(ref data)) => {
-                last_value = data.clone();
-            }
-            Ok(XmlEvent::Characters(ref data)) => {
-                last_value = data.clone();
             }
-            Err(e) => {
-                return Err(MyError(e.to_string()));
+        }
+        Ok(StatusMessage {
+            message_id: String::from(element.attr("message_id").unwrap_or_default()),
+            in_response_to: String::from(element.attr("in_response_to").unwrap_or_default()),
+            status_type: StatusType::parse(
+                element.attr("status_type").unwrap_or_default(),
+                &details,
+            )?,
+            message: element.element_text("Message"),
+        })
+    }
+}
+
+impl ToElement for StatusMessage {
+    fn to_element(&self, ver: Version) -> Result<Element, TaxiiError> {
+        let mut element = Element::new(ver, "Status_Message")
+            .with_attr(
+                "message_id",
+                message_id(ver, self.message_id.as_str())?.as_str(),
+            )
+            .with_attr("in_response_to", self.in_response_to.as_str())
+            .with_attr("status_type", self.status_type.to_str());
+        let details = self.status_type.details();
+        if ver == Version::V10 && !details.is_empty() {
+            let values: Vec<&str> = details.iter().map(|(_, v)| v.as_str()).collect();
+            element.push(Element::text_element(
+                ver,
+                "Status_Detail",
+                values.join(", ").as_str(),
+            ));
+        } else if !details.is_empty() {
+            let mut status_detail = Element::new(ver, "Status_Detail");
+            for (name, value) in details.iter() {
+                status_detail.push(
+                    Element::text_element(ver, "Detail", value.as_str())
+                        .with_attr("name", name.as_str()),
+                );
             }
-            _ => {}
+            element.push(status_detail);
         }
+        if let Some(message) = &self.message {
+            element.push(Element::text_element(ver, "Message", message.as_str()));
+        }
+        Ok(element)
     }
-    Ok(status_message)
+}
+
+pub fn parse_status_message(doc: &[u8]) -> Result<StatusMessage, TaxiiError> {
+    from_xml(doc)
+}
+
+/// Build a Status_Message answering the message `in_response_to`, e.g. to acknowledge an
+/// Inbox_Message with SUCCESS.
+pub fn create_status_message_body(
+    ver: Version,
+    in_response_to: &str,
+    status_type: &StatusType,
+    message: Option<&str>,
+) -> Result<String, TaxiiError> {
+    let status_message = StatusMessage {
+        message_id: String::from(""),
+        in_response_to: String::from(in_response_to),
+        status_type: status_type.clone(),
+        message: message.map(String::from),
+    };
+    to_xml(ver, &status_message)
 }
 
 #[cfg(test)]
 mod tests {
     use std::{env, fs::read_to_string, path::Path};
 
-    use crate::taxii::status_message::parse_status_message;
+    use crate::taxii::{
+        status_message::{create_status_message_body, parse_status_message, StatusType},
+        version::Version,
+    };
+
+    fn read_sample(name: &str) -> String {
+        let path = env::var("CARGO_MANIFEST_DIR").unwrap();
+        read_to_string(Path::new(path.as_str()).join("test").join(name)).unwrap()
+    }
 
     #[test]
     fn test_parse_status_message() {
-        let path = env::var("CARGO_MANIFEST_DIR").unwrap();
-        let path =
-            Path::new(path.as_str()).join("test/sample-status-message-response-bad-message.xml");
-        let doc = read_to_string(path).unwrap();
+        let doc = read_sample("sample-status-message-response-bad-message.xml");
         let status_message = parse_status_message(doc.as_bytes()).unwrap();
         assert_eq!("9125177396285394141", status_message.message_id);
         assert_eq!("0", status_message.in_response_to);
-        assert_eq!("BAD_MESSAGE", status_message.status_type);
+        assert_eq!(StatusType::BadMessage, status_message.status_type);
+        assert_eq!("BAD_MESSAGE", status_message.status_type.to_str());
+    }
+
+    #[test]
+    fn test_parse_status_message_details() {
+        let doc = read_sample("sample-status-message-response-retry.xml");
+        let status_message = parse_status_message(doc.as_bytes()).unwrap();
+        assert_eq!(
+            StatusType::Retry {
+                estimated_wait: Some(1)
+            },
+            status_message.status_type
+        );
+
+        let doc = read_sample("sample-status-message-response-pending.xml");
+        let status_message = parse_status_message(doc.as_bytes()).unwrap();
+        assert_eq!(
+            StatusType::Pending {
+                estimated_wait: Some(1),
+                result_id: Some(String::from("7d2b7e4c-4ce4-4a3c-9d4e-6a2a9ffb3d50")),
+                will_push: Some(false),
+            },
+            status_message.status_type
+        );
+
+        let doc = read_sample("sample-status-message-response-unsupported-content.xml");
+        let status_message = parse_status_message(doc.as_bytes()).unwrap();
+        assert_eq!(
+            StatusType::UnsupportedContent {
+                supported_content: vec![
+                    String::from("urn:stix.mitre.org:xml:1.1.1"),
+                    String::from("urn:stix.mitre.org:xml:1.2"),
+                ]
+            },
+            status_message.status_type
+        );
+        assert_eq!(
+            "status message: UNSUPPORTED_CONTENT SUPPORTED_CONTENT=urn:stix.mitre.org:xml:1.1.1 \
+             SUPPORTED_CONTENT=urn:stix.mitre.org:xml:1.2: Content binding not supported",
+            status_message.to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_status_type() {
+        let details = vec![(String::from("ESTIMATED_WAIT"), String::from("soon"))];
+        assert!(StatusType::parse("RETRY", &details).is_err());
+        assert!(StatusType::parse("", &[]).is_err());
+        let status_type = StatusType::parse("CUSTOM_STATUS", &details).unwrap();
+        assert_eq!("CUSTOM_STATUS", status_type.to_str());
+        assert_eq!(details, status_type.details());
+        assert_eq!(
+            StatusType::NotFound { item: None },
+            StatusType::parse("NOT_FOUND", &[]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_create_status_message_body() {
+        let status_type = StatusType::Pending {
+            estimated_wait: Some(30),
+            result_id: Some(String::from("result-1")),
+            will_push: Some(false),
+        };
+        let doc =
+            create_status_message_body(Version::V11, "message-1", &status_type, Some("later"))
+                .unwrap();
+        let status_message = parse_status_message(doc.as_bytes()).unwrap();
+        assert_eq!("message-1", status_message.in_response_to);
+        assert_eq!(status_type, status_message.status_type);
+        assert_eq!(Some(String::from("later")), status_message.message);
     }
 }
@@ -0,0 +1,242 @@
+//! The stores behind the TAXII 1.1 server. `MemoryStore` is the only implementation, so the
+//! inbox is library-only as far as persistence goes: content pushed to a server backed by a
+//! `MemoryStore` is lost when the process exits. To keep it, embed `server::new_app` with a
+//! persistent `Store`, or `server::new_inbox_app` with a sink such as `Taxii21Sink`, which adds
+//! the content to a TAXII 2.1 backend.
+
+use std::collections::HashMap;
+
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+
+use super::{
+    collections::Collection,
+    content_block::ContentBlock,
+    errors::TaxiiError,
+    poll::TimeRange,
+    subscriptions::{PushParameters, Subscription, SubscriptionParameters, SubscriptionStatus},
+};
+
+/// The storage behind the TAXII 1.1 server: the collections it offers, their content and the
+/// subscriptions on them. The server adds the services of a collection, a store only describes
+/// it.
+pub trait Store {
+    fn collections(&self) -> Vec<Collection>;
+    /// The content of a collection within the time range, in the order it was added. The
+    /// Timestamp_Label of a block is the time it was added.
+    fn poll(
+        &self,
+        collection_name: &str,
+        time_range: &TimeRange,
+    ) -> Result<Vec<ContentBlock>, TaxiiError>;
+    fn add_content(
+        &mut self,
+        collection_name: &str,
+        content_block: &ContentBlock,
+    ) -> Result<(), TaxiiError>;
+    fn subscribe(
+        &mut self,
+        collection_name: &str,
+        subscription_parameters: Option<&SubscriptionParameters>,
+        push_parameters: Option<&PushParameters>,
+    ) -> Result<Subscription, TaxiiError>;
+    /// Set the status of a subscription, returning None when there is no such subscription.
+    fn update_subscription(
+        &mut self,
+        collection_name: &str,
+        subscription_id: &str,
+        status: SubscriptionStatus,
+    ) -> Result<Option<Subscription>, TaxiiError>;
+    fn subscriptions(&self, collection_name: &str) -> Result<Vec<Subscription>, TaxiiError>;
+
+    fn collection(&self, collection_name: &str) -> Option<Collection> {
+        self.collections()
+            .into_iter()
+            .find(|c| c.collection_name == collection_name)
+    }
+    fn subscription(
+        &self,
+        collection_name: &str,
+        subscription_id: &str,
+    ) -> Result<Option<Subscription>, TaxiiError> {
+        Ok(self
+            .subscriptions(collection_name)?
+            .into_iter()
+            .find(|s| s.id == subscription_id))
+    }
+}
+
+/// A store keeping everything in memory, e.g. for tests and for relaying content. Nothing
+/// survives a restart.
+#[derive(Default)]
+pub struct MemoryStore {
+    collections: Vec<Collection>,
+    content: HashMap<String, Vec<ContentBlock>>,
+    subscriptions: HashMap<String, Vec<Subscription>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+    pub fn add_collection(&mut self, collection: Collection) {
+        self.content.insert(
+            collection.collection_name.clone(),
+            Vec::<ContentBlock>::new(),
+        );
+        self.subscriptions.insert(
+            collection.collection_name.clone(),
+            Vec::<Subscription>::new(),
+        );
+        self.collections.push(collection);
+    }
+}
+
+fn unknown_collection(collection_name: &str) -> TaxiiError {
+    TaxiiError::Storage(format!("unknown collection: {}", collection_name))
+}
+
+impl Store for MemoryStore {
+    fn collections(&self) -> Vec<Collection> {
+        self.collections.clone()
+    }
+    fn poll(
+        &self,
+        collection_name: &str,
+        time_range: &TimeRange,
+    ) -> Result<Vec<ContentBlock>, TaxiiError> {
+        let content = match self.content.get(collection_name) {
+            Some(v) => v,
+            None => return Err(unknown_collection(collection_name)),
+        };
+        let in_range = |content_block: &&ContentBlock| match content_block.timestamp_label {
+            Some(v) => {
+                time_range.exclusive_begin.is_none_or(|begin| v > begin)
+                    && time_range.inclusive_end.is_none_or(|end| v <= end)
+            }
+            None => false,
+        };
+        Ok(content.iter().filter(in_range).cloned().collect())
+    }
+    fn add_content(
+        &mut self,
+        collection_name: &str,
+        content_block: &ContentBlock,
+    ) -> Result<(), TaxiiError> {
+        let content = match self.content.get_mut(collection_name) {
+            Some(v) => v,
+            None => return Err(unknown_collection(collection_name)),
+        };
+        // labels must increase for polling with an exclusive begin to not miss content added
+        // within the same clock tick
+        let mut timestamp_label = Utc::now();
+        if let Some(last) = content.last().and_then(|v| v.timestamp_label) {
+            if timestamp_label <= last {
+                timestamp_label = last + Duration::microseconds(1);
+            }
+        }
+        let mut content_block = content_block.clone();
+        content_block.timestamp_label = Some(timestamp_label);
+        content.push(content_block);
+        Ok(())
+    }
+    fn subscribe(
+        &mut self,
+        collection_name: &str,
+        subscription_parameters: Option<&SubscriptionParameters>,
+        push_parameters: Option<&PushParameters>,
+    ) -> Result<Subscription, TaxiiError> {
+        let subscriptions = match self.subscriptions.get_mut(collection_name) {
+            Some(v) => v,
+            None => return Err(unknown_collection(collection_name)),
+        };
+        let mut subscription = Subscription::new_empty();
+        subscription.id = Uuid::new_v4().to_string();
+        subscription.subscription_parameters = subscription_parameters.cloned();
+        subscription.push_parameters = push_parameters.cloned();
+        subscriptions.push(subscription.clone());
+        Ok(subscription)
+    }
+    fn update_subscription(
+        &mut self,
+        collection_name: &str,
+        subscription_id: &str,
+        status: SubscriptionStatus,
+    ) -> Result<Option<Subscription>, TaxiiError> {
+        let subscriptions = match self.subscriptions.get_mut(collection_name) {
+            Some(v) => v,
+            None => return Err(unknown_collection(collection_name)),
+        };
+        Ok(subscriptions
+            .iter_mut()
+            .find(|s| s.id == subscription_id)
+            .map(|s| {
+                s.status = status;
+                s.clone()
+            }))
+    }
+    fn subscriptions(&self, collection_name: &str) -> Result<Vec<Subscription>, TaxiiError> {
+        match self.subscriptions.get(collection_name) {
+            Some(v) => Ok(v.clone()),
+            None => Err(unknown_collection(collection_name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MemoryStore, Store};
+    use crate::taxii::{
+        collections::Collection, content_block::ContentBlock, poll::TimeRange,
+        subscriptions::SubscriptionStatus,
+    };
+
+    #[test]
+    fn test_memory_store() {
+        let mut store = MemoryStore::new();
+        let mut collection = Collection::new_empty();
+        collection.collection_name = String::from("stix-data");
+        store.add_collection(collection);
+        assert!(store.collection("stix-data").is_some());
+        assert!(store.collection("other-data").is_none());
+
+        for content in ["one", "two", "three"] {
+            store
+                .add_content("stix-data", &ContentBlock::new("binding-1", content))
+                .unwrap();
+        }
+        assert!(store
+            .add_content("other-data", &ContentBlock::new("binding-1", "one"))
+            .is_err());
+        let content = store.poll("stix-data", &TimeRange::default()).unwrap();
+        assert_eq!(3, content.len());
+        let time_range = TimeRange {
+            exclusive_begin: content[0].timestamp_label,
+            inclusive_end: content[1].timestamp_label,
+        };
+        let content = store.poll("stix-data", &time_range).unwrap();
+        assert_eq!(1, content.len());
+        assert_eq!("two", content[0].content);
+
+        let subscription = store.subscribe("stix-data", None, None).unwrap();
+        assert_eq!(SubscriptionStatus::Active, subscription.status);
+        let paused = store
+            .update_subscription(
+                "stix-data",
+                subscription.id.as_str(),
+                SubscriptionStatus::Paused,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(SubscriptionStatus::Paused, paused.status);
+        assert!(store
+            .update_subscription("stix-data", "unknown", SubscriptionStatus::Paused)
+            .unwrap()
+            .is_none());
+        let subscription = store
+            .subscription("stix-data", subscription.id.as_str())
+            .unwrap()
+            .unwrap();
+        assert_eq!(SubscriptionStatus::Paused, subscription.status);
+    }
+}
@@ -1,9 +1,11 @@
-use xml::{reader, writer, EventReader};
-
 use super::{
-    errors::MyError,
+    errors::TaxiiError,
+    message::{
+        collection_name_attr, from_xml, message_id, to_xml, Element, FromElement, ToElement,
+    },
+    query::{parse_query, DefaultQuery, DEFAULT_QUERY_FORMAT_ID},
     types::{ContentBinding, ResponseType},
-    version::{taxii_request, write_xml, write_xml_tag_with_data, Version},
+    version::{taxii_request, Version},
 };
 
 /*
@@ -17,7 +19,7 @@ for the named TAXII Data Collection.
 */
 
 #[derive(Debug, Copy, Clone, PartialEq)]
-enum SubscribeAction {
+pub enum SubscribeAction {
     Subscribe,
     Unsubscribe,
     Pause,
@@ -26,6 +28,19 @@ enum SubscribeAction {
 }
 
 impl SubscribeAction {
+    pub fn parse(v: &str) -> Result<SubscribeAction, TaxiiError> {
+        match v {
+            "SUBSCRIBE" => Ok(SubscribeAction::Subscribe),
+            "UNSUBSCRIBE" => Ok(SubscribeAction::Unsubscribe),
+            "PAUSE" => Ok(SubscribeAction::Pause),
+            "RESUME" => Ok(SubscribeAction::Resume),
+            "STATUS" => Ok(SubscribeAction::Status),
+            _ => Err(TaxiiError::Protocol(format!(
+                "could not parse subscribe action: {}",
+                v
+            ))),
+        }
+    }
     pub fn to_str(&self) -> &str {
         match self {
             SubscribeAction::Subscribe => "SUBSCRIBE",
@@ -37,163 +52,248 @@ impl SubscribeAction {
     }
 }
 
-struct SubscriptionParameters {
-    reponse_type: ResponseType,
-    content_bindings: Vec<ContentBinding>,
-    query: Option<String>,
-    query_format_id: Option<String>,
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubscriptionParameters {
+    pub response_type: ResponseType,
+    pub content_bindings: Vec<ContentBinding>,
+    pub query: Option<String>,
+    pub query_format_id: Option<String>,
+}
+
+/// Where the server should push content for a subscription, e.g. the address of an inbox
+/// service.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PushParameters {
+    pub protocol_binding: String,
+    pub address: String,
+    pub message_binding: String,
+}
+
+impl SubscriptionParameters {
+    /// Set the query to a TAXII Default Query.
+    pub fn set_default_query(&mut self, query: &DefaultQuery) -> Result<(), TaxiiError> {
+        self.query = Some(query.to_xml()?);
+        self.query_format_id = Some(String::from(DEFAULT_QUERY_FORMAT_ID));
+        Ok(())
+    }
+
+    /// The TAXII Default Query of the subscription, None when there is no query.
+    pub fn default_query(&self) -> Result<Option<DefaultQuery>, TaxiiError> {
+        parse_query(
+            self.query.as_deref().unwrap_or_default(),
+            self.query_format_id.as_deref().unwrap_or_default(),
+        )
+    }
+}
+
+impl FromElement for SubscriptionParameters {
+    fn from_element(element: &Element) -> Result<SubscriptionParameters, TaxiiError> {
+        element.expect("Subscription_Parameters")?;
+        element.check_elements(&["Response_Type", "Content_Binding", "Query"])?;
+        let response_type = match element.element_text("Response_Type") {
+            Some(v) => ResponseType::parse(v.as_str())?,
+            None => ResponseType::Full,
+        };
+        let query = match element.element("Query") {
+            Some(v) => Some(v.content()?),
+            None => None,
+        };
+        Ok(SubscriptionParameters {
+            response_type,
+            content_bindings: element
+                .elements_named("Content_Binding")
+                .map(ContentBinding::from_element)
+                .collect::<Result<Vec<_>, _>>()?,
+            query,
+            query_format_id: element
+                .element("Query")
+                .and_then(|v| v.attr("format_id"))
+                .map(String::from),
+        })
+    }
+}
+
+impl ToElement for SubscriptionParameters {
+    fn to_element(&self, ver: Version) -> Result<Element, TaxiiError> {
+        let mut element = Element::new(ver, "Subscription_Parameters").with_element(
+            Element::text_element(ver, "Response_Type", self.response_type.to_str()),
+        );
+        for content_binding in self.content_bindings.iter() {
+            element.push(content_binding.to_element(ver)?);
+        }
+        if let Some(query) = &self.query {
+            let mut query_element = Element::new(ver, "Query");
+            if let Some(query_format_id) = &self.query_format_id {
+                query_element.set_attr("format_id", query_format_id.as_str());
+            }
+            element.push(query_element.with_content(query.as_str())?);
+        }
+        Ok(element)
+    }
+}
+
+// TAXII 1.0 has Delivery_Parameters, which also hold the content bindings to deliver, where 1.1
+// has Push_Parameters and the content bindings in the Subscription_Parameters.
+fn push_parameters_tag(ver: Version) -> &'static str {
+    match ver {
+        Version::V10 => "Delivery_Parameters",
+        _ => "Push_Parameters",
+    }
 }
 
-struct PushParameters {
-    protocol_binding: String,
-    address: String,
-    message_binding: String,
+impl FromElement for PushParameters {
+    fn from_element(element: &Element) -> Result<PushParameters, TaxiiError> {
+        element.expect(push_parameters_tag(element.version()))?;
+        element.check_elements(&[
+            "Protocol_Binding",
+            "Address",
+            "Message_Binding",
+            "Content_Binding",
+        ])?;
+        Ok(PushParameters {
+            protocol_binding: element.element_text("Protocol_Binding").unwrap_or_default(),
+            address: element.element_text("Address").unwrap_or_default(),
+            message_binding: element.element_text("Message_Binding").unwrap_or_default(),
+        })
+    }
+}
+
+impl ToElement for PushParameters {
+    fn to_element(&self, ver: Version) -> Result<Element, TaxiiError> {
+        Ok(Element::new(ver, push_parameters_tag(ver))
+            .with_element(Element::text_element(
+                ver,
+                "Protocol_Binding",
+                self.protocol_binding.as_str(),
+            ))
+            .with_element(Element::text_element(ver, "Address", self.address.as_str()))
+            .with_element(Element::text_element(
+                ver,
+                "Message_Binding",
+                self.message_binding.as_str(),
+            )))
+    }
+}
+
+/// The subscription and push parameters of a subscription or a subscription request.
+fn read_parameters(
+    element: &Element,
+) -> Result<(Option<SubscriptionParameters>, Option<PushParameters>), TaxiiError> {
+    if element.version() == Version::V10 {
+        return match element.element("Delivery_Parameters") {
+            Some(v) => {
+                let content_bindings = v
+                    .elements_named("Content_Binding")
+                    .map(ContentBinding::from_element)
+                    .collect::<Result<Vec<_>, _>>()?;
+                let subscription_parameters = match content_bindings.is_empty() {
+                    true => None,
+                    false => Some(SubscriptionParameters {
+                        response_type: ResponseType::Full,
+                        content_bindings,
+                        query: None,
+                        query_format_id: None,
+                    }),
+                };
+                Ok((
+                    subscription_parameters,
+                    Some(PushParameters::from_element(v)?),
+                ))
+            }
+            None => Ok((None, None)),
+        };
+    }
+    let subscription_parameters = match element.element("Subscription_Parameters") {
+        Some(v) => Some(SubscriptionParameters::from_element(v)?),
+        None => None,
+    };
+    let push_parameters = match element.element("Push_Parameters") {
+        Some(v) => Some(PushParameters::from_element(v)?),
+        None => None,
+    };
+    Ok((subscription_parameters, push_parameters))
+}
+
+fn write_parameters(
+    ver: Version,
+    element: &mut Element,
+    subscription_parameters: Option<&SubscriptionParameters>,
+    push_parameters: Option<&PushParameters>,
+) -> Result<(), TaxiiError> {
+    if ver != Version::V10 {
+        if let Some(subscription_parameters) = subscription_parameters {
+            element.push(subscription_parameters.to_element(ver)?);
+        }
+        if let Some(push_parameters) = push_parameters {
+            element.push(push_parameters.to_element(ver)?);
+        }
+        return Ok(());
+    }
+    let mut content_bindings = &Vec::<ContentBinding>::new();
+    if let Some(v) = subscription_parameters {
+        if v.query.is_some() {
+            return Err(TaxiiError::unsupported(ver, "queries"));
+        }
+        if v.response_type != ResponseType::Full {
+            return Err(TaxiiError::unsupported(
+                ver,
+                format!("{} subscriptions", v.response_type.to_str()).as_str(),
+            ));
+        }
+        content_bindings = &v.content_bindings;
+    }
+    if let Some(push_parameters) = push_parameters {
+        let mut delivery_parameters = push_parameters.to_element(ver)?;
+        for content_binding in content_bindings.iter() {
+            delivery_parameters.push(content_binding.to_element(ver)?);
+        }
+        element.push(delivery_parameters);
+    }
+    Ok(())
+}
+
+// TAXII 1.0 manages subscriptions to feeds
+fn subscription_management_tag(ver: Version, message: &str) -> String {
+    match ver {
+        Version::V10 => format!("Manage_Feed_Subscription_{}", message),
+        _ => format!("Subscription_Management_{}", message),
+    }
 }
 
 // TODO: Extended Headers?
 // TODO: <ds:Signature>
 
-fn create_subscribe_request_body(
+pub fn create_subscribe_request_body(
     ver: Version,
     action: SubscribeAction,
     collection_name: &str,
     subscription_id: Option<&str>,
     subscription_parameters: Option<&SubscriptionParameters>,
     push_parameters: Option<&PushParameters>,
-) -> Result<String, MyError> {
-    let mut buf_writer: Vec<u8> = Vec::with_capacity(128);
-    let mut writer = writer::EmitterConfig::new()
-        .write_document_declaration(false)
-        .perform_indent(true)
-        .create_writer(&mut buf_writer);
-
-    let msg_id = ver.message_id();
-    let tag = format!("taxii_11:Subscription_Management_Request");
-    let elem = writer::XmlEvent::start_element(tag.as_str())
-        .attr("action", action.to_str())
-        .attr("message_id", msg_id.as_str())
-        .attr("collection_name", collection_name)
-        .ns("taxii_11", ver.xml_namespace());
-
-    // <Subscription_Management_Request>
-    write_xml(&mut writer, elem)?;
-
-    if action != SubscribeAction::Subscribe && subscription_id.is_some() {
-        // <Subscription_ID></Subscription_ID>
-        write_xml_tag_with_data(
-            &mut writer,
-            "taxii_11:Subscription_ID",
-            subscription_id.unwrap(),
-        )?;
-    }
+) -> Result<String, TaxiiError> {
     if action == SubscribeAction::Subscribe && subscription_id.is_some() {
-        return Err(MyError(String::from(
+        return Err(TaxiiError::Protocol(String::from(
             "unexpected subscription ID provided with subscribe action",
         )));
     }
-
-    if action == SubscribeAction::Subscribe && subscription_parameters.is_some() {
-        let subscription_parameters = subscription_parameters.unwrap();
-        // <Subscription_Parameters>
-        write_xml(
-            &mut writer,
-            writer::XmlEvent::start_element("taxii_11:Subscription_Parameters"),
-        )?;
-        // <Response_Type></Response_Type>
-        write_xml_tag_with_data(
-            &mut writer,
-            "taxii_11:Response_Type",
-            subscription_parameters.reponse_type.to_str(),
-        )?;
-        {
-            for content_binding in subscription_parameters.content_bindings.iter() {
-                // <Content_Binding>
-                write_xml(
-                    &mut writer,
-                    writer::XmlEvent::start_element("taxii_11:Content_Binding")
-                        .attr("binding_id", content_binding.binding_id.as_str()),
-                )?;
-                match &content_binding.subtype_id {
-                    Some(subtype_id) => {
-                        write_xml(
-                            &mut writer,
-                            writer::XmlEvent::start_element("taxii_11:Subtype")
-                                .attr("binding_id", &subtype_id.as_str()),
-                        )?;
-                        write_xml(&mut writer, writer::XmlEvent::end_element())?;
-                    }
-                    None => (),
-                }
-                // </Content_Binding>
-                write_xml(&mut writer, writer::XmlEvent::end_element())?;
-            }
-            {
-                match &subscription_parameters.query {
-                    Some(query) => {
-                        // <Query>
-                        match &subscription_parameters.query_format_id {
-                            Some(query_format_id) => {
-                                write_xml(
-                                    &mut writer,
-                                    writer::XmlEvent::start_element("taxii_11:Query")
-                                        .attr("format_id", query_format_id.as_str()),
-                                )?;
-                            }
-                            None => write_xml(
-                                &mut writer,
-                                writer::XmlEvent::start_element("taxii_11:Query"),
-                            )?,
-                        }
-                        write_xml(&mut writer, writer::XmlEvent::characters(query.as_str()))?;
-                        // </Query>
-                        write_xml(&mut writer, writer::XmlEvent::end_element())?;
-                    }
-                    None => (),
-                }
-            }
-        }
-        // </Subscription_Parameters>
-        write_xml(&mut writer, writer::XmlEvent::end_element())?;
-    }
-    if action == SubscribeAction::Subscribe && push_parameters.is_some() {
-        let push_parameters = push_parameters.unwrap();
-        // <Push_Parameters>
-        write_xml(
-            &mut writer,
-            writer::XmlEvent::start_element("taxii_11:Push_Parameters"),
-        )?;
-        {
-            // <Protocol_Binding></Protocol_Binding>
-            write_xml_tag_with_data(
-                &mut writer,
-                "taxii_11:Protocol_Binding",
-                &push_parameters.protocol_binding.as_str(),
-            )?;
-        }
-        {
-            // <Address></Address>
-            write_xml_tag_with_data(
-                &mut writer,
-                "taxii_11:Address",
-                &push_parameters.address.as_str(),
-            )?;
-        }
-        {
-            // <Message_Binding></Message_Binding>
-            write_xml_tag_with_data(
-                &mut writer,
-                "taxii_11:Message_Binding",
-                &push_parameters.message_binding.as_str(),
-            )?;
-        }
-        // </Push_Parameters>
-        write_xml(&mut writer, writer::XmlEvent::end_element())?;
+    if let (SubscribeAction::Unsubscribe | SubscribeAction::Pause | SubscribeAction::Resume, None) =
+        (action, subscription_id)
+    {
+        return Err(TaxiiError::Protocol(format!(
+            "missing subscription ID for {} action",
+            action.to_str()
+        )));
     }
 
-    // </Subscription_Management_Request>
-    write_xml(&mut writer, writer::XmlEvent::end_element())?;
-    // TODO: better check on conversion than unwrap
-    return Ok(String::from_utf8(buf_writer).unwrap());
+    let mut subscription_request = SubscriptionRequest::new_empty();
+    subscription_request.action = action;
+    subscription_request.collection_name = String::from(collection_name);
+    subscription_request.subscription_id = subscription_id.map(String::from);
+    if action == SubscribeAction::Subscribe {
+        subscription_request.subscription_parameters = subscription_parameters.cloned();
+        subscription_request.push_parameters = push_parameters.cloned();
+    }
+    to_xml(ver, &subscription_request)
 }
 
 pub fn subscribe_request(
@@ -202,18 +302,16 @@ pub fn subscribe_request(
     password: &str,
     ver: Version,
     collection_name: &str,
-) {
-    match create_subscribe_request_body(
+) -> Result<String, TaxiiError> {
+    let request_body = create_subscribe_request_body(
         ver,
         SubscribeAction::Subscribe,
         collection_name,
         None,
         None,
         None,
-    ) {
-        Ok(request_body) => taxii_request(url, username, password, &request_body, ver),
-        Err(err) => panic!("{}", err),
-    }
+    )?;
+    taxii_request(url, username, password, &request_body, ver)
 }
 
 pub fn unsubscribe_request(
@@ -223,304 +321,401 @@ pub fn unsubscribe_request(
     ver: Version,
     collection_name: &str,
     subscription_id: &str,
-) {
-    match create_subscribe_request_body(
+) -> Result<String, TaxiiError> {
+    let request_body = create_subscribe_request_body(
         ver,
         SubscribeAction::Unsubscribe,
         collection_name,
         Some(subscription_id),
         None,
         None,
-    ) {
-        Ok(request_body) => taxii_request(url, username, password, &request_body, ver),
-        Err(err) => panic!("{}", err),
-    }
+    )?;
+    taxii_request(url, username, password, &request_body, ver)
 }
 
-pub fn status_request(
+pub fn pause_request(
     url: &str,
     username: &str,
     password: &str,
     ver: Version,
     collection_name: &str,
     subscription_id: &str,
-) {
-    match create_subscribe_request_body(
+) -> Result<String, TaxiiError> {
+    let request_body = create_subscribe_request_body(
         ver,
-        SubscribeAction::Status,
+        SubscribeAction::Pause,
         collection_name,
         Some(subscription_id),
         None,
         None,
-    ) {
-        Ok(request_body) => taxii_request(url, username, password, &request_body, ver),
-        Err(err) => panic!("{}", err),
-    }
+    )?;
+    taxii_request(url, username, password, &request_body, ver)
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
-enum SubscriptionManagementResponseTag {
-    SubscriptionManagementResponse,
-    Subscription,
-    SubscriptionID,
-    SubscriptionParameters,
-    ResponseType,
-    PollInstance,
-    ProtocolBinding,
-    Address,
-    MessageBinding,
-}
-
-impl SubscriptionManagementResponseTag {
-    fn parse(tag: &str) -> Result<SubscriptionManagementResponseTag, MyError> {
-        match tag {
-            "Subscription_Management_Response" => {
-                Ok(SubscriptionManagementResponseTag::SubscriptionManagementResponse)
-            }
-            "Subscription" => Ok(SubscriptionManagementResponseTag::Subscription),
-            "Subscription_ID" => Ok(SubscriptionManagementResponseTag::SubscriptionID),
-            "Subscription_Parameters" => {
-                Ok(SubscriptionManagementResponseTag::SubscriptionParameters)
-            }
-            "Response_Type" => Ok(SubscriptionManagementResponseTag::ResponseType),
-            "Poll_Instance" => Ok(SubscriptionManagementResponseTag::PollInstance),
-            "Protocol_Binding" => Ok(SubscriptionManagementResponseTag::ProtocolBinding),
-            "Address" => Ok(SubscriptionManagementResponseTag::Address),
-            "Message_Binding" => Ok(SubscriptionManagementResponseTag::MessageBinding),
-            _ => Err(MyError(format!("could not parse tag: {}", tag))),
-        }
-    }
-    fn matches_expected_depth(&self, depth: usize) -> bool {
-        match self {
-            SubscriptionManagementResponseTag::SubscriptionManagementResponse => depth == 0,
-            SubscriptionManagementResponseTag::Subscription => depth == 1,
-            SubscriptionManagementResponseTag::SubscriptionID => depth == 2,
-            SubscriptionManagementResponseTag::SubscriptionParameters => depth == 2,
-            SubscriptionManagementResponseTag::ResponseType => depth == 3,
-            SubscriptionManagementResponseTag::PollInstance => depth == 2,
-            SubscriptionManagementResponseTag::ProtocolBinding => depth == 3,
-            SubscriptionManagementResponseTag::Address => depth == 3,
-            SubscriptionManagementResponseTag::MessageBinding => depth == 3,
-        }
-    }
-    fn to_str(&self) -> &str {
-        match self {
-            SubscriptionManagementResponseTag::SubscriptionManagementResponse => {
-                "Subscription_Management_Response"
-            }
-            SubscriptionManagementResponseTag::Subscription => "Subscription",
-            SubscriptionManagementResponseTag::SubscriptionID => "Subscription_ID",
-            SubscriptionManagementResponseTag::SubscriptionParameters => "Subscription_Parameters",
-            SubscriptionManagementResponseTag::ResponseType => "Response_Type",
-            SubscriptionManagementResponseTag::PollInstance => "Poll_Instance",
-            SubscriptionManagementResponseTag::ProtocolBinding => "Protocol_Binding",
-            SubscriptionManagementResponseTag::Address => "Address",
-            SubscriptionManagementResponseTag::MessageBinding => "Message_Binding",
-        }
-    }
+pub fn resume_request(
+    url: &str,
+    username: &str,
+    password: &str,
+    ver: Version,
+    collection_name: &str,
+    subscription_id: &str,
+) -> Result<String, TaxiiError> {
+    let request_body = create_subscribe_request_body(
+        ver,
+        SubscribeAction::Resume,
+        collection_name,
+        Some(subscription_id),
+        None,
+        None,
+    )?;
+    taxii_request(url, username, password, &request_body, ver)
+}
+
+pub fn status_request(
+    url: &str,
+    username: &str,
+    password: &str,
+    ver: Version,
+    collection_name: &str,
+    subscription_id: &str,
+) -> Result<String, TaxiiError> {
+    let request_body = create_subscribe_request_body(
+        ver,
+        SubscribeAction::Status,
+        collection_name,
+        Some(subscription_id),
+        None,
+        None,
+    )?;
+    taxii_request(url, username, password, &request_body, ver)
 }
 
-#[derive(Clone)]
-struct PollInstance {
-    protocol_binding: String,
-    address: String,
-    message_bindings: Vec<String>,
+/// A Poll_Instance of a subscription, i.e. where the subscriber can poll for its content.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PollInstance {
+    pub protocol_binding: String,
+    pub address: String,
+    pub message_bindings: Vec<String>,
 }
 
 impl PollInstance {
-    fn new_empty() -> PollInstance {
-        return PollInstance {
+    pub fn new_empty() -> PollInstance {
+        PollInstance {
             protocol_binding: String::from(""),
             address: String::from(""),
             message_bindings: Vec::<String>::new(),
-        };
+        }
     }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
-enum SubscriptionStatus {
+pub enum SubscriptionStatus {
     Active,
     Paused,
     Unsubscribed,
 }
 
 impl SubscriptionStatus {
-    pub fn parse(v: &str) -> Result<SubscriptionStatus, MyError> {
+    pub fn parse(v: &str) -> Result<SubscriptionStatus, TaxiiError> {
         match v {
             "ACTIVE" => Ok(SubscriptionStatus::Active),
             "PAUSED" => Ok(SubscriptionStatus::Paused),
             "UNSUBSCRIBED" => Ok(SubscriptionStatus::Unsubscribed),
-            _ => Err(MyError(format!(
+            _ => Err(TaxiiError::Protocol(format!(
                 "could not parse subscription status: {}",
                 v
             ))),
         }
     }
+    pub fn to_str(&self) -> &str {
+        match self {
+            SubscriptionStatus::Active => "ACTIVE",
+            SubscriptionStatus::Paused => "PAUSED",
+            SubscriptionStatus::Unsubscribed => "UNSUBSCRIBED",
+        }
+    }
 }
 
-struct Subscription {
-    status: SubscriptionStatus,
-    id: String,
-    response_type: ResponseType,
-    poll_instances: Vec<PollInstance>,
-    collection_name: String,
+#[derive(Clone, Debug, PartialEq)]
+pub struct Subscription {
+    pub status: SubscriptionStatus,
+    pub id: String,
+    pub subscription_parameters: Option<SubscriptionParameters>,
+    pub push_parameters: Option<PushParameters>,
+    pub poll_instances: Vec<PollInstance>,
 }
 
 impl Subscription {
     pub fn new_empty() -> Subscription {
-        return Subscription {
+        Subscription {
             status: SubscriptionStatus::Active,
             id: String::from(""),
-            response_type: ResponseType::Full,
+            subscription_parameters: None,
+            push_parameters: None,
             poll_instances: Vec::<PollInstance>::new(),
-            collection_name: String::from(""),
-        };
+        }
+    }
+    /// The response type of the subscription, FULL when the server did not return its
+    /// Subscription_Parameters.
+    pub fn response_type(&self) -> ResponseType {
+        match &self.subscription_parameters {
+            Some(v) => v.response_type,
+            None => ResponseType::Full,
+        }
     }
 }
 
+/// A parsed Subscription_Management_Response. A STATUS request returns every subscription the
+/// requester has on the collection, the other actions return the one they acted on.
+#[derive(Clone, Debug, PartialEq)]
 pub struct SubscriptionResponse {
-    message_id: String,
-    in_response_to: String,
-    subscription: Subscription,
+    pub message_id: String,
+    pub in_response_to: String,
+    pub collection_name: String,
+    pub message: Option<String>,
+    pub subscriptions: Vec<Subscription>,
 }
 
 impl SubscriptionResponse {
-    fn new_empty() -> SubscriptionResponse {
-        return SubscriptionResponse {
+    pub fn new_empty() -> SubscriptionResponse {
+        SubscriptionResponse {
             message_id: String::from(""),
             in_response_to: String::from(""),
-            subscription: Subscription::new_empty(),
-        };
+            collection_name: String::from(""),
+            message: None,
+            subscriptions: Vec::<Subscription>::new(),
+        }
+    }
+    pub fn find_subscription(&self, subscription_id: &str) -> Option<&Subscription> {
+        self.subscriptions.iter().find(|s| s.id == subscription_id)
     }
 }
 
-// TODO: test that we ignore a treat tags with 1 cardinality as errors,
-// e.g. that there is only one <Subscription> tag.
-
-pub fn parse_subscription_management_response(doc: &[u8]) -> Result<SubscriptionResponse, MyError> {
-    let mut tag_stack = Vec::<SubscriptionManagementResponseTag>::new();
-    let mut subscription_response = SubscriptionResponse::new_empty();
-    let mut cur_poll_instance: Option<PollInstance> = None;
-    let mut last_value: String = String::new();
-    let xml_parser = EventReader::new(doc);
-    for e in xml_parser {
-        match e {
-            Ok(reader::XmlEvent::StartElement {
-                name, attributes, ..
-            }) => {
-                let tag = match SubscriptionManagementResponseTag::parse(name.local_name.as_str()) {
-                    Ok(v) => v,
-                    Err(err) => return Err(err),
-                };
-                if !tag.matches_expected_depth(tag_stack.len()) {
-                    return Err(MyError(format!(
-                        "tag at unexpected depth of {} expected: {}",
-                        tag_stack.len(),
-                        name.local_name.as_str()
-                    )));
-                }
-                tag_stack.push(tag);
-                match tag {
-                    SubscriptionManagementResponseTag::SubscriptionManagementResponse => {
-                        for attr in attributes {
-                            match attr.name.local_name.as_str() {
-                                "message_id" => {
-                                    subscription_response.message_id = attr.value.clone()
-                                }
-                                "in_response_to" => {
-                                    subscription_response.in_response_to = attr.value.clone()
-                                }
-                                "collection_name" => {
-                                    subscription_response.subscription.collection_name =
-                                        attr.value.clone();
-                                }
-                                "xmlns:taxii" | "xmlns:taxii_11" | "xmlns:tdq" => {
-                                    // TODO: ignored for now
-                                }
-                                _ => {
-                                    return Err(MyError(format!(
-                                        "unrecogized attribute: {}",
-                                        attr.name.local_name
-                                    )))
-                                }
-                            }
-                        }
-                    }
-                    SubscriptionManagementResponseTag::Subscription => {
-                        for attr in attributes {
-                            match attr.name.local_name.as_str() {
-                                "status" => {
-                                    subscription_response.subscription.status =
-                                        match SubscriptionStatus::parse(attr.value.as_str()) {
-                                            Ok(status) => status,
-                                            Err(err) => return Err(MyError(err.to_string())),
-                                        }
-                                }
-                                _ => {
-                                    return Err(MyError(format!(
-                                        "unrecogized attribute: {}",
-                                        attr.name.local_name
-                                    )))
-                                }
-                            }
-                        }
-                    }
-                    SubscriptionManagementResponseTag::PollInstance => {
-                        cur_poll_instance = Some(PollInstance::new_empty())
-                    }
-                    // We only match on tags that we need to parse attributes from. This default
-                    // match is therefore: keep calm and carry on.
-                    _ => (),
-                }
-            }
-            Ok(reader::XmlEvent::EndElement { name }) => {
-                let end_tag = SubscriptionManagementResponseTag::parse(name.local_name.as_str())?;
-                let tag = tag_stack.pop();
-                if tag.is_none() || tag.unwrap() != end_tag {
-                    return Err(MyError(format!("malformed XML response")));
-                }
-                match end_tag {
-                    SubscriptionManagementResponseTag::SubscriptionID => {
-                        subscription_response.subscription.id = last_value.clone()
-                    }
-                    SubscriptionManagementResponseTag::ResponseType => {
-                        subscription_response.subscription.response_type =
-                            ResponseType::parse(last_value.as_str())?
-                    }
-                    SubscriptionManagementResponseTag::PollInstance => match cur_poll_instance {
-                        Some(ref poll_instance) => subscription_response
-                            .subscription
-                            .poll_instances
-                            .push(poll_instance.clone()),
-                        None => {
-                            return Err(MyError(format!("unexpected end tag for Poll_Instance")))
-                        }
-                    },
-                    SubscriptionManagementResponseTag::ProtocolBinding => match cur_poll_instance {
-                        Some(ref mut v) => v.protocol_binding = last_value.clone(),
-                        None => return Err(MyError(format!("unexpected Protocol_Binding tag"))),
-                    },
-                    SubscriptionManagementResponseTag::Address => match cur_poll_instance {
-                        Some(ref mut v) => v.address = last_value.clone(),
-                        None => return Err(MyError(format!("unexpected Address tag"))),
-                    },
-                    SubscriptionManagementResponseTag::MessageBinding => match cur_poll_instance {
-                        Some(ref mut v) => v.message_bindings.push(last_value.clone()),
-                        None => return Err(MyError(format!("unexpected Address tag"))),
-                    },
-                    _ => (),
-                }
-            }
-            Ok(reader::XmlEvent::Characters(ref data)) => {
-                last_value = data.clone();
-            }
-            Err(e) => {
-                return Err(MyError(e.to_string()));
-            }
-            _ => {}
+impl FromElement for PollInstance {
+    fn from_element(element: &Element) -> Result<PollInstance, TaxiiError> {
+        element.expect("Poll_Instance")?;
+        element.check_elements(&["Protocol_Binding", "Address", "Message_Binding"])?;
+        Ok(PollInstance {
+            protocol_binding: element.element_text("Protocol_Binding").unwrap_or_default(),
+            address: element.element_text("Address").unwrap_or_default(),
+            message_bindings: element
+                .elements_named("Message_Binding")
+                .map(|e| e.text())
+                .collect(),
+        })
+    }
+}
+
+impl ToElement for PollInstance {
+    fn to_element(&self, ver: Version) -> Result<Element, TaxiiError> {
+        let mut element = Element::new(ver, "Poll_Instance")
+            .with_element(Element::text_element(
+                ver,
+                "Protocol_Binding",
+                self.protocol_binding.as_str(),
+            ))
+            .with_element(Element::text_element(ver, "Address", self.address.as_str()));
+        for message_binding in self.message_bindings.iter() {
+            element.push(Element::text_element(
+                ver,
+                "Message_Binding",
+                message_binding.as_str(),
+            ));
         }
+        Ok(element)
     }
-    Ok(subscription_response)
+}
+
+impl FromElement for Subscription {
+    fn from_element(element: &Element) -> Result<Subscription, TaxiiError> {
+        element.expect("Subscription")?;
+        element.check_elements(&[
+            "Subscription_ID",
+            "Subscription_Parameters",
+            "Push_Parameters",
+            "Delivery_Parameters",
+            "Poll_Instance",
+        ])?;
+        let (subscription_parameters, push_parameters) = read_parameters(element)?;
+        Ok(Subscription {
+            status: match element.attr("status") {
+                Some(v) => SubscriptionStatus::parse(v)?,
+                None => SubscriptionStatus::Active,
+            },
+            id: element.element_text("Subscription_ID").unwrap_or_default(),
+            subscription_parameters,
+            push_parameters,
+            poll_instances: element
+                .elements_named("Poll_Instance")
+                .map(PollInstance::from_element)
+                .collect::<Result<Vec<_>, _>>()?,
+        })
+    }
+}
+
+impl ToElement for Subscription {
+    fn to_element(&self, ver: Version) -> Result<Element, TaxiiError> {
+        let mut element = Element::new(ver, "Subscription");
+        // TAXII 1.0 subscriptions have no status
+        if ver != Version::V10 {
+            element.set_attr("status", self.status.to_str());
+        }
+        element.push(Element::text_element(
+            ver,
+            "Subscription_ID",
+            self.id.as_str(),
+        ));
+        write_parameters(
+            ver,
+            &mut element,
+            self.subscription_parameters.as_ref(),
+            self.push_parameters.as_ref(),
+        )?;
+        for poll_instance in self.poll_instances.iter() {
+            element.push(poll_instance.to_element(ver)?);
+        }
+        Ok(element)
+    }
+}
+
+impl FromElement for SubscriptionResponse {
+    fn from_element(element: &Element) -> Result<SubscriptionResponse, TaxiiError> {
+        let ver = element.version();
+        element.expect(subscription_management_tag(ver, "Response").as_str())?;
+        element.check_elements(&["Message", "Subscription"])?;
+        Ok(SubscriptionResponse {
+            message_id: String::from(element.attr("message_id").unwrap_or_default()),
+            in_response_to: String::from(element.attr("in_response_to").unwrap_or_default()),
+            collection_name: String::from(
+                element.attr(collection_name_attr(ver)).unwrap_or_default(),
+            ),
+            message: element.element_text("Message"),
+            subscriptions: element
+                .elements_named("Subscription")
+                .map(Subscription::from_element)
+                .collect::<Result<Vec<_>, _>>()?,
+        })
+    }
+}
+
+impl ToElement for SubscriptionResponse {
+    fn to_element(&self, ver: Version) -> Result<Element, TaxiiError> {
+        let mut element = Element::new(ver, subscription_management_tag(ver, "Response").as_str())
+            .with_attr(
+                "message_id",
+                message_id(ver, self.message_id.as_str())?.as_str(),
+            )
+            .with_attr("in_response_to", self.in_response_to.as_str())
+            .with_attr(collection_name_attr(ver), self.collection_name.as_str());
+        if let Some(message) = &self.message {
+            element.push(Element::text_element(ver, "Message", message.as_str()));
+        }
+        for subscription in self.subscriptions.iter() {
+            element.push(subscription.to_element(ver)?);
+        }
+        Ok(element)
+    }
+}
+
+pub fn parse_subscription_management_response(
+    doc: &[u8],
+) -> Result<SubscriptionResponse, TaxiiError> {
+    from_xml(doc)
+}
+
+/// A parsed Subscription_Management_Request, as received by a collection management service, or
+/// the Manage_Feed_Subscription_Request of TAXII 1.0.
+#[derive(Clone, Debug)]
+pub struct SubscriptionRequest {
+    pub message_id: String,
+    pub action: SubscribeAction,
+    pub collection_name: String,
+    pub subscription_id: Option<String>,
+    pub subscription_parameters: Option<SubscriptionParameters>,
+    pub push_parameters: Option<PushParameters>,
+}
+
+impl SubscriptionRequest {
+    pub fn new_empty() -> SubscriptionRequest {
+        SubscriptionRequest {
+            message_id: String::from(""),
+            action: SubscribeAction::Status,
+            collection_name: String::from(""),
+            subscription_id: None,
+            subscription_parameters: None,
+            push_parameters: None,
+        }
+    }
+}
+
+impl FromElement for SubscriptionRequest {
+    fn from_element(element: &Element) -> Result<SubscriptionRequest, TaxiiError> {
+        let ver = element.version();
+        element.expect(subscription_management_tag(ver, "Request").as_str())?;
+        element.check_elements(&[
+            "Subscription_ID",
+            "Subscription_Parameters",
+            "Push_Parameters",
+            "Delivery_Parameters",
+        ])?;
+        let (subscription_parameters, push_parameters) = read_parameters(element)?;
+        Ok(SubscriptionRequest {
+            message_id: String::from(element.attr("message_id").unwrap_or_default()),
+            action: SubscribeAction::parse(element.required_attr("action")?)?,
+            collection_name: String::from(
+                element.attr(collection_name_attr(ver)).unwrap_or_default(),
+            ),
+            subscription_id: element.element_text("Subscription_ID"),
+            subscription_parameters,
+            push_parameters,
+        })
+    }
+}
+
+impl ToElement for SubscriptionRequest {
+    fn to_element(&self, ver: Version) -> Result<Element, TaxiiError> {
+        if let (Version::V10, SubscribeAction::Pause | SubscribeAction::Resume) = (ver, self.action)
+        {
+            return Err(TaxiiError::unsupported(
+                ver,
+                format!("the {} action", self.action.to_str()).as_str(),
+            ));
+        }
+        let mut element = Element::new(ver, subscription_management_tag(ver, "Request").as_str())
+            .with_attr("action", self.action.to_str())
+            .with_attr(
+                "message_id",
+                message_id(ver, self.message_id.as_str())?.as_str(),
+            )
+            .with_attr(collection_name_attr(ver), self.collection_name.as_str());
+        if let Some(subscription_id) = &self.subscription_id {
+            element.push(Element::text_element(
+                ver,
+                "Subscription_ID",
+                subscription_id.as_str(),
+            ));
+        }
+        write_parameters(
+            ver,
+            &mut element,
+            self.subscription_parameters.as_ref(),
+            self.push_parameters.as_ref(),
+        )?;
+        Ok(element)
+    }
+}
+
+pub fn parse_subscription_management_request(
+    doc: &[u8],
+) -> Result<SubscriptionRequest, TaxiiError> {
+    from_xml(doc)
+}
+
+pub fn create_subscription_management_response_body(
+    ver: Version,
+    subscription_response: &SubscriptionResponse,
+) -> Result<String, TaxiiError> {
+    to_xml(ver, subscription_response)
 }
 
 #[cfg(test)]
@@ -528,11 +723,23 @@ mod tests {
     use crate::taxii::subscriptions::{ResponseType, SubscriptionStatus};
 
     use super::{
-        create_subscribe_request_body, parse_subscription_management_response, SubscribeAction,
-        Version,
+        create_subscribe_request_body, create_subscription_management_response_body,
+        parse_subscription_management_request, parse_subscription_management_response,
+        ContentBinding, PushParameters, SubscribeAction, SubscriptionParameters,
+        SubscriptionResponse, Version,
     };
     use std::{env, fs::read_to_string, path::Path};
 
+    fn read_response(name: &str) -> SubscriptionResponse {
+        let path = env::var("CARGO_MANIFEST_DIR").unwrap();
+        let path = Path::new(path.as_str()).join("test").join(name);
+        let doc = read_to_string(path).unwrap();
+        match parse_subscription_management_response(doc.as_bytes()) {
+            Ok(v) => v,
+            Err(err) => panic!("test failed: {}", err),
+        }
+    }
+
     #[test]
     fn test_create_subscribe_request_body() {
         let result = create_subscribe_request_body(
@@ -561,25 +768,103 @@ mod tests {
     }
 
     #[test]
-    fn test_parse_subscription_management_response_subscribe() {
-        let path = env::var("CARGO_MANIFEST_DIR").unwrap();
-        let path = Path::new(path.as_str())
-            .join("test/sample-subscription-management-response-subscribe.xml");
-        let doc = read_to_string(path).unwrap();
-        let subscription_response = match parse_subscription_management_response(doc.as_bytes()) {
-            Ok(v) => v,
-            Err(err) => panic!("test failed: {}", err),
+    fn test_create_subscribe_request_body_actions() {
+        for action in [
+            SubscribeAction::Unsubscribe,
+            SubscribeAction::Pause,
+            SubscribeAction::Resume,
+        ] {
+            let result = create_subscribe_request_body(
+                Version::V11,
+                action,
+                "collection-name-1",
+                None,
+                None,
+                None,
+            );
+            assert!(result.is_err());
+            let result = create_subscribe_request_body(
+                Version::V11,
+                action,
+                "collection-name-1",
+                Some("subscription-id-1"),
+                None,
+                None,
+            )
+            .unwrap();
+            assert!(result.contains(format!("action=\"{}\"", action.to_str()).as_str()));
+            assert!(result.contains("subscription-id-1</taxii_11:Subscription_ID>"));
+        }
+
+        // STATUS without a subscription ID asks for all the subscriptions on the collection
+        let result = create_subscribe_request_body(
+            Version::V11,
+            SubscribeAction::Status,
+            "collection-name-1",
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        assert!(!result.contains("Subscription_ID"));
+
+        let push_parameters = PushParameters {
+            protocol_binding: String::from("urn:taxii.mitre.org:protocol:https:1.0"),
+            address: String::from("https://inbox.example.com/services/inbox"),
+            message_binding: String::from("urn:taxii.mitre.org:message:xml:1.1"),
         };
+        let result = create_subscribe_request_body(
+            Version::V11,
+            SubscribeAction::Subscribe,
+            "collection-name-1",
+            None,
+            None,
+            Some(&push_parameters),
+        )
+        .unwrap();
+        assert!(result.contains(
+            "<taxii_11:Address>https://inbox.example.com/services/inbox</taxii_11:Address>"
+        ));
+    }
+
+    #[test]
+    fn test_create_subscribe_request_body_subtype() {
+        let subscription_parameters = SubscriptionParameters {
+            response_type: ResponseType::Full,
+            content_bindings: vec![ContentBinding {
+                binding_id: String::from("urn:stix.mitre.org:xml:1.1.1"),
+                subtype_id: Some(String::from("indicators")),
+            }],
+            query: None,
+            query_format_id: None,
+        };
+        let result = create_subscribe_request_body(
+            Version::V11,
+            SubscribeAction::Subscribe,
+            "collection-name-1",
+            None,
+            Some(&subscription_parameters),
+            None,
+        )
+        .unwrap();
+        assert!(result.contains("<taxii_11:Subtype subtype_id=\"indicators\" />"));
+    }
+
+    #[test]
+    fn test_parse_subscription_management_response_subscribe() {
+        let subscription_response =
+            read_response("sample-subscription-management-response-subscribe.xml");
         assert_eq!("3326595023702419548", subscription_response.message_id);
         assert_eq!(
             "ec5e5744-5b91-4533-adbc-be2d1a1cf160",
             subscription_response.in_response_to
         );
-        let sub = subscription_response.subscription;
-        assert_eq!("stix-data", sub.collection_name);
+        assert_eq!("stix-data", subscription_response.collection_name);
+        assert_eq!(1, subscription_response.subscriptions.len());
+        let sub = &subscription_response.subscriptions[0];
         assert_eq!(SubscriptionStatus::Active, sub.status);
         assert_eq!("8954140241256270840", sub.id);
-        assert_eq!(ResponseType::Full, sub.response_type);
+        assert_eq!(ResponseType::Full, sub.response_type());
         assert_eq!(2, sub.poll_instances.len());
         assert_eq!(
             "urn:taxii.mitre.org:protocol:https:1.0",
@@ -618,24 +903,19 @@ mod tests {
 
     #[test]
     fn test_parse_subscription_management_response_unsubscribe() {
-        let path = env::var("CARGO_MANIFEST_DIR").unwrap();
-        let path = Path::new(path.as_str())
-            .join("test/sample-subscription-management-response-unsubscribe.xml");
-        let doc = read_to_string(path).unwrap();
-        let subscription_response = match parse_subscription_management_response(doc.as_bytes()) {
-            Ok(v) => v,
-            Err(err) => panic!("test failed: {}", err),
-        };
+        let subscription_response =
+            read_response("sample-subscription-management-response-unsubscribe.xml");
         assert_eq!("3214749113040463214", subscription_response.message_id);
         assert_eq!(
             "3135d61d-d990-4706-b394-9b441d4f2d3f",
             subscription_response.in_response_to
         );
-        let sub = subscription_response.subscription;
-        assert_eq!("stix-data", sub.collection_name);
+        assert_eq!("stix-data", subscription_response.collection_name);
+        assert_eq!(1, subscription_response.subscriptions.len());
+        let sub = &subscription_response.subscriptions[0];
         assert_eq!(SubscriptionStatus::Unsubscribed, sub.status);
         assert_eq!("8954140241256270840", sub.id);
-        assert_eq!(ResponseType::Full, sub.response_type);
+        assert_eq!(ResponseType::Full, sub.response_type());
         assert_eq!(2, sub.poll_instances.len());
         assert_eq!(
             "urn:taxii.mitre.org:protocol:https:1.0",
@@ -671,4 +951,172 @@ mod tests {
             sub.poll_instances[1].message_bindings[1]
         );
     }
+
+    #[test]
+    fn test_parse_subscription_management_response_status() {
+        let subscription_response =
+            read_response("sample-subscription-management-response-status.xml");
+        assert_eq!("stix-data", subscription_response.collection_name);
+        assert_eq!(
+            Some(String::from("2 subscriptions")),
+            subscription_response.message
+        );
+        assert_eq!(2, subscription_response.subscriptions.len());
+
+        let sub = subscription_response
+            .find_subscription("8954140241256270840")
+            .unwrap();
+        assert_eq!(SubscriptionStatus::Active, sub.status);
+        let params = sub.subscription_parameters.as_ref().unwrap();
+        assert_eq!(ResponseType::Full, params.response_type);
+        assert_eq!(2, params.content_bindings.len());
+        assert_eq!(
+            "urn:stix.mitre.org:xml:1.1.1",
+            params.content_bindings[0].binding_id
+        );
+        assert_eq!(None, params.content_bindings[0].subtype_id);
+        assert_eq!(
+            Some(String::from("indicators")),
+            params.content_bindings[1].subtype_id
+        );
+        let push = sub.push_parameters.as_ref().unwrap();
+        assert_eq!(
+            "urn:taxii.mitre.org:protocol:https:1.0",
+            push.protocol_binding
+        );
+        assert_eq!("https://inbox.example.com/services/inbox", push.address);
+        assert_eq!("urn:taxii.mitre.org:message:xml:1.1", push.message_binding);
+        assert_eq!(1, sub.poll_instances.len());
+        assert_eq!(
+            vec![String::from("urn:taxii.mitre.org:message:xml:1.1")],
+            sub.poll_instances[0].message_bindings
+        );
+
+        let sub = subscription_response
+            .find_subscription("1203718260549728154")
+            .unwrap();
+        assert_eq!(SubscriptionStatus::Paused, sub.status);
+        assert_eq!(ResponseType::CountOnly, sub.response_type());
+        let params = sub.subscription_parameters.as_ref().unwrap();
+        assert_eq!(
+            Some(String::from("urn:taxii.mitre.org:query:default:1.0")),
+            params.query_format_id
+        );
+        assert!(sub.push_parameters.is_none());
+        assert!(sub.poll_instances.is_empty());
+
+        assert!(subscription_response.find_subscription("0").is_none());
+    }
+
+    #[test]
+    fn test_subscription_management_round_trip() {
+        let subscription_parameters = SubscriptionParameters {
+            response_type: ResponseType::CountOnly,
+            content_bindings: vec![ContentBinding {
+                binding_id: String::from("urn:stix.mitre.org:xml:1.1.1"),
+                subtype_id: None,
+            }],
+            query: None,
+            query_format_id: None,
+        };
+        let request = create_subscribe_request_body(
+            Version::V11,
+            SubscribeAction::Subscribe,
+            "collection-name-1",
+            None,
+            Some(&subscription_parameters),
+            None,
+        )
+        .unwrap();
+        let subscription_request =
+            parse_subscription_management_request(request.as_bytes()).unwrap();
+        assert_eq!(SubscribeAction::Subscribe, subscription_request.action);
+        assert_eq!("collection-name-1", subscription_request.collection_name);
+        assert_eq!(None, subscription_request.subscription_id);
+        let v = subscription_request.subscription_parameters.unwrap();
+        assert_eq!(ResponseType::CountOnly, v.response_type);
+        assert_eq!(subscription_parameters.content_bindings, v.content_bindings);
+
+        let subscription_response =
+            read_response("sample-subscription-management-response-status.xml");
+        let response =
+            create_subscription_management_response_body(Version::V11, &subscription_response)
+                .unwrap();
+        let round_trip = parse_subscription_management_response(response.as_bytes()).unwrap();
+        assert_eq!(subscription_response.message_id, round_trip.message_id);
+        assert_eq!(
+            subscription_response.collection_name,
+            round_trip.collection_name
+        );
+        assert_eq!(2, round_trip.subscriptions.len());
+        let subscription = round_trip.find_subscription("1203718260549728154").unwrap();
+        assert_eq!(SubscriptionStatus::Paused, subscription.status);
+        assert_eq!(ResponseType::CountOnly, subscription.response_type());
+        let subscription = round_trip.find_subscription("8954140241256270840").unwrap();
+        assert_eq!(
+            "https://inbox.example.com/services/inbox",
+            subscription.push_parameters.as_ref().unwrap().address
+        );
+    }
+
+    #[test]
+    fn test_subscription_management_10() {
+        let subscription_response =
+            read_response("sample-10-manage-feed-subscription-response.xml");
+        assert_eq!("stix-data", subscription_response.collection_name);
+        let sub = &subscription_response.subscriptions[0];
+        assert_eq!(SubscriptionStatus::Active, sub.status);
+        assert_eq!("2214372936", sub.id);
+        assert_eq!(
+            "https://partner.example.com/services/inbox",
+            sub.push_parameters.as_ref().unwrap().address
+        );
+        assert_eq!(
+            "urn:stix.mitre.org:xml:1.0.1",
+            sub.subscription_parameters
+                .as_ref()
+                .unwrap()
+                .content_bindings[0]
+                .binding_id
+        );
+        assert_eq!(1, sub.poll_instances.len());
+
+        let push_parameters = sub.push_parameters.clone().unwrap();
+        let doc = create_subscribe_request_body(
+            Version::V10,
+            SubscribeAction::Subscribe,
+            "stix-data",
+            None,
+            sub.subscription_parameters.as_ref(),
+            Some(&push_parameters),
+        )
+        .unwrap();
+        assert!(doc.starts_with("<taxii:Manage_Feed_Subscription_Request"));
+        assert!(doc.contains("feed_name=\"stix-data\""));
+        let subscription_request = parse_subscription_management_request(doc.as_bytes()).unwrap();
+        assert_eq!("stix-data", subscription_request.collection_name);
+        assert_eq!(
+            push_parameters.address,
+            subscription_request.push_parameters.unwrap().address
+        );
+        assert_eq!(
+            "urn:stix.mitre.org:xml:1.0.1",
+            subscription_request
+                .subscription_parameters
+                .unwrap()
+                .content_bindings[0]
+                .binding_id
+        );
+
+        // TAXII 1.0 subscriptions cannot be paused
+        assert!(create_subscribe_request_body(
+            Version::V10,
+            SubscribeAction::Pause,
+            "stix-data",
+            Some("2214372936"),
+            None,
+            None,
+        )
+        .is_err());
+    }
 }
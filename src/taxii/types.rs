@@ -1,10 +1,83 @@
-use super::errors::MyError;
+use super::{
+    errors::TaxiiError,
+    message::{parse_bool, parse_number, Element, FromElement, ToElement},
+    version::Version,
+};
 
+/// A Content_Binding. TAXII 1.0 only has the binding id, as the text of the element, where 1.1
+/// has it as an attribute with an optional subtype.
+#[derive(Clone, Debug, PartialEq)]
 pub struct ContentBinding {
     pub binding_id: String,
     pub subtype_id: Option<String>,
 }
 
+impl FromElement for ContentBinding {
+    fn from_element(element: &Element) -> Result<ContentBinding, TaxiiError> {
+        element.expect("Content_Binding")?;
+        if element.version() == Version::V10 {
+            return Ok(ContentBinding {
+                binding_id: element.text(),
+                subtype_id: None,
+            });
+        }
+        element.check_elements(&["Subtype"])?;
+        let subtype_id = match element.element("Subtype") {
+            Some(v) => Some(String::from(v.required_attr("subtype_id")?)),
+            None => None,
+        };
+        Ok(ContentBinding {
+            binding_id: String::from(element.required_attr("binding_id")?),
+            subtype_id,
+        })
+    }
+}
+
+impl ToElement for ContentBinding {
+    fn to_element(&self, ver: Version) -> Result<Element, TaxiiError> {
+        if ver == Version::V10 {
+            return Ok(Element::text_element(
+                ver,
+                "Content_Binding",
+                self.binding_id.as_str(),
+            ));
+        }
+        let mut element =
+            Element::new(ver, "Content_Binding").with_attr("binding_id", self.binding_id.as_str());
+        if let Some(subtype_id) = &self.subtype_id {
+            element.push(Element::new(ver, "Subtype").with_attr("subtype_id", subtype_id.as_str()));
+        }
+        Ok(element)
+    }
+}
+
+/// The number of records in a collection that match a request. `partial_count` is set when the
+/// number is a lower bound.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordCount {
+    pub count: u64,
+    pub partial_count: bool,
+}
+
+impl FromElement for RecordCount {
+    fn from_element(element: &Element) -> Result<RecordCount, TaxiiError> {
+        element.expect("Record_Count")?;
+        Ok(RecordCount {
+            count: parse_number("Record_Count", element.text().as_str())?,
+            partial_count: element.attr("partial_count").is_some_and(parse_bool),
+        })
+    }
+}
+
+impl ToElement for RecordCount {
+    fn to_element(&self, ver: Version) -> Result<Element, TaxiiError> {
+        Ok(
+            Element::text_element(ver, "Record_Count", self.count.to_string().as_str())
+                .with_attr("partial_count", self.partial_count.to_string().as_str()),
+        )
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum ResponseType {
     Full,
@@ -12,11 +85,14 @@ pub enum ResponseType {
 }
 
 impl ResponseType {
-    pub fn parse(v: &str) -> Result<ResponseType, MyError> {
+    pub fn parse(v: &str) -> Result<ResponseType, TaxiiError> {
         match v {
             "FULL" => Ok(ResponseType::Full),
             "COUNT_ONLY" => Ok(ResponseType::CountOnly),
-            _ => Err(MyError(format!("could not parse response type: {}", v))),
+            _ => Err(TaxiiError::Protocol(format!(
+                "could not parse response type: {}",
+                v
+            ))),
         }
     }
     pub fn to_str(&self) -> &str {
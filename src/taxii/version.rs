@@ -1,11 +1,16 @@
 use rand::prelude::*;
-use reqwest;
+use reqwest::{
+    self,
+    header::{HeaderMap, HeaderValue, ACCEPT, CONTENT_TYPE, USER_AGENT},
+};
+use tracing::warn;
 use uuid::Uuid;
-use xml::writer::{EmitterConfig, EventWriter, XmlEvent};
+use xml::reader::{self, EventReader};
 
-use crate::taxii::files::write_cache_file_with_filestamp;
-
-use super::errors::MyError;
+use super::{
+    archive::Archive, collections::CollectionInformationRequest, errors::TaxiiError,
+    message::to_xml, services::DiscoveryRequest,
+};
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Version {
@@ -14,178 +19,292 @@ pub enum Version {
     V21,
 }
 
-static NAMESPACE_10: &'static str = "http://taxii.mitre.org/messages/taxii_xml_binding-1";
-static NAMESPACE_11: &'static str = "http://taxii.mitre.org/messages/taxii_xml_binding-1.1";
+static NAMESPACE_10: &str = "http://taxii.mitre.org/messages/taxii_xml_binding-1";
+static NAMESPACE_11: &str = "http://taxii.mitre.org/messages/taxii_xml_binding-1.1";
 
-// TODO: CONTENT_TYPE_10?
-static CONTENT_TYPE_11: &'static str = "application/xml";
-static CONTENT_TYPE_21: &'static str = "application/taxii+json;version=2.1";
+// The HTTP binding is the same for the TAXII 1.0 and 1.1 XML message bindings
+static CONTENT_TYPE_11: &str = "application/xml";
+static CONTENT_TYPE_21: &str = "application/taxii+json;version=2.1";
 
 // Version URN for the TAXII Services Specification 1.0
-static SERVICES_VERSION_URN_10: &'static str = "urn:taxii.mitre.org:services:1.0";
+static SERVICES_VERSION_URN_10: &str = "urn:taxii.mitre.org:services:1.0";
 // Version URN for the TAXII XML Message Binding Specification 1.0
-static XML_BINDING_VERSION_URN_10: &'static str = "urn:taxii.mitre.org:message:xml:1.0";
+static XML_BINDING_VERSION_URN_10: &str = "urn:taxii.mitre.org:message:xml:1.0";
 
 // Version URN for the TAXII Services Specification 1.1
-static SERVICES_VERSION_URN_11: &'static str = "urn:taxii.mitre.org:services:1.1";
+static SERVICES_VERSION_URN_11: &str = "urn:taxii.mitre.org:services:1.1";
 // Version URN for the TAXII XML Message Binding Specification 1.1
-static XML_BINDING_VERSION_URN_11: &'static str = "urn:taxii.mitre.org:message:xml:1.1";
+static XML_BINDING_VERSION_URN_11: &str = "urn:taxii.mitre.org:message:xml:1.1";
 
 // Version URN for the TAXII HTTP Protocol Binding Specification 1.0
 // Note: not HTTP/1.0, but the 1.0 version of the TAXII binding to HTTP
-static XML_BINDING_HTTP_10: &'static str = "urn:taxii.mitre.org:protocol:http:1.0";
+pub static XML_BINDING_HTTP_10: &str = "urn:taxii.mitre.org:protocol:http:1.0";
 
 // Version URN for the TAXII HTTPS Protocol Binding Specification 1.0
 // Note: not HTTP/1.0, but the 1.0 version of the TAXII binding to HTTPS
-static XML_BINDING_HTTPS_10: &'static str = "urn:taxii.mitre.org:protocol:https:1.0";
+pub static XML_BINDING_HTTPS_10: &str = "urn:taxii.mitre.org:protocol:https:1.0";
 
-static DEFAULT_TAXII_PROTOCOL_URN: &'static str = "urn:taxii.mitre.org:protocol:http:1.0";
-static DEFAULT_TAXII_SERVICES_URN: &'static str = "urn:taxii.mitre.org:services:1.1";
+static DEFAULT_TAXII_PROTOCOL_URN: &str = "urn:taxii.mitre.org:protocol:http:1.0";
 
 impl Version {
-    pub fn xml_namespace(&self) -> &str {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            Version::V10 => "1.0",
+            Version::V11 => "1.1",
+            Version::V21 => "2.1",
+        }
+    }
+    /// The version of the XML binding with the namespace, if any.
+    pub fn from_xml_namespace(namespace: &str) -> Option<Version> {
+        match namespace {
+            v if v == NAMESPACE_10 => Some(Version::V10),
+            v if v == NAMESPACE_11 => Some(Version::V11),
+            _ => None,
+        }
+    }
+    pub fn xml_namespace(&self) -> Result<&'static str, TaxiiError> {
+        match self {
+            Version::V10 => Ok(NAMESPACE_10),
+            Version::V11 => Ok(NAMESPACE_11),
+            _ => Err(TaxiiError::unsupported(*self, "XML messages")),
+        }
+    }
+    /// The prefix the namespace of the XML binding is written with.
+    pub fn xml_prefix(&self) -> Result<&'static str, TaxiiError> {
+        match self {
+            Version::V10 => Ok("taxii"),
+            Version::V11 => Ok("taxii_11"),
+            _ => Err(TaxiiError::unsupported(*self, "XML messages")),
+        }
+    }
+    pub fn xml_binding_urn(&self) -> Result<&'static str, TaxiiError> {
         match self {
-            Version::V10 => NAMESPACE_10,
-            Version::V11 => NAMESPACE_11,
-            _ => panic!("TODO: version does not support XML"),
+            Version::V10 => Ok(XML_BINDING_VERSION_URN_10),
+            Version::V11 => Ok(XML_BINDING_VERSION_URN_11),
+            _ => Err(TaxiiError::unsupported(*self, "XML messages")),
         }
     }
-    pub fn xml_binding_urn(&self) -> &str {
+    pub fn services_version_urn(&self) -> Result<&'static str, TaxiiError> {
         match self {
-            Version::V10 => XML_BINDING_VERSION_URN_10,
-            Version::V11 => XML_BINDING_VERSION_URN_11,
-            _ => panic!("TODO: version does not support XML"),
+            Version::V10 => Ok(SERVICES_VERSION_URN_10),
+            Version::V11 => Ok(SERVICES_VERSION_URN_11),
+            _ => Err(TaxiiError::unsupported(*self, "XML messages")),
         }
     }
-    pub fn content_type(&self) -> &str {
+    pub fn content_type(&self) -> &'static str {
         match self {
-            Version::V10 => panic!("TODO"),
-            Version::V11 => CONTENT_TYPE_11,
+            Version::V10 | Version::V11 => CONTENT_TYPE_11,
             Version::V21 => CONTENT_TYPE_21,
         }
     }
-    pub fn message_id(&self) -> String {
+    pub fn message_id(&self) -> Result<String, TaxiiError> {
         match self {
             Version::V10 => {
                 // TODO: is this expensive to create?
                 let mut rng = thread_rng();
                 let v: u64 = rng.gen();
-                return v.to_string();
+                Ok(v.to_string())
             }
             // TODO: the taxiistand example server uses what looks like a numeric representation
             // of a UUID. Should we?
             Version::V11 => {
                 let id = Uuid::new_v4();
-                return id.to_string();
+                Ok(id.to_string())
             }
-            _ => panic!("TODO: does V21 use message IDs?"),
+            // TAXII 2.1 requests are not messages and have no IDs
+            Version::V21 => Err(TaxiiError::unsupported(*self, "message IDs")),
         }
     }
 }
 
-pub fn write_xml<'a, E>(writer: &mut EventWriter<&mut Vec<u8>>, event: E) -> Result<(), MyError>
-where
-    E: Into<XmlEvent<'a>>,
-{
-    match writer.write(event) {
-        Ok(_) => Ok(()),
-        Err(err) => return Err(MyError(err.to_string())),
-    }
+pub fn create_discovery_request_body(ver: Version) -> Result<String, TaxiiError> {
+    let discovery_request = DiscoveryRequest {
+        message_id: String::from(""),
+    };
+    to_xml(ver, &discovery_request)
 }
 
-pub fn write_xml_tag_with_data(
-    writer: &mut EventWriter<&mut Vec<u8>>,
-    tag: &str,
-    data: &str,
-) -> Result<(), MyError> {
-    // TODO: escape tag, or test if it has bad characters
-    write_xml(writer, XmlEvent::start_element(tag))?;
-    write_xml(writer, XmlEvent::characters(data))?;
-    write_xml(writer, XmlEvent::end_element())?;
-    Ok(())
+pub fn create_collection_information_request_body(ver: Version) -> Result<String, TaxiiError> {
+    let collection_information_request = CollectionInformationRequest {
+        message_id: String::from(""),
+    };
+    to_xml(ver, &collection_information_request)
 }
 
-fn create_simple_request_body(tag: &str, ver: Version) -> Result<String, MyError> {
-    let mut buf_writer: Vec<u8> = Vec::with_capacity(128);
-    let mut writer = EmitterConfig::new()
-        .write_document_declaration(false)
-        .perform_indent(true)
-        .create_writer(&mut buf_writer);
-    let msg_id = ver.message_id();
-    let tag = format!("taxii_11:{}", tag);
-    let elem = XmlEvent::start_element(tag.as_str())
-        .attr("message_id", msg_id.as_str())
-        .ns("taxii_11", ver.xml_namespace());
-    match writer.write(elem) {
-        Ok(_) => (),
-        Err(err) => return Err(MyError(err.to_string())),
-    }
-    let end_elem = XmlEvent::end_element();
-    match writer.write(end_elem) {
-        Ok(_) => (),
-        Err(err) => return Err(MyError(err.to_string())),
+/// The tag and message_id of the root element of a message, e.g. for a service to dispatch on
+/// the message type before parsing the rest.
+pub fn parse_message_root(doc: &[u8]) -> Result<(String, String), TaxiiError> {
+    for e in EventReader::new(doc) {
+        match e {
+            Ok(reader::XmlEvent::StartElement {
+                name, attributes, ..
+            }) => {
+                let message_id = attributes
+                    .iter()
+                    .find(|a| a.name.local_name == "message_id")
+                    .map(|a| a.value.clone())
+                    .unwrap_or_default();
+                return Ok((name.local_name, message_id));
+            }
+            Ok(_) => (),
+            Err(err) => return Err(TaxiiError::Xml(err.to_string())),
+        }
     }
-    // TODO: better check on conversion than unwrap
-    return Ok(String::from_utf8(buf_writer).unwrap());
-}
-
-pub fn create_discovery_request_body(ver: Version) -> Result<String, MyError> {
-    create_simple_request_body("Discovery_Request", ver)
-}
-
-pub fn create_collection_information_request_body(ver: Version) -> Result<String, MyError> {
-    create_simple_request_body("Collection_Information_Request", ver)
+    Err(TaxiiError::Xml("missing root element".to_string()))
 }
 
 // TODO: the generic XML document defclaration fails when talking to test.taxiistand.com -- is
 // that the typical behaviour for other TAXII servers?
 
-pub fn taxii_request(url: &str, username: &str, password: &str, request_body: &str, ver: Version) {
+/// The HTTP headers required by the TAXII HTTP protocol binding, shared by the blocking and async
+/// clients.
+pub fn taxii_headers(ver: Version) -> Result<HeaderMap, TaxiiError> {
+    let xml_binding_urn = HeaderValue::from_static(ver.xml_binding_urn()?);
+    let content_type = HeaderValue::from_static(ver.content_type());
+    let mut headers = HeaderMap::new();
+    headers.insert(ACCEPT, content_type.clone());
+    headers.insert(CONTENT_TYPE, content_type);
+    // TODO: default and make configurable in the library
+    headers.insert(
+        USER_AGENT,
+        HeaderValue::from_static("github.com/mthurst0/stix-rust"),
+    );
+    headers.insert("X-TAXII-Accept", xml_binding_urn.clone());
+    headers.insert("X-TAXII-Content-Type", xml_binding_urn);
+    headers.insert(
+        "X-TAXII-Protocol",
+        HeaderValue::from_static(DEFAULT_TAXII_PROTOCOL_URN),
+    );
+    headers.insert(
+        "X-TAXII-Services",
+        HeaderValue::from_static(ver.services_version_urn()?),
+    );
+    Ok(headers)
+}
+
+/// POST a TAXII message with the blocking client and return the response body.
+pub fn taxii_request(
+    url: &str,
+    username: &str,
+    password: &str,
+    request_body: &str,
+    ver: Version,
+) -> Result<String, TaxiiError> {
     let client = reqwest::blocking::Client::new();
-    let xml_binding_urn = ver.xml_binding_urn();
-    println!("TODO-request_body={}", request_body);
     let request = match client
         .post(url)
         .basic_auth(username, Some(password))
         .body(String::from(request_body))
-        .header("Accept", ver.content_type())
-        .header("Content-Type", ver.content_type())
-        // TODO: default and make configurable in the library
-        .header("User-Agent", "github.com/mthurst0/stix-rust")
-        .header("X-TAXII-Accept", xml_binding_urn)
-        .header("X-TAXII-Content-Type", xml_binding_urn)
-        .header("X-TAXII-Protocol", DEFAULT_TAXII_PROTOCOL_URN)
-        .header("X-TAXII-Services", DEFAULT_TAXII_SERVICES_URN)
+        .headers(taxii_headers(ver)?)
         .build()
     {
-        Ok(req) => {
-            println!("{:?}", req);
-            req
-        }
-        Err(err) => panic!("{}", err),
+        Ok(v) => v,
+        Err(err) => return Err(TaxiiError::Transport(err.to_string())),
     };
-    match client.execute(request) {
-        Ok(resp) => {
-            println!("resp={:?}", resp);
-            let response_body = resp.text().unwrap();
-            println!("body={}", response_body);
-            _ = write_cache_file_with_filestamp("taxii-response", response_body.as_str());
+    let response = match client.execute(request) {
+        Ok(v) => v,
+        Err(err) => return Err(TaxiiError::Transport(err.to_string())),
+    };
+    let status = response.status();
+    let response_body = match response.text() {
+        Ok(v) => v,
+        Err(err) => return Err(TaxiiError::Transport(err.to_string())),
+    };
+    if let Some(archive) = Archive::from_env() {
+        if let Err(err) = archive.store(url, ver, request_body, status.as_u16(), &response_body) {
+            warn!("could not archive response: {}", err);
         }
-        Err(err) => panic!("{}", err),
     }
+    if !status.is_success() {
+        return Err(TaxiiError::HttpStatus(status.as_u16()));
+    }
+    Ok(response_body)
 }
 
-pub fn discovery_request(url: &str, username: &str, password: &str, ver: Version) {
-    match create_discovery_request_body(ver) {
-        Ok(request_body) => taxii_request(url, username, password, request_body.as_str(), ver),
-        Err(err) => panic!("{}", err),
-    };
+pub fn discovery_request(
+    url: &str,
+    username: &str,
+    password: &str,
+    ver: Version,
+) -> Result<String, TaxiiError> {
+    let request_body = create_discovery_request_body(ver)?;
+    taxii_request(url, username, password, request_body.as_str(), ver)
 }
 
 // TODO: the request mechanism doesn't really belong in the "version" namespace
-pub fn collection_information_request(url: &str, username: &str, password: &str, ver: Version) {
-    match create_collection_information_request_body(ver) {
-        Ok(request_body) => taxii_request(url, username, password, request_body.as_str(), ver),
-        Err(err) => panic!("{}", err),
+pub fn collection_information_request(
+    url: &str,
+    username: &str,
+    password: &str,
+    ver: Version,
+) -> Result<String, TaxiiError> {
+    let request_body = create_collection_information_request_body(ver)?;
+    taxii_request(url, username, password, request_body.as_str(), ver)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        create_discovery_request_body, discovery_request, parse_message_root, taxii_headers,
+        Version,
     };
+    use crate::taxii::errors::TaxiiError;
+
+    #[test]
+    fn test_unsupported_version() {
+        let unsupported = |result: Result<&str, TaxiiError>| {
+            matches!(
+                result,
+                Err(TaxiiError::UnsupportedVersion {
+                    version: Version::V21,
+                    ..
+                })
+            )
+        };
+        assert!(unsupported(Version::V21.xml_namespace()));
+        assert!(unsupported(Version::V21.xml_prefix()));
+        assert!(unsupported(Version::V21.xml_binding_urn()));
+        assert!(unsupported(Version::V21.services_version_urn()));
+        assert!(Version::V21.message_id().is_err());
+        assert!(taxii_headers(Version::V21).is_err());
+        match create_discovery_request_body(Version::V21) {
+            Err(err) => assert_eq!("TAXII 2.1 does not support XML messages", err.to_string()),
+            Ok(v) => panic!("expected an unsupported version error: {}", v),
+        }
+
+        assert_eq!("application/xml", Version::V10.content_type());
+        let headers = taxii_headers(Version::V10).unwrap();
+        assert_eq!(
+            "urn:taxii.mitre.org:services:1.0",
+            headers.get("X-TAXII-Services").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_message_root_errors() {
+        assert!(matches!(parse_message_root(b""), Err(TaxiiError::Xml(_))));
+        assert!(matches!(
+            parse_message_root(b"<Discovery_Request message_id=1/>"),
+            Err(TaxiiError::Xml(_))
+        ));
+    }
+
+    #[test]
+    fn test_request_errors() {
+        // nothing listens on port 1
+        let result = discovery_request(
+            "http://127.0.0.1:1/services/discovery",
+            "guest",
+            "guest",
+            Version::V11,
+        );
+        assert!(matches!(result, Err(TaxiiError::Transport(_))));
+        let result = discovery_request(
+            "http://127.0.0.1:1/services/discovery",
+            "guest",
+            "guest",
+            Version::V21,
+        );
+        assert!(matches!(result, Err(TaxiiError::UnsupportedVersion { .. })));
+    }
 }
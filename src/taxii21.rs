@@ -1,5 +1,17 @@
-mod backend;
-mod errors;
-mod file_backend;
+pub mod acl;
+pub mod api_keys;
+pub mod auth;
+pub mod backend;
+pub mod client;
+pub mod errors;
+pub mod file_backend;
+pub mod media_type;
+pub mod memory_backend;
+pub mod metrics;
 mod middleware;
+pub mod rate_limit;
+pub mod replicator;
 pub mod server;
+#[cfg(test)]
+mod test_utils;
+pub mod tls;
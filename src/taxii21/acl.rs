@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+
+use super::{api_keys::Access, auth::Principal, errors::MyError};
+
+/// Who an entry applies to, written as `*` for every authenticated principal, `anonymous` for
+/// unauthenticated requests, or the name of a principal.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(from = "String", into = "String")]
+pub enum AclPrincipal {
+    Any,
+    Anonymous,
+    Named(String),
+}
+
+impl AclPrincipal {
+    fn matches(&self, principal: &Option<Principal>) -> bool {
+        match (self, principal) {
+            (AclPrincipal::Any, Some(_)) => true,
+            (AclPrincipal::Anonymous, None) => true,
+            (AclPrincipal::Named(name), Some(v)) => *name == v.username,
+            _ => false,
+        }
+    }
+}
+
+impl From<String> for AclPrincipal {
+    fn from(v: String) -> Self {
+        match v.as_str() {
+            "*" => AclPrincipal::Any,
+            "anonymous" => AclPrincipal::Anonymous,
+            _ => AclPrincipal::Named(v),
+        }
+    }
+}
+
+impl From<AclPrincipal> for String {
+    fn from(v: AclPrincipal) -> Self {
+        v.to_string()
+    }
+}
+
+impl std::fmt::Display for AclPrincipal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AclPrincipal::Any => write!(f, "*"),
+            AclPrincipal::Anonymous => write!(f, "anonymous"),
+            AclPrincipal::Named(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Who may read or write which collections, e.g.
+/// ```toml
+/// [[acl]]
+/// principal = "alice"
+/// api_root = "api1"
+/// collection = "collection1"
+/// permissions = ["read", "write"]
+/// ```
+/// An entry without `collection` applies to all collections of the api root.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AclEntry {
+    pub principal: AclPrincipal,
+    pub api_root: String,
+    pub collection: Option<String>,
+    pub permissions: Vec<String>,
+}
+
+impl AclEntry {
+    fn matches(&self, principal: &Option<Principal>, api_root: &str, collection_id: &str) -> bool {
+        self.principal.matches(principal)
+            && self.api_root == api_root
+            && self.collection.as_ref().is_none_or(|v| v == collection_id)
+    }
+}
+
+/// The access control list of the collections: access not granted by an entry is denied.
+#[derive(Clone, Debug, Default)]
+pub struct Acl {
+    entries: Vec<(AclEntry, Vec<Access>)>,
+}
+
+impl Acl {
+    pub fn new(entries: &[AclEntry]) -> Result<Acl, MyError> {
+        let mut acl = Acl::default();
+        for entry in entries.iter() {
+            let mut permissions = Vec::<Access>::new();
+            for v in entry.permissions.iter() {
+                match Access::parse(v) {
+                    Some(v) => permissions.push(v),
+                    None => {
+                        return Err(MyError(format!(
+                            "bad permission {:?} for {}, expected read or write",
+                            v, entry.principal
+                        )))
+                    }
+                }
+            }
+            acl.entries.push((entry.clone(), permissions));
+        }
+        Ok(acl)
+    }
+
+    pub fn allows(
+        &self,
+        principal: &Option<Principal>,
+        access: Access,
+        api_root: &str,
+        collection_id: &str,
+    ) -> bool {
+        self.entries.iter().any(|(entry, permissions)| {
+            permissions.contains(&access) && entry.matches(principal, api_root, collection_id)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Acl, AclEntry, AclPrincipal};
+    use crate::taxii21::{api_keys::Access, auth::Principal};
+
+    #[test]
+    fn test_acl() {
+        let cfg = r#"
+[[acl]]
+principal = "alice"
+api_root = "api1"
+permissions = ["read", "write"]
+
+[[acl]]
+principal = "*"
+api_root = "api1"
+collection = "public"
+permissions = ["read"]
+
+[[acl]]
+principal = "anonymous"
+api_root = "api2"
+permissions = ["read"]
+"#;
+        #[derive(serde::Deserialize)]
+        struct Config {
+            acl: Vec<AclEntry>,
+        }
+        let cfg: Config = toml::from_str(cfg).unwrap();
+        assert_eq!(
+            AclPrincipal::Named(String::from("alice")),
+            cfg.acl[0].principal
+        );
+        assert_eq!(AclPrincipal::Any, cfg.acl[1].principal);
+        assert_eq!(AclPrincipal::Anonymous, cfg.acl[2].principal);
+        let acl = Acl::new(&cfg.acl).unwrap();
+        let alice = Some(Principal {
+            username: String::from("alice"),
+            scopes: None,
+        });
+        let bob = Some(Principal {
+            username: String::from("bob"),
+            scopes: None,
+        });
+        assert!(acl.allows(&alice, Access::Write, "api1", "private"));
+        assert!(!acl.allows(&alice, Access::Read, "api2", "private"));
+        assert!(acl.allows(&bob, Access::Read, "api1", "public"));
+        assert!(!acl.allows(&bob, Access::Write, "api1", "public"));
+        assert!(!acl.allows(&bob, Access::Read, "api1", "private"));
+        assert!(!acl.allows(&None, Access::Read, "api1", "public"));
+        assert!(acl.allows(&None, Access::Read, "api2", "private"));
+        assert!(!acl.allows(&bob, Access::Read, "api2", "private"));
+        // the entry for anonymous requests does not apply to a principal of that name
+        let anonymous = Some(Principal {
+            username: String::from("anonymous"),
+            scopes: None,
+        });
+        assert!(!acl.allows(&anonymous, Access::Read, "api2", "private"));
+
+        let mut entries = cfg.acl.clone();
+        entries[0].permissions.push(String::from("admin"));
+        assert!(Acl::new(&entries).is_err());
+    }
+}
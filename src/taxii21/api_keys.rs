@@ -0,0 +1,443 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use tracing::{info, warn};
+
+use super::{auth::Principal, errors::MyError};
+use crate::taxii::files::write_file_atomically;
+
+/// The kind of access a scope grants.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+impl Access {
+    pub fn parse(v: &str) -> Option<Access> {
+        match v {
+            "read" => Some(Access::Read),
+            "write" => Some(Access::Write),
+            _ => None,
+        }
+    }
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Access::Read => "read",
+            Access::Write => "write",
+        }
+    }
+}
+
+/// Read or write access to an api root, or to a single collection of it, written as
+/// `read:<api root>` or `write:<api root>/<collection id>`. Write access does not include read
+/// access.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Scope {
+    pub access: Access,
+    pub api_root: String,
+    pub collection_id: Option<String>,
+}
+
+impl Scope {
+    pub fn parse(v: &str) -> Result<Scope, MyError> {
+        let bad = || {
+            MyError(format!(
+                "bad scope {:?}, expected read|write:<api root>[/<collection id>]",
+                v
+            ))
+        };
+        let (access, target) = match v.split_once(':') {
+            Some(v) => v,
+            None => return Err(bad()),
+        };
+        let access = match Access::parse(access) {
+            Some(v) => v,
+            None => return Err(bad()),
+        };
+        let (api_root, collection_id) = match target.split_once('/') {
+            Some((api_root, collection_id)) => (api_root, Some(String::from(collection_id))),
+            None => (target, None),
+        };
+        if api_root.is_empty() || collection_id.as_deref() == Some("") {
+            return Err(bad());
+        }
+        Ok(Scope {
+            access,
+            api_root: String::from(api_root),
+            collection_id,
+        })
+    }
+
+    pub fn allows(&self, access: Access, api_root: &str, collection_id: &str) -> bool {
+        self.access == access
+            && self.api_root == api_root
+            && self
+                .collection_id
+                .as_ref()
+                .is_none_or(|v| v == collection_id)
+    }
+}
+
+impl std::fmt::Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.access.to_str(), self.api_root)?;
+        if let Some(collection_id) = &self.collection_id {
+            write!(f, "/{}", collection_id)?;
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<String> for Scope {
+    type Error = MyError;
+    fn try_from(v: String) -> Result<Self, Self::Error> {
+        Scope::parse(v.as_str())
+    }
+}
+
+impl From<Scope> for String {
+    fn from(v: Scope) -> Self {
+        v.to_string()
+    }
+}
+
+/// An API key, as stored: only the hash of the secret part of the key is kept.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ApiKey {
+    pub id: String,
+    pub name: String,
+    pub key_hash: String,
+    pub scopes: Vec<Scope>,
+    pub created: DateTime<Utc>,
+    pub revoked: Option<DateTime<Utc>>,
+}
+
+impl ApiKey {
+    pub fn is_revoked(&self) -> bool {
+        self.revoked.is_some()
+    }
+}
+
+fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    to_hex(&bytes)
+}
+
+fn to_hex(v: &[u8]) -> String {
+    v.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The SHA-256 hash of an API key secret. The secrets are random, so a fast hash is enough.
+fn hash_secret(secret: &str) -> String {
+    to_hex(&Sha256::digest(secret.as_bytes()))
+}
+
+/// The API keys known to the server, by id.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ApiKeys {
+    pub keys: BTreeMap<String, ApiKey>,
+}
+
+impl ApiKeys {
+    /// Read the keys from a file. A missing file means no keys were created yet.
+    pub fn load(path: &Path) -> Result<ApiKeys, MyError> {
+        let data = match std::fs::read_to_string(path) {
+            Ok(v) => v,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(ApiKeys::default()),
+            Err(err) => return Err(MyError(err.to_string())),
+        };
+        match serde_json::from_str::<ApiKeys>(data.as_str()) {
+            Ok(v) => Ok(v),
+            Err(err) => Err(MyError(format!("bad api keys {}: {}", path.display(), err))),
+        }
+    }
+
+    /// Write the keys to a file, replacing it atomically.
+    pub fn save(&self, path: &Path) -> Result<(), MyError> {
+        let data = match serde_json::to_string_pretty(self) {
+            Ok(v) => v,
+            Err(err) => return Err(MyError(err.to_string())),
+        };
+        match write_file_atomically(path, data.as_str()) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(MyError(err.to_string())),
+        }
+    }
+
+    /// Create a key with the scopes. Returns the key as stored, and the key to give to its user,
+    /// `<id>.<secret>`, which is not kept.
+    pub fn create(&mut self, name: &str, scopes: &[Scope]) -> (ApiKey, String) {
+        let id = random_hex(8);
+        let secret = random_hex(32);
+        let key = ApiKey {
+            id: id.clone(),
+            name: String::from(name),
+            key_hash: hash_secret(secret.as_str()),
+            scopes: scopes.to_vec(),
+            created: Utc::now(),
+            revoked: None,
+        };
+        self.keys.insert(id.clone(), key.clone());
+        (key, format!("{}.{}", id, secret))
+    }
+
+    pub fn revoke(&mut self, id: &str) -> Result<(), MyError> {
+        match self.keys.get_mut(id) {
+            Some(key) => {
+                if key.revoked.is_none() {
+                    key.revoked = Some(Utc::now());
+                }
+                Ok(())
+            }
+            None => Err(MyError(format!("no api key {}", id))),
+        }
+    }
+
+    /// The principal of a valid, unrevoked key, None otherwise.
+    pub fn authenticate(&self, token: &str) -> Option<Principal> {
+        let (id, secret) = token.split_once('.')?;
+        let key = self.keys.get(id)?;
+        let hash = hash_secret(secret);
+        // compare in constant time, to not tell how much of the hash matched
+        let matches: bool = hash.as_bytes().ct_eq(key.key_hash.as_bytes()).into();
+        if !matches || key.is_revoked() {
+            return None;
+        }
+        // the name is only a label, the id is unique
+        Some(Principal {
+            username: key.id.clone(),
+            scopes: Some(key.scopes.clone()),
+        })
+    }
+}
+
+/// How often the API keys file is checked for changes.
+pub const RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The API keys of a file, read again when the file changes so that keys created or revoked
+/// with the admin commands apply to a running server. The file is checked by
+/// [`reload_api_keys`], not on each request.
+pub struct ApiKeyStore {
+    path: PathBuf,
+    // the modification time of the file when it was read
+    keys: RwLock<(Option<SystemTime>, ApiKeys)>,
+}
+
+impl ApiKeyStore {
+    pub fn new(path: &Path) -> Result<ApiKeyStore, MyError> {
+        let modified = modified(path);
+        let keys = ApiKeys::load(path)?;
+        Ok(ApiKeyStore {
+            path: path.to_path_buf(),
+            keys: RwLock::new((modified, keys)),
+        })
+    }
+
+    pub fn authenticate(&self, token: &str) -> Option<Principal> {
+        self.keys.read().unwrap().1.authenticate(token)
+    }
+
+    /// Read the file again if it changed since it was read. Returns whether it was read; on
+    /// errors the keys read last are kept.
+    pub fn reload(&self) -> Result<bool, MyError> {
+        let modified = modified(self.path.as_path());
+        if modified == self.keys.read().unwrap().0 {
+            return Ok(false);
+        }
+        let keys = ApiKeys::load(self.path.as_path())?;
+        *self.keys.write().unwrap() = (modified, keys);
+        Ok(true)
+    }
+}
+
+/// Reload the API keys every interval, and whenever the process gets SIGHUP.
+pub async fn reload_api_keys(store: Arc<ApiKeyStore>, interval: Duration) {
+    let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+        Ok(v) => Some(v),
+        Err(err) => {
+            warn!("cannot handle SIGHUP: {}", err);
+            None
+        }
+    };
+    let mut ticks = tokio::time::interval(interval);
+    loop {
+        match &mut hangup {
+            Some(hangup) => tokio::select! {
+                _ = ticks.tick() => {}
+                _ = hangup.recv() => {}
+            },
+            None => {
+                ticks.tick().await;
+            }
+        }
+        let store = store.clone();
+        match tokio::task::spawn_blocking(move || store.reload()).await {
+            Ok(Ok(true)) => info!("reloaded api keys"),
+            Ok(Ok(false)) => {}
+            Ok(Err(err)) => warn!("keeping the api keys: {}", err),
+            Err(err) => warn!("keeping the api keys: {}", err),
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|v| v.modified()).ok()
+}
+
+static USAGE: &str = "usage: keys [--file <path>] create <name> <scope>... | list | revoke <id>
+scopes: read:<api root>[/<collection id>], write:<api root>[/<collection id>]";
+
+/// The admin commands for the API keys of a file, `api-keys.json` unless given with `--file`.
+pub fn main(args: &[String]) -> Result<(), MyError> {
+    let mut args = args.to_vec();
+    let mut path = PathBuf::from("api-keys.json");
+    if let Some(i) = args.iter().position(|v| v == "--file") {
+        match args.get(i + 1) {
+            Some(v) => path = PathBuf::from(v),
+            None => return Err(MyError(String::from(USAGE))),
+        }
+        args.drain(i..i + 2);
+    }
+    let mut keys = ApiKeys::load(path.as_path())?;
+    match args.first().map(|v| v.as_str()) {
+        Some("create") if args.len() >= 3 => {
+            let mut scopes = Vec::<Scope>::new();
+            for v in args[2..].iter() {
+                scopes.push(Scope::parse(v)?);
+            }
+            let (key, token) = keys.create(args[1].as_str(), &scopes);
+            keys.save(path.as_path())?;
+            println!("created api key {} ({})", key.id, key.name);
+            println!("{}", token);
+            println!("the key is not stored and cannot be shown again");
+            Ok(())
+        }
+        Some("list") => {
+            for key in keys.keys.values() {
+                let scopes: Vec<String> = key.scopes.iter().map(|v| v.to_string()).collect();
+                println!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    key.id,
+                    key.name,
+                    key.created.to_rfc3339(),
+                    match key.revoked {
+                        Some(v) => format!("revoked {}", v.to_rfc3339()),
+                        None => String::from("active"),
+                    },
+                    scopes.join(" ")
+                );
+            }
+            Ok(())
+        }
+        Some("revoke") if args.len() == 2 => {
+            keys.revoke(args[1].as_str())?;
+            keys.save(path.as_path())?;
+            println!("revoked api key {}", args[1]);
+            Ok(())
+        }
+        _ => Err(MyError(String::from(USAGE))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use uuid::Uuid;
+
+    use super::{Access, ApiKeyStore, ApiKeys, Scope};
+
+    #[test]
+    fn test_scope() {
+        let scope = Scope::parse("read:api1").unwrap();
+        assert!(scope.allows(Access::Read, "api1", "collection1"));
+        assert!(!scope.allows(Access::Write, "api1", "collection1"));
+        assert!(!scope.allows(Access::Read, "api2", "collection1"));
+        assert_eq!("read:api1", scope.to_string());
+
+        let scope = Scope::parse("write:api1/collection1").unwrap();
+        assert!(scope.allows(Access::Write, "api1", "collection1"));
+        assert!(!scope.allows(Access::Write, "api1", "collection2"));
+        assert!(!scope.allows(Access::Read, "api1", "collection1"));
+        assert_eq!("write:api1/collection1", scope.to_string());
+
+        for bad in [
+            "",
+            "read",
+            "admin:api1",
+            "read:",
+            "read:api1/",
+            "write:/collection1",
+        ] {
+            assert!(Scope::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_api_keys() {
+        let path = env::temp_dir().join(format!("api-keys-{}.json", Uuid::new_v4()));
+
+        let mut keys = ApiKeys::load(path.as_path()).unwrap();
+        assert!(keys.keys.is_empty());
+        let scopes = vec![Scope::parse("read:api1").unwrap()];
+        let (key, token) = keys.create("consumer", &scopes);
+        assert!(!token.contains(key.key_hash.as_str()));
+        keys.save(path.as_path()).unwrap();
+        let saved = std::fs::read_to_string(path.as_path()).unwrap();
+        assert!(!saved.contains(token.split_once('.').unwrap().1));
+
+        let store = ApiKeyStore::new(path.as_path()).unwrap();
+        let principal = store.authenticate(token.as_str()).unwrap();
+        assert_eq!(key.id, principal.username);
+        assert_eq!(Some(scopes), principal.scopes);
+        assert!(store.authenticate(format!("{}0", token).as_str()).is_none());
+        assert!(store.authenticate("nokey.secret").is_none());
+        assert!(store.authenticate(key.id.as_str()).is_none());
+
+        // revoked with the admin command while the server is running
+        let args = vec![
+            String::from("--file"),
+            path.to_string_lossy().to_string(),
+            String::from("revoke"),
+            key.id.clone(),
+        ];
+        // make sure the modification time changes
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        super::main(&args).unwrap();
+        assert!(ApiKeys::load(path.as_path()).unwrap().keys[&key.id].is_revoked());
+        // applies once the file is reloaded
+        assert!(store.authenticate(token.as_str()).is_some());
+        assert!(store.reload().unwrap());
+        assert!(!store.reload().unwrap());
+        assert!(store.authenticate(token.as_str()).is_none());
+
+        // a bad file keeps the keys read last
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        std::fs::write(path.as_path(), "{").unwrap();
+        assert!(store.reload().is_err());
+        assert!(store.authenticate(token.as_str()).is_none());
+
+        std::fs::remove_file(path.as_path()).unwrap();
+
+        let args = vec![
+            String::from("--file"),
+            path.to_string_lossy().to_string(),
+            String::from("revoke"),
+            String::from("nokey"),
+        ];
+        assert!(super::main(&args).is_err());
+        assert!(!path.exists());
+    }
+}
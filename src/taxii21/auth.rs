@@ -0,0 +1,294 @@
+use std::{
+    collections::HashMap,
+    future::{ready, Ready},
+};
+
+use actix_web::{
+    dev::Payload, error::ErrorUnauthorized, Error, FromRequest, HttpMessage, HttpRequest,
+};
+use argon2::{Argon2, Params};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use password_hash::{rand_core::OsRng, PasswordHasher, PasswordVerifier, SaltString};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    api_keys::{Access, Scope},
+    errors::MyError,
+};
+
+/// The cost of new password hashes: 19 MiB, 2 passes, 1 lane, as recommended by OWASP for
+/// Argon2id.
+pub const DEFAULT_PARAMS: Params = Params::DEFAULT;
+
+/// Cheap parameters, to keep the tests fast.
+#[cfg(test)]
+pub(crate) const TEST_PARAMS: Params = match Params::new(64, 1, 1, None) {
+    Ok(v) => v,
+    Err(_) => panic!("invalid test parameters"),
+};
+
+/// An Argon2 password hash, read from and written as a PHC string, e.g.
+/// `$argon2id$v=19$m=19456,t=2,p=1$<salt>$<hash>` as written by the `argon2` command line tool.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PasswordHash {
+    phc: String,
+}
+
+impl PasswordHash {
+    /// Hash a password with Argon2id and a random salt.
+    pub fn new(password: &str, params: &Params) -> PasswordHash {
+        let salt = SaltString::generate(&mut OsRng);
+        let argon2 = Argon2::new(
+            argon2::Algorithm::Argon2id,
+            argon2::Version::V0x13,
+            params.clone(),
+        );
+        // hashing only fails on parameters and salts that Params and SaltString do not allow
+        let hash = argon2
+            .hash_password(password.as_bytes(), &salt)
+            .expect("argon2 parameters are valid");
+        PasswordHash {
+            phc: hash.to_string(),
+        }
+    }
+
+    pub fn parse(v: &str) -> Result<PasswordHash, MyError> {
+        let bad = |what: &str| MyError(format!("bad password hash: {}", what));
+        let hash = match password_hash::PasswordHash::new(v) {
+            Ok(v) => v,
+            Err(err) => return Err(bad(err.to_string().as_str())),
+        };
+        if argon2::Algorithm::try_from(hash.algorithm).is_err() {
+            return Err(bad("not an argon2 hash"));
+        }
+        if hash.version != Some(argon2::Version::V0x13.into()) {
+            return Err(bad("unsupported version"));
+        }
+        if ["m", "t", "p"]
+            .iter()
+            .any(|v| hash.params.get(*v).is_none())
+        {
+            return Err(bad("missing m, t or p"));
+        }
+        if let Err(err) = Params::try_from(&hash) {
+            return Err(bad(err.to_string().as_str()));
+        }
+        if hash.salt.is_none() || hash.hash.is_none() {
+            return Err(bad("missing salt or hash"));
+        }
+        Ok(PasswordHash {
+            phc: String::from(v),
+        })
+    }
+
+    /// Whether the password matches, compared in constant time by the argon2 crate.
+    pub fn verify(&self, password: &str) -> bool {
+        match password_hash::PasswordHash::new(self.phc.as_str()) {
+            Ok(hash) => Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok(),
+            Err(_) => false,
+        }
+    }
+}
+
+impl std::fmt::Display for PasswordHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.phc)
+    }
+}
+
+/// A user of the server, as configured.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct User {
+    pub username: String,
+    /// The PHC string of the Argon2 hash of the password.
+    pub password_hash: String,
+}
+
+/// The authenticated user of a request. Handlers get it as an extractor, e.g.
+/// `principal: Option<Principal>` where anonymous requests are allowed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Principal {
+    pub username: String,
+    /// What an API key may access; users have no scopes and may access everything.
+    pub scopes: Option<Vec<Scope>>,
+}
+
+impl Principal {
+    pub fn allows(&self, access: Access, api_root: &str, collection_id: &str) -> bool {
+        match &self.scopes {
+            Some(scopes) => scopes
+                .iter()
+                .any(|v| v.allows(access, api_root, collection_id)),
+            None => true,
+        }
+    }
+}
+
+impl FromRequest for Principal {
+    type Error = Error;
+    type Future = Ready<Result<Principal, Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(match req.extensions().get::<Principal>() {
+            Some(v) => Ok(v.clone()),
+            None => Err(ErrorUnauthorized("not authenticated")),
+        })
+    }
+}
+
+/// The users allowed to use the server, with their password hashes.
+pub struct UserStore {
+    users: HashMap<String, PasswordHash>,
+    // verified for unknown users, so that they take as long to reject as a wrong password
+    dummy_hash: PasswordHash,
+}
+
+impl UserStore {
+    pub fn new() -> UserStore {
+        UserStore {
+            users: HashMap::<String, PasswordHash>::new(),
+            dummy_hash: PasswordHash::new("", &DEFAULT_PARAMS),
+        }
+    }
+    pub fn from_users(users: &[User]) -> Result<UserStore, MyError> {
+        let mut store = UserStore::new();
+        for user in users.iter() {
+            let hash = match PasswordHash::parse(user.password_hash.as_str()) {
+                Ok(v) => v,
+                Err(err) => return Err(MyError(format!("user {}: {}", user.username, err))),
+            };
+            store.add_user(user.username.as_str(), &hash);
+        }
+        Ok(store)
+    }
+    pub fn add_user(&mut self, username: &str, password_hash: &PasswordHash) {
+        self.users
+            .insert(String::from(username), password_hash.clone());
+    }
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+    /// The principal of a user with a correct password, None otherwise.
+    pub fn authenticate(&self, username: &str, password: &str) -> Option<Principal> {
+        match self.users.get(username) {
+            Some(hash) if hash.verify(password) => Some(Principal {
+                username: String::from(username),
+                scopes: None,
+            }),
+            Some(_) => None,
+            None => {
+                self.dummy_hash.verify(password);
+                None
+            }
+        }
+    }
+}
+
+impl Default for UserStore {
+    fn default() -> Self {
+        UserStore::new()
+    }
+}
+
+/// The token of an `Authorization: Bearer` header value.
+pub fn parse_bearer_token(v: &str) -> Option<String> {
+    let (scheme, token) = v.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("bearer") || token.trim().is_empty() {
+        return None;
+    }
+    Some(String::from(token.trim()))
+}
+
+/// The username and password of an `Authorization: Basic` header value.
+pub fn parse_basic_credentials(v: &str) -> Option<(String, String)> {
+    let (scheme, credentials) = v.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let credentials = STANDARD.decode(credentials.trim()).ok()?;
+    let credentials = String::from_utf8(credentials).ok()?;
+    let (username, password) = credentials.split_once(':')?;
+    Some((String::from(username), String::from(password)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        parse_basic_credentials, parse_bearer_token, PasswordHash, User, UserStore, TEST_PARAMS,
+    };
+
+    #[test]
+    fn test_password_hash() {
+        // written by `echo -n password | argon2 somesalt -id -t 2 -k 64 -p 1 -l 32`
+        let phc =
+            "$argon2id$v=19$m=64,t=2,p=1$c29tZXNhbHQ$FqGkmHNGCd0BRW2kBt6fPZ2pPmyGwwChL8FGUhTOSSI";
+        let hash = PasswordHash::parse(phc).unwrap();
+        assert!(hash.verify("password"));
+        assert!(!hash.verify("Password"));
+        assert_eq!(phc, hash.to_string());
+
+        let hash = PasswordHash::new("secret", &TEST_PARAMS);
+        assert_eq!(
+            hash,
+            PasswordHash::parse(hash.to_string().as_str()).unwrap()
+        );
+        assert!(hash.verify("secret"));
+        assert!(!hash.verify("Secret"));
+        assert!(hash.to_string().starts_with("$argon2id$v=19$m=64,t=1,p=1$"));
+        // the salt is random
+        assert_ne!(hash, PasswordHash::new("secret", &TEST_PARAMS));
+
+        for bad in [
+            "",
+            "password",
+            "$bcrypt$v=19$m=64,t=2,p=1$c29tZXNhbHQ$FqGkmHNGCd0BRW2kBt6fPZ2p",
+            "$argon2id$v=16$m=64,t=2,p=1$c29tZXNhbHQ$FqGkmHNGCd0BRW2kBt6fPZ2p",
+            "$argon2id$v=19$m=64,t=2$c29tZXNhbHQ$FqGkmHNGCd0BRW2kBt6fPZ2p",
+            "$argon2id$v=19$m=64,t=0,p=1$c29tZXNhbHQ$FqGkmHNGCd0BRW2kBt6fPZ2p",
+            "$argon2id$v=19$m=64,t=2,p=1$c29tZXNhbHQ$!!",
+        ] {
+            assert!(PasswordHash::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_user_store() {
+        let users = vec![User {
+            username: String::from("alice"),
+            password_hash: PasswordHash::new("secret", &TEST_PARAMS).to_string(),
+        }];
+        let store = UserStore::from_users(&users).unwrap();
+        let principal = store.authenticate("alice", "secret").unwrap();
+        assert_eq!("alice", principal.username);
+        assert!(store.authenticate("alice", "wrong").is_none());
+        assert!(store.authenticate("bob", "secret").is_none());
+        let users = vec![User {
+            username: String::from("bob"),
+            password_hash: String::from("plain"),
+        }];
+        assert!(UserStore::from_users(&users).is_err());
+    }
+
+    #[test]
+    fn test_parse_basic_credentials() {
+        assert_eq!(
+            Some((String::from("Aladdin"), String::from("open sesame"))),
+            parse_basic_credentials("Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==")
+        );
+        assert_eq!(
+            Some((String::from("user"), String::from("pa:ss"))),
+            parse_basic_credentials("basic dXNlcjpwYTpzcw==")
+        );
+        assert_eq!(None, parse_basic_credentials("Bearer abc"));
+        assert_eq!(None, parse_basic_credentials("Basic !!"));
+        assert_eq!(None, parse_basic_credentials("Basic dXNlcg=="));
+        assert_eq!(
+            Some(String::from("abc.def")),
+            parse_bearer_token("Bearer abc.def")
+        );
+        assert_eq!(None, parse_bearer_token("Bearer "));
+        assert_eq!(None, parse_bearer_token("Basic dXNlcg=="));
+    }
+}
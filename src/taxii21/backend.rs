@@ -1,6 +1,13 @@
-use super::{errors::MyError, server::ManifestRecord};
+use std::collections::HashMap;
 
-#[derive(Clone)]
+use chrono::{DateTime, Utc};
+
+use super::{
+    errors::MyError,
+    server::{ManifestRecord, Object, Status},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum MatchField {
     Id,
     SpecVersion,
@@ -8,33 +15,257 @@ pub enum MatchField {
     Version,
 }
 
+impl MatchField {
+    pub fn parse(v: &str) -> Result<MatchField, MyError> {
+        match v {
+            "match[id]" => Ok(MatchField::Id),
+            "match[spec_version]" => Ok(MatchField::SpecVersion),
+            "match[type]" => Ok(MatchField::Type),
+            "match[version]" => Ok(MatchField::Version),
+            _ => Err(MyError(format!("could not parse match field: {}", v))),
+        }
+    }
+    pub fn to_str(self) -> &'static str {
+        match self {
+            MatchField::Id => "match[id]",
+            MatchField::SpecVersion => "match[spec_version]",
+            MatchField::Type => "match[type]",
+            MatchField::Version => "match[version]",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Match {
-    field: MatchField,
-    values: Vec<String>,
+    pub field: MatchField,
+    pub values: Vec<String>,
 }
 
+/// The URL filtering parameters of the TAXII 2.1 manifest, objects and versions endpoints. The
+/// same type is used by the server to parse a request and by the client to build one.
+#[derive(Clone, Debug)]
 pub struct Filtering {
-    added_after: Option<chrono::DateTime<chrono::Utc>>,
-    limit: u32,
-    next: String,
-    matches: Vec<Match>,
+    pub added_after: Option<DateTime<Utc>>,
+    /// The requested page size, 0 when the client left it to the server.
+    pub limit: u32,
+    /// The opaque `next` value from a previous page, empty for the first page.
+    pub next: String,
+    pub matches: Vec<Match>,
 }
 
 impl Filtering {
     pub fn no_filter() -> Filtering {
-        return Filtering {
+        Filtering {
             added_after: None,
             limit: 0,
             next: String::from(""),
             matches: Vec::<Match>::new(),
-        };
+        }
+    }
+    pub fn parse(query: &HashMap<String, String>) -> Result<Filtering, MyError> {
+        let mut filtering = Filtering::no_filter();
+        for (key, value) in query.iter() {
+            match key.as_str() {
+                "added_after" => {
+                    filtering.added_after = match DateTime::parse_from_rfc3339(value) {
+                        Ok(v) => Some(v.with_timezone(&Utc)),
+                        Err(err) => return Err(MyError(format!("bad added_after: {}", err))),
+                    }
+                }
+                "limit" => {
+                    filtering.limit = match value.parse::<u32>() {
+                        Ok(v) => v,
+                        Err(err) => return Err(MyError(format!("bad limit: {}", err))),
+                    }
+                }
+                "next" => filtering.next = value.clone(),
+                _ => {
+                    let field = MatchField::parse(key.as_str())?;
+                    let values = value.split(',').map(|v| String::from(v.trim())).collect();
+                    filtering.matches.push(Match { field, values });
+                }
+            }
+        }
+        Ok(filtering)
+    }
+    pub fn with_added_after(mut self, added_after: DateTime<Utc>) -> Filtering {
+        self.added_after = Some(added_after);
+        self
+    }
+    pub fn with_limit(mut self, limit: u32) -> Filtering {
+        self.limit = limit;
+        self
+    }
+    pub fn with_next(mut self, next: &str) -> Filtering {
+        self.next = String::from(next);
+        self
+    }
+    pub fn with_match(mut self, field: MatchField, values: &[&str]) -> Filtering {
+        self.matches.push(Match {
+            field,
+            values: values.iter().map(|v| String::from(*v)).collect(),
+        });
+        self
+    }
+    /// The filtering as URL query parameters.
+    pub fn to_query(&self) -> Vec<(String, String)> {
+        let mut query = Vec::<(String, String)>::new();
+        if let Some(added_after) = self.added_after {
+            query.push((
+                String::from("added_after"),
+                added_after.to_rfc3339_opts(chrono::SecondsFormat::Micros, true),
+            ));
+        }
+        if self.limit > 0 {
+            query.push((String::from("limit"), self.limit.to_string()));
+        }
+        if !self.next.is_empty() {
+            query.push((String::from("next"), self.next.clone()));
+        }
+        for m in self.matches.iter() {
+            query.push((String::from(m.field.to_str()), m.values.join(",")));
+        }
+        query
+    }
+    fn match_values(&self, field: MatchField) -> Option<Vec<&str>> {
+        let mut result: Option<Vec<&str>> = None;
+        for m in self.matches.iter().filter(|m| m.field == field) {
+            let values = result.get_or_insert_with(Vec::<&str>::new);
+            m.values.iter().for_each(|v| values.push(v.as_str()));
+        }
+        result
+    }
+    /// Select the manifest records that match the filter, ordered by `date_added`.
+    /// `default_version` is the `match[version]` applied when the request does not specify one,
+    /// i.e. "last" for the manifest and objects endpoints and "all" for the versions endpoint.
+    pub fn apply(&self, records: &[ManifestRecord], default_version: &str) -> Vec<ManifestRecord> {
+        let ids = self.match_values(MatchField::Id);
+        let types = self.match_values(MatchField::Type);
+        let spec_versions = self.match_values(MatchField::SpecVersion);
+        let versions = self
+            .match_values(MatchField::Version)
+            .unwrap_or_else(|| vec![default_version]);
+        let candidates: Vec<&ManifestRecord> = records
+            .iter()
+            .filter(|rec| match self.added_after {
+                Some(added_after) => rec.date_added > added_after,
+                None => true,
+            })
+            .filter(|rec| match &ids {
+                Some(ids) => ids.contains(&rec.id.as_str()),
+                None => true,
+            })
+            .filter(|rec| match &types {
+                Some(types) => types.contains(&object_type(rec.id.as_str())),
+                None => true,
+            })
+            .filter(|rec| match &spec_versions {
+                Some(spec_versions) => spec_versions.contains(&rec.spec_version()),
+                None => true,
+            })
+            .collect();
+        let mut first = HashMap::<&str, &str>::new();
+        let mut last = HashMap::<&str, &str>::new();
+        for rec in candidates.iter() {
+            let id = rec.id.as_str();
+            let version = rec.version.as_str();
+            match first.get(id) {
+                Some(v) if compare_versions(v, version).is_le() => (),
+                _ => _ = first.insert(id, version),
+            }
+            match last.get(id) {
+                Some(v) if compare_versions(v, version).is_ge() => (),
+                _ => _ = last.insert(id, version),
+            }
+        }
+        let mut result: Vec<ManifestRecord> = candidates
+            .into_iter()
+            .filter(|rec| {
+                versions.iter().any(|v| match *v {
+                    "all" => true,
+                    "first" => first.get(rec.id.as_str()) == Some(&rec.version.as_str()),
+                    "last" => last.get(rec.id.as_str()) == Some(&rec.version.as_str()),
+                    v => compare_versions(v, rec.version.as_str()).is_eq(),
+                })
+            })
+            .cloned()
+            .collect();
+        result.sort_by_key(|a| a.date_added);
+        result
+    }
+    /// Cut one page out of `items`. The page size is the requested limit, capped by
+    /// `server_limit`. Returns the page, whether there is more, and the `next` value for the
+    /// following page.
+    pub fn paginate<T>(&self, items: Vec<T>, server_limit: u32) -> (Vec<T>, bool, Option<String>) {
+        let limit = match self.limit {
+            0 => server_limit,
+            v => v.min(server_limit),
+        } as usize;
+        let start = self.next.parse::<usize>().unwrap_or(0).min(items.len());
+        let end = (start + limit).min(items.len());
+        let more = end < items.len();
+        let page: Vec<T> = items.into_iter().skip(start).take(end - start).collect();
+        let next = if more { Some(end.to_string()) } else { None };
+        (page, more, next)
     }
 }
 
+/// The STIX type of an object, taken from its identifier, e.g. "indicator" for
+/// "indicator--cd981c25-8042-4166-8945-51178443bdac".
+pub fn object_type(id: &str) -> &str {
+    match id.split_once("--") {
+        Some((typ, _)) => typ,
+        None => id,
+    }
+}
+
+/// Order two object versions. Versions are timestamps, compare them as such when they parse and
+/// fall back to a string comparison otherwise.
+pub fn compare_versions(a: &str, b: &str) -> std::cmp::Ordering {
+    match (
+        DateTime::parse_from_rfc3339(a),
+        DateTime::parse_from_rfc3339(b),
+    ) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        _ => a.cmp(b),
+    }
+}
+
+/// Keep the objects that have a manifest record in `records`, in the order of `records`.
+pub fn select_objects(objects: &[Object], records: &[ManifestRecord]) -> Vec<Object> {
+    let mut result = Vec::<Object>::new();
+    for rec in records.iter() {
+        if let Some(object) = objects
+            .iter()
+            .find(|o| o.id == rec.id && compare_versions(o.version(), &rec.version).is_eq())
+        {
+            result.push(object.clone());
+        }
+    }
+    result
+}
+
 pub trait Backend {
+    /// The ids of the collections of the backend.
+    fn get_collections(&self) -> Result<Vec<String>, MyError>;
     fn get_manifests(
         &self,
         collection_id: &str,
         filtering: &Filtering,
     ) -> Result<Vec<ManifestRecord>, MyError>;
+    fn get_objects(
+        &self,
+        collection_id: &str,
+        filtering: &Filtering,
+    ) -> Result<Vec<Object>, MyError>;
+    /// Add objects to a collection, returning the status of the request. Objects that are already
+    /// in the collection at the same version are reported as failures.
+    fn add_objects(&mut self, collection_id: &str, objects: &[Object]) -> Result<Status, MyError>;
+    /// Delete the versions of an object selected by `filtering`, returning how many were deleted.
+    fn delete_object(
+        &mut self,
+        collection_id: &str,
+        object_id: &str,
+        filtering: &Filtering,
+    ) -> Result<usize, MyError>;
 }
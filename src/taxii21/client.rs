@@ -0,0 +1,567 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures::{stream, Stream, TryStreamExt};
+use reqwest::{header::HeaderMap, Method, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+
+use super::{
+    backend::Filtering,
+    errors::ErrorMessage,
+    server::{
+        APIRootConfig, CollectionConfig, Collections, Discovery, Envelope, Manifest,
+        ManifestRecord, Object, Status, Versions,
+    },
+};
+
+const MEDIA_TYPE_TAXII2: &str = "application/taxii+json;version=2.1";
+const DEFAULT_DISCOVERY_PATH: &str = "/taxii2/";
+
+#[derive(Debug)]
+pub enum ClientError {
+    /// The request could not be sent, or the response could not be read.
+    Transport(String),
+    /// The server answered with an error status and a TAXII error message body.
+    Taxii { status: u16, error: ErrorMessage },
+    /// The server answered with an error status but without a TAXII error message body.
+    Http { status: u16, body: String },
+    /// The server cannot answer in a media type we accept.
+    NotAcceptable,
+    /// The response body is not the resource we asked for.
+    Decode(String),
+}
+
+impl std::fmt::Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientError::Transport(err) => write!(f, "transport error: {}", err),
+            ClientError::Taxii { status, error } => match &error.description {
+                Some(description) => write!(f, "{}: {}: {}", status, error.title, description),
+                None => write!(f, "{}: {}", status, error.title),
+            },
+            ClientError::Http { status, body } => write!(f, "HTTP status {}: {}", status, body),
+            ClientError::NotAcceptable => write!(f, "no acceptable media type"),
+            ClientError::Decode(err) => write!(f, "could not decode response: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+/// One page of a paginated resource.
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub more: bool,
+    pub next: Option<String>,
+    pub date_added_first: Option<DateTime<Utc>>,
+    pub date_added_last: Option<DateTime<Utc>>,
+}
+
+impl<T> Page<T> {
+    /// The filtering that fetches the page after this one, or None on the last page. Servers that
+    /// do not hand out `next` are paged with `added_after`.
+    pub fn next_filtering(&self, filtering: &Filtering) -> Option<Filtering> {
+        if !self.more {
+            return None;
+        }
+        match (&self.next, self.date_added_last) {
+            (Some(next), _) => Some(filtering.clone().with_next(next.as_str())),
+            (None, Some(date_added_last)) => {
+                Some(filtering.clone().with_added_after(date_added_last))
+            }
+            (None, None) => None,
+        }
+    }
+}
+
+/// A TAXII 2.1 client.
+#[derive(Clone)]
+pub struct Client {
+    client: reqwest::Client,
+    base_url: String,
+    discovery_path: String,
+    basic_auth: Option<(String, String)>,
+}
+
+impl Client {
+    /// Create a client for the server at `base_url`, e.g. "https://example.com".
+    pub fn new(base_url: &str) -> Client {
+        Client::with_client(reqwest::Client::new(), base_url)
+    }
+    pub fn with_client(client: reqwest::Client, base_url: &str) -> Client {
+        Client {
+            client,
+            base_url: String::from(base_url.trim_end_matches('/')),
+            discovery_path: String::from(DEFAULT_DISCOVERY_PATH),
+            basic_auth: None,
+        }
+    }
+    pub fn with_basic_auth(mut self, username: &str, password: &str) -> Client {
+        self.basic_auth = Some((String::from(username), String::from(password)));
+        self
+    }
+    /// Use a discovery endpoint other than the default "/taxii2/".
+    pub fn with_discovery_path(mut self, discovery_path: &str) -> Client {
+        self.discovery_path = String::from(discovery_path);
+        self
+    }
+
+    /// The URL of an api root, given either as the full URL listed in the discovery resource or
+    /// as a path relative to the server.
+    pub fn api_root_url(&self, api_root: &str) -> String {
+        let url = if api_root.starts_with("http://") || api_root.starts_with("https://") {
+            String::from(api_root)
+        } else {
+            format!("{}/{}", self.base_url, api_root.trim_start_matches('/'))
+        };
+        if url.ends_with('/') {
+            url
+        } else {
+            format!("{}/", url)
+        }
+    }
+
+    fn collection_url(&self, api_root: &str, collection_id: &str) -> String {
+        format!(
+            "{}collections/{}/",
+            self.api_root_url(api_root),
+            collection_id
+        )
+    }
+
+    fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        let request = self
+            .client
+            .request(method, url)
+            .header("Accept", MEDIA_TYPE_TAXII2);
+        match &self.basic_auth {
+            Some((username, password)) => request.basic_auth(username, Some(password)),
+            None => request,
+        }
+    }
+
+    /// Send a request and turn error statuses into errors, preferring the TAXII error message
+    /// when the server sent one.
+    async fn send(&self, request: RequestBuilder) -> Result<Response, ClientError> {
+        let response = match request.send().await {
+            Ok(v) => v,
+            Err(err) => return Err(ClientError::Transport(err.to_string())),
+        };
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        if status == StatusCode::NOT_ACCEPTABLE {
+            return Err(ClientError::NotAcceptable);
+        }
+        let body = match response.text().await {
+            Ok(v) => v,
+            Err(err) => return Err(ClientError::Transport(err.to_string())),
+        };
+        match serde_json::from_str::<ErrorMessage>(body.as_str()) {
+            Ok(error) => Err(ClientError::Taxii {
+                status: status.as_u16(),
+                error,
+            }),
+            Err(_) => Err(ClientError::Http {
+                status: status.as_u16(),
+                body,
+            }),
+        }
+    }
+
+    async fn decode<T: DeserializeOwned>(response: Response) -> Result<T, ClientError> {
+        let content_type = response
+            .headers()
+            .get("Content-Type")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("");
+        if !content_type.starts_with("application/taxii+json")
+            && !content_type.starts_with("application/stix+json")
+        {
+            return Err(ClientError::Decode(format!(
+                "unexpected content type: {}",
+                content_type
+            )));
+        }
+        let body = match response.bytes().await {
+            Ok(v) => v,
+            Err(err) => return Err(ClientError::Transport(err.to_string())),
+        };
+        match serde_json::from_slice::<T>(&body) {
+            Ok(v) => Ok(v),
+            Err(err) => Err(ClientError::Decode(err.to_string())),
+        }
+    }
+
+    async fn get<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        filtering: Option<&Filtering>,
+    ) -> Result<(T, HeaderMap), ClientError> {
+        let mut request = self.request(Method::GET, url);
+        if let Some(filtering) = filtering {
+            request = request.query(&filtering.to_query());
+        }
+        let response = self.send(request).await?;
+        let headers = response.headers().clone();
+        Ok((Client::decode::<T>(response).await?, headers))
+    }
+
+    pub async fn discovery(&self) -> Result<Discovery, ClientError> {
+        let url = format!("{}{}", self.base_url, self.discovery_path);
+        Ok(self.get::<Discovery>(url.as_str(), None).await?.0)
+    }
+
+    pub async fn api_root(&self, api_root: &str) -> Result<APIRootConfig, ClientError> {
+        let url = self.api_root_url(api_root);
+        Ok(self.get::<APIRootConfig>(url.as_str(), None).await?.0)
+    }
+
+    pub async fn collections(&self, api_root: &str) -> Result<Vec<CollectionConfig>, ClientError> {
+        let url = format!("{}collections/", self.api_root_url(api_root));
+        let collections = self.get::<Collections>(url.as_str(), None).await?.0;
+        Ok(collections.collections.unwrap_or_default())
+    }
+
+    pub async fn collection(
+        &self,
+        api_root: &str,
+        collection_id: &str,
+    ) -> Result<CollectionConfig, ClientError> {
+        let url = self.collection_url(api_root, collection_id);
+        Ok(self.get::<CollectionConfig>(url.as_str(), None).await?.0)
+    }
+
+    pub async fn manifest(
+        &self,
+        api_root: &str,
+        collection_id: &str,
+        filtering: &Filtering,
+    ) -> Result<Page<ManifestRecord>, ClientError> {
+        let url = format!("{}manifest/", self.collection_url(api_root, collection_id));
+        let (manifest, headers) = self.get::<Manifest>(url.as_str(), Some(filtering)).await?;
+        Ok(new_page(
+            manifest.objects.unwrap_or_default(),
+            manifest.more,
+            manifest.next,
+            &headers,
+        ))
+    }
+
+    pub async fn objects(
+        &self,
+        api_root: &str,
+        collection_id: &str,
+        filtering: &Filtering,
+    ) -> Result<Page<Object>, ClientError> {
+        let url = format!("{}objects/", self.collection_url(api_root, collection_id));
+        let (envelope, headers) = self.get::<Envelope>(url.as_str(), Some(filtering)).await?;
+        Ok(new_page(
+            envelope.objects.unwrap_or_default(),
+            envelope.more,
+            envelope.next,
+            &headers,
+        ))
+    }
+
+    /// All manifest records matching `filtering`, fetching pages as the stream is consumed.
+    pub fn manifest_stream<'a>(
+        &'a self,
+        api_root: &'a str,
+        collection_id: &'a str,
+        filtering: Filtering,
+    ) -> impl Stream<Item = Result<ManifestRecord, ClientError>> + 'a {
+        stream::try_unfold(Some(filtering), move |filtering| async move {
+            let filtering = match filtering {
+                Some(v) => v,
+                None => return Ok(None),
+            };
+            let page = self.manifest(api_root, collection_id, &filtering).await?;
+            let next_filtering = page.next_filtering(&filtering);
+            Ok(Some((page.items, next_filtering)))
+        })
+        .map_ok(|items| stream::iter(items.into_iter().map(Ok)))
+        .try_flatten()
+    }
+
+    /// All objects matching `filtering`, fetching pages as the stream is consumed.
+    pub fn objects_stream<'a>(
+        &'a self,
+        api_root: &'a str,
+        collection_id: &'a str,
+        filtering: Filtering,
+    ) -> impl Stream<Item = Result<Object, ClientError>> + 'a {
+        stream::try_unfold(Some(filtering), move |filtering| async move {
+            let filtering = match filtering {
+                Some(v) => v,
+                None => return Ok(None),
+            };
+            let page = self.objects(api_root, collection_id, &filtering).await?;
+            let next_filtering = page.next_filtering(&filtering);
+            Ok(Some((page.items, next_filtering)))
+        })
+        .map_ok(|items| stream::iter(items.into_iter().map(Ok)))
+        .try_flatten()
+    }
+
+    pub async fn add_objects(
+        &self,
+        api_root: &str,
+        collection_id: &str,
+        objects: &[Object],
+    ) -> Result<Status, ClientError> {
+        let url = format!("{}objects/", self.collection_url(api_root, collection_id));
+        let mut envelope = Envelope::new();
+        envelope.objects = Some(objects.to_vec());
+        let body = match serde_json::to_vec(&envelope) {
+            Ok(v) => v,
+            Err(err) => return Err(ClientError::Decode(err.to_string())),
+        };
+        let request = self
+            .request(Method::POST, url.as_str())
+            .header("Content-Type", MEDIA_TYPE_TAXII2)
+            .body(body);
+        let response = self.send(request).await?;
+        Client::decode::<Status>(response).await
+    }
+
+    pub async fn object(
+        &self,
+        api_root: &str,
+        collection_id: &str,
+        object_id: &str,
+        filtering: &Filtering,
+    ) -> Result<Page<Object>, ClientError> {
+        let url = format!(
+            "{}objects/{}/",
+            self.collection_url(api_root, collection_id),
+            object_id
+        );
+        let (envelope, headers) = self.get::<Envelope>(url.as_str(), Some(filtering)).await?;
+        Ok(new_page(
+            envelope.objects.unwrap_or_default(),
+            envelope.more,
+            envelope.next,
+            &headers,
+        ))
+    }
+
+    pub async fn delete_object(
+        &self,
+        api_root: &str,
+        collection_id: &str,
+        object_id: &str,
+        filtering: &Filtering,
+    ) -> Result<(), ClientError> {
+        let url = format!(
+            "{}objects/{}/",
+            self.collection_url(api_root, collection_id),
+            object_id
+        );
+        let request = self
+            .request(Method::DELETE, url.as_str())
+            .query(&filtering.to_query());
+        self.send(request).await?;
+        Ok(())
+    }
+
+    pub async fn versions(
+        &self,
+        api_root: &str,
+        collection_id: &str,
+        object_id: &str,
+        filtering: &Filtering,
+    ) -> Result<Page<String>, ClientError> {
+        let url = format!(
+            "{}objects/{}/versions/",
+            self.collection_url(api_root, collection_id),
+            object_id
+        );
+        let (versions, headers) = self.get::<Versions>(url.as_str(), Some(filtering)).await?;
+        Ok(new_page(
+            versions.versions.unwrap_or_default(),
+            versions.more,
+            None,
+            &headers,
+        ))
+    }
+
+    pub async fn status(&self, api_root: &str, status_id: &str) -> Result<Status, ClientError> {
+        let url = format!("{}status/{}/", self.api_root_url(api_root), status_id);
+        Ok(self.get::<Status>(url.as_str(), None).await?.0)
+    }
+
+    /// Poll a status resource until it is no longer pending, checking at most `attempts` times.
+    pub async fn wait_for_status(
+        &self,
+        api_root: &str,
+        status_id: &str,
+        interval: Duration,
+        attempts: u32,
+    ) -> Result<Status, ClientError> {
+        let mut status = self.status(api_root, status_id).await?;
+        for _ in 1..attempts {
+            if status.status != "pending" {
+                break;
+            }
+            tokio::time::sleep(interval).await;
+            status = self.status(api_root, status_id).await?;
+        }
+        Ok(status)
+    }
+}
+
+fn header_timestamp(headers: &HeaderMap, name: &str) -> Option<DateTime<Utc>> {
+    let value = headers.get(name)?.to_str().ok()?;
+    match DateTime::parse_from_rfc3339(value) {
+        Ok(v) => Some(v.with_timezone(&Utc)),
+        Err(_) => None,
+    }
+}
+
+fn new_page<T>(
+    items: Vec<T>,
+    more: Option<bool>,
+    next: Option<String>,
+    headers: &HeaderMap,
+) -> Page<T> {
+    Page {
+        items,
+        more: more.unwrap_or(false),
+        next,
+        date_added_first: header_timestamp(headers, "X-TAXII-Date-Added-First"),
+        date_added_last: header_timestamp(headers, "X-TAXII-Date-Added-Last"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::TryStreamExt;
+
+    use super::{Client, ClientError};
+    use crate::taxii21::{
+        backend::{Filtering, MatchField},
+        server::Object,
+        test_utils::{new_backend, new_indicator, start_server},
+    };
+
+    #[actix_web::test]
+    async fn test_client_resources() {
+        let addr = start_server(new_backend());
+        let client =
+            Client::new(format!("http://{}", addr).as_str()).with_discovery_path("/taxii2");
+
+        let discovery = client.discovery().await.unwrap();
+        assert_eq!("test server", discovery.title);
+
+        let api_root = client.api_root("api1").await.unwrap();
+        assert_eq!("api root 1", api_root.title);
+        assert_eq!(10485760, api_root.max_content_length);
+
+        let collections = client.collections("api1").await.unwrap();
+        assert_eq!(1, collections.len());
+        assert_eq!("collection1", collections[0].id);
+        let collection = client.collection("api1", "collection1").await.unwrap();
+        assert_eq!("collection 1", collection.title);
+
+        match client.api_root("not-found").await {
+            Err(ClientError::Http { status, .. }) => assert_eq!(404, status),
+            _ => panic!("expected a 404 for an unknown api root"),
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_client_objects() {
+        let addr = start_server(new_backend());
+        let client = Client::new(format!("http://{}", addr).as_str());
+
+        let objects: Vec<Object> = (0..5)
+            .map(|i| {
+                new_indicator(
+                    format!("indicator--00000000-0000-4000-8000-00000000000{}", i).as_str(),
+                    "2023-01-01T00:00:00.000Z",
+                )
+            })
+            .collect();
+        let status = client
+            .add_objects("api1", "collection1", &objects)
+            .await
+            .unwrap();
+        assert_eq!(5, status.total_count);
+        assert_eq!(5, status.success_count);
+        let status = client
+            .wait_for_status("api1", status.id.as_str(), Duration::from_millis(10), 3)
+            .await
+            .unwrap();
+        assert_eq!("complete", status.status);
+
+        // the server returns pages of two records, the streams follow `next` through all of them
+        let page = client
+            .manifest("api1", "collection1", &Filtering::no_filter())
+            .await
+            .unwrap();
+        assert_eq!(2, page.items.len());
+        assert!(page.more);
+        assert!(page.next.is_some());
+        assert!(page.date_added_first.is_some());
+        let manifest: Vec<_> = client
+            .manifest_stream("api1", "collection1", Filtering::no_filter())
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(5, manifest.len());
+        let all: Vec<Object> = client
+            .objects_stream("api1", "collection1", Filtering::no_filter())
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(5, all.len());
+        assert_eq!(objects[0].id, all[0].id);
+
+        let object_id = "indicator--00000000-0000-4000-8000-000000000001";
+        let update = new_indicator(object_id, "2023-02-01T00:00:00.000Z");
+        client
+            .add_objects("api1", "collection1", &[update])
+            .await
+            .unwrap();
+        let versions = client
+            .versions("api1", "collection1", object_id, &Filtering::no_filter())
+            .await
+            .unwrap();
+        assert_eq!(2, versions.items.len());
+        let object = client
+            .object("api1", "collection1", object_id, &Filtering::no_filter())
+            .await
+            .unwrap();
+        assert_eq!(1, object.items.len());
+        assert_eq!("2023-02-01T00:00:00.000Z", object.items[0].version());
+        let filtering = Filtering::no_filter().with_match(MatchField::Version, &["first"]);
+        let object = client
+            .object("api1", "collection1", object_id, &filtering)
+            .await
+            .unwrap();
+        assert_eq!("2023-01-01T00:00:00.000Z", object.items[0].version());
+
+        client
+            .delete_object("api1", "collection1", object_id, &Filtering::no_filter())
+            .await
+            .unwrap();
+        match client
+            .delete_object("api1", "collection1", object_id, &Filtering::no_filter())
+            .await
+        {
+            Err(ClientError::Taxii { status, error }) => {
+                assert_eq!(404, status);
+                assert_eq!("object not found", error.title);
+            }
+            _ => panic!("expected a TAXII error"),
+        }
+        match client.add_objects("api1", "not-found", &objects).await {
+            Err(ClientError::Taxii { status, .. }) => assert_eq!(404, status),
+            _ => panic!("expected a TAXII error"),
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+
 #[derive(Debug)]
 pub struct MyError(pub String);
 
@@ -8,3 +10,35 @@ impl std::fmt::Display for MyError {
 }
 
 impl std::error::Error for MyError {}
+
+/// The TAXII 2.1 error message resource, sent in the body of error responses.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ErrorMessage {
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_details: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+impl ErrorMessage {
+    pub fn new(title: &str, description: Option<&str>, http_status: u16) -> ErrorMessage {
+        ErrorMessage {
+            title: String::from(title),
+            description: description.map(String::from),
+            error_id: None,
+            error_code: None,
+            http_status: Some(http_status.to_string()),
+            external_details: None,
+            details: None,
+        }
+    }
+}
@@ -1,12 +1,18 @@
-use std::path::Path;
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
 
+use fs2::FileExt;
 use serde::{Deserialize, Serialize};
 use tracing::info;
 
 use super::{
     backend::{Backend, Filtering},
     errors::MyError,
-    server::{CollectionConfig, ManifestRecord, Object},
+    memory_backend::CollectionObjects,
+    server::{CollectionConfig, ManifestRecord, Object, Status},
 };
 
 pub struct FileBackend {
@@ -15,45 +21,282 @@ pub struct FileBackend {
 
 impl FileBackend {
     pub fn new(root_dir: &str) -> FileBackend {
-        return FileBackend {
+        FileBackend {
             root_dir: String::from(root_dir),
+        }
+    }
+    fn collection_path(&self, collection_id: &str) -> PathBuf {
+        Path::new(self.root_dir.as_str()).join(format!("collection-{}.json", collection_id))
+    }
+    /// Open a collection file, locked so that other processes sharing the root directory, e.g.
+    /// the mirror and the server, do not write it while it is read or written. The lock is
+    /// shared for reads, exclusive for writes, and released when the file is dropped.
+    fn open_collection(&self, collection_id: &str, write: bool) -> Result<File, MyError> {
+        let file = match OpenOptions::new()
+            .read(true)
+            .write(write)
+            .open(self.collection_path(collection_id))
+        {
+            Ok(v) => v,
+            // TODO: not found error
+            Err(err) => return Err(MyError(err.to_string())),
+        };
+        let locked = if write {
+            file.lock_exclusive()
+        } else {
+            file.lock_shared()
+        };
+        match locked {
+            Ok(_) => Ok(file),
+            Err(err) => Err(MyError(err.to_string())),
+        }
+    }
+    fn read_collection(file: &mut File) -> Result<FileCollection, MyError> {
+        let mut collection = String::new();
+        if let Err(err) = file.read_to_string(&mut collection) {
+            return Err(MyError(err.to_string()));
+        }
+        let err = match serde_json::from_slice::<FileCollection>(collection.as_bytes()) {
+            Ok(v) => return Ok(v),
+            Err(err) => err,
+        };
+        match serde_json::from_slice::<LegacyFileCollection>(collection.as_bytes()) {
+            Ok(v) => Ok(FileCollection {
+                config: v.config,
+                data: v.data,
+            }),
+            Err(_) => {
+                info!("err-in-json={}", err);
+                Err(MyError(err.to_string()))
+            }
+        }
+    }
+    /// Replace the content of a collection file opened, and locked, for writing.
+    fn write_collection(file: &mut File, collection: &FileCollection) -> Result<(), MyError> {
+        let data = match serde_json::to_string_pretty(collection) {
+            Ok(v) => v,
+            Err(err) => return Err(MyError(err.to_string())),
         };
+        let written = file
+            .set_len(0)
+            .and_then(|_| file.seek(SeekFrom::Start(0)))
+            .and_then(|_| file.write_all(data.as_bytes()));
+        match written {
+            Ok(_) => Ok(()),
+            Err(err) => Err(MyError(err.to_string())),
+        }
     }
 }
 
+/// A collection file: the collection resource, with its objects and manifest, e.g.
+/// ```json
+/// {"id": "aaaabbbb", "title": "...", "can_read": true, "can_write": true,
+///  "media_types": [...], "objects": [...], "manifest": [...]}
+/// ```
 #[derive(Deserialize, Serialize)]
 struct FileCollection {
+    #[serde(flatten)]
+    config: CollectionConfig,
+    #[serde(flatten)]
+    data: CollectionObjects,
+}
+
+/// The layout of collection files written before the collection resource moved to the top
+/// level, with it under a `config` key. These files are still read, and are rewritten in the
+/// current layout by the next write to the collection.
+#[derive(Deserialize)]
+struct LegacyFileCollection {
     config: CollectionConfig,
-    objects: Vec<Object>,
-    manifest: Vec<ManifestRecord>,
+    #[serde(flatten)]
+    data: CollectionObjects,
 }
 
 impl Backend for FileBackend {
+    fn get_collections(&self) -> Result<Vec<String>, MyError> {
+        let entries = match std::fs::read_dir(self.root_dir.as_str()) {
+            Ok(v) => v,
+            Err(err) => return Err(MyError(format!("{}: {}", self.root_dir, err))),
+        };
+        let mut collection_ids = Vec::<String>::new();
+        for entry in entries {
+            let name = match entry {
+                Ok(v) => v.file_name(),
+                Err(err) => return Err(MyError(err.to_string())),
+            };
+            let name = name.to_string_lossy();
+            if let Some(v) = name
+                .strip_prefix("collection-")
+                .and_then(|v| v.strip_suffix(".json"))
+            {
+                collection_ids.push(String::from(v));
+            }
+        }
+        collection_ids.sort();
+        Ok(collection_ids)
+    }
     fn get_manifests(
         &self,
         collection_id: &str,
         filtering: &Filtering,
     ) -> Result<Vec<ManifestRecord>, MyError> {
-        let path =
-            Path::new(self.root_dir.as_str()).join(format!("collection-{}.json", collection_id));
-        let collection = match std::fs::read_to_string(path) {
-            Ok(v) => v,
-            // TODO: not found error
-            Err(err) => return Err(MyError(err.to_string())),
-        };
-        let collection = match serde_json::from_slice::<FileCollection>(collection.as_bytes()) {
-            Ok(v) => v,
-            Err(err) => {
-                info!("err-in-json={}", err);
-                let msg = err.to_string();
-                return Err(MyError(err.to_string()));
-            }
-        };
-        let mut result = Vec::<ManifestRecord>::new();
-        collection
-            .manifest
-            .iter()
-            .for_each(|rec| result.push(rec.clone()));
-        Ok(result)
+        let mut file = self.open_collection(collection_id, false)?;
+        let collection = FileBackend::read_collection(&mut file)?;
+        Ok(collection.data.get_manifests(filtering))
+    }
+    fn get_objects(
+        &self,
+        collection_id: &str,
+        filtering: &Filtering,
+    ) -> Result<Vec<Object>, MyError> {
+        let mut file = self.open_collection(collection_id, false)?;
+        let collection = FileBackend::read_collection(&mut file)?;
+        Ok(collection.data.get_objects(filtering))
+    }
+    fn add_objects(&mut self, collection_id: &str, objects: &[Object]) -> Result<Status, MyError> {
+        let mut file = self.open_collection(collection_id, true)?;
+        let mut collection = FileBackend::read_collection(&mut file)?;
+        let status = collection.data.add_objects(objects);
+        FileBackend::write_collection(&mut file, &collection)?;
+        Ok(status)
+    }
+    fn delete_object(
+        &mut self,
+        collection_id: &str,
+        object_id: &str,
+        filtering: &Filtering,
+    ) -> Result<usize, MyError> {
+        let mut file = self.open_collection(collection_id, true)?;
+        let mut collection = FileBackend::read_collection(&mut file)?;
+        let deleted = collection.data.delete_object(object_id, filtering);
+        FileBackend::write_collection(&mut file, &collection)?;
+        Ok(deleted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::FileBackend;
+    use crate::taxii21::{
+        backend::{Backend, Filtering, MatchField},
+        server::Object,
+    };
+
+    #[test]
+    fn test_file_backend_get_collections() {
+        let root_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+        let backend = FileBackend::new(format!("{}/test/file-backend/", root_dir).as_str());
+        assert_eq!(
+            vec!["aaaabbbb", "aaaacccc", "aaaadddd"],
+            backend.get_collections().unwrap()
+        );
+        let backend = FileBackend::new(format!("{}/test/no-such-dir/", root_dir).as_str());
+        assert!(backend.get_collections().is_err());
+    }
+
+    #[test]
+    fn test_file_backend_get_objects() {
+        let root_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+        let backend = FileBackend::new(format!("{}/test/file-backend/", root_dir).as_str());
+
+        let manifests = backend
+            .get_manifests("aaaadddd", &Filtering::no_filter())
+            .unwrap();
+        assert_eq!(5, manifests.len());
+
+        let filtering = Filtering::no_filter().with_match(MatchField::Version, &["all"]);
+        let objects = backend.get_objects("aaaadddd", &filtering).unwrap();
+        assert_eq!(8, objects.len());
+
+        let filtering = Filtering::no_filter()
+            .with_match(
+                MatchField::Id,
+                &["indicator--6770298f-0fd8-471a-ab8c-1c658a46574e"],
+            )
+            .with_match(MatchField::Version, &["first"]);
+        let objects = backend.get_objects("aaaadddd", &filtering).unwrap();
+        assert_eq!(1, objects.len());
+        assert_eq!("2016-11-03T12:30:59.000Z", objects[0].version());
+
+        let filtering = Filtering::no_filter().with_match(MatchField::Type, &["malware"]);
+        let objects = backend.get_objects("aaaadddd", &filtering).unwrap();
+        assert_eq!(1, objects.len());
+        assert_eq!("2018-02-23T18:30:00.000Z", objects[0].version());
+
+        assert!(backend
+            .get_objects("not-found", &Filtering::no_filter())
+            .is_err());
+    }
+
+    #[test]
+    fn test_file_backend_legacy_layout() {
+        let root_dir = env::temp_dir().join(format!("file-backend-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root_dir).unwrap();
+        let path = root_dir.join("collection-legacy.json");
+        let legacy = r#"{
+            "config": {"id": "legacy", "title": "Legacy", "can_read": true, "can_write": true,
+                "media_types": ["application/stix+json;version=2.1"]},
+            "objects": [],
+            "manifest": []
+        }"#;
+        std::fs::write(&path, legacy).unwrap();
+        let mut backend = FileBackend::new(root_dir.to_str().unwrap());
+        let filtering = Filtering::no_filter();
+        assert!(backend
+            .get_objects("legacy", &filtering)
+            .unwrap()
+            .is_empty());
+
+        let mut object = Object::new(
+            "indicator",
+            "indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f",
+        );
+        object.modified = Some(String::from("2016-04-06T20:03:48.000Z"));
+        backend.add_objects("legacy", &[object]).unwrap();
+        let written: serde_json::Value =
+            serde_json::from_str(std::fs::read_to_string(&path).unwrap().as_str()).unwrap();
+        assert_eq!("legacy", written["id"]);
+        assert!(written.get("config").is_none());
+        assert_eq!(1, backend.get_objects("legacy", &filtering).unwrap().len());
+        std::fs::remove_dir_all(&root_dir).unwrap();
+    }
+
+    #[test]
+    fn test_file_backend_concurrent_writes() {
+        let root_dir = env::temp_dir().join(format!("file-backend-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root_dir).unwrap();
+        let collection = r#"{"id": "shared", "title": "Shared", "can_read": true,
+            "can_write": true, "media_types": ["application/stix+json;version=2.1"],
+            "objects": [], "manifest": []}"#;
+        std::fs::write(root_dir.join("collection-shared.json"), collection).unwrap();
+
+        // backends sharing a root directory, as the mirror and the server do, do not lose each
+        // other's writes
+        let threads = (0..4)
+            .map(|i| {
+                let root_dir = root_dir.clone();
+                std::thread::spawn(move || {
+                    let mut backend = FileBackend::new(root_dir.to_str().unwrap());
+                    for j in 0..10 {
+                        let mut object = Object::new(
+                            "indicator",
+                            format!("indicator--{}", uuid::Uuid::new_v4()).as_str(),
+                        );
+                        object.modified = Some(format!("2016-04-06T20:{:02}:{:02}.000Z", i, j));
+                        backend.add_objects("shared", &[object]).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        let backend = FileBackend::new(root_dir.to_str().unwrap());
+        let objects = backend
+            .get_objects("shared", &Filtering::no_filter())
+            .unwrap();
+        assert_eq!(40, objects.len());
+        std::fs::remove_dir_all(&root_dir).unwrap();
     }
 }
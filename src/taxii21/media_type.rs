@@ -0,0 +1,303 @@
+/// A media type with its parameters, as in a Content-Type header (RFC 7231 section 3.1.1.1).
+/// The type, subtype and parameter names are lowercased.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MediaType {
+    pub type_: String,
+    pub subtype: String,
+    pub params: Vec<(String, String)>,
+}
+
+impl MediaType {
+    pub fn parse(v: &str) -> Option<MediaType> {
+        let mut parts = split_unquoted(v, ';').into_iter();
+        let (type_, subtype) = parts.next()?.trim().split_once('/')?;
+        if !is_token(type_) || !is_token(subtype) {
+            return None;
+        }
+        let mut params = Vec::<(String, String)>::new();
+        for param in parts {
+            let param = param.trim();
+            if param.is_empty() {
+                continue;
+            }
+            let (name, value) = param.split_once('=')?;
+            let name = name.trim();
+            if !is_token(name) {
+                return None;
+            }
+            let value = parse_value(value.trim())?;
+            params.push((name.to_ascii_lowercase(), value));
+        }
+        Some(MediaType {
+            type_: type_.to_ascii_lowercase(),
+            subtype: subtype.to_ascii_lowercase(),
+            params,
+        })
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Whether this is `type_/subtype` with the version, where no version means the latest.
+    pub fn is(&self, type_: &str, subtype: &str, version: &str) -> bool {
+        self.type_ == type_
+            && self.subtype == subtype
+            && self.param("version").is_none_or(|v| v == version)
+    }
+}
+
+/// A media range of an Accept header with its weight, e.g. `application/*;q=0.5`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MediaRange {
+    /// The range; its type or subtype may be `*`. Parameters after `q` are dropped.
+    pub media_type: MediaType,
+    pub q: f32,
+}
+
+impl MediaRange {
+    /// How specifically the range matches the media type, None when it does not. A range
+    /// without a version matches any version.
+    fn matches(&self, v: &MediaType) -> Option<usize> {
+        let range = &self.media_type;
+        let specificity = match (range.type_.as_str(), range.subtype.as_str()) {
+            ("*", "*") => 0,
+            ("*", _) => return None,
+            (type_, "*") if type_ == v.type_ => 1,
+            (type_, subtype) if type_ == v.type_ && subtype == v.subtype => 2,
+            _ => return None,
+        };
+        for (name, value) in range.params.iter() {
+            if v.param(name) != Some(value.as_str()) {
+                return None;
+            }
+        }
+        Some(specificity + range.params.len())
+    }
+}
+
+fn parse_q(v: &str) -> Option<f32> {
+    let valid = match v.split_once('.') {
+        Some((int, frac)) => {
+            (int == "0" || int == "1")
+                && frac.len() <= 3
+                && frac.bytes().all(|b| b.is_ascii_digit())
+        }
+        None => v == "0" || v == "1",
+    };
+    match v.parse::<f32>() {
+        Ok(q) if valid && q <= 1.0 => Some(q),
+        _ => None,
+    }
+}
+
+/// The media ranges of an Accept header (RFC 7231 section 5.3.2). Malformed ranges are skipped.
+pub fn parse_accept(v: &str) -> Vec<MediaRange> {
+    let mut ranges = Vec::<MediaRange>::new();
+    for element in split_unquoted(v, ',') {
+        if element.trim().is_empty() {
+            continue;
+        }
+        let mut media_type = match MediaType::parse(element) {
+            Some(v) => v,
+            None => continue,
+        };
+        let mut q = Some(1.0);
+        if let Some(pos) = media_type.params.iter().position(|(k, _)| k == "q") {
+            q = parse_q(media_type.params[pos].1.as_str());
+            media_type.params.truncate(pos);
+        }
+        if let Some(q) = q {
+            ranges.push(MediaRange { media_type, q });
+        }
+    }
+    ranges
+}
+
+/// The offer the Accept header prefers: each offer gets the weight of the most specific range
+/// matching it, and the first offer with the highest weight above 0 wins.
+pub fn negotiate<'a>(accept: &str, offers: &'a [MediaType]) -> Option<&'a MediaType> {
+    let ranges = parse_accept(accept);
+    let mut best: Option<(&MediaType, f32)> = None;
+    for offer in offers.iter() {
+        let q = ranges
+            .iter()
+            .filter_map(|range| range.matches(offer).map(|v| (v, range.q)))
+            .max_by_key(|(specificity, _)| *specificity)
+            .map_or(0.0, |(_, q)| q);
+        if q > 0.0 && best.is_none_or(|(_, best)| q > best) {
+            best = Some((offer, q));
+        }
+    }
+    best.map(|(offer, _)| offer)
+}
+
+/// The parts of a header value between the separators, which may also appear in quoted strings.
+fn split_unquoted(v: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::<&str>::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in v.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if quoted && c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            quoted = !quoted;
+        } else if c == separator && !quoted {
+            parts.push(&v[start..i]);
+            start = i + c.len_utf8();
+        }
+    }
+    parts.push(&v[start..]);
+    parts
+}
+
+/// A parameter value: a token, or a quoted string with backslash escapes (RFC 7230 section
+/// 3.2.6).
+fn parse_value(v: &str) -> Option<String> {
+    let quoted = match v.strip_prefix('"') {
+        Some(v) => v,
+        None if is_token(v) => return Some(String::from(v)),
+        None => return None,
+    };
+    let mut value = String::new();
+    let mut chars = quoted.chars();
+    loop {
+        match chars.next()? {
+            '"' => break,
+            '\\' => value.push(chars.next()?),
+            c => value.push(c),
+        }
+    }
+    match chars.next() {
+        Some(_) => None,
+        None => Some(value),
+    }
+}
+
+fn is_token(v: &str) -> bool {
+    !v.is_empty()
+        && v.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{negotiate, parse_accept, MediaType};
+
+    #[test]
+    fn test_media_type() {
+        let v = MediaType::parse("Application/TAXII+json ; Version=\"2.1\"").unwrap();
+        assert_eq!("application", v.type_);
+        assert_eq!("taxii+json", v.subtype);
+        assert_eq!(Some("2.1"), v.param("version"));
+        assert!(v.is("application", "taxii+json", "2.1"));
+        assert!(!v.is("application", "taxii+json", "2.0"));
+        let v = MediaType::parse("application/taxii+json").unwrap();
+        assert!(v.is("application", "taxii+json", "2.1"));
+        assert!(!v.is("application", "json", "2.1"));
+        for bad in [
+            "",
+            "application",
+            "application/",
+            "/json",
+            "a b/json",
+            "a/json;v",
+            "a/json;v=",
+            "a/json;v=a b",
+            "a/json;v=\"2.1",
+            "a/json;v=\"2\".1",
+        ] {
+            assert!(MediaType::parse(bad).is_none(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_quoted_string() {
+        let v = MediaType::parse(r#"a/json;x="a;b=c";version="2.1""#).unwrap();
+        assert_eq!(Some("a;b=c"), v.param("x"));
+        assert_eq!(Some("2.1"), v.param("version"));
+        let v = MediaType::parse(r#"a/json;x="say \"hi\", \\o/""#).unwrap();
+        assert_eq!(Some(r#"say "hi", \o/"#), v.param("x"));
+
+        // separators in quoted strings do not split the Accept header
+        let ranges = parse_accept(r#"application/taxii+json;version="2.1,x";q=0.5, a/b"#);
+        assert_eq!(2, ranges.len());
+        assert_eq!(Some("2.1,x"), ranges[0].media_type.param("version"));
+        assert_eq!(0.5, ranges[0].q);
+        assert_eq!("b", ranges[1].media_type.subtype);
+        let taxii = MediaType::parse("application/taxii+json;version=2.1").unwrap();
+        assert_eq!(
+            None,
+            negotiate(r#"application/taxii+json;version="2.1,x""#, &[taxii])
+        );
+    }
+
+    #[test]
+    fn test_parse_accept() {
+        let ranges = parse_accept(
+            " application/taxii+json;version=2.1 , application/*;q=0.5, */*; q=0.1 ;ext=1,,bad, a/b;q=2",
+        );
+        let ranges: Vec<(String, String, usize, f32)> = ranges
+            .iter()
+            .map(|v| {
+                (
+                    v.media_type.type_.clone(),
+                    v.media_type.subtype.clone(),
+                    v.media_type.params.len(),
+                    v.q,
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    String::from("application"),
+                    String::from("taxii+json"),
+                    1,
+                    1.0
+                ),
+                (String::from("application"), String::from("*"), 0, 0.5),
+                (String::from("*"), String::from("*"), 0, 0.1),
+            ],
+            ranges
+        );
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let taxii = MediaType::parse("application/taxii+json;version=2.1").unwrap();
+        let stix = MediaType::parse("application/stix+json;version=2.1").unwrap();
+        let offers = vec![taxii.clone(), stix.clone()];
+        let cases = [
+            ("application/taxii+json;version=2.1", Some(&taxii)),
+            ("application/taxii+json", Some(&taxii)),
+            ("application/stix+json", Some(&stix)),
+            ("*/*", Some(&taxii)),
+            ("application/*", Some(&taxii)),
+            (
+                "application/taxii+json;q=0.5, application/stix+json",
+                Some(&stix),
+            ),
+            (
+                "application/*;q=0.5, application/stix+json;q=0.8",
+                Some(&stix),
+            ),
+            // the most specific range applies
+            ("application/*, application/taxii+json;q=0", Some(&stix)),
+            ("application/taxii+json;version=2.0", None),
+            ("application/json, text/html", None),
+            ("*/*;q=0", None),
+            ("", None),
+        ];
+        for (accept, expected) in cases {
+            assert_eq!(expected, negotiate(accept, &offers), "{}", accept);
+        }
+        assert_eq!(None, negotiate("application/stix+json", &offers[..1]));
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+
+use chrono::{SubsecRound, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{
+    backend::{compare_versions, select_objects, Backend, Filtering},
+    errors::MyError,
+    server::{ManifestRecord, Object, Status, StatusDetails},
+};
+
+/// The objects of a collection together with their manifest records.
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct CollectionObjects {
+    pub objects: Vec<Object>,
+    pub manifest: Vec<ManifestRecord>,
+}
+
+impl CollectionObjects {
+    pub fn get_manifests(&self, filtering: &Filtering) -> Vec<ManifestRecord> {
+        filtering.apply(&self.manifest, "last")
+    }
+    pub fn get_objects(&self, filtering: &Filtering) -> Vec<Object> {
+        select_objects(&self.objects, &self.get_manifests(filtering))
+    }
+    pub fn add_objects(&mut self, objects: &[Object]) -> Status {
+        // date_added is exchanged with microseconds, e.g. in X-TAXII-Date-Added-Last: keep no
+        // more for an added_after taken from a header to exclude the record it came from
+        let now = Utc::now().trunc_subsecs(6);
+        let mut status = Status::new(Uuid::new_v4().to_string().as_str());
+        status.status = String::from("complete");
+        status.request_timestamp = Some(now);
+        status.total_count = objects.len() as u32;
+        let mut successes = Vec::<StatusDetails>::new();
+        let mut failures = Vec::<StatusDetails>::new();
+        for object in objects.iter() {
+            let details = StatusDetails {
+                id: object.id.clone(),
+                version: String::from(object.version()),
+                message: None,
+            };
+            let exists = self.manifest.iter().any(|rec| {
+                rec.id == object.id && compare_versions(&rec.version, object.version()).is_eq()
+            });
+            if exists {
+                failures.push(StatusDetails {
+                    message: Some(String::from("object version already exists")),
+                    ..details
+                });
+                continue;
+            }
+            self.objects.push(object.clone());
+            self.manifest.push(ManifestRecord::new(object, now));
+            successes.push(details);
+        }
+        status.success_count = successes.len() as u32;
+        status.failure_count = failures.len() as u32;
+        if !successes.is_empty() {
+            status.successes = Some(successes);
+        }
+        if !failures.is_empty() {
+            status.failures = Some(failures);
+        }
+        status
+    }
+    pub fn delete_object(&mut self, object_id: &str, filtering: &Filtering) -> usize {
+        let deleted: Vec<ManifestRecord> = filtering
+            .apply(&self.manifest, "all")
+            .into_iter()
+            .filter(|rec| rec.id == object_id)
+            .collect();
+        let is_deleted = |id: &str, version: &str| {
+            deleted
+                .iter()
+                .any(|rec| rec.id == id && compare_versions(&rec.version, version).is_eq())
+        };
+        self.manifest
+            .retain(|rec| !is_deleted(rec.id.as_str(), rec.version.as_str()));
+        self.objects
+            .retain(|object| !is_deleted(object.id.as_str(), object.version()));
+        deleted.len()
+    }
+}
+
+/// A backend that keeps collections in memory, for tests and for servers that do not need
+/// persistence.
+pub struct MemoryBackend {
+    collections: HashMap<String, CollectionObjects>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> MemoryBackend {
+        MemoryBackend {
+            collections: HashMap::<String, CollectionObjects>::new(),
+        }
+    }
+    pub fn add_collection(&mut self, collection_id: &str) {
+        self.collections
+            .entry(String::from(collection_id))
+            .or_default();
+    }
+    fn get_collection(&self, collection_id: &str) -> Result<&CollectionObjects, MyError> {
+        match self.collections.get(collection_id) {
+            Some(v) => Ok(v),
+            None => Err(MyError(format!(
+                "could not find collection={}",
+                collection_id
+            ))),
+        }
+    }
+    fn get_collection_mut(
+        &mut self,
+        collection_id: &str,
+    ) -> Result<&mut CollectionObjects, MyError> {
+        match self.collections.get_mut(collection_id) {
+            Some(v) => Ok(v),
+            None => Err(MyError(format!(
+                "could not find collection={}",
+                collection_id
+            ))),
+        }
+    }
+}
+
+impl Default for MemoryBackend {
+    fn default() -> Self {
+        MemoryBackend::new()
+    }
+}
+
+impl Backend for MemoryBackend {
+    fn get_collections(&self) -> Result<Vec<String>, MyError> {
+        let mut collection_ids: Vec<String> = self.collections.keys().cloned().collect();
+        collection_ids.sort();
+        Ok(collection_ids)
+    }
+    fn get_manifests(
+        &self,
+        collection_id: &str,
+        filtering: &Filtering,
+    ) -> Result<Vec<ManifestRecord>, MyError> {
+        Ok(self.get_collection(collection_id)?.get_manifests(filtering))
+    }
+    fn get_objects(
+        &self,
+        collection_id: &str,
+        filtering: &Filtering,
+    ) -> Result<Vec<Object>, MyError> {
+        Ok(self.get_collection(collection_id)?.get_objects(filtering))
+    }
+    fn add_objects(&mut self, collection_id: &str, objects: &[Object]) -> Result<Status, MyError> {
+        Ok(self.get_collection_mut(collection_id)?.add_objects(objects))
+    }
+    fn delete_object(
+        &mut self,
+        collection_id: &str,
+        object_id: &str,
+        filtering: &Filtering,
+    ) -> Result<usize, MyError> {
+        Ok(self
+            .get_collection_mut(collection_id)?
+            .delete_object(object_id, filtering))
+    }
+}
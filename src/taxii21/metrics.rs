@@ -0,0 +1,192 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+
+use super::errors::MyError;
+
+/// The route label of requests that matched no route, to keep the labels few.
+pub const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// The metrics of the server, exposed at `/metrics` in the Prometheus text format. Every app
+/// state has its own registry.
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    objects_served: IntCounterVec,
+    objects_added: IntCounterVec,
+    backend_duration: HistogramVec,
+    statuses: IntGaugeVec,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        let requests = IntCounterVec::new(
+            Opts::new(
+                "taxii_http_requests_total",
+                "HTTP requests by route and status.",
+            ),
+            &["route", "method", "status"],
+        )
+        .unwrap();
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "taxii_http_request_duration_seconds",
+                "HTTP request latencies by route.",
+            ),
+            &["route", "method"],
+        )
+        .unwrap();
+        let objects_served = IntCounterVec::new(
+            Opts::new(
+                "taxii_objects_served_total",
+                "STIX objects served by collection.",
+            ),
+            &["api_root", "collection"],
+        )
+        .unwrap();
+        let objects_added = IntCounterVec::new(
+            Opts::new(
+                "taxii_objects_added_total",
+                "STIX objects added by collection.",
+            ),
+            &["api_root", "collection"],
+        )
+        .unwrap();
+        let backend_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "taxii_backend_operation_duration_seconds",
+                "Backend operation latencies.",
+            ),
+            &["operation"],
+        )
+        .unwrap();
+        let statuses = IntGaugeVec::new(
+            Opts::new("taxii_status_resources", "Status resources by state."),
+            &["status"],
+        )
+        .unwrap();
+        let registry = Registry::new();
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry
+            .register(Box::new(request_duration.clone()))
+            .unwrap();
+        registry.register(Box::new(objects_served.clone())).unwrap();
+        registry.register(Box::new(objects_added.clone())).unwrap();
+        registry
+            .register(Box::new(backend_duration.clone()))
+            .unwrap();
+        registry.register(Box::new(statuses.clone())).unwrap();
+        Metrics {
+            registry,
+            requests,
+            request_duration,
+            objects_served,
+            objects_added,
+            backend_duration,
+            statuses,
+        }
+    }
+
+    pub fn observe_request(&self, route: &str, method: &str, status: u16, duration: Duration) {
+        self.requests
+            .with_label_values(&[route, method, status.to_string().as_str()])
+            .inc();
+        self.request_duration
+            .with_label_values(&[route, method])
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn add_objects_served(&self, api_root: &str, collection_id: &str, count: u64) {
+        self.objects_served
+            .with_label_values(&[api_root, collection_id])
+            .inc_by(count);
+    }
+
+    pub fn add_objects_added(&self, api_root: &str, collection_id: &str, count: u64) {
+        self.objects_added
+            .with_label_values(&[api_root, collection_id])
+            .inc_by(count);
+    }
+
+    /// Run a backend operation, observing how long it takes.
+    pub fn time_backend<T>(&self, operation: &str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.backend_duration
+            .with_label_values(&[operation])
+            .observe(start.elapsed().as_secs_f64());
+        result
+    }
+
+    /// Set the number of status resources of each state, dropping the states no longer seen.
+    pub fn set_status_counts(&self, counts: &HashMap<String, i64>) {
+        self.statuses.reset();
+        for (status, count) in counts.iter() {
+            self.statuses
+                .with_label_values(&[status.as_str()])
+                .set(*count);
+        }
+    }
+
+    /// The metrics in the Prometheus text format.
+    pub fn encode(&self) -> Result<String, MyError> {
+        let mut buffer = Vec::<u8>::new();
+        if let Err(err) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            return Err(MyError(err.to_string()));
+        }
+        match String::from_utf8(buffer) {
+            Ok(v) => Ok(v),
+            Err(err) => Err(MyError(err.to_string())),
+        }
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, time::Duration};
+
+    use super::Metrics;
+
+    #[test]
+    fn test_metrics() {
+        let metrics = Metrics::new();
+        metrics.observe_request("/taxii2", "GET", 200, Duration::from_millis(20));
+        metrics.observe_request("/taxii2", "GET", 200, Duration::from_millis(30));
+        metrics.add_objects_served("api1", "collection1", 3);
+        assert_eq!(42, metrics.time_backend("get_objects", || 42));
+        let mut counts = HashMap::<String, i64>::new();
+        counts.insert(String::from("pending"), 2);
+        metrics.set_status_counts(&counts);
+        counts.clear();
+        counts.insert(String::from("complete"), 1);
+        metrics.set_status_counts(&counts);
+
+        let text = metrics.encode().unwrap();
+        assert!(text.contains(
+            "taxii_http_requests_total{method=\"GET\",route=\"/taxii2\",status=\"200\"} 2"
+        ));
+        assert!(text.contains(
+            "taxii_http_request_duration_seconds_count{method=\"GET\",route=\"/taxii2\"} 2"
+        ));
+        assert!(text.contains(
+            "taxii_objects_served_total{api_root=\"api1\",collection=\"collection1\"} 3"
+        ));
+        assert!(text.contains(
+            "taxii_backend_operation_duration_seconds_count{operation=\"get_objects\"} 1"
+        ));
+        assert!(text.contains("taxii_status_resources{status=\"complete\"} 1"));
+        assert!(!text.contains("taxii_status_resources{status=\"pending\"}"));
+    }
+}
@@ -1,17 +1,84 @@
 use lazy_static::lazy_static;
-use regex::Regex;
-use std::future::{ready, Ready};
+use std::{
+    future::{ready, Ready},
+    rc::Rc,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use actix_web::{
     body::EitherBody,
     dev::{self, Service, ServiceRequest, ServiceResponse, Transform},
-    Error, HttpResponse,
+    http::{
+        header::{HeaderName, HeaderValue, WWW_AUTHENTICATE},
+        Method, StatusCode,
+    },
+    web, Error, HttpMessage, HttpResponse,
 };
 use futures_util::future::LocalBoxFuture;
+use tracing::{field, info, info_span, warn, Instrument};
+use uuid::Uuid;
+
+use super::{
+    api_keys::ApiKeyStore,
+    auth::{parse_basic_credentials, parse_bearer_token, Principal, UserStore},
+    media_type::{negotiate, MediaType},
+    metrics::{Metrics, UNMATCHED_ROUTE},
+    rate_limit::{consumer, too_many_requests, RateLimiter},
+    server::{taxii_error, CONTENT_TYPE_STIX2, CONTENT_TYPE_TAXII2},
+    tls::ClientCertificate,
+};
+
+static SUPPORTED_TAXII_VERSION: &str = "2.1";
+
+lazy_static! {
+    static ref TAXII_OFFERS: Vec<MediaType> = vec![MediaType::parse(CONTENT_TYPE_TAXII2).unwrap()];
+    static ref OBJECT_OFFERS: Vec<MediaType> = vec![
+        MediaType::parse(CONTENT_TYPE_TAXII2).unwrap(),
+        MediaType::parse(CONTENT_TYPE_STIX2).unwrap(),
+    ];
+}
+
+/// The media types the endpoint of a request may respond with, in order of preference. Getting
+/// objects may also respond with a STIX bundle.
+fn offers(method: &Method, path: &str) -> &'static [MediaType] {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let objects = matches!(
+        segments.as_slice(),
+        [_, "collections", _, "objects"] | [_, "collections", _, "objects", _]
+    );
+    if *method == Method::GET && objects {
+        &OBJECT_OFFERS
+    } else {
+        &TAXII_OFFERS
+    }
+}
+
+/// Negotiates the media type of the response with the Accept header (RFC 7231 section 5.3.2),
+/// responding 406 when none of the media types of the endpoint is acceptable. The chosen
+/// `MediaType` is put in the request extensions. Requests to the skipped paths, such as health
+/// checks, pass unchecked.
+pub struct CheckAcceptHeader {
+    skip_paths: Vec<String>,
+}
 
-static SUPPORTED_TAXII_VERSION: &'static str = "2.1";
+impl CheckAcceptHeader {
+    pub fn new() -> CheckAcceptHeader {
+        CheckAcceptHeader {
+            skip_paths: Vec::<String>::new(),
+        }
+    }
+    pub fn with_skip_path(mut self, path: &str) -> CheckAcceptHeader {
+        self.skip_paths.push(String::from(path));
+        self
+    }
+}
 
-pub struct CheckAcceptHeader;
+impl Default for CheckAcceptHeader {
+    fn default() -> Self {
+        CheckAcceptHeader::new()
+    }
+}
 
 impl<S, B> Transform<S, ServiceRequest> for CheckAcceptHeader
 where
@@ -26,11 +93,15 @@ where
     type Future = Ready<Result<Self::Transform, Self::InitError>>;
 
     fn new_transform(&self, service: S) -> Self::Future {
-        ready(Ok(CheckAcceptHeaderMiddleware { service }))
+        ready(Ok(CheckAcceptHeaderMiddleware {
+            service,
+            skip_paths: self.skip_paths.clone(),
+        }))
     }
 }
 pub struct CheckAcceptHeaderMiddleware<S> {
     service: S,
+    skip_paths: Vec<String>,
 }
 
 impl<S, B> Service<ServiceRequest> for CheckAcceptHeaderMiddleware<S>
@@ -46,37 +117,639 @@ where
     dev::forward_ready!(service);
 
     fn call(&self, request: ServiceRequest) -> Self::Future {
-        lazy_static! {
-            static ref RE: Regex =
-                Regex::new(r"^application/taxii\+json(;version=(\d\.\d))?$").unwrap();
-        }
-        let valid_accept_header = match request.headers().get("accept") {
-            Some(v) => match v.to_str() {
-                Ok(v) => match RE.captures(v) {
-                    Some(c) => {
-                        let ver = c.get(2).map_or("", |m| m.as_str());
-                        ver == SUPPORTED_TAXII_VERSION
-                    }
-                    None => false,
+        if !self.skip_paths.iter().any(|v| v == request.path()) {
+            let offers = offers(request.method(), request.path());
+            // TAXII clients must send an Accept header, so a missing one is not taken as */*
+            let media_type = match request.headers().get("accept") {
+                Some(v) => match v.to_str() {
+                    Ok(v) => negotiate(v, offers).cloned(),
+                    Err(_) => None,
                 },
-                Err(err) => false,
-            },
+                None => None,
+            };
+            match media_type {
+                Some(v) => {
+                    request.extensions_mut().insert(v);
+                }
+                None => {
+                    let (request, _pl) = request.into_parts();
+                    let response = HttpResponse::NotAcceptable()
+                        .finish()
+                        // constructed responses map to "right" body
+                        .map_into_right_body();
+
+                    return Box::pin(async { Ok(ServiceResponse::new(request, response)) });
+                }
+            }
+        }
+
+        let res = self.service.call(request);
+        Box::pin(async move {
+            // forwarded responses map to "left" body
+            res.await.map(ServiceResponse::map_into_left_body)
+        })
+    }
+}
+
+/// Requires `application/taxii+json;version=2.1` as the Content-Type of requests with a body,
+/// responding 415 otherwise.
+pub struct CheckContentType;
+
+impl<S, B> Transform<S, ServiceRequest> for CheckContentType
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = CheckContentTypeMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CheckContentTypeMiddleware { service }))
+    }
+}
+pub struct CheckContentTypeMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for CheckContentTypeMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    dev::forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let has_body = matches!(
+            *request.method(),
+            Method::POST | Method::PUT | Method::PATCH
+        );
+        let content_type = request
+            .headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+            .and_then(MediaType::parse);
+        let valid_content_type = match content_type {
+            Some(v) => v.is("application", "taxii+json", SUPPORTED_TAXII_VERSION),
             None => false,
         };
-        if !valid_accept_header {
+        if has_body && !valid_content_type {
             let (request, _pl) = request.into_parts();
-            let response = HttpResponse::NotAcceptable()
-                .finish()
-                // constructed responses map to "right" body
-                .map_into_right_body();
-
+            let response = taxii_error(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "unsupported media type",
+                Some("the request body must be application/taxii+json;version=2.1"),
+            )
+            .map_into_right_body();
             return Box::pin(async { Ok(ServiceResponse::new(request, response)) });
         }
 
+        let res = self.service.call(request);
+        Box::pin(async move { res.await.map(ServiceResponse::map_into_left_body) })
+    }
+}
+
+/// Limits the requests of each consumer of a rate limited api root, responding 429 with
+/// Retry-After once its token bucket is empty. Runs after `Authenticate`, as the consumer is the
+/// authenticated principal, or else the client address.
+pub struct RateLimit {
+    rate_limiter: Option<Arc<RateLimiter>>,
+}
+
+impl RateLimit {
+    pub fn new(rate_limiter: Option<Arc<RateLimiter>>) -> RateLimit {
+        RateLimit { rate_limiter }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RateLimitMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service,
+            rate_limiter: self.rate_limiter.clone(),
+        }))
+    }
+}
+pub struct RateLimitMiddleware<S> {
+    service: S,
+    rate_limiter: Option<Arc<RateLimiter>>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    dev::forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            let api_root = api_root(request.path());
+            if rate_limiter.is_limited(api_root) {
+                let consumer =
+                    consumer(request.extensions().get::<Principal>(), request.peer_addr());
+                if let Err(retry_after) = rate_limiter.check(api_root, consumer.as_str()) {
+                    let (request, _pl) = request.into_parts();
+                    let response =
+                        too_many_requests(retry_after, "the request rate limit is exceeded")
+                            .map_into_right_body();
+                    return Box::pin(async { Ok(ServiceResponse::new(request, response)) });
+                }
+            }
+        }
+
+        let res = self.service.call(request);
+        Box::pin(async move { res.await.map(ServiceResponse::map_into_left_body) })
+    }
+}
+
+/// The api root a path is in, or the first segment of paths outside api roots.
+fn api_root(path: &str) -> &str {
+    let path = path.trim_start_matches('/');
+    path.split('/').next().unwrap_or_default()
+}
+
+/// The header with the id of a request, taken from the request when it has one.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// The id of a request: the X-Request-ID it came with when that is usable as such, or a new one.
+fn request_id(request: &ServiceRequest) -> String {
+    let incoming = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok());
+    match incoming {
+        Some(v) if !v.is_empty() && v.len() <= 128 && v.bytes().all(|b| b.is_ascii_graphic()) => {
+            String::from(v)
+        }
+        _ => Uuid::new_v4().to_string(),
+    }
+}
+
+/// Runs each request in a `request` span with its id, principal, api root, collection, query
+/// and result count, and logs its status and duration once done. The id is returned in the
+/// X-Request-ID header of the response.
+pub struct TraceRequest;
+
+impl<S, B> Transform<S, ServiceRequest> for TraceRequest
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = TraceRequestMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(TraceRequestMiddleware { service }))
+    }
+}
+pub struct TraceRequestMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for TraceRequestMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    dev::forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let start = Instant::now();
+        let request_id = request_id(&request);
+        // the fields not known yet are recorded by the handlers or once the response is ready
+        let span = info_span!(
+            "request",
+            request_id = request_id.as_str(),
+            method = %request.method(),
+            path = request.path(),
+            query = request.query_string(),
+            principal = field::Empty,
+            api_root = field::Empty,
+            collection = field::Empty,
+            count = field::Empty,
+        );
+        let res = {
+            let _enter = span.enter();
+            self.service.call(request)
+        };
+        Box::pin(async move {
+            let res = res.instrument(span.clone()).await;
+            let duration_ms = start.elapsed().as_millis() as u64;
+            let mut res = match res {
+                Ok(v) => v,
+                Err(err) => {
+                    span.in_scope(|| warn!(error = %err, duration_ms, "request failed"));
+                    return Err(err);
+                }
+            };
+            {
+                let request = res.request();
+                if let Some(principal) = request.extensions().get::<Principal>() {
+                    span.record("principal", principal.username.as_str());
+                }
+                if let Some(v) = request.match_info().get("api_root") {
+                    span.record("api_root", v);
+                }
+                if let Some(v) = request.match_info().get("collection_id") {
+                    span.record("collection", v);
+                }
+            }
+            let status = res.status().as_u16();
+            span.in_scope(|| info!(status, duration_ms, "request completed"));
+            if let Ok(v) = HeaderValue::from_str(request_id.as_str()) {
+                res.headers_mut()
+                    .insert(HeaderName::from_static(REQUEST_ID_HEADER), v);
+            }
+            Ok(res)
+        })
+    }
+}
+
+/// Counts the requests by route, method and status, and observes their latencies.
+pub struct RecordMetrics {
+    metrics: Arc<Metrics>,
+}
+
+impl RecordMetrics {
+    pub fn new(metrics: Arc<Metrics>) -> RecordMetrics {
+        RecordMetrics { metrics }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RecordMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RecordMetricsMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RecordMetricsMiddleware {
+            service,
+            metrics: self.metrics.clone(),
+        }))
+    }
+}
+pub struct RecordMetricsMiddleware<S> {
+    service: S,
+    metrics: Arc<Metrics>,
+}
+
+impl<S, B> Service<ServiceRequest> for RecordMetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    dev::forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let start = Instant::now();
+        // the route pattern rather than the path, to keep the labels few
+        let route = request
+            .match_pattern()
+            .unwrap_or_else(|| String::from(UNMATCHED_ROUTE));
+        let method = request.method().to_string();
+        let metrics = self.metrics.clone();
         let res = self.service.call(request);
         Box::pin(async move {
-            // forwarded responses map to "left" body
-            res.await.map(ServiceResponse::map_into_left_body)
+            let res = res.await;
+            let status = match &res {
+                Ok(v) => v.status(),
+                Err(err) => err.as_response_error().status_code(),
+            };
+            metrics.observe_request(
+                route.as_str(),
+                method.as_str(),
+                status.as_u16(),
+                start.elapsed(),
+            );
+            res
+        })
+    }
+}
+
+/// Requires HTTP Basic credentials of a user in the user store or a bearer API key, and makes
+/// the authenticated `auth::Principal` available to handlers. Requests over a connection with a
+/// verified client certificate are authenticated by it, and may not also carry credentials.
+/// With a rate limiter, failed attempts count against the bucket of the client address, and
+/// clients with an empty bucket get 429 before their credentials are checked. Requests pass
+/// unauthenticated when neither store is configured, or when they are to one of the skipped
+/// paths.
+pub struct Authenticate {
+    users: Option<Arc<UserStore>>,
+    api_keys: Option<Arc<ApiKeyStore>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    skip_paths: Vec<String>,
+}
+
+impl Authenticate {
+    pub fn new(users: Option<Arc<UserStore>>, api_keys: Option<Arc<ApiKeyStore>>) -> Authenticate {
+        Authenticate {
+            users,
+            api_keys,
+            rate_limiter: None,
+            skip_paths: Vec::<String>::new(),
+        }
+    }
+    pub fn with_rate_limiter(mut self, rate_limiter: Option<Arc<RateLimiter>>) -> Authenticate {
+        self.rate_limiter = rate_limiter;
+        self
+    }
+    pub fn with_skip_path(mut self, path: &str) -> Authenticate {
+        self.skip_paths.push(String::from(path));
+        self
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Authenticate
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = AuthenticateMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AuthenticateMiddleware {
+            service: Rc::new(service),
+            users: self.users.clone(),
+            api_keys: self.api_keys.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+            skip_paths: self.skip_paths.clone(),
+        }))
+    }
+}
+pub struct AuthenticateMiddleware<S> {
+    service: Rc<S>,
+    users: Option<Arc<UserStore>>,
+    api_keys: Option<Arc<ApiKeyStore>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    skip_paths: Vec<String>,
+}
+
+static BASIC_CHALLENGE: &str = "Basic realm=\"TAXII\", charset=\"UTF-8\"";
+static BEARER_CHALLENGE: &str = "Bearer realm=\"TAXII\"";
+
+/// The credentials of a request, as accepted by the configured stores.
+enum Credentials {
+    Basic(String, String),
+    Bearer(String),
+}
+
+/// A 401 response with a TAXII error, and the challenges of the accepted schemes.
+fn unauthorized(
+    request: ServiceRequest,
+    challenges: &[&'static str],
+    description: &str,
+) -> ServiceResponse {
+    let (request, _pl) = request.into_parts();
+    let mut response = taxii_error(
+        StatusCode::UNAUTHORIZED,
+        "authentication required",
+        Some(description),
+    );
+    for challenge in challenges {
+        response
+            .headers_mut()
+            .append(WWW_AUTHENTICATE, HeaderValue::from_static(challenge));
+    }
+    ServiceResponse::new(request, response)
+}
+
+/// The bucket failed authentication attempts of a client count against: that of its address,
+/// in the api root of the request.
+struct FailedAttempts {
+    rate_limiter: Arc<RateLimiter>,
+    api_root: String,
+    consumer: String,
+}
+
+impl FailedAttempts {
+    fn new(rate_limiter: &Option<Arc<RateLimiter>>, request: &ServiceRequest) -> Option<Self> {
+        let rate_limiter = rate_limiter.as_ref()?;
+        let api_root = api_root(request.path());
+        if !rate_limiter.is_limited(api_root) {
+            return None;
+        }
+        Some(FailedAttempts {
+            rate_limiter: rate_limiter.clone(),
+            api_root: String::from(api_root),
+            consumer: consumer(None, request.peer_addr()),
         })
     }
+
+    /// How long until the client may try again, when it has used up its attempts.
+    fn blocked(&self) -> Option<Duration> {
+        self.rate_limiter
+            .peek(self.api_root.as_str(), self.consumer.as_str())
+            .err()
+    }
+
+    fn add(&self) {
+        // an empty bucket is told on the next attempt
+        let _ = self
+            .rate_limiter
+            .check(self.api_root.as_str(), self.consumer.as_str());
+    }
+}
+
+impl<S> AuthenticateMiddleware<S> {
+    fn challenges(&self) -> Vec<&'static str> {
+        let mut challenges = Vec::<&'static str>::new();
+        if self.users.is_some() {
+            challenges.push(BASIC_CHALLENGE);
+        }
+        if self.api_keys.is_some() {
+            challenges.push(BEARER_CHALLENGE);
+        }
+        challenges
+    }
+
+    fn credentials(&self, request: &ServiceRequest) -> Option<Credentials> {
+        let v = match request.headers().get("authorization") {
+            Some(v) => match v.to_str() {
+                Ok(v) => v,
+                Err(err) => return None,
+            },
+            None => return None,
+        };
+        if self.users.is_some() {
+            if let Some((username, password)) = parse_basic_credentials(v) {
+                return Some(Credentials::Basic(username, password));
+            }
+        }
+        if self.api_keys.is_some() {
+            if let Some(token) = parse_bearer_token(v) {
+                return Some(Credentials::Bearer(token));
+            }
+        }
+        None
+    }
+}
+
+impl<S, B> Service<ServiceRequest> for AuthenticateMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    dev::forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let certificate = request.conn_data::<ClientCertificate>().cloned();
+        if let Some(certificate) = certificate {
+            // a request authenticated by its connection does not also carry credentials
+            if request.headers().contains_key("authorization") {
+                let (request, _pl) = request.into_parts();
+                let response = taxii_error(
+                    StatusCode::BAD_REQUEST,
+                    "conflicting credentials",
+                    Some("the connection is authenticated by a client certificate"),
+                );
+                let response = ServiceResponse::new(request, response).map_into_right_body();
+                return Box::pin(async { Ok(response) });
+            }
+            request.extensions_mut().insert(Principal {
+                username: certificate.username,
+                scopes: certificate.scopes,
+            });
+            return Box::pin(async move {
+                service
+                    .call(request)
+                    .await
+                    .map(ServiceResponse::map_into_left_body)
+            });
+        }
+        let skipped = self.skip_paths.iter().any(|v| v == request.path());
+        if skipped || (self.users.is_none() && self.api_keys.is_none()) {
+            return Box::pin(async move {
+                service
+                    .call(request)
+                    .await
+                    .map(ServiceResponse::map_into_left_body)
+            });
+        }
+        let failed_attempts = FailedAttempts::new(&self.rate_limiter, &request);
+        if let Some(retry_after) = failed_attempts.as_ref().and_then(|v| v.blocked()) {
+            let (request, _pl) = request.into_parts();
+            let response =
+                too_many_requests(retry_after, "too many failed authentication attempts")
+                    .map_into_right_body();
+            return Box::pin(async { Ok(ServiceResponse::new(request, response)) });
+        }
+        let challenges = self.challenges();
+        let principal = match self.credentials(&request) {
+            Some(Credentials::Bearer(token)) => match &self.api_keys {
+                Some(api_keys) => api_keys.authenticate(token.as_str()),
+                None => None,
+            },
+            Some(Credentials::Basic(username, password)) => {
+                let users = self.users.clone().unwrap();
+                return Box::pin(async move {
+                    // hashing the password takes a while, keep it off the worker thread
+                    let principal = web::block(move || {
+                        users.authenticate(username.as_str(), password.as_str())
+                    })
+                    .await?;
+                    match principal {
+                        Some(principal) => {
+                            request.extensions_mut().insert(principal);
+                            service
+                                .call(request)
+                                .await
+                                .map(ServiceResponse::map_into_left_body)
+                        }
+                        None => {
+                            if let Some(failed_attempts) = failed_attempts {
+                                failed_attempts.add();
+                            }
+                            Ok(
+                                unauthorized(request, &challenges, "invalid username or password")
+                                    .map_into_right_body(),
+                            )
+                        }
+                    }
+                });
+            }
+            None => {
+                if let Some(failed_attempts) = failed_attempts {
+                    failed_attempts.add();
+                }
+                let response =
+                    unauthorized(request, &challenges, "missing or malformed credentials");
+                return Box::pin(async { Ok(response.map_into_right_body()) });
+            }
+        };
+        match principal {
+            Some(principal) => {
+                request.extensions_mut().insert(principal);
+                Box::pin(async move {
+                    service
+                        .call(request)
+                        .await
+                        .map(ServiceResponse::map_into_left_body)
+                })
+            }
+            None => {
+                if let Some(failed_attempts) = failed_attempts {
+                    failed_attempts.add();
+                }
+                let response = unauthorized(request, &challenges, "invalid or revoked api key");
+                Box::pin(async { Ok(response.map_into_right_body()) })
+            }
+        }
+    }
 }
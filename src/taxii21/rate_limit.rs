@@ -0,0 +1,372 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    num::NonZeroUsize,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    http::{header::RETRY_AFTER, StatusCode},
+    HttpResponse,
+};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+
+use super::{auth::Principal, errors::MyError, server::taxii_error};
+
+/// The buckets, and the daily quotas, kept; once there are this many, the least recently used
+/// one is dropped, so its consumer starts again with a full bucket or quota.
+const MAX_BUCKETS: usize = 10000;
+
+/// The request rate and daily object quota of each consumer of an api root, e.g.
+/// ```toml
+/// [[rate_limits]]
+/// api_root = "api1"
+/// requests_per_second = 5.0
+/// burst = 20
+/// daily_objects = 100000
+/// ```
+/// A consumer is the authenticated principal, or the client address of anonymous requests.
+/// Failed authentication attempts count against the bucket of the client address.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RateLimitConfig {
+    pub api_root: String,
+    pub requests_per_second: f64,
+    /// The requests a consumer may make at once after being idle.
+    pub burst: u32,
+    /// The objects a consumer may get per UTC day; unlimited when missing.
+    pub daily_objects: Option<u64>,
+}
+
+/// The token bucket of a consumer.
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// The objects a consumer got on a day.
+struct Quota {
+    day: NaiveDate,
+    objects: u64,
+}
+
+/// The rate limits of the api roots, and the state of their consumers. Api roots without a
+/// configured limit are unlimited.
+pub struct RateLimiter {
+    limits: HashMap<String, RateLimitConfig>,
+    buckets: Mutex<LruCache<(String, String), Bucket>>,
+    quotas: Mutex<LruCache<(String, String), Quota>>,
+}
+
+impl RateLimiter {
+    pub fn new(configs: &[RateLimitConfig]) -> Result<RateLimiter, MyError> {
+        let mut limits = HashMap::<String, RateLimitConfig>::new();
+        for config in configs.iter() {
+            if config.requests_per_second <= 0.0 || config.burst == 0 {
+                return Err(MyError(format!(
+                    "bad rate limit for {}: requests_per_second and burst must be positive",
+                    config.api_root
+                )));
+            }
+            if limits.contains_key(&config.api_root) {
+                return Err(MyError(format!(
+                    "more than one rate limit for {}",
+                    config.api_root
+                )));
+            }
+            limits.insert(config.api_root.clone(), config.clone());
+        }
+        Ok(RateLimiter {
+            limits,
+            buckets: Mutex::new(LruCache::new(NonZeroUsize::new(MAX_BUCKETS).unwrap())),
+            quotas: Mutex::new(LruCache::new(NonZeroUsize::new(MAX_BUCKETS).unwrap())),
+        })
+    }
+
+    pub fn is_limited(&self, api_root: &str) -> bool {
+        self.limits.contains_key(api_root)
+    }
+
+    /// Take a token from the bucket of the consumer, or tell how long until there is one.
+    pub fn check(&self, api_root: &str, consumer: &str) -> Result<(), Duration> {
+        self.check_at(api_root, consumer, Instant::now())
+    }
+
+    fn check_at(&self, api_root: &str, consumer: &str, now: Instant) -> Result<(), Duration> {
+        let limit = match self.limits.get(api_root) {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket =
+            buckets.get_or_insert_mut((String::from(api_root), String::from(consumer)), || {
+                Bucket {
+                    tokens: limit.burst as f64,
+                    updated: now,
+                }
+            });
+        bucket.tokens = refill(limit, bucket, now);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        Err(retry_after(limit, bucket.tokens))
+    }
+
+    /// Like `check`, without taking the token.
+    pub fn peek(&self, api_root: &str, consumer: &str) -> Result<(), Duration> {
+        self.peek_at(api_root, consumer, Instant::now())
+    }
+
+    fn peek_at(&self, api_root: &str, consumer: &str, now: Instant) -> Result<(), Duration> {
+        let limit = match self.limits.get(api_root) {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+        let buckets = self.buckets.lock().unwrap();
+        let tokens = match buckets.peek(&(String::from(api_root), String::from(consumer))) {
+            Some(bucket) => refill(limit, bucket, now),
+            None => return Ok(()),
+        };
+        match tokens >= 1.0 {
+            true => Ok(()),
+            false => Err(retry_after(limit, tokens)),
+        }
+    }
+
+    /// The objects the consumer may still get today, None when there is no quota.
+    pub fn remaining_objects(&self, api_root: &str, consumer: &str) -> Option<u64> {
+        self.remaining_objects_on(api_root, consumer, Utc::now().date_naive())
+    }
+
+    fn remaining_objects_on(&self, api_root: &str, consumer: &str, day: NaiveDate) -> Option<u64> {
+        let quota = self.limits.get(api_root)?.daily_objects?;
+        let quotas = self.quotas.lock().unwrap();
+        match quotas.peek(&(String::from(api_root), String::from(consumer))) {
+            Some(v) if v.day == day => Some(quota.saturating_sub(v.objects)),
+            _ => Some(quota),
+        }
+    }
+
+    /// Count objects the consumer got against its quota of the day.
+    pub fn add_objects(&self, api_root: &str, consumer: &str, count: u64) {
+        self.add_objects_on(api_root, consumer, count, Utc::now().date_naive())
+    }
+
+    fn add_objects_on(&self, api_root: &str, consumer: &str, count: u64, day: NaiveDate) {
+        if self
+            .limits
+            .get(api_root)
+            .is_none_or(|v| v.daily_objects.is_none())
+        {
+            return;
+        }
+        let mut quotas = self.quotas.lock().unwrap();
+        let quota =
+            quotas.get_or_insert_mut((String::from(api_root), String::from(consumer)), || Quota {
+                day,
+                objects: 0,
+            });
+        // the quota of a past day starts again
+        if quota.day != day {
+            *quota = Quota { day, objects: 0 };
+        }
+        quota.objects += count;
+    }
+}
+
+/// The tokens of the bucket at the time, refilled since it was last updated up to the burst.
+fn refill(limit: &RateLimitConfig, bucket: &Bucket, now: Instant) -> f64 {
+    let elapsed = now.saturating_duration_since(bucket.updated);
+    (bucket.tokens + elapsed.as_secs_f64() * limit.requests_per_second).min(limit.burst as f64)
+}
+
+/// The time until a bucket with the tokens has one.
+fn retry_after(limit: &RateLimitConfig, tokens: f64) -> Duration {
+    Duration::from_secs_f64((1.0 - tokens) / limit.requests_per_second)
+}
+
+/// Who a limit applies to: the authenticated principal, or else the client address.
+pub fn consumer(principal: Option<&Principal>, peer_addr: Option<SocketAddr>) -> String {
+    match (principal, peer_addr) {
+        (Some(v), _) => format!("principal:{}", v.username),
+        (None, Some(v)) => format!("ip:{}", v.ip()),
+        (None, None) => String::from("ip:unknown"),
+    }
+}
+
+/// The time until the quotas of the day are reset.
+pub fn until_tomorrow(now: DateTime<Utc>) -> Duration {
+    let tomorrow = now
+        .date_naive()
+        .succ_opt()
+        .and_then(|v| v.and_hms_opt(0, 0, 0));
+    match tomorrow {
+        Some(v) => (Utc.from_utc_datetime(&v) - now)
+            .to_std()
+            .unwrap_or_default(),
+        None => Duration::ZERO,
+    }
+}
+
+/// A 429 response with a TAXII error, telling when to retry in whole seconds.
+pub(crate) fn too_many_requests(retry_after: Duration, description: &str) -> HttpResponse {
+    let mut response = taxii_error(
+        StatusCode::TOO_MANY_REQUESTS,
+        "too many requests",
+        Some(description),
+    );
+    let seconds = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+    response
+        .headers_mut()
+        .insert(RETRY_AFTER, seconds.to_string().parse().unwrap());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+
+    use super::{consumer, until_tomorrow, RateLimitConfig, RateLimiter, MAX_BUCKETS};
+    use crate::taxii21::auth::Principal;
+
+    fn new_limiter(daily_objects: Option<u64>) -> RateLimiter {
+        RateLimiter::new(&[RateLimitConfig {
+            api_root: String::from("api1"),
+            requests_per_second: 2.0,
+            burst: 3,
+            daily_objects,
+        }])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let limiter = new_limiter(None);
+        let now = Instant::now();
+        for _ in 0..3 {
+            assert_eq!(Ok(()), limiter.check_at("api1", "ip:10.0.0.1", now));
+        }
+        assert_eq!(
+            Err(Duration::from_millis(500)),
+            limiter.check_at("api1", "ip:10.0.0.1", now)
+        );
+        // other consumers and api roots have their own buckets
+        assert_eq!(Ok(()), limiter.check_at("api1", "ip:10.0.0.2", now));
+        for _ in 0..10 {
+            assert_eq!(Ok(()), limiter.check_at("api2", "ip:10.0.0.1", now));
+        }
+        let later = now + Duration::from_millis(500);
+        assert_eq!(Ok(()), limiter.check_at("api1", "ip:10.0.0.1", later));
+        assert!(limiter.check_at("api1", "ip:10.0.0.1", later).is_err());
+        // refills up to the burst only
+        let much_later = now + Duration::from_secs(3600);
+        for _ in 0..3 {
+            assert_eq!(Ok(()), limiter.check_at("api1", "ip:10.0.0.1", much_later));
+        }
+        assert!(limiter.check_at("api1", "ip:10.0.0.1", much_later).is_err());
+
+        // peeking does not take a token
+        assert_eq!(Ok(()), limiter.peek_at("api1", "ip:10.0.0.3", now));
+        assert_eq!(Ok(()), limiter.check_at("api1", "ip:10.0.0.2", now));
+        assert_eq!(Ok(()), limiter.peek_at("api1", "ip:10.0.0.2", now));
+        assert_eq!(Ok(()), limiter.check_at("api1", "ip:10.0.0.2", now));
+        assert_eq!(
+            Err(Duration::from_millis(500)),
+            limiter.peek_at("api1", "ip:10.0.0.2", now)
+        );
+        assert_eq!(
+            Err(Duration::from_millis(500)),
+            limiter.peek_at("api1", "ip:10.0.0.2", now)
+        );
+
+        let bad = RateLimitConfig {
+            api_root: String::from("api1"),
+            requests_per_second: 0.0,
+            burst: 1,
+            daily_objects: None,
+        };
+        assert!(RateLimiter::new(&[bad]).is_err());
+    }
+
+    #[test]
+    fn test_max_buckets() {
+        let limiter = new_limiter(None);
+        let now = Instant::now();
+        for _ in 0..3 {
+            assert_eq!(Ok(()), limiter.check_at("api1", "ip:10.0.0.1", now));
+        }
+        for i in 0..MAX_BUCKETS - 1 {
+            let consumer = format!("principal:{}", i);
+            assert_eq!(Ok(()), limiter.check_at("api1", consumer.as_str(), now));
+        }
+        // used again, so kept
+        assert!(limiter.check_at("api1", "ip:10.0.0.1", now).is_err());
+        for i in 0..2 {
+            let consumer = format!("other:{}", i);
+            assert_eq!(Ok(()), limiter.check_at("api1", consumer.as_str(), now));
+        }
+        assert_eq!(MAX_BUCKETS, limiter.buckets.lock().unwrap().len());
+        assert!(limiter.check_at("api1", "ip:10.0.0.1", now).is_err());
+        // the least recently used bucket was dropped
+        assert!(!limiter
+            .buckets
+            .lock()
+            .unwrap()
+            .contains(&(String::from("api1"), String::from("principal:0"))));
+    }
+
+    #[test]
+    fn test_daily_quota() {
+        let limiter = new_limiter(Some(10));
+        let day = NaiveDate::from_ymd_opt(2023, 5, 1).unwrap();
+        let next_day = day.succ_opt().unwrap();
+        assert_eq!(Some(10), limiter.remaining_objects_on("api1", "a", day));
+        limiter.add_objects_on("api1", "a", 7, day);
+        assert_eq!(Some(3), limiter.remaining_objects_on("api1", "a", day));
+        limiter.add_objects_on("api1", "a", 7, day);
+        assert_eq!(Some(0), limiter.remaining_objects_on("api1", "a", day));
+        assert_eq!(Some(10), limiter.remaining_objects_on("api1", "b", day));
+        assert_eq!(
+            Some(10),
+            limiter.remaining_objects_on("api1", "a", next_day)
+        );
+        assert_eq!(None, limiter.remaining_objects_on("api2", "a", day));
+        limiter.add_objects_on("api1", "a", 4, next_day);
+        assert_eq!(Some(6), limiter.remaining_objects_on("api1", "a", next_day));
+        for i in 0..MAX_BUCKETS {
+            let consumer = format!("principal:{}", i);
+            limiter.add_objects_on("api1", consumer.as_str(), 1, next_day);
+        }
+        assert_eq!(MAX_BUCKETS, limiter.quotas.lock().unwrap().len());
+        assert_eq!(
+            Some(10),
+            limiter.remaining_objects_on("api1", "a", next_day)
+        );
+        assert_eq!(
+            None,
+            new_limiter(None).remaining_objects_on("api1", "a", day)
+        );
+
+        let now = Utc.with_ymd_and_hms(2023, 5, 1, 23, 59, 30).unwrap();
+        assert_eq!(Duration::from_secs(30), until_tomorrow(now));
+        assert_eq!(Duration::ZERO, until_tomorrow(DateTime::<Utc>::MAX_UTC));
+    }
+
+    #[test]
+    fn test_consumer() {
+        let principal = Principal {
+            username: String::from("alice"),
+            scopes: None,
+        };
+        let addr = "10.0.0.1:4242".parse().ok();
+        assert_eq!("principal:alice", consumer(Some(&principal), addr));
+        assert_eq!("ip:10.0.0.1", consumer(None, addr));
+        assert_eq!("ip:unknown", consumer(None, None));
+    }
+}
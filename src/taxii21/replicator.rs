@@ -0,0 +1,731 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use super::{
+    backend::{Backend, Filtering, MatchField},
+    client::{Client, ClientError, Page},
+    errors::MyError,
+    file_backend::FileBackend,
+    server::Object,
+};
+use crate::taxii::files::write_file_atomically;
+
+fn default_interval() -> u64 {
+    3600
+}
+
+/// A remote TAXII 2.1 collection to replicate, and the local collection it is replicated to.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ReplicationConfig {
+    /// The name the checkpoint of the replication is kept under.
+    pub name: String,
+    /// The URL of the remote server, e.g. "https://example.com".
+    pub base_url: String,
+    pub api_root: String,
+    pub collection_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// The id of the local collection the objects are added to.
+    pub local_collection_id: String,
+    /// Seconds between two replications.
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ReplicatorConfig {
+    /// The file the checkpoints of the replications are kept in.
+    pub checkpoint_path: String,
+    /// The root directory of the file backend of the local server.
+    pub file_backend: String,
+    #[serde(default)]
+    pub replications: Vec<ReplicationConfig>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct ReplicatorConfigFile {
+    replicator: ReplicatorConfig,
+}
+
+impl ReplicatorConfig {
+    pub fn load_toml(path: &Path) -> Result<ReplicatorConfig, MyError> {
+        let cfg = match std::fs::read_to_string(path) {
+            Ok(v) => v,
+            Err(err) => return Err(MyError(err.to_string())),
+        };
+        match toml::from_str::<ReplicatorConfigFile>(cfg.as_str()) {
+            Ok(v) => Ok(v.replicator),
+            Err(err) => Err(MyError(err.to_string())),
+        }
+    }
+}
+
+/// How far a remote collection has been replicated.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Checkpoint {
+    /// The X-TAXII-Date-Added-Last of the last result added to the local collection, the
+    /// `added_after` of the next request.
+    pub added_after: Option<DateTime<Utc>>,
+    /// The `next` of the page after the last one added, while the pages of a result are added:
+    /// objects sharing the date_added of the end of a page are on the next page, and would be
+    /// skipped by an `added_after` of that date. When the server rejects it, the replication
+    /// starts again from `added_after`.
+    #[serde(default)]
+    pub next: Option<String>,
+    pub last_run: Option<DateTime<Utc>>,
+}
+
+/// The checkpoints of all replications, persisted across restarts.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Checkpoints {
+    pub replications: BTreeMap<String, Checkpoint>,
+}
+
+impl Checkpoints {
+    /// Read the checkpoints from a file. A missing file means nothing was replicated yet.
+    pub fn load(path: &Path) -> Result<Checkpoints, MyError> {
+        let data = match std::fs::read_to_string(path) {
+            Ok(v) => v,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Checkpoints::default())
+            }
+            Err(err) => return Err(MyError(err.to_string())),
+        };
+        match serde_json::from_str::<Checkpoints>(data.as_str()) {
+            Ok(v) => Ok(v),
+            Err(err) => Err(MyError(format!(
+                "bad checkpoints {}: {}",
+                path.display(),
+                err
+            ))),
+        }
+    }
+
+    /// Write the checkpoints to a file, replacing it atomically.
+    pub fn save(&self, path: &Path) -> Result<(), MyError> {
+        let data = match serde_json::to_string_pretty(self) {
+            Ok(v) => v,
+            Err(err) => return Err(MyError(err.to_string())),
+        };
+        match write_file_atomically(path, data.as_str()) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(MyError(err.to_string())),
+        }
+    }
+}
+
+/// How often a failed request is retried, waiting twice as long after each attempt.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: u32, initial_backoff: Duration, max_backoff: Duration) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            initial_backoff,
+            max_backoff,
+        }
+    }
+    /// The wait before a retry, `attempt` counting from 0.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt));
+        backoff.min(self.max_backoff)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::new(5, Duration::from_secs(1), Duration::from_secs(60))
+    }
+}
+
+/// Errors that may go away when the request is sent again: the server could not be reached,
+/// failed or asked us to slow down.
+fn is_retryable(err: &ClientError) -> bool {
+    match err {
+        ClientError::Transport(_) => true,
+        ClientError::Taxii { status, .. } | ClientError::Http { status, .. } => {
+            *status >= 500 || *status == 429
+        }
+        ClientError::NotAcceptable | ClientError::Decode(_) => false,
+    }
+}
+
+/// Errors of a request the server will not accept as it is, e.g. with a `next` it forgot.
+fn is_rejected(err: &ClientError) -> bool {
+    match err {
+        ClientError::Taxii { status, .. } | ClientError::Http { status, .. } => {
+            (400..500).contains(status) && *status != 429
+        }
+        _ => false,
+    }
+}
+
+/// What one replication added, or would add in a dry run.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReplicationReport {
+    pub pages: usize,
+    pub objects: usize,
+    /// In a dry run, the objects whose version the local collection does not hold.
+    pub new_objects: usize,
+    pub success_count: u32,
+    /// Objects the local collection already holds at the same version.
+    pub failure_count: u32,
+    pub added_after: Option<DateTime<Utc>>,
+}
+
+/// Replicates a remote TAXII 2.1 collection into a local backend collection. Objects are fetched
+/// with `added_after` set to the checkpoint, all versions of them, a page at a time; after each
+/// page is added the checkpoint moves to the `next` of the following page, or to the page's
+/// X-TAXII-Date-Added-Last once the last page is added, and is saved, so an interrupted
+/// replication resumes with the first page that was not added.
+pub struct Replicator {
+    config: ReplicationConfig,
+    client: Client,
+    backend: Arc<Mutex<dyn Backend + Send>>,
+    checkpoint_path: PathBuf,
+    retry_policy: RetryPolicy,
+    dry_run: bool,
+}
+
+impl Replicator {
+    pub fn new(
+        config: &ReplicationConfig,
+        backend: Arc<Mutex<dyn Backend + Send>>,
+        checkpoint_path: &Path,
+    ) -> Replicator {
+        let client = Client::new(config.base_url.as_str());
+        let client = match (&config.username, &config.password) {
+            (Some(username), password) => {
+                client.with_basic_auth(username.as_str(), password.as_deref().unwrap_or_default())
+            }
+            (None, _) => client,
+        };
+        Replicator {
+            config: config.clone(),
+            client,
+            backend,
+            checkpoint_path: checkpoint_path.to_path_buf(),
+            retry_policy: RetryPolicy::default(),
+            dry_run: false,
+        }
+    }
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Replicator {
+        self.retry_policy = retry_policy;
+        self
+    }
+    /// In a dry run the remote objects are fetched and compared with the local collection, but
+    /// nothing is added and the checkpoint is left as it is.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Replicator {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn checkpoint(&self) -> Result<Checkpoint, MyError> {
+        let checkpoints = Checkpoints::load(self.checkpoint_path.as_path())?;
+        Ok(checkpoints
+            .replications
+            .get(&self.config.name)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn save_checkpoint(&self, checkpoint: &Checkpoint) -> Result<(), MyError> {
+        let path = self.checkpoint_path.as_path();
+        let mut checkpoints = Checkpoints::load(path)?;
+        checkpoints
+            .replications
+            .insert(self.config.name.clone(), checkpoint.clone());
+        checkpoints.save(path)
+    }
+
+    async fn fetch_page(&self, filtering: &Filtering) -> Result<Page<Object>, ClientError> {
+        let mut attempt = 0;
+        loop {
+            match self
+                .client
+                .objects(
+                    self.config.api_root.as_str(),
+                    self.config.collection_id.as_str(),
+                    filtering,
+                )
+                .await
+            {
+                Ok(v) => return Ok(v),
+                Err(err) if is_retryable(&err) && attempt < self.retry_policy.max_retries => {
+                    let backoff = self.retry_policy.backoff(attempt);
+                    warn!(
+                        "replication {}: retrying in {:?}: {}",
+                        self.config.name, backoff, err
+                    );
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// The ids and versions the local collection holds, to report what a dry run would add.
+    fn local_versions(&self) -> Result<HashSet<(String, String)>, MyError> {
+        let backend = match self.backend.lock() {
+            Ok(v) => v,
+            Err(err) => return Err(MyError(err.to_string())),
+        };
+        let filtering = Filtering::no_filter().with_match(MatchField::Version, &["all"]);
+        Ok(backend
+            .get_manifests(self.config.local_collection_id.as_str(), &filtering)?
+            .into_iter()
+            .map(|rec| (rec.id, rec.version))
+            .collect())
+    }
+
+    fn add_objects(
+        &self,
+        objects: &[Object],
+        report: &mut ReplicationReport,
+    ) -> Result<(), MyError> {
+        let mut backend = match self.backend.lock() {
+            Ok(v) => v,
+            Err(err) => return Err(MyError(err.to_string())),
+        };
+        let status = backend.add_objects(self.config.local_collection_id.as_str(), objects)?;
+        report.success_count += status.success_count;
+        report.failure_count += status.failure_count;
+        Ok(())
+    }
+
+    /// Fetch the objects added to the remote collection since the checkpoint and add them to the
+    /// local collection.
+    pub async fn replicate(&self) -> Result<ReplicationReport, MyError> {
+        let mut checkpoint = self.checkpoint()?;
+        let local_versions = match self.dry_run {
+            true => self.local_versions()?,
+            false => HashSet::<(String, String)>::new(),
+        };
+        let mut report = ReplicationReport::default();
+        let mut filtering = Filtering::no_filter().with_match(MatchField::Version, &["all"]);
+        if let Some(added_after) = checkpoint.added_after {
+            filtering = filtering.with_added_after(added_after);
+        }
+        if let Some(next) = &checkpoint.next {
+            filtering = filtering.with_next(next.as_str());
+        }
+        let mut resumed = checkpoint.next.is_some();
+        loop {
+            let page = match self.fetch_page(&filtering).await {
+                Ok(v) => v,
+                // the `next` of an interrupted replication went stale: start again from
+                // `added_after`, which adds the objects of the pages added before once more
+                Err(err) if resumed && is_rejected(&err) => {
+                    warn!(
+                        "replication {}: restarting from {:?}: {}",
+                        self.config.name, checkpoint.added_after, err
+                    );
+                    resumed = false;
+                    checkpoint.next = None;
+                    if !self.dry_run {
+                        self.save_checkpoint(&checkpoint)?;
+                    }
+                    filtering = filtering.with_next("");
+                    continue;
+                }
+                Err(err) => return Err(MyError(err.to_string())),
+            };
+            resumed = false;
+            report.pages += 1;
+            report.objects += page.items.len();
+            if self.dry_run {
+                report.new_objects += page
+                    .items
+                    .iter()
+                    .filter(|v| {
+                        !local_versions.contains(&(v.id.clone(), String::from(v.version())))
+                    })
+                    .count();
+            } else if !page.items.is_empty() {
+                self.add_objects(&page.items, &mut report)?;
+                match (&page.next, page.more) {
+                    (Some(next), true) => checkpoint.next = Some(next.clone()),
+                    // without the header the page is fetched again next time, which adds nothing
+                    _ => {
+                        checkpoint.next = None;
+                        if page.date_added_last.is_some() {
+                            checkpoint.added_after = page.date_added_last;
+                        }
+                    }
+                }
+                checkpoint.last_run = Some(Utc::now());
+                self.save_checkpoint(&checkpoint)?;
+            } else {
+                checkpoint.next = None;
+            }
+            if page.date_added_last.is_some() {
+                report.added_after = page.date_added_last;
+            }
+            filtering = match page.next_filtering(&filtering) {
+                Some(v) => v,
+                None => break,
+            };
+        }
+        if !self.dry_run {
+            checkpoint.last_run = Some(Utc::now());
+            self.save_checkpoint(&checkpoint)?;
+        }
+        Ok(report)
+    }
+
+    /// Replicate on the configured interval, forever. Failed replications are logged and
+    /// retried at the next interval.
+    pub async fn run(&self) {
+        loop {
+            match self.replicate().await {
+                Ok(report) => info!(
+                    "replication {}: dry_run={} pages={} objects={} new={} successes={} failures={}",
+                    self.config.name,
+                    self.dry_run,
+                    report.pages,
+                    report.objects,
+                    report.new_objects,
+                    report.success_count,
+                    report.failure_count
+                ),
+                Err(err) => warn!("replication {} failed: {}", self.config.name, err),
+            }
+            tokio::time::sleep(Duration::from_secs(self.config.interval)).await;
+        }
+    }
+}
+
+/// Run the replications configured by a toml file into the file backend of the local server.
+/// A dry run replicates once and reports what would be added.
+pub fn main(path: &Path, dry_run: bool) -> Result<(), MyError> {
+    let cfg = ReplicatorConfig::load_toml(path)?;
+    let backend: Arc<Mutex<dyn Backend + Send>> =
+        Arc::new(Mutex::new(FileBackend::new(cfg.file_backend.as_str())));
+    let checkpoint_path = Path::new(cfg.checkpoint_path.as_str());
+    let replicators: Vec<Replicator> = cfg
+        .replications
+        .iter()
+        .map(|v| Replicator::new(v, backend.clone(), checkpoint_path).with_dry_run(dry_run))
+        .collect();
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(v) => v,
+        Err(err) => return Err(MyError(err.to_string())),
+    };
+    runtime.block_on(async {
+        if dry_run {
+            for replicator in replicators.iter() {
+                let report = replicator.replicate().await?;
+                info!(
+                    "replication {}: would add {} of {} objects",
+                    replicator.config.name, report.new_objects, report.objects
+                );
+            }
+            return Ok(());
+        }
+        futures::future::join_all(replicators.iter().map(|v| v.run())).await;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env, fs,
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use actix_web::{dev::Service, error::InternalError, http::StatusCode, HttpServer};
+    use uuid::Uuid;
+
+    use super::{
+        Checkpoint, Checkpoints, ReplicationConfig, Replicator, ReplicatorConfig, RetryPolicy,
+    };
+    use crate::taxii21::{
+        backend::{Backend, Filtering, MatchField},
+        memory_backend::MemoryBackend,
+        server::{new_app, taxii_error, Object},
+        test_utils::{new_app_state, new_backend, new_indicator, start_server},
+    };
+
+    fn indicator(i: u32, modified: &str) -> Object {
+        let id = format!("indicator--00000000-0000-4000-8000-{:012}", i);
+        new_indicator(id.as_str(), modified)
+    }
+
+    fn temp_checkpoint_path() -> PathBuf {
+        env::temp_dir().join(format!("checkpoints-{}.json", Uuid::new_v4()))
+    }
+
+    fn new_config(base_url: &str) -> ReplicationConfig {
+        ReplicationConfig {
+            name: String::from("remote"),
+            base_url: String::from(base_url),
+            api_root: String::from("api1"),
+            collection_id: String::from("collection1"),
+            username: None,
+            password: None,
+            local_collection_id: String::from("local"),
+            interval: 60,
+        }
+    }
+
+    #[test]
+    fn test_load_config() {
+        let path = format!(
+            "{}/test/sample-replicator.toml",
+            env::var("CARGO_MANIFEST_DIR").unwrap()
+        );
+        let cfg = ReplicatorConfig::load_toml(Path::new(path.as_str())).unwrap();
+        assert_eq!(1, cfg.replications.len());
+        assert_eq!("collection1", cfg.replications[0].collection_id);
+        assert_eq!(Some(String::from("guest")), cfg.replications[0].username);
+        assert_eq!(3600, cfg.replications[0].interval);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::new(5, Duration::from_secs(1), Duration::from_secs(10));
+        assert_eq!(Duration::from_secs(1), policy.backoff(0));
+        assert_eq!(Duration::from_secs(4), policy.backoff(2));
+        assert_eq!(Duration::from_secs(10), policy.backoff(4));
+        assert_eq!(Duration::from_secs(10), policy.backoff(40));
+    }
+
+    #[actix_web::test]
+    async fn test_replicate() {
+        let remote = new_backend();
+        let addr = start_server(remote.clone());
+        let base_url = format!("http://{}", addr);
+        let mut local = MemoryBackend::new();
+        local.add_collection("local");
+        let local = Arc::new(Mutex::new(local));
+        let checkpoint_path = temp_checkpoint_path();
+        let local_versions = || {
+            let filtering = Filtering::no_filter().with_match(MatchField::Version, &["all"]);
+            local
+                .lock()
+                .unwrap()
+                .get_manifests("local", &filtering)
+                .unwrap()
+                .len()
+        };
+
+        let objects: Vec<Object> = (0..5)
+            .map(|i| indicator(i, "2023-01-01T00:00:00.000Z"))
+            .collect();
+        remote
+            .lock()
+            .unwrap()
+            .add_objects("collection1", &objects)
+            .unwrap();
+
+        // a dry run reports what would be added, but adds nothing
+        let replicator = Replicator::new(
+            &new_config(base_url.as_str()),
+            local.clone(),
+            &checkpoint_path,
+        )
+        .with_dry_run(true);
+        let report = replicator.replicate().await.unwrap();
+        assert_eq!(5, report.objects);
+        assert_eq!(5, report.new_objects);
+        assert_eq!(3, report.pages);
+        assert_eq!(0, local_versions());
+        assert_eq!(
+            Checkpoints::default(),
+            Checkpoints::load(&checkpoint_path).unwrap()
+        );
+
+        let replicator = Replicator::new(
+            &new_config(base_url.as_str()),
+            local.clone(),
+            &checkpoint_path,
+        );
+        let report = replicator.replicate().await.unwrap();
+        assert_eq!(5, report.objects);
+        assert_eq!(5, report.success_count);
+        assert_eq!(5, local_versions());
+        let checkpoint = replicator.checkpoint().unwrap();
+        assert!(checkpoint.added_after.is_some());
+        assert_eq!(report.added_after, checkpoint.added_after);
+
+        // only what was added since the checkpoint is fetched, including new versions
+        let report = replicator.replicate().await.unwrap();
+        assert_eq!(0, report.objects);
+        let update = indicator(1, "2023-02-01T00:00:00.000Z");
+        remote
+            .lock()
+            .unwrap()
+            .add_objects(
+                "collection1",
+                &[update, indicator(5, "2023-01-01T00:00:00.000Z")],
+            )
+            .unwrap();
+        let replicator = Replicator::new(
+            &new_config(base_url.as_str()),
+            local.clone(),
+            &checkpoint_path,
+        );
+        let report = replicator.replicate().await.unwrap();
+        assert_eq!(2, report.objects);
+        assert_eq!(2, report.success_count);
+        assert_eq!(7, local_versions());
+        assert!(replicator.checkpoint().unwrap().added_after > checkpoint.added_after);
+
+        // errors that do not go away are not retried
+        let mut config = new_config(base_url.as_str());
+        config.collection_id = String::from("not-found");
+        let replicator =
+            Replicator::new(&config, local.clone(), &checkpoint_path).with_retry_policy(
+                RetryPolicy::new(3, Duration::from_secs(60), Duration::from_secs(60)),
+            );
+        assert!(replicator.replicate().await.is_err());
+
+        // an unreachable server is retried before giving up
+        let config = new_config("http://127.0.0.1:1");
+        let replicator =
+            Replicator::new(&config, local.clone(), &checkpoint_path).with_retry_policy(
+                RetryPolicy::new(2, Duration::from_millis(1), Duration::from_millis(10)),
+            );
+        assert!(replicator.replicate().await.is_err());
+        assert_eq!(7, local_versions());
+
+        fs::remove_file(&checkpoint_path).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_replicate_resume() {
+        let remote = new_backend();
+        let addr = start_server(remote.clone());
+        let base_url = format!("http://{}", addr);
+        let mut local = MemoryBackend::new();
+        local.add_collection("local");
+        let local = Arc::new(Mutex::new(local));
+        let checkpoint_path = temp_checkpoint_path();
+
+        // added together, the objects share their date_added across the pages of the result
+        let objects: Vec<Object> = (0..5)
+            .map(|i| indicator(i, "2023-01-01T00:00:00.000Z"))
+            .collect();
+        remote
+            .lock()
+            .unwrap()
+            .add_objects("collection1", &objects)
+            .unwrap();
+
+        // a replication interrupted after its first page resumes with the second one
+        let mut checkpoints = Checkpoints::default();
+        checkpoints.replications.insert(
+            String::from("remote"),
+            Checkpoint {
+                next: Some(String::from("2")),
+                ..Checkpoint::default()
+            },
+        );
+        checkpoints.save(&checkpoint_path).unwrap();
+        let replicator = Replicator::new(
+            &new_config(base_url.as_str()),
+            local.clone(),
+            &checkpoint_path,
+        );
+        let report = replicator.replicate().await.unwrap();
+        assert_eq!(2, report.pages);
+        assert_eq!(3, report.objects);
+        assert_eq!(3, report.success_count);
+        let checkpoint = replicator.checkpoint().unwrap();
+        assert_eq!(None, checkpoint.next);
+        assert_eq!(report.added_after, checkpoint.added_after);
+
+        fs::remove_file(&checkpoint_path).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_replicate_stale_next() {
+        let remote = new_backend();
+        // a server that no longer knows the `next` of the checkpoint
+        let app_state = new_app_state(remote.clone());
+        let server = HttpServer::new(move || {
+            new_app(app_state.clone()).wrap_fn(|req, srv| {
+                let stale = req.query_string().contains("next=stale");
+                let fut = match stale {
+                    true => None,
+                    false => Some(srv.call(req)),
+                };
+                async move {
+                    match fut {
+                        Some(fut) => fut.await,
+                        None => Err(actix_web::Error::from(InternalError::from_response(
+                            "stale",
+                            taxii_error(StatusCode::BAD_REQUEST, "bad next", None),
+                        ))),
+                    }
+                }
+            })
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let base_url = format!("http://{}", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+        let mut local = MemoryBackend::new();
+        local.add_collection("local");
+        let local = Arc::new(Mutex::new(local));
+        let checkpoint_path = temp_checkpoint_path();
+
+        let objects: Vec<Object> = (0..5)
+            .map(|i| indicator(i, "2023-01-01T00:00:00.000Z"))
+            .collect();
+        remote
+            .lock()
+            .unwrap()
+            .add_objects("collection1", &objects)
+            .unwrap();
+
+        // the rejected `next` is dropped and the replication starts again from `added_after`
+        let mut checkpoints = Checkpoints::default();
+        checkpoints.replications.insert(
+            String::from("remote"),
+            Checkpoint {
+                next: Some(String::from("stale")),
+                ..Checkpoint::default()
+            },
+        );
+        checkpoints.save(&checkpoint_path).unwrap();
+        let replicator = Replicator::new(
+            &new_config(base_url.as_str()),
+            local.clone(),
+            &checkpoint_path,
+        );
+        let report = replicator.replicate().await.unwrap();
+        assert_eq!(3, report.pages);
+        assert_eq!(5, report.objects);
+        assert_eq!(5, report.success_count);
+        let checkpoint = replicator.checkpoint().unwrap();
+        assert_eq!(None, checkpoint.next);
+        assert_eq!(report.added_after, checkpoint.added_after);
+
+        // and the following runs go on from the checkpoint
+        let report = replicator.replicate().await.unwrap();
+        assert_eq!(0, report.objects);
+
+        fs::remove_file(&checkpoint_path).unwrap();
+    }
+}
@@ -1,67 +1,77 @@
-use std::{collections::HashMap, path::Path, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use crate::taxii21::middleware;
 use actix_web::{
     body::{BoxBody, EitherBody},
     dev::{ServiceFactory, ServiceRequest, ServiceResponse},
-    web, App, Error, HttpRequest, HttpResponse, HttpServer,
+    http::StatusCode,
+    web, App, Error, HttpMessage, HttpRequest, HttpResponse, HttpResponseBuilder, HttpServer,
 };
 use chrono::{DateTime, Utc};
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 use std::sync::Mutex;
-use tracing::info;
+use tracing::{info, Span};
 
 use super::{
-    backend::{Backend, Filtering},
-    errors::MyError,
+    acl::{Acl, AclEntry, AclPrincipal},
+    api_keys::{reload_api_keys, Access, ApiKeyStore, RELOAD_INTERVAL},
+    auth::{PasswordHash, Principal, User, UserStore},
+    backend::{select_objects, Backend, Filtering, Match, MatchField},
+    errors::{ErrorMessage, MyError},
     file_backend::FileBackend,
+    media_type::MediaType,
+    metrics::Metrics,
+    rate_limit::{consumer, too_many_requests, until_tomorrow, RateLimitConfig, RateLimiter},
+    tls::{reload_on_sighup, Tls, TlsConfig},
 };
 
-#[derive(Clone, Serialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Discovery {
-    title: String,
-    description: Option<String>,
-    contact: Option<String>,
-    default: Option<String>,
-    api_roots: Option<Vec<String>>,
+    pub title: String,
+    pub description: Option<String>,
+    pub contact: Option<String>,
+    pub default: Option<String>,
+    pub api_roots: Option<Vec<String>>,
 }
 
 impl Discovery {
     pub fn new_empty() -> Discovery {
-        return Discovery {
+        Discovery {
             title: String::new(),
             description: None,
             contact: None,
             default: None,
             api_roots: None,
-        };
+        }
     }
 }
 
 #[derive(Clone, Deserialize, Serialize)]
 pub struct APIRootConfig {
-    title: String,
-    description: Option<String>,
-    versions: Vec<String>,
-    max_content_length: u64,
+    pub title: String,
+    pub description: Option<String>,
+    pub versions: Vec<String>,
+    pub max_content_length: u64,
 }
 
 impl APIRootConfig {
     pub fn new(
         title: &str,
         description: Option<&str>,
-        versions: &Vec<String>,
+        versions: &[String],
         max_content_length: u64,
     ) -> APIRootConfig {
-        return APIRootConfig {
+        APIRootConfig {
             title: String::from(title),
-            description: match description {
-                Some(v) => Some(String::from(v)),
-                None => None,
-            },
-            versions: versions.clone(),
+            description: description.map(String::from),
+            versions: versions.to_vec(),
             max_content_length,
-        };
+        }
     }
 }
 
@@ -75,12 +85,12 @@ pub struct APIRoot {
 
 impl APIRoot {
     pub fn new(config: &APIRootConfig) -> APIRoot {
-        return APIRoot {
+        APIRoot {
             config: config.clone(),
             api_root_server_record_limit: None,
             statii: HashMap::<String, Status>::new(),
             collections: Collections::new(),
-        };
+        }
     }
     pub fn add_status(&mut self, status: &Status) {
         self.statii.insert(status.id.clone(), status.clone());
@@ -92,28 +102,31 @@ impl APIRoot {
 
 #[derive(Clone, Deserialize, Serialize)]
 pub struct StatusDetails {
-    id: String,
-    version: String,
-    message: Option<String>,
+    pub id: String,
+    pub version: String,
+    pub message: Option<String>,
 }
 
 #[derive(Clone, Deserialize, Serialize)]
 pub struct Status {
-    id: String,
-    status: String, // TODO: StatusEnum
-    request_timestamp: Option<DateTime<Utc>>,
-    total_count: u32,
-    success_count: u32,
-    successes: Option<Vec<StatusDetails>>,
-    failure_count: u32,
-    failures: Option<Vec<StatusDetails>>,
-    pending_count: u32,
-    pendings: Option<Vec<StatusDetails>>,
+    pub id: String,
+    pub status: String, // TODO: StatusEnum
+    pub request_timestamp: Option<DateTime<Utc>>,
+    pub total_count: u32,
+    pub success_count: u32,
+    pub successes: Option<Vec<StatusDetails>>,
+    pub failure_count: u32,
+    pub failures: Option<Vec<StatusDetails>>,
+    pub pending_count: u32,
+    pub pendings: Option<Vec<StatusDetails>>,
+    /// The collection the objects were added to, which decides who may read the status.
+    #[serde(skip)]
+    pub collection_id: Option<String>,
 }
 
 impl Status {
     pub fn new(id: &str) -> Status {
-        return Status {
+        Status {
             id: String::from(id),
             status: String::from(""),
             request_timestamp: None,
@@ -124,45 +137,131 @@ impl Status {
             failures: None,
             pending_count: 0,
             pendings: None,
-        };
+            collection_id: None,
+        }
     }
 }
 
-#[derive(Clone, Deserialize, Serialize)]
+/// A STIX object. Only the common properties the server needs are typed, all other properties
+/// are kept as they were received.
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Object {
-    created: Option<DateTime<Utc>>,
-    description: String,
-    id: String,
-    indicator_types: Vec<String>,
-    is_family: bool,
-    malware_types: Vec<String>,
-    modified: Option<DateTime<Utc>>,
-    name: String,
-    pattern: String,
-    pattern_type: String, // TODO: enum
-    spec_version: String,
+    pub id: String,
+    #[serde(rename(serialize = "type", deserialize = "type"))]
+    pub typ: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spec_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+    #[serde(flatten)]
+    pub properties: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Object {
+    pub fn new(typ: &str, id: &str) -> Object {
+        Object {
+            id: String::from(id),
+            typ: String::from(typ),
+            spec_version: Some(String::from("2.1")),
+            created: None,
+            modified: None,
+            properties: serde_json::Map::new(),
+        }
+    }
+    /// The version of the object: its `modified` timestamp, or `created` for objects that cannot
+    /// be modified, such as marking definitions.
+    pub fn version(&self) -> &str {
+        match &self.modified {
+            Some(v) => v.as_str(),
+            None => self.created.as_deref().unwrap_or(""),
+        }
+    }
+    pub fn media_type(&self) -> String {
+        format!(
+            "application/stix+json;version={}",
+            self.spec_version.as_deref().unwrap_or("2.1")
+        )
+    }
+}
+
+/// The envelope resource returned by the objects endpoints.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Envelope {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub more: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub objects: Option<Vec<Object>>,
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Envelope {
+    pub fn new() -> Envelope {
+        Envelope {
+            more: None,
+            next: None,
+            objects: None,
+        }
+    }
+}
+
+/// A STIX bundle, returned by the objects endpoints instead of the envelope to clients
+/// accepting `application/stix+json`.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Bundle {
     #[serde(rename(serialize = "type", deserialize = "type"))]
-    typ: String,
-    valid_from: Option<DateTime<Utc>>,
+    pub typ: String,
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub objects: Option<Vec<Object>>,
+}
+
+impl Bundle {
+    pub fn new(objects: Vec<Object>) -> Bundle {
+        Bundle {
+            typ: String::from("bundle"),
+            id: format!("bundle--{}", uuid::Uuid::new_v4()),
+            objects: Some(objects),
+        }
+    }
+}
+
+/// The versions resource returned by the object versions endpoint.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Versions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub more: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub versions: Option<Vec<String>>,
 }
 
 #[derive(Clone, Deserialize, Serialize)]
 pub struct Collections {
-    collections: Option<Vec<CollectionConfig>>,
+    pub collections: Option<Vec<CollectionConfig>>,
+}
+
+impl Default for Collections {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Collections {
     pub fn new() -> Collections {
-        return Collections { collections: None };
+        Collections { collections: None }
     }
     pub fn add_collection(&mut self, collection: &CollectionConfig) {
         match &mut self.collections {
             Some(collections) => collections.push(collection.clone()),
-            None => {
-                let mut new_collections = Vec::<CollectionConfig>::new();
-                new_collections.push(collection.clone());
-                self.collections = Some(new_collections);
-            }
+            None => self.collections = Some(vec![collection.clone()]),
         }
     }
     pub fn get_collection(&self, id: &str) -> Option<&CollectionConfig> {
@@ -188,27 +287,27 @@ pub struct Collection {
 
 impl Collection {
     pub fn new(id: &str, title: &str) -> Collection {
-        return Collection {
+        Collection {
             config: CollectionConfig::new(id, title),
             manifests: Vec::<ManifestRecord>::new(),
-        };
+        }
     }
 }
 
 #[derive(Clone, Deserialize, Serialize)]
 pub struct CollectionConfig {
-    id: String,
-    title: String,
-    description: Option<String>,
-    alias: Option<String>,
-    can_read: bool,
-    can_write: bool,
-    media_types: Option<Vec<String>>,
+    pub id: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub alias: Option<String>,
+    pub can_read: bool,
+    pub can_write: bool,
+    pub media_types: Option<Vec<String>>,
 }
 
 impl CollectionConfig {
     pub fn new(id: &str, title: &str) -> CollectionConfig {
-        return CollectionConfig {
+        CollectionConfig {
             id: String::from(id),
             title: String::from(title),
             description: None,
@@ -216,31 +315,61 @@ impl CollectionConfig {
             can_read: false,
             can_write: false,
             media_types: None,
-        };
+        }
     }
 }
 
 #[derive(Clone, Deserialize, Serialize)]
 pub struct Manifest {
-    more: Option<bool>,
-    objects: Option<Vec<ManifestRecord>>,
+    pub more: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+    pub objects: Option<Vec<ManifestRecord>>,
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Manifest {
     pub fn new() -> Manifest {
-        return Manifest {
+        Manifest {
             more: None,
+            next: None,
             objects: None,
-        };
+        }
     }
 }
 
 #[derive(Clone, Deserialize, Serialize)]
 pub struct ManifestRecord {
-    id: String,
-    date_added: chrono::DateTime<Utc>,
-    version: String,
-    media_type: Option<String>,
+    pub id: String,
+    pub date_added: chrono::DateTime<Utc>,
+    pub version: String,
+    pub media_type: Option<String>,
+}
+
+impl ManifestRecord {
+    pub fn new(object: &Object, date_added: DateTime<Utc>) -> ManifestRecord {
+        ManifestRecord {
+            id: object.id.clone(),
+            date_added,
+            version: String::from(object.version()),
+            media_type: Some(object.media_type()),
+        }
+    }
+    /// The STIX version of the object, taken from the media type.
+    pub fn spec_version(&self) -> &str {
+        match &self.media_type {
+            Some(v) => match v.split_once("version=") {
+                Some((_, ver)) => ver,
+                None => "2.1",
+            },
+            None => "2.1",
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize)]
@@ -255,6 +384,48 @@ pub struct Taxii2ServerConfig {
 #[derive(Deserialize, Serialize)]
 pub struct AppConfig {
     taxii2_server: Taxii2ServerConfig,
+    /// Users allowed to access the server with HTTP Basic authentication; anonymous access
+    /// when empty.
+    #[serde(default)]
+    users: Vec<User>,
+    /// The file of the API keys managed with the `keys` admin commands.
+    api_keys: Option<String>,
+    /// Who may access which collections; everyone may access every collection when empty.
+    #[serde(default)]
+    acl: Vec<AclEntry>,
+    /// HTTPS, and optionally client certificates, instead of plain HTTP.
+    tls: Option<TlsConfig>,
+    /// The request rates and object quotas of the consumers of the api roots.
+    #[serde(default)]
+    rate_limits: Vec<RateLimitConfig>,
+}
+
+impl AppConfig {
+    /// Read the configuration. Its users, ACL and rate limits are checked as the server is set
+    /// up with them, or by `validate`.
+    pub fn load(path: &Path) -> Result<AppConfig, MyError> {
+        let cfg = match std::fs::read_to_string(path) {
+            Ok(cfg) => cfg,
+            Err(err) => return Err(MyError(format!("{}: {}", path.display(), err))),
+        };
+        let cfg: AppConfig = match toml::from_str(cfg.as_str()) {
+            Ok(cfg) => cfg,
+            Err(err) => return Err(MyError(format!("{}: {}", path.display(), err))),
+        };
+        Ok(cfg)
+    }
+    /// Check the password hashes of the users, the ACL and the rate limits, without setting up
+    /// a user store, which hashes a dummy password.
+    pub fn validate(&self) -> Result<(), MyError> {
+        for user in self.users.iter() {
+            if let Err(err) = PasswordHash::parse(user.password_hash.as_str()) {
+                return Err(MyError(format!("user {}: {}", user.username, err)));
+            }
+        }
+        Acl::new(&self.acl)?;
+        RateLimiter::new(&self.rate_limits)?;
+        Ok(())
+    }
 }
 
 #[derive(Clone)]
@@ -263,47 +434,139 @@ struct AppStateWrapper {
 }
 
 #[derive(Clone)]
-struct AppState {
+pub struct AppState {
     pub server: Discovery,
     pub default_server_record_limit: u32,
     pub api_roots: HashMap<String, APIRoot>,
     pub backend: Option<Arc<Mutex<dyn Backend + Send>>>,
+    pub users: Option<Arc<UserStore>>,
+    pub api_keys: Option<Arc<ApiKeyStore>>,
+    pub acl: Option<Acl>,
+    pub tls: Option<TlsConfig>,
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    pub metrics: Arc<Metrics>,
+    /// The configuration file the state was loaded from.
+    pub config_path: Option<PathBuf>,
 }
 
 const DEFAULT_SERVER_LIMIT: u32 = 100;
 
 impl AppState {
     pub fn new_empty() -> AppState {
-        return AppState {
+        AppState {
             server: Discovery::new_empty(),
             default_server_record_limit: DEFAULT_SERVER_LIMIT,
             api_roots: HashMap::<String, APIRoot>::new(),
             backend: None,
-        };
+            users: None,
+            api_keys: None,
+            acl: None,
+            tls: None,
+            rate_limiter: None,
+            metrics: Arc::new(Metrics::new()),
+            config_path: None,
+        }
     }
     pub fn add_file_backend(&mut self, root_dir: &str) {
         let backend = FileBackend::new(root_dir);
         let backend = Arc::new(Mutex::new(backend));
         self.backend = Some(backend);
     }
-    pub fn load_toml(path: &Path) -> Result<AppState, MyError> {
-        let cfg = match std::fs::read_to_string(path) {
-            Ok(cfg) => cfg,
-            Err(err) => return Err(MyError(err.to_string())),
+    pub fn set_backend(&mut self, backend: Arc<Mutex<dyn Backend + Send>>) {
+        self.backend = Some(backend);
+    }
+    /// Require HTTP Basic authentication by one of the users of the store.
+    pub fn set_users(&mut self, users: UserStore) {
+        self.users = Some(Arc::new(users));
+    }
+    /// Accept the API keys of the store as bearer tokens, limited to their scopes.
+    pub fn set_api_keys(&mut self, api_keys: ApiKeyStore) {
+        self.api_keys = Some(Arc::new(api_keys));
+    }
+    /// Limit the access to the collections to what the ACL grants.
+    pub fn set_acl(&mut self, acl: Acl) {
+        self.acl = Some(acl);
+    }
+    /// Limit the requests and objects of the consumers of the api roots.
+    pub fn set_rate_limiter(&mut self, rate_limiter: RateLimiter) {
+        self.rate_limiter = Some(Arc::new(rate_limiter));
+    }
+    /// Whether the principal may access the collection, as allowed by both the scopes of its
+    /// API key and the ACL.
+    pub fn allows(
+        &self,
+        principal: &Option<Principal>,
+        access: Access,
+        api_root: &str,
+        collection_id: &str,
+    ) -> bool {
+        let scopes = match principal {
+            Some(v) => v.allows(access, api_root, collection_id),
+            None => true,
         };
-        let cfg: AppConfig = match toml::from_str(cfg.as_str()) {
-            Ok(cfg) => cfg,
-            Err(err) => return Err(MyError(err.to_string())),
+        let acl = match &self.acl {
+            Some(v) => v.allows(principal, access, api_root, collection_id),
+            None => true,
         };
+        scopes && acl
+    }
+    /// The collection with `can_read` and `can_write` of the principal, None when it may do
+    /// neither. The ACL and scopes only take away from the configured values, which are kept
+    /// without them.
+    pub fn collection_for(
+        &self,
+        principal: &Option<Principal>,
+        api_root: &str,
+        collection: &CollectionConfig,
+    ) -> Option<CollectionConfig> {
+        let scoped = principal.as_ref().is_some_and(|v| v.scopes.is_some());
+        if self.acl.is_none() && !scoped {
+            return Some(collection.clone());
+        }
+        let id = collection.id.as_str();
+        let can_read = collection.can_read && self.allows(principal, Access::Read, api_root, id);
+        let can_write = collection.can_write && self.allows(principal, Access::Write, api_root, id);
+        if !can_read && !can_write {
+            return None;
+        }
+        let mut collection = collection.clone();
+        collection.can_read = can_read;
+        collection.can_write = can_write;
+        Some(collection)
+    }
+    /// The maximum number of records returned in one page by an api root.
+    pub fn get_record_limit(&self, api_root: &str) -> Option<u32> {
+        self.api_roots.get(api_root).map(|api_root| {
+            api_root
+                .api_root_server_record_limit
+                .unwrap_or(self.default_server_record_limit)
+        })
+    }
+    pub fn load_toml(path: &Path) -> Result<AppState, MyError> {
+        let cfg = AppConfig::load(path)?;
         let mut app_state = AppState::new_empty();
         app_state.server.title = cfg.taxii2_server.title;
         app_state.server.description = cfg.taxii2_server.description;
         app_state.server.contact = cfg.taxii2_server.contact;
         app_state.server.default = Some(cfg.taxii2_server.default);
         app_state.server.api_roots = Some(cfg.taxii2_server.api_roots);
+        if !cfg.users.is_empty() {
+            app_state.set_users(UserStore::from_users(&cfg.users)?);
+        }
+        if let Some(path) = &cfg.api_keys {
+            app_state.set_api_keys(ApiKeyStore::new(Path::new(path.as_str()))?);
+        }
+        if !cfg.acl.is_empty() {
+            app_state.set_acl(Acl::new(&cfg.acl)?);
+        }
+        app_state.tls = cfg.tls;
+        if !cfg.rate_limits.is_empty() {
+            app_state.set_rate_limiter(RateLimiter::new(&cfg.rate_limits)?);
+        }
         let root_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
         let root_dir = format!("{}/test/file-backend/", root_dir);
         app_state.add_file_backend(root_dir.as_str());
+        app_state.config_path = Some(path.to_path_buf());
         Ok(app_state)
     }
     pub fn add_status(&mut self, api_root: &str, status: &Status) -> Result<(), MyError> {
@@ -318,12 +581,9 @@ impl AppState {
     }
     pub fn get_status(&self, api_root: &str, status_id: &str) -> Option<Status> {
         match self.api_roots.get(api_root) {
-            Some(api_root) => match api_root.statii.get(status_id) {
-                Some(status) => return Some(status.clone()),
-                None => return None,
-            },
-            None => return None,
-        };
+            Some(api_root) => api_root.statii.get(status_id).cloned(),
+            None => None,
+        }
     }
     pub fn add_collection(
         &mut self,
@@ -341,13 +601,21 @@ impl AppState {
     }
     pub fn get_collections(&self, api_root: &str) -> Option<&Collections> {
         match self.api_roots.get(api_root) {
-            Some(api_root) => return Some(&api_root.collections),
-            None => return None,
-        };
+            Some(api_root) => Some(&api_root.collections),
+            None => None,
+        }
     }
 }
 
-const CONTENT_TYPE_TAXII2: &'static str = "application/taxii+json;version=2.1";
+pub(crate) const CONTENT_TYPE_TAXII2: &str = "application/taxii+json;version=2.1";
+pub(crate) const CONTENT_TYPE_STIX2: &str = "application/stix+json;version=2.1";
+
+/// Whether the client negotiated a STIX bundle rather than a TAXII envelope.
+fn accepts_bundle(req: &HttpRequest) -> bool {
+    req.extensions()
+        .get::<MediaType>()
+        .is_some_and(|v| v.subtype == "stix+json")
+}
 
 async fn handle_discovery(
     wrapper: web::Data<AppStateWrapper>,
@@ -389,6 +657,7 @@ struct APIRootStatusPath {
 async fn handle_api_root_status(
     wrapper: web::Data<AppStateWrapper>,
     path: web::Path<APIRootStatusPath>,
+    principal: Option<Principal>,
     req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     let app_state = wrapper.app_state.lock().unwrap();
@@ -396,6 +665,18 @@ async fn handle_api_root_status(
         Some(v) => v,
         None => return Ok(HttpResponse::NotFound().finish()),
     };
+    // the status of an add is for those who may add to the collection
+    if let Some(collection_id) = &status.collection_id {
+        if let Some(response) = check_access(
+            &app_state,
+            &principal,
+            Access::Write,
+            path.api_root.as_str(),
+            collection_id.as_str(),
+        ) {
+            return Ok(response);
+        }
+    }
     Ok(HttpResponse::Ok()
         .append_header(("Content-Type", CONTENT_TYPE_TAXII2))
         .json(web::Json(status)))
@@ -404,6 +685,7 @@ async fn handle_api_root_status(
 async fn handle_api_root_collections(
     wrapper: web::Data<AppStateWrapper>,
     path: web::Path<APIRootPath>,
+    principal: Option<Principal>,
     req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     let app_state = wrapper.app_state.lock().unwrap();
@@ -411,6 +693,14 @@ async fn handle_api_root_collections(
         Some(v) => v,
         None => return Ok(HttpResponse::NotFound().finish()),
     };
+    // only the collections the principal may access, as it may access them
+    let mut visible = Collections::new();
+    for collection in collections.collections.iter().flatten() {
+        if let Some(v) = app_state.collection_for(&principal, path.api_root.as_str(), collection) {
+            visible.add_collection(&v);
+        }
+    }
+    let collections = visible;
     Ok(HttpResponse::Ok()
         .append_header(("Content-Type", CONTENT_TYPE_TAXII2))
         .json(web::Json(collections)))
@@ -425,6 +715,7 @@ struct APIRootCollectionPath {
 async fn handle_api_root_collection(
     wrapper: web::Data<AppStateWrapper>,
     path: web::Path<APIRootCollectionPath>,
+    principal: Option<Principal>,
     req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     let app_state = wrapper.app_state.lock().unwrap();
@@ -436,34 +727,618 @@ async fn handle_api_root_collection(
         Some(v) => v,
         None => return Ok(HttpResponse::NotFound().finish()),
     };
+    let collection = match app_state.collection_for(&principal, path.api_root.as_str(), collection)
+    {
+        Some(v) => v,
+        None => {
+            return Ok(taxii_error(
+                StatusCode::FORBIDDEN,
+                "access denied",
+                Some(format!("no access to collection {}", path.collection_id).as_str()),
+            ))
+        }
+    };
     Ok(HttpResponse::Ok()
         .append_header(("Content-Type", CONTENT_TYPE_TAXII2))
         .json(web::Json(collection)))
 }
 
+/// An error response with a TAXII error message body.
+pub(crate) fn taxii_error(
+    status: StatusCode,
+    title: &str,
+    description: Option<&str>,
+) -> HttpResponse {
+    HttpResponse::build(status)
+        .append_header(("Content-Type", CONTENT_TYPE_TAXII2))
+        .json(ErrorMessage::new(title, description, status.as_u16()))
+}
+
+/// A 403 response when the collection is not configured for the access, or the principal may
+/// not access it.
+fn check_access(
+    app_state: &AppState,
+    principal: &Option<Principal>,
+    access: Access,
+    api_root: &str,
+    collection_id: &str,
+) -> Option<HttpResponse> {
+    let configured = app_state
+        .get_collections(api_root)
+        .and_then(|v| v.get_collection(collection_id))
+        .is_none_or(|v| match access {
+            Access::Read => v.can_read,
+            Access::Write => v.can_write,
+        });
+    if !configured {
+        return Some(taxii_error(
+            StatusCode::FORBIDDEN,
+            "access denied",
+            Some(
+                format!(
+                    "collection {} does not allow {} access",
+                    collection_id,
+                    access.to_str()
+                )
+                .as_str(),
+            ),
+        ));
+    }
+    if app_state.allows(principal, access, api_root, collection_id) {
+        return None;
+    }
+    let principal = principal
+        .as_ref()
+        .map_or(AclPrincipal::Anonymous.to_string(), |v| v.username.clone());
+    Some(taxii_error(
+        StatusCode::FORBIDDEN,
+        "access denied",
+        Some(
+            format!(
+                "{} has no {} access to collection {}",
+                principal,
+                access.to_str(),
+                collection_id
+            )
+            .as_str(),
+        ),
+    ))
+}
+
+/// Set the X-TAXII-Date-Added-First and X-TAXII-Date-Added-Last headers for a page of records.
+fn append_date_added_headers(response: &mut HttpResponseBuilder, records: &[ManifestRecord]) {
+    if let (Some(first), Some(last)) = (records.first(), records.last()) {
+        let format = chrono::SecondsFormat::Micros;
+        response.append_header((
+            "X-TAXII-Date-Added-First",
+            first.date_added.to_rfc3339_opts(format, true),
+        ));
+        response.append_header((
+            "X-TAXII-Date-Added-Last",
+            last.date_added.to_rfc3339_opts(format, true),
+        ));
+    }
+}
+
+/// Add a match on `field` unless the request already has one.
+fn with_default_match(mut filtering: Filtering, field: MatchField, value: &str) -> Filtering {
+    if !filtering.matches.iter().any(|m| m.field == field) {
+        filtering.matches.push(Match {
+            field,
+            values: vec![String::from(value)],
+        });
+    }
+    filtering
+}
+
+/// Restrict the filtering to a single object.
+fn with_object_id(mut filtering: Filtering, object_id: &str) -> Filtering {
+    filtering.matches.retain(|m| m.field != MatchField::Id);
+    filtering.matches.push(Match {
+        field: MatchField::Id,
+        values: vec![String::from(object_id)],
+    });
+    filtering
+}
+
 async fn handle_api_root_collection_manifests(
     wrapper: web::Data<AppStateWrapper>,
     path: web::Path<APIRootCollectionPath>,
+    query: web::Query<HashMap<String, String>>,
+    principal: Option<Principal>,
     req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     let app_state = wrapper.app_state.lock().unwrap();
+    if let Some(response) = check_access(
+        &app_state,
+        &principal,
+        Access::Read,
+        path.api_root.as_str(),
+        path.collection_id.as_str(),
+    ) {
+        return Ok(response);
+    }
+    let limit = app_state
+        .get_record_limit(path.api_root.as_str())
+        .unwrap_or(app_state.default_server_record_limit);
+    let filtering = match Filtering::parse(&query) {
+        Ok(v) => v,
+        Err(err) => {
+            return Ok(taxii_error(
+                StatusCode::BAD_REQUEST,
+                "invalid filter",
+                Some(err.to_string().as_str()),
+            ))
+        }
+    };
     let backend = match &app_state.backend {
         Some(v) => v,
         None => return Ok(HttpResponse::InternalServerError().finish()),
     };
     let backend = backend.lock().unwrap();
-    match backend.get_manifests(path.collection_id.as_str(), &Filtering::no_filter()) {
+    let records = app_state.metrics.time_backend("get_manifests", || {
+        backend.get_manifests(path.collection_id.as_str(), &filtering)
+    });
+    match records {
         Ok(v) => {
+            let (records, more, next) = filtering.paginate(v, limit);
+            Span::current().record("count", records.len());
+            let mut response = HttpResponse::Ok();
+            response.append_header(("Content-Type", CONTENT_TYPE_TAXII2));
+            append_date_added_headers(&mut response, &records);
             let mut result = Manifest::new();
-            if v.len() > 0 {
-                result.objects = Some(v);
+            if !records.is_empty() {
+                result.more = Some(more);
+                result.next = next;
+                result.objects = Some(records);
+            }
+            Ok(response.json(web::Json(result)))
+        }
+        Err(err) => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+/// The envelope for the objects selected by `filtering`, or a bundle of them for `bundle`. No
+/// more objects are served than are left of the daily quota of the consumer.
+fn objects_response(
+    app_state: &AppState,
+    api_root: &str,
+    collection_id: &str,
+    filtering: &Filtering,
+    bundle: bool,
+    consumer: &str,
+) -> HttpResponse {
+    let mut limit = match app_state.get_record_limit(api_root) {
+        Some(v) => v,
+        None => return taxii_error(StatusCode::NOT_FOUND, "api root not found", None),
+    };
+    let quota = app_state
+        .rate_limiter
+        .as_ref()
+        .and_then(|v| v.remaining_objects(api_root, consumer));
+    match quota {
+        Some(0) => {
+            return too_many_requests(
+                until_tomorrow(Utc::now()),
+                "the daily object quota is used up",
+            )
+        }
+        Some(v) => limit = limit.min(v.min(u32::MAX as u64) as u32),
+        None => (),
+    }
+    let backend = match &app_state.backend {
+        Some(v) => v.lock().unwrap(),
+        None => {
+            return taxii_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "no backend configured",
+                None,
+            )
+        }
+    };
+    let (records, objects) = match (
+        app_state.metrics.time_backend("get_manifests", || {
+            backend.get_manifests(collection_id, filtering)
+        }),
+        app_state.metrics.time_backend("get_objects", || {
+            backend.get_objects(collection_id, filtering)
+        }),
+    ) {
+        (Ok(records), Ok(objects)) => (records, objects),
+        (Err(err), _) | (_, Err(err)) => {
+            return taxii_error(
+                StatusCode::NOT_FOUND,
+                "collection not found",
+                Some(err.to_string().as_str()),
+            )
+        }
+    };
+    let (records, more, next) = filtering.paginate(records, limit);
+    let objects = select_objects(&objects, &records);
+    Span::current().record("count", objects.len());
+    app_state
+        .metrics
+        .add_objects_served(api_root, collection_id, objects.len() as u64);
+    if let Some(rate_limiter) = &app_state.rate_limiter {
+        rate_limiter.add_objects(api_root, consumer, objects.len() as u64);
+    }
+    let mut response = HttpResponse::Ok();
+    append_date_added_headers(&mut response, &records);
+    if bundle {
+        response.append_header(("Content-Type", CONTENT_TYPE_STIX2));
+        return response.json(web::Json(Bundle::new(objects)));
+    }
+    response.append_header(("Content-Type", CONTENT_TYPE_TAXII2));
+    let mut envelope = Envelope::new();
+    if !records.is_empty() {
+        envelope.more = Some(more);
+        envelope.next = next;
+        envelope.objects = Some(objects);
+    }
+    response.json(web::Json(envelope))
+}
+
+async fn handle_api_root_collection_objects(
+    wrapper: web::Data<AppStateWrapper>,
+    path: web::Path<APIRootCollectionPath>,
+    query: web::Query<HashMap<String, String>>,
+    principal: Option<Principal>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let app_state = wrapper.app_state.lock().unwrap();
+    if let Some(response) = check_access(
+        &app_state,
+        &principal,
+        Access::Read,
+        path.api_root.as_str(),
+        path.collection_id.as_str(),
+    ) {
+        return Ok(response);
+    }
+    let filtering = match Filtering::parse(&query) {
+        Ok(v) => v,
+        Err(err) => {
+            return Ok(taxii_error(
+                StatusCode::BAD_REQUEST,
+                "invalid filter",
+                Some(err.to_string().as_str()),
+            ))
+        }
+    };
+    Ok(objects_response(
+        &app_state,
+        path.api_root.as_str(),
+        path.collection_id.as_str(),
+        &filtering,
+        accepts_bundle(&req),
+        consumer(principal.as_ref(), req.peer_addr()).as_str(),
+    ))
+}
+
+/// The request body, or a 413 response as soon as it is longer than `limit`, whether its length
+/// is announced with Content-Length or not.
+async fn read_body(
+    req: &HttpRequest,
+    mut payload: web::Payload,
+    limit: u64,
+) -> Result<web::BytesMut, HttpResponse> {
+    let too_large = || {
+        taxii_error(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "request body too large",
+            Some(format!("the api root accepts at most {} bytes", limit).as_str()),
+        )
+    };
+    let content_length = req
+        .headers()
+        .get("content-length")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if content_length.is_some_and(|v| v > limit) {
+        return Err(too_large());
+    }
+    let mut body = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = match chunk {
+            Ok(v) => v,
+            Err(err) => {
+                return Err(taxii_error(
+                    StatusCode::BAD_REQUEST,
+                    "invalid request body",
+                    Some(err.to_string().as_str()),
+                ))
             }
-            return Ok(HttpResponse::Ok()
-                .append_header(("Content-Type", CONTENT_TYPE_TAXII2))
-                .json(web::Json(result)));
+        };
+        if (body.len() + chunk.len()) as u64 > limit {
+            return Err(too_large());
         }
-        Err(err) => return Ok(HttpResponse::NotFound().finish()),
+        body.extend_from_slice(&chunk);
     }
+    Ok(body)
+}
+
+async fn handle_api_root_collection_add_objects(
+    wrapper: web::Data<AppStateWrapper>,
+    path: web::Path<APIRootCollectionPath>,
+    payload: web::Payload,
+    principal: Option<Principal>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    // not holding the lock while the body is read
+    let max_content_length = {
+        let app_state = wrapper.app_state.lock().unwrap();
+        if let Some(response) = check_access(
+            &app_state,
+            &principal,
+            Access::Write,
+            path.api_root.as_str(),
+            path.collection_id.as_str(),
+        ) {
+            return Ok(response);
+        }
+        match app_state.api_roots.get(path.api_root.as_str()) {
+            Some(v) => v.config.max_content_length,
+            None => {
+                return Ok(taxii_error(
+                    StatusCode::NOT_FOUND,
+                    "api root not found",
+                    None,
+                ))
+            }
+        }
+    };
+    let body = match read_body(&req, payload, max_content_length).await {
+        Ok(v) => v,
+        Err(response) => return Ok(response),
+    };
+    let mut app_state = wrapper.app_state.lock().unwrap();
+    let envelope = match serde_json::from_slice::<Envelope>(&body) {
+        Ok(v) => v,
+        Err(err) => {
+            return Ok(taxii_error(
+                StatusCode::BAD_REQUEST,
+                "invalid envelope",
+                Some(err.to_string().as_str()),
+            ))
+        }
+    };
+    let objects = envelope.objects.unwrap_or_default();
+    Span::current().record("count", objects.len());
+    info!(
+        "adding {} objects to {}/{} by {}",
+        objects.len(),
+        path.api_root,
+        path.collection_id,
+        principal
+            .as_ref()
+            .map_or(AclPrincipal::Anonymous.to_string(), |v| v.username.clone())
+    );
+    let status = match &app_state.backend {
+        Some(backend) => {
+            let mut backend = backend.lock().unwrap();
+            app_state.metrics.time_backend("add_objects", || {
+                backend.add_objects(path.collection_id.as_str(), &objects)
+            })
+        }
+        None => {
+            return Ok(taxii_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "no backend configured",
+                None,
+            ))
+        }
+    };
+    let mut status = match status {
+        Ok(v) => v,
+        Err(err) => {
+            return Ok(taxii_error(
+                StatusCode::NOT_FOUND,
+                "collection not found",
+                Some(err.to_string().as_str()),
+            ))
+        }
+    };
+    status.collection_id = Some(path.collection_id.clone());
+    if let Err(err) = app_state.add_status(path.api_root.as_str(), &status) {
+        return Ok(HttpResponse::InternalServerError().finish());
+    }
+    app_state.metrics.add_objects_added(
+        path.api_root.as_str(),
+        path.collection_id.as_str(),
+        status.success_count as u64,
+    );
+    Ok(HttpResponse::Accepted()
+        .append_header(("Content-Type", CONTENT_TYPE_TAXII2))
+        .json(web::Json(status)))
+}
+
+#[derive(Deserialize)]
+struct APIRootObjectPath {
+    api_root: String,
+    collection_id: String,
+    object_id: String,
+}
+
+async fn handle_api_root_collection_object(
+    wrapper: web::Data<AppStateWrapper>,
+    path: web::Path<APIRootObjectPath>,
+    query: web::Query<HashMap<String, String>>,
+    principal: Option<Principal>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let app_state = wrapper.app_state.lock().unwrap();
+    if let Some(response) = check_access(
+        &app_state,
+        &principal,
+        Access::Read,
+        path.api_root.as_str(),
+        path.collection_id.as_str(),
+    ) {
+        return Ok(response);
+    }
+    let filtering = match Filtering::parse(&query) {
+        Ok(v) => with_object_id(v, path.object_id.as_str()),
+        Err(err) => {
+            return Ok(taxii_error(
+                StatusCode::BAD_REQUEST,
+                "invalid filter",
+                Some(err.to_string().as_str()),
+            ))
+        }
+    };
+    Ok(objects_response(
+        &app_state,
+        path.api_root.as_str(),
+        path.collection_id.as_str(),
+        &filtering,
+        accepts_bundle(&req),
+        consumer(principal.as_ref(), req.peer_addr()).as_str(),
+    ))
+}
+
+async fn handle_api_root_collection_delete_object(
+    wrapper: web::Data<AppStateWrapper>,
+    path: web::Path<APIRootObjectPath>,
+    query: web::Query<HashMap<String, String>>,
+    principal: Option<Principal>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let app_state = wrapper.app_state.lock().unwrap();
+    if let Some(response) = check_access(
+        &app_state,
+        &principal,
+        Access::Write,
+        path.api_root.as_str(),
+        path.collection_id.as_str(),
+    ) {
+        return Ok(response);
+    }
+    if !app_state.api_roots.contains_key(path.api_root.as_str()) {
+        return Ok(taxii_error(
+            StatusCode::NOT_FOUND,
+            "api root not found",
+            None,
+        ));
+    }
+    let filtering = match Filtering::parse(&query) {
+        Ok(v) => with_object_id(v, path.object_id.as_str()),
+        Err(err) => {
+            return Ok(taxii_error(
+                StatusCode::BAD_REQUEST,
+                "invalid filter",
+                Some(err.to_string().as_str()),
+            ))
+        }
+    };
+    let filtering = with_default_match(filtering, MatchField::Version, "all");
+    let deleted = match &app_state.backend {
+        Some(backend) => {
+            let mut backend = backend.lock().unwrap();
+            app_state.metrics.time_backend("delete_object", || {
+                backend.delete_object(
+                    path.collection_id.as_str(),
+                    path.object_id.as_str(),
+                    &filtering,
+                )
+            })
+        }
+        None => {
+            return Ok(taxii_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "no backend configured",
+                None,
+            ))
+        }
+    };
+    match deleted {
+        Ok(0) => Ok(taxii_error(StatusCode::NOT_FOUND, "object not found", None)),
+        Ok(_) => Ok(HttpResponse::Ok()
+            .append_header(("Content-Type", CONTENT_TYPE_TAXII2))
+            .finish()),
+        Err(err) => Ok(taxii_error(
+            StatusCode::NOT_FOUND,
+            "collection not found",
+            Some(err.to_string().as_str()),
+        )),
+    }
+}
+
+async fn handle_api_root_collection_object_versions(
+    wrapper: web::Data<AppStateWrapper>,
+    path: web::Path<APIRootObjectPath>,
+    query: web::Query<HashMap<String, String>>,
+    principal: Option<Principal>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let app_state = wrapper.app_state.lock().unwrap();
+    if let Some(response) = check_access(
+        &app_state,
+        &principal,
+        Access::Read,
+        path.api_root.as_str(),
+        path.collection_id.as_str(),
+    ) {
+        return Ok(response);
+    }
+    let limit = match app_state.get_record_limit(path.api_root.as_str()) {
+        Some(v) => v,
+        None => {
+            return Ok(taxii_error(
+                StatusCode::NOT_FOUND,
+                "api root not found",
+                None,
+            ))
+        }
+    };
+    let filtering = match Filtering::parse(&query) {
+        Ok(v) => with_object_id(v, path.object_id.as_str()),
+        Err(err) => {
+            return Ok(taxii_error(
+                StatusCode::BAD_REQUEST,
+                "invalid filter",
+                Some(err.to_string().as_str()),
+            ))
+        }
+    };
+    let filtering = with_default_match(filtering, MatchField::Version, "all");
+    let records = match &app_state.backend {
+        Some(backend) => {
+            let backend = backend.lock().unwrap();
+            app_state.metrics.time_backend("get_manifests", || {
+                backend.get_manifests(path.collection_id.as_str(), &filtering)
+            })
+        }
+        None => {
+            return Ok(taxii_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "no backend configured",
+                None,
+            ))
+        }
+    };
+    let records = match records {
+        Ok(v) => v,
+        Err(err) => {
+            return Ok(taxii_error(
+                StatusCode::NOT_FOUND,
+                "collection not found",
+                Some(err.to_string().as_str()),
+            ))
+        }
+    };
+    if records.is_empty() {
+        return Ok(taxii_error(StatusCode::NOT_FOUND, "object not found", None));
+    }
+    let (records, more, _next) = filtering.paginate(records, limit);
+    Span::current().record("count", records.len());
+    let mut response = HttpResponse::Ok();
+    response.append_header(("Content-Type", CONTENT_TYPE_TAXII2));
+    append_date_added_headers(&mut response, &records);
+    let versions = Versions {
+        more: Some(more),
+        versions: Some(records.into_iter().map(|rec| rec.version).collect()),
+    };
+    Ok(response.json(web::Json(versions)))
 }
 
 #[derive(Debug)]
@@ -474,29 +1349,216 @@ pub struct ListenAddr {
 
 impl ListenAddr {
     pub fn new(ip: &str, port: u16) -> ListenAddr {
-        return ListenAddr {
+        ListenAddr {
             ip: String::from(ip),
             port,
+        }
+    }
+}
+
+/// The result of one of the readiness checks.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Check {
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collections: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Check {
+    fn ok() -> Check {
+        Check {
+            status: String::from("ok"),
+            collections: None,
+            error: None,
+        }
+    }
+    fn failed(error: &str) -> Check {
+        Check {
+            status: String::from("failed"),
+            collections: None,
+            error: Some(String::from(error)),
+        }
+    }
+}
+
+/// The readiness resource: `ready` when all checks are ok, `degraded` otherwise.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Readiness {
+    pub status: String,
+    pub checks: BTreeMap<String, Check>,
+}
+
+/// Whether the process is alive, for liveness probes.
+async fn handle_healthz() -> Result<HttpResponse, Error> {
+    Ok(HttpResponse::Ok().json(serde_json::json!({"status": "ok"})))
+}
+
+/// Whether the configuration file is still valid, the backend can list its collections, and
+/// it has those of each api root, for readiness probes: 503 when not. State built without a
+/// configuration file has no `config` check.
+async fn handle_readyz(wrapper: web::Data<AppStateWrapper>) -> Result<HttpResponse, Error> {
+    let config_path = wrapper.app_state.lock().unwrap().config_path.clone();
+    let mut checks = BTreeMap::<String, Check>::new();
+    if let Some(path) = config_path {
+        let validate = move || AppConfig::load(path.as_path())?.validate();
+        let config = match web::block(validate).await {
+            Ok(Ok(_)) => Check::ok(),
+            Ok(Err(err)) => Check::failed(err.to_string().as_str()),
+            Err(err) => Check::failed(err.to_string().as_str()),
+        };
+        checks.insert(String::from("config"), config);
+    }
+    // the state is not locked while the backend is, so requests go on during a slow backend
+    let (backend, metrics, api_roots) = {
+        let app_state = wrapper.app_state.lock().unwrap();
+        let api_roots: Vec<(String, Vec<String>)> = app_state
+            .api_roots
+            .iter()
+            .map(|(name, api_root)| {
+                let configured = api_root
+                    .collections
+                    .collections
+                    .iter()
+                    .flatten()
+                    .map(|v| v.id.clone())
+                    .collect();
+                (name.clone(), configured)
+            })
+            .collect();
+        (
+            app_state.backend.clone(),
+            app_state.metrics.clone(),
+            api_roots,
+        )
+    };
+    let collections = match &backend {
+        Some(backend) => {
+            let backend = backend.lock().unwrap();
+            metrics.time_backend("get_collections", || backend.get_collections())
+        }
+        None => Err(MyError(String::from("no backend configured"))),
+    };
+    let backend = match &collections {
+        Ok(v) => Check {
+            collections: Some(v.len()),
+            ..Check::ok()
+        },
+        Err(err) => Check::failed(err.to_string().as_str()),
+    };
+    checks.insert(String::from("backend"), backend);
+    for (name, configured) in api_roots.iter() {
+        let check = match &collections {
+            Ok(available) => {
+                let missing: Vec<&str> = configured
+                    .iter()
+                    .filter(|id| !available.iter().any(|v| v == *id))
+                    .map(|v| v.as_str())
+                    .collect();
+                match missing.is_empty() {
+                    true => Check {
+                        collections: Some(configured.len()),
+                        ..Check::ok()
+                    },
+                    false => Check::failed(
+                        format!("collections not in the backend: {}", missing.join(", ")).as_str(),
+                    ),
+                }
+            }
+            Err(_) => Check::failed("backend unavailable"),
         };
+        checks.insert(format!("api_root:{}", name), check);
+    }
+    let ready = checks.values().all(|v| v.status == "ok");
+    let readiness = Readiness {
+        status: String::from(if ready { "ready" } else { "degraded" }),
+        checks,
+    };
+    if ready {
+        Ok(HttpResponse::Ok().json(readiness))
+    } else {
+        Ok(HttpResponse::ServiceUnavailable().json(readiness))
+    }
+}
+
+/// The number of status resources of each state, over all api roots.
+fn status_counts(app_state: &AppState) -> HashMap<String, i64> {
+    let mut counts = HashMap::<String, i64>::new();
+    for api_root in app_state.api_roots.values() {
+        for status in api_root.statii.values() {
+            *counts.entry(status.status.clone()).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+async fn handle_metrics(wrapper: web::Data<AppStateWrapper>) -> Result<HttpResponse, Error> {
+    let metrics = {
+        let app_state = wrapper.app_state.lock().unwrap();
+        app_state
+            .metrics
+            .set_status_counts(&status_counts(&app_state));
+        app_state.metrics.clone()
+    };
+    match metrics.encode() {
+        Ok(v) => Ok(HttpResponse::Ok()
+            .content_type(prometheus::TEXT_FORMAT)
+            .body(v)),
+        Err(err) => Ok(HttpResponse::InternalServerError().finish()),
     }
 }
 
-fn new_app(
+/// The body of the responses of the app, from the handlers or from one of the middlewares.
+pub type AppBody = EitherBody<EitherBody<EitherBody<EitherBody<BoxBody>>>>;
+
+pub fn new_app(
     app_state: Arc<Mutex<AppState>>,
 ) -> actix_web::App<
     impl ServiceFactory<
         ServiceRequest,
         Config = (),
-        Response = ServiceResponse<EitherBody<BoxBody>>,
+        Response = ServiceResponse<AppBody>,
         Error = actix_web::Error,
         InitError = (),
     >,
 > {
+    let (users, api_keys, rate_limiter, metrics) = {
+        let app_state = app_state.lock().unwrap();
+        (
+            app_state.users.clone(),
+            app_state.api_keys.clone(),
+            app_state.rate_limiter.clone(),
+            app_state.metrics.clone(),
+        )
+    };
     let wrapper = AppStateWrapper { app_state };
-    return App::new()
+    // the middlewares run in the reverse order of wrapping: TraceRequest, RecordMetrics,
+    // Authenticate, RateLimit, CheckContentType, then CheckAcceptHeader
+    App::new()
         .app_data(web::Data::new(wrapper.clone()))
-        .wrap(middleware::CheckAcceptHeader)
+        .wrap(
+            middleware::CheckAcceptHeader::new()
+                .with_skip_path("/metrics")
+                .with_skip_path("/healthz")
+                .with_skip_path("/readyz"),
+        )
+        .wrap(middleware::CheckContentType)
+        .wrap(middleware::RateLimit::new(rate_limiter.clone()))
+        // before RateLimit, which limits the authenticated principal
+        .wrap(
+            middleware::Authenticate::new(users, api_keys)
+                .with_rate_limiter(rate_limiter)
+                .with_skip_path("/healthz")
+                .with_skip_path("/readyz"),
+        )
+        // outermost, to count and log the responses of the other middlewares too
+        .wrap(middleware::RecordMetrics::new(metrics))
+        .wrap(middleware::TraceRequest)
         .service(web::resource("/taxii2").route(web::get().to(handle_discovery)))
+        .service(web::resource("/metrics").route(web::get().to(handle_metrics)))
+        .service(web::resource("/healthz").route(web::get().to(handle_healthz)))
+        .service(web::resource("/readyz").route(web::get().to(handle_readyz)))
         .service(web::resource("/{api_root}/").route(web::get().to(handle_api_root)))
         .service(
             web::resource("/{api_root}/status/{status_id}/")
@@ -513,7 +1575,21 @@ fn new_app(
         .service(
             web::resource("/{api_root}/collections/{collection_id}/manifest/")
                 .route(web::get().to(handle_api_root_collection_manifests)),
-        );
+        )
+        .service(
+            web::resource("/{api_root}/collections/{collection_id}/objects/")
+                .route(web::get().to(handle_api_root_collection_objects))
+                .route(web::post().to(handle_api_root_collection_add_objects)),
+        )
+        .service(
+            web::resource("/{api_root}/collections/{collection_id}/objects/{object_id}/")
+                .route(web::get().to(handle_api_root_collection_object))
+                .route(web::delete().to(handle_api_root_collection_delete_object)),
+        )
+        .service(
+            web::resource("/{api_root}/collections/{collection_id}/objects/{object_id}/versions/")
+                .route(web::get().to(handle_api_root_collection_object_versions)),
+        )
 }
 
 #[tokio::main]
@@ -524,11 +1600,37 @@ pub async fn main() -> std::io::Result<()> {
         Ok(app_state) => app_state,
         Err(err) => panic!("err={}", err),
     };
+    let tls = match &app_state.tls {
+        Some(v) => match Tls::new(v) {
+            Ok(v) => Some(Arc::new(v)),
+            Err(err) => panic!("err={}", err),
+        },
+        None => None,
+    };
+    if let Some(api_keys) = &app_state.api_keys {
+        tokio::spawn(reload_api_keys(api_keys.clone(), RELOAD_INTERVAL));
+    }
     let app_state = Arc::new(Mutex::new(app_state));
     let addr = ListenAddr::new("127.0.0.1", 8080);
-    info!("listening: {}:{}", addr.ip, addr.port);
+    let tls = match tls {
+        Some(v) => v,
+        None => {
+            info!("listening: http://{}:{}", addr.ip, addr.port);
+            return HttpServer::new(move || new_app(app_state.clone()))
+                .bind((addr.ip, addr.port))?
+                .run()
+                .await;
+        }
+    };
+    let server_config = match tls.server_config() {
+        Ok(v) => v,
+        Err(err) => panic!("err={}", err),
+    };
+    tokio::spawn(reload_on_sighup(tls.clone()));
+    info!("listening: https://{}:{}", addr.ip, addr.port);
     HttpServer::new(move || new_app(app_state.clone()))
-        .bind((addr.ip, addr.port))?
+        .on_connect(move |connection, data| tls.on_connect(connection, data))
+        .bind_rustls_0_23((addr.ip, addr.port), server_config)?
         .run()
         .await
 }
@@ -574,6 +1676,1013 @@ mod tests {
         Ok(())
     }
 
+    #[actix_web::test]
+    async fn test_accept_negotiation() -> Result<(), Error> {
+        let app_state = new_limited_app_state();
+        let mut object = Object::new(
+            "indicator",
+            "indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f",
+        );
+        object.modified = Some(String::from("2016-04-06T20:03:48.000Z"));
+        if let Some(backend) = &app_state.backend {
+            let mut backend = backend.lock().unwrap();
+            backend.add_objects("collection1", &[object]).unwrap();
+        }
+        let app_state = Arc::new(Mutex::new(app_state));
+        let app = test::init_service(new_app(app_state)).await;
+        let taxii = Some("application/taxii+json;version=2.1");
+        let stix = Some("application/stix+json;version=2.1");
+        let cases = [
+            ("/taxii2", "application/taxii+json", taxii),
+            ("/taxii2", "text/html, */*;q=0.1", taxii),
+            ("/taxii2", " application/json ,application/* ; q=0.5", taxii),
+            ("/taxii2", "application/stix+json", None),
+            ("/taxii2", "application/taxii+json;q=0", None),
+            ("/api1/collections/", "*/*", taxii),
+            ("/api1/collections/collection1/objects/", "*/*", taxii),
+            (
+                "/api1/collections/collection1/objects/",
+                "application/stix+json",
+                stix,
+            ),
+            (
+                "/api1/collections/collection1/objects/",
+                "application/taxii+json;q=0.5, application/stix+json;version=2.1;q=0.9",
+                stix,
+            ),
+            (
+                "/api1/collections/collection1/objects/indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f/",
+                "application/stix+json",
+                stix,
+            ),
+            ("/api1/collections/collection1/manifest/", "application/stix+json", None),
+        ];
+        for (uri, accept, content_type) in cases {
+            let req = test::TestRequest::get()
+                .uri(uri)
+                .append_header(("Accept", accept))
+                .to_request();
+            let resp = app.call(req).await?;
+            match content_type {
+                Some(content_type) => {
+                    assert_eq!(resp.status(), http::StatusCode::OK, "{} {}", uri, accept);
+                    assert_eq!(content_type, resp.headers().get("Content-Type").unwrap());
+                }
+                None => {
+                    assert_eq!(
+                        resp.status(),
+                        http::StatusCode::NOT_ACCEPTABLE,
+                        "{} {}",
+                        uri,
+                        accept
+                    );
+                }
+            }
+        }
+
+        let req = test::TestRequest::get()
+            .uri("/api1/collections/collection1/objects/")
+            .append_header(("Accept", "application/stix+json"))
+            .to_request();
+        let resp = app.call(req).await?;
+        let body = to_bytes(resp.into_body()).await?;
+        let bundle: Bundle = serde_json::from_slice(&body).unwrap();
+        assert_eq!("bundle", bundle.typ);
+        assert!(bundle.id.starts_with("bundle--"));
+        assert_eq!(1, bundle.objects.unwrap().len());
+
+        let app = App::new()
+            .wrap(middleware::CheckAcceptHeader::new().with_skip_path("/healthz"))
+            .route("/healthz", web::get().to(HttpResponse::Ok))
+            .route("/other", web::get().to(HttpResponse::Ok));
+        let app = test::init_service(app).await;
+        let req = test::TestRequest::get().uri("/healthz").to_request();
+        assert_eq!(app.call(req).await?.status(), http::StatusCode::OK);
+        let req = test::TestRequest::get().uri("/other").to_request();
+        assert_eq!(
+            app.call(req).await?.status(),
+            http::StatusCode::NOT_ACCEPTABLE
+        );
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_authentication() -> Result<(), Error> {
+        use crate::taxii21::auth::{PasswordHash, TEST_PARAMS};
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let mut users = UserStore::new();
+        users.add_user("alice", &PasswordHash::new("secret", &TEST_PARAMS));
+        let mut app_state = AppState::new_empty();
+        app_state.set_users(users);
+        let app_state = Arc::new(Mutex::new(app_state));
+        let app = test::init_service(new_app(app_state.clone())).await;
+        let basic = |credentials: &str| format!("Basic {}", STANDARD.encode(credentials));
+
+        let req = test::TestRequest::get()
+            .uri("/taxii2")
+            .append_header(("Accept", "application/taxii+json;version=2.1"))
+            .to_request();
+        let resp = app.call(req).await?;
+        assert_eq!(resp.status(), http::StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            "Basic realm=\"TAXII\", charset=\"UTF-8\"",
+            resp.headers().get("WWW-Authenticate").unwrap()
+        );
+        assert_eq!(
+            "application/taxii+json;version=2.1",
+            resp.headers().get("Content-Type").unwrap()
+        );
+        let body = to_bytes(resp.into_body()).await?;
+        let error: ErrorMessage = serde_json::from_slice(&body).unwrap();
+        assert_eq!(Some(String::from("401")), error.http_status);
+
+        for credentials in ["alice:wrong", "bob:secret"] {
+            let req = test::TestRequest::get()
+                .uri("/taxii2")
+                .append_header(("Accept", "application/taxii+json;version=2.1"))
+                .append_header(("Authorization", basic(credentials)))
+                .to_request();
+            let resp = app.call(req).await?;
+            assert_eq!(resp.status(), http::StatusCode::UNAUTHORIZED);
+            assert!(resp.headers().contains_key("WWW-Authenticate"));
+        }
+
+        let req = test::TestRequest::get()
+            .uri("/taxii2")
+            .append_header(("Accept", "application/taxii+json;version=2.1"))
+            .append_header(("Authorization", basic("alice:secret")))
+            .to_request();
+        let resp = app.call(req).await?;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+
+        // the principal is available to handlers
+        let users = app_state.lock().unwrap().users.clone();
+        let app = test::init_service(
+            App::new()
+                .wrap(middleware::Authenticate::new(users, None))
+                .route(
+                    "/",
+                    web::get().to(|principal: Principal| async move { principal.username }),
+                ),
+        )
+        .await;
+        let req = test::TestRequest::get()
+            .uri("/")
+            .append_header(("Authorization", basic("alice:secret")))
+            .to_request();
+        let resp = app.call(req).await?;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert_eq!("alice", to_bytes(resp.into_body()).await?);
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_api_key_scopes() -> Result<(), Error> {
+        use crate::taxii21::{
+            api_keys::{ApiKeys, Scope},
+            memory_backend::MemoryBackend,
+        };
+
+        let path = std::env::temp_dir().join(format!("api-keys-{}.json", uuid::Uuid::new_v4()));
+        let mut keys = ApiKeys::load(path.as_path()).unwrap();
+        let scopes =
+            |v: &[&str]| -> Vec<Scope> { v.iter().map(|v| Scope::parse(v).unwrap()).collect() };
+        let (_, reader) = keys.create("reader", &scopes(&["read:api1"]));
+        let (_, writer) = keys.create("writer", &scopes(&["write:api1/collection1"]));
+        let (_, other) = keys.create("other", &scopes(&["read:api1/collection2", "write:api2"]));
+        keys.save(path.as_path()).unwrap();
+
+        let mut backend = MemoryBackend::new();
+        backend.add_collection("collection1");
+        let mut app_state = AppState::new_empty();
+        app_state.set_backend(Arc::new(Mutex::new(backend)));
+        app_state.set_api_keys(ApiKeyStore::new(path.as_path()).unwrap());
+        let versions = vec![String::from("application/taxii+json;version=2.1")];
+        app_state.api_roots.insert(
+            String::from("api1"),
+            APIRoot::new(&APIRootConfig::new("api root 1", None, &versions, 1000)),
+        );
+        let mut collection = CollectionConfig::new("collection1", "collection 1");
+        collection.can_read = true;
+        collection.can_write = true;
+        app_state.add_collection("api1", &collection).unwrap();
+        let app = test::init_service(new_app(Arc::new(Mutex::new(app_state)))).await;
+
+        let envelope = r#"{"objects": [{"type": "indicator", "spec_version": "2.1",
+            "id": "indicator--c2a8e7ba-0b1b-4b4c-9d4c-2b2a3e3e0a01",
+            "created": "2023-01-01T00:00:00.000Z", "modified": "2023-01-01T00:00:00.000Z",
+            "pattern": "[ipv4-addr:value = '198.51.100.1']", "pattern_type": "stix",
+            "valid_from": "2023-01-01T00:00:00Z"}]}"#;
+        let request = |method: &str, uri: &str, token: Option<&str>| {
+            let mut req = match method {
+                "POST" => test::TestRequest::post()
+                    .insert_header(("Content-Type", "application/taxii+json;version=2.1"))
+                    .set_payload(envelope),
+                _ => test::TestRequest::get(),
+            }
+            .uri(uri)
+            .append_header(("Accept", "application/taxii+json;version=2.1"));
+            if let Some(token) = token {
+                req = req.append_header(("Authorization", format!("Bearer {}", token)));
+            }
+            req.to_request()
+        };
+        let manifest = "/api1/collections/collection1/manifest/";
+        let objects = "/api1/collections/collection1/objects/";
+        let cases = [
+            ("GET", manifest, None, http::StatusCode::UNAUTHORIZED),
+            (
+                "GET",
+                objects,
+                Some("nokey.secret"),
+                http::StatusCode::UNAUTHORIZED,
+            ),
+            ("GET", manifest, Some(reader.as_str()), http::StatusCode::OK),
+            ("GET", objects, Some(reader.as_str()), http::StatusCode::OK),
+            (
+                "POST",
+                objects,
+                Some(reader.as_str()),
+                http::StatusCode::FORBIDDEN,
+            ),
+            (
+                "GET",
+                manifest,
+                Some(writer.as_str()),
+                http::StatusCode::FORBIDDEN,
+            ),
+            (
+                "GET",
+                objects,
+                Some(writer.as_str()),
+                http::StatusCode::FORBIDDEN,
+            ),
+            (
+                "POST",
+                objects,
+                Some(writer.as_str()),
+                http::StatusCode::ACCEPTED,
+            ),
+            (
+                "GET",
+                manifest,
+                Some(other.as_str()),
+                http::StatusCode::FORBIDDEN,
+            ),
+            (
+                "GET",
+                objects,
+                Some(other.as_str()),
+                http::StatusCode::FORBIDDEN,
+            ),
+            (
+                "POST",
+                objects,
+                Some(other.as_str()),
+                http::StatusCode::FORBIDDEN,
+            ),
+        ];
+        for (method, uri, token, status) in cases {
+            let resp = app.call(request(method, uri, token)).await?;
+            assert_eq!(resp.status(), status, "{} {} {:?}", method, uri, token);
+            if status == http::StatusCode::UNAUTHORIZED {
+                assert_eq!(
+                    "Bearer realm=\"TAXII\"",
+                    resp.headers().get("WWW-Authenticate").unwrap()
+                );
+            }
+            if status == http::StatusCode::FORBIDDEN {
+                let body = to_bytes(resp.into_body()).await?;
+                let error: ErrorMessage = serde_json::from_slice(&body).unwrap();
+                assert_eq!(Some(String::from("403")), error.http_status);
+            }
+        }
+
+        // the object added by the writer is seen by the reader
+        let resp = app
+            .call(request("GET", objects, Some(reader.as_str())))
+            .await?;
+        let body = to_bytes(resp.into_body()).await?;
+        let envelope: Envelope = serde_json::from_slice(&body).unwrap();
+        assert_eq!(1, envelope.objects.unwrap().len());
+
+        std::fs::remove_file(path.as_path()).unwrap();
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_collection_acl() -> Result<(), Error> {
+        use crate::taxii21::{
+            auth::{PasswordHash, TEST_PARAMS},
+            memory_backend::MemoryBackend,
+        };
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let mut users = UserStore::new();
+        users.add_user("alice", &PasswordHash::new("alice", &TEST_PARAMS));
+        users.add_user("bob", &PasswordHash::new("bob", &TEST_PARAMS));
+        let cfg = r#"
+[[acl]]
+principal = "alice"
+api_root = "api1"
+collection = "private"
+permissions = ["read", "write"]
+
+[[acl]]
+principal = "*"
+api_root = "api1"
+collection = "public"
+permissions = ["read"]
+
+[[acl]]
+principal = "alice"
+api_root = "api1"
+collection = "archive"
+permissions = ["read", "write"]
+"#;
+        #[derive(Deserialize)]
+        struct Config {
+            acl: Vec<AclEntry>,
+        }
+        let cfg: Config = toml::from_str(cfg).unwrap();
+
+        let mut backend = MemoryBackend::new();
+        backend.add_collection("private");
+        backend.add_collection("public");
+        backend.add_collection("archive");
+        let mut app_state = AppState::new_empty();
+        app_state.set_backend(Arc::new(Mutex::new(backend)));
+        app_state.set_users(users);
+        app_state.set_acl(Acl::new(&cfg.acl).unwrap());
+        let versions = vec![String::from("application/taxii+json;version=2.1")];
+        app_state.api_roots.insert(
+            String::from("api1"),
+            APIRoot::new(&APIRootConfig::new("api root 1", None, &versions, 1000)),
+        );
+        for id in ["private", "public"] {
+            let mut collection = CollectionConfig::new(id, id);
+            collection.can_read = true;
+            collection.can_write = true;
+            app_state.add_collection("api1", &collection).unwrap();
+        }
+        // the ACL cannot grant more than the collection allows
+        let mut collection = CollectionConfig::new("archive", "archive");
+        collection.can_read = true;
+        app_state.add_collection("api1", &collection).unwrap();
+        let app = test::init_service(new_app(Arc::new(Mutex::new(app_state)))).await;
+        let request = |post: bool, uri: &str, user: &str| {
+            match post {
+                true => test::TestRequest::post()
+                    .insert_header(("Content-Type", "application/taxii+json;version=2.1"))
+                    .set_payload(r#"{"objects": []}"#),
+                false => test::TestRequest::get(),
+            }
+            .uri(uri)
+            .append_header(("Accept", "application/taxii+json;version=2.1"))
+            .append_header((
+                "Authorization",
+                format!("Basic {}", STANDARD.encode(format!("{}:{}", user, user))),
+            ))
+            .to_request()
+        };
+
+        let listing = |body: &[u8]| -> Vec<(String, bool, bool)> {
+            let collections: Collections = serde_json::from_slice(body).unwrap();
+            collections
+                .collections
+                .unwrap_or_default()
+                .iter()
+                .map(|v| (v.id.clone(), v.can_read, v.can_write))
+                .collect()
+        };
+        let resp = app
+            .call(request(false, "/api1/collections/", "alice"))
+            .await?;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert_eq!(
+            vec![
+                (String::from("private"), true, true),
+                (String::from("public"), true, false),
+                (String::from("archive"), true, false)
+            ],
+            listing(&to_bytes(resp.into_body()).await?)
+        );
+        let resp = app
+            .call(request(false, "/api1/collections/", "bob"))
+            .await?;
+        assert_eq!(
+            vec![(String::from("public"), true, false)],
+            listing(&to_bytes(resp.into_body()).await?)
+        );
+
+        let cases = [
+            (
+                false,
+                "/api1/collections/private/",
+                "bob",
+                http::StatusCode::FORBIDDEN,
+            ),
+            (
+                false,
+                "/api1/collections/public/",
+                "bob",
+                http::StatusCode::OK,
+            ),
+            (
+                false,
+                "/api1/collections/private/objects/",
+                "bob",
+                http::StatusCode::FORBIDDEN,
+            ),
+            (
+                false,
+                "/api1/collections/private/manifest/",
+                "bob",
+                http::StatusCode::FORBIDDEN,
+            ),
+            (
+                true,
+                "/api1/collections/public/objects/",
+                "bob",
+                http::StatusCode::FORBIDDEN,
+            ),
+            (
+                false,
+                "/api1/collections/public/objects/",
+                "bob",
+                http::StatusCode::OK,
+            ),
+            (
+                true,
+                "/api1/collections/private/objects/",
+                "alice",
+                http::StatusCode::ACCEPTED,
+            ),
+            (
+                true,
+                "/api1/collections/public/objects/",
+                "alice",
+                http::StatusCode::FORBIDDEN,
+            ),
+            (
+                false,
+                "/api1/collections/archive/objects/",
+                "alice",
+                http::StatusCode::OK,
+            ),
+            (
+                true,
+                "/api1/collections/archive/objects/",
+                "alice",
+                http::StatusCode::FORBIDDEN,
+            ),
+        ];
+        for (post, uri, user, status) in cases {
+            let resp = app.call(request(post, uri, user)).await?;
+            assert_eq!(resp.status(), status, "{} {}", uri, user);
+            if status == http::StatusCode::FORBIDDEN {
+                let body = to_bytes(resp.into_body()).await?;
+                let error: ErrorMessage = serde_json::from_slice(&body).unwrap();
+                assert_eq!("access denied", error.title);
+            }
+        }
+
+        // the status of an add only for those who may add to the collection
+        let resp = app
+            .call(request(true, "/api1/collections/private/objects/", "alice"))
+            .await?;
+        let status: Status = serde_json::from_slice(&to_bytes(resp.into_body()).await?).unwrap();
+        let uri = format!("/api1/status/{}/", status.id);
+        let resp = app.call(request(false, uri.as_str(), "alice")).await?;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let resp = app.call(request(false, uri.as_str(), "bob")).await?;
+        assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+        Ok(())
+    }
+
+    fn new_limited_app_state() -> AppState {
+        use crate::taxii21::memory_backend::MemoryBackend;
+
+        let mut backend = MemoryBackend::new();
+        backend.add_collection("collection1");
+        let mut app_state = AppState::new_empty();
+        app_state.set_backend(Arc::new(Mutex::new(backend)));
+        let versions = vec![String::from("application/taxii+json;version=2.1")];
+        app_state.api_roots.insert(
+            String::from("api1"),
+            APIRoot::new(&APIRootConfig::new("api root 1", None, &versions, 1000)),
+        );
+        let mut collection = CollectionConfig::new("collection1", "collection 1");
+        collection.can_read = true;
+        collection.can_write = true;
+        app_state.add_collection("api1", &collection).unwrap();
+        app_state
+    }
+
+    #[actix_web::test]
+    async fn test_request_limits() -> Result<(), Error> {
+        let app_state = Arc::new(Mutex::new(new_limited_app_state()));
+        let app = test::init_service(new_app(app_state)).await;
+        let small = r#"{"objects": []}"#.to_string();
+        let large = format!(r#"{{"objects": [], "padding": "{}"}}"#, "x".repeat(2000));
+        let cases = [
+            (
+                Some("application/taxii+json;version=2.1"),
+                &small,
+                http::StatusCode::ACCEPTED,
+            ),
+            (
+                Some("application/taxii+json"),
+                &small,
+                http::StatusCode::ACCEPTED,
+            ),
+            (
+                Some("application/taxii+json; version=\"2.1\""),
+                &small,
+                http::StatusCode::ACCEPTED,
+            ),
+            (
+                Some("application/taxii+json;version=2.0"),
+                &small,
+                http::StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ),
+            (
+                Some("application/json"),
+                &small,
+                http::StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ),
+            (None, &small, http::StatusCode::UNSUPPORTED_MEDIA_TYPE),
+            (
+                Some("application/taxii+json;version=2.1"),
+                &large,
+                http::StatusCode::PAYLOAD_TOO_LARGE,
+            ),
+        ];
+        for (content_type, body, status) in cases {
+            let mut req = test::TestRequest::post()
+                .uri("/api1/collections/collection1/objects/")
+                .append_header(("Accept", "application/taxii+json;version=2.1"))
+                .set_payload(body.clone());
+            if let Some(content_type) = content_type {
+                req = req.insert_header(("Content-Type", content_type));
+            }
+            let resp = app.call(req.to_request()).await?;
+            assert_eq!(resp.status(), status, "{:?}", content_type);
+            if status != http::StatusCode::ACCEPTED {
+                let body = to_bytes(resp.into_body()).await?;
+                let error: ErrorMessage = serde_json::from_slice(&body).unwrap();
+                assert_eq!(Some(status.as_u16().to_string()), error.http_status);
+            }
+        }
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_rate_limits() -> Result<(), Error> {
+        let mut app_state = new_limited_app_state();
+        app_state.set_rate_limiter(
+            RateLimiter::new(&[RateLimitConfig {
+                api_root: String::from("api1"),
+                requests_per_second: 0.001,
+                burst: 2,
+                daily_objects: None,
+            }])
+            .unwrap(),
+        );
+        let app = test::init_service(new_app(Arc::new(Mutex::new(app_state)))).await;
+        let get = |addr: &str| {
+            test::TestRequest::get()
+                .uri("/api1/collections/collection1/manifest/")
+                .append_header(("Accept", "application/taxii+json;version=2.1"))
+                .peer_addr(addr.parse().unwrap())
+                .to_request()
+        };
+        for _ in 0..2 {
+            let resp = app.call(get("10.0.0.1:1000")).await?;
+            assert_eq!(resp.status(), http::StatusCode::OK);
+        }
+        let resp = app.call(get("10.0.0.1:1001")).await?;
+        assert_eq!(resp.status(), http::StatusCode::TOO_MANY_REQUESTS);
+        let retry_after = resp.headers().get("Retry-After").unwrap().to_str().unwrap();
+        assert!(retry_after.parse::<u64>().unwrap() > 900);
+        let body = to_bytes(resp.into_body()).await?;
+        let error: ErrorMessage = serde_json::from_slice(&body).unwrap();
+        assert_eq!(Some(String::from("429")), error.http_status);
+        // other clients and the discovery are not limited
+        let resp = app.call(get("10.0.0.2:1000")).await?;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let req = test::TestRequest::get()
+            .uri("/taxii2")
+            .append_header(("Accept", "application/taxii+json;version=2.1"))
+            .peer_addr("10.0.0.1:1000".parse().unwrap())
+            .to_request();
+        assert_eq!(app.call(req).await?.status(), http::StatusCode::OK);
+
+        let mut app_state = new_limited_app_state();
+        if let Some(backend) = &app_state.backend {
+            let mut backend = backend.lock().unwrap();
+            let mut objects = Vec::<Object>::new();
+            for id in [
+                "indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f",
+                "indicator--6770298f-0fd8-471a-ab8c-1c658a46574e",
+            ] {
+                let mut object = Object::new("indicator", id);
+                object.modified = Some(String::from("2016-04-06T20:03:48.000Z"));
+                objects.push(object);
+            }
+            backend.add_objects("collection1", &objects).unwrap();
+        }
+        app_state.set_rate_limiter(
+            RateLimiter::new(&[RateLimitConfig {
+                api_root: String::from("api1"),
+                requests_per_second: 100.0,
+                burst: 100,
+                daily_objects: Some(1),
+            }])
+            .unwrap(),
+        );
+        let app = test::init_service(new_app(Arc::new(Mutex::new(app_state)))).await;
+        let get = || {
+            test::TestRequest::get()
+                .uri("/api1/collections/collection1/objects/")
+                .append_header(("Accept", "application/taxii+json;version=2.1"))
+                .peer_addr("10.0.0.1:1000".parse().unwrap())
+                .to_request()
+        };
+        let resp = app.call(get()).await?;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let body = to_bytes(resp.into_body()).await?;
+        let envelope: Envelope = serde_json::from_slice(&body).unwrap();
+        assert_eq!(1, envelope.objects.unwrap().len());
+        assert_eq!(Some(true), envelope.more);
+        let resp = app.call(get()).await?;
+        assert_eq!(resp.status(), http::StatusCode::TOO_MANY_REQUESTS);
+        assert!(resp.headers().contains_key("Retry-After"));
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_failed_authentication_rate_limit() -> Result<(), Error> {
+        use crate::taxii21::auth::{PasswordHash, TEST_PARAMS};
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let mut users = UserStore::new();
+        users.add_user("alice", &PasswordHash::new("secret", &TEST_PARAMS));
+        let mut app_state = new_limited_app_state();
+        app_state.set_users(users);
+        app_state.set_rate_limiter(
+            RateLimiter::new(&[RateLimitConfig {
+                api_root: String::from("api1"),
+                requests_per_second: 0.001,
+                burst: 2,
+                daily_objects: None,
+            }])
+            .unwrap(),
+        );
+        let app = test::init_service(new_app(Arc::new(Mutex::new(app_state)))).await;
+        let get = |addr: &str, password: &str| {
+            test::TestRequest::get()
+                .uri("/api1/collections/collection1/manifest/")
+                .append_header(("Accept", "application/taxii+json;version=2.1"))
+                .append_header((
+                    "Authorization",
+                    format!("Basic {}", STANDARD.encode(format!("alice:{}", password))),
+                ))
+                .peer_addr(addr.parse().unwrap())
+                .to_request()
+        };
+        for _ in 0..2 {
+            let resp = app.call(get("10.0.0.1:1000", "guess")).await?;
+            assert_eq!(resp.status(), http::StatusCode::UNAUTHORIZED);
+        }
+        // no more attempts from the address, not even with the right password
+        let resp = app.call(get("10.0.0.1:1000", "guess")).await?;
+        assert_eq!(resp.status(), http::StatusCode::TOO_MANY_REQUESTS);
+        assert!(resp.headers().contains_key("Retry-After"));
+        let resp = app.call(get("10.0.0.1:1000", "secret")).await?;
+        assert_eq!(resp.status(), http::StatusCode::TOO_MANY_REQUESTS);
+        // other addresses are not affected, and their successful attempts count against the
+        // bucket of the principal only
+        for _ in 0..2 {
+            let resp = app.call(get("10.0.0.2:1000", "secret")).await?;
+            assert_eq!(resp.status(), http::StatusCode::OK);
+        }
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_metrics() -> Result<(), Error> {
+        let app_state = Arc::new(Mutex::new(new_limited_app_state()));
+        let app = test::init_service(new_app(app_state)).await;
+        let object = r#"{"objects": [{"type": "indicator", "spec_version": "2.1",
+            "id": "indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f",
+            "created": "2016-04-06T20:03:48.000Z", "modified": "2016-04-06T20:03:48.000Z"}]}"#;
+        let req = test::TestRequest::post()
+            .uri("/api1/collections/collection1/objects/")
+            .append_header(("Accept", "application/taxii+json;version=2.1"))
+            .append_header(("Content-Type", "application/taxii+json;version=2.1"))
+            .set_payload(object)
+            .to_request();
+        assert_eq!(app.call(req).await?.status(), http::StatusCode::ACCEPTED);
+        for _ in 0..2 {
+            let req = test::TestRequest::get()
+                .uri("/api1/collections/collection1/objects/")
+                .append_header(("Accept", "application/taxii+json;version=2.1"))
+                .to_request();
+            assert_eq!(app.call(req).await?.status(), http::StatusCode::OK);
+        }
+        let req = test::TestRequest::get().uri("/taxii2").to_request();
+        assert_eq!(
+            app.call(req).await?.status(),
+            http::StatusCode::NOT_ACCEPTABLE
+        );
+
+        // no Accept header needed
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let resp = app.call(req).await?;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert!(resp
+            .headers()
+            .get("Content-Type")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain"));
+        let body = to_bytes(resp.into_body()).await?;
+        let text = std::str::from_utf8(&body).unwrap();
+        for expected in [
+            r#"taxii_http_requests_total{method="GET",route="/{api_root}/collections/{collection_id}/objects/",status="200"} 2"#,
+            r#"taxii_http_requests_total{method="POST",route="/{api_root}/collections/{collection_id}/objects/",status="202"} 1"#,
+            r#"taxii_http_requests_total{method="GET",route="/taxii2",status="406"} 1"#,
+            r#"taxii_http_request_duration_seconds_count{method="GET",route="/{api_root}/collections/{collection_id}/objects/"} 2"#,
+            r#"taxii_objects_served_total{api_root="api1",collection="collection1"} 2"#,
+            r#"taxii_objects_added_total{api_root="api1",collection="collection1"} 1"#,
+            r#"taxii_backend_operation_duration_seconds_count{operation="add_objects"} 1"#,
+            r#"taxii_backend_operation_duration_seconds_count{operation="get_objects"} 2"#,
+            r#"taxii_status_resources{status="complete"} 1"#,
+        ] {
+            assert!(text.contains(expected), "{}\n{}", expected, text);
+        }
+        Ok(())
+    }
+
+    /// The log lines written by a test.
+    #[derive(Clone)]
+    struct LogBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for LogBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for LogBuffer {
+        type Writer = LogBuffer;
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_request_tracing() -> Result<(), Error> {
+        use crate::taxii21::auth::{PasswordHash, TEST_PARAMS};
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let buffer = LogBuffer(Arc::new(Mutex::new(Vec::new())));
+        let subscriber = tracing_subscriber::fmt()
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .with_writer(buffer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let mut app_state = new_limited_app_state();
+        let mut object = Object::new(
+            "indicator",
+            "indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f",
+        );
+        object.modified = Some(String::from("2016-04-06T20:03:48.000Z"));
+        if let Some(backend) = &app_state.backend {
+            let mut backend = backend.lock().unwrap();
+            backend.add_objects("collection1", &[object]).unwrap();
+        }
+        let mut users = UserStore::new();
+        users.add_user("alice", &PasswordHash::new("secret", &TEST_PARAMS));
+        app_state.set_users(users);
+        let app = test::init_service(new_app(Arc::new(Mutex::new(app_state)))).await;
+        let req = test::TestRequest::get()
+            .uri("/api1/collections/collection1/objects/?match[type]=indicator")
+            .append_header(("Accept", "application/taxii+json;version=2.1"))
+            .append_header((
+                "Authorization",
+                format!("Basic {}", STANDARD.encode("alice:secret")),
+            ))
+            .append_header(("X-Request-ID", "abc-123"))
+            .to_request();
+        let resp = app.call(req).await?;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert_eq!("abc-123", resp.headers().get("X-Request-ID").unwrap());
+
+        let req = test::TestRequest::get().uri("/taxii2").to_request();
+        let resp = app.call(req).await?;
+        let generated = resp
+            .headers()
+            .get("X-Request-ID")
+            .unwrap()
+            .to_str()
+            .unwrap();
+        assert!(uuid::Uuid::parse_str(generated).is_ok());
+        let req = test::TestRequest::get()
+            .uri("/taxii2")
+            .append_header(("X-Request-ID", "not an id"))
+            .to_request();
+        let resp = app.call(req).await?;
+        let replaced = resp
+            .headers()
+            .get("X-Request-ID")
+            .unwrap()
+            .to_str()
+            .unwrap();
+        assert!(uuid::Uuid::parse_str(replaced).is_ok());
+
+        let logs = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let line = logs
+            .lines()
+            .map(|v| serde_json::from_str::<serde_json::Value>(v).unwrap())
+            .find(|v| v["span"]["request_id"] == "abc-123" && v["fields"]["status"] == 200)
+            .unwrap();
+        assert_eq!("request completed", line["fields"]["message"]);
+        assert!(line["fields"]["duration_ms"].is_u64());
+        let span = &line["span"];
+        assert_eq!("GET", span["method"]);
+        assert_eq!("alice", span["principal"]);
+        assert_eq!("match[type]=indicator", span["query"]);
+        assert_eq!("api1", span["api_root"]);
+        assert_eq!("collection1", span["collection"]);
+        assert_eq!(1, span["count"]);
+        // the unauthenticated requests are logged too
+        assert_eq!(3, logs.matches(r#""message":"request completed""#).count());
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_validate_config() {
+        let root_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        let mut cfg =
+            AppConfig::load(&PathBuf::from(root_dir).join("test/sample-server.toml")).unwrap();
+        assert!(cfg.validate().is_ok());
+        cfg.users.push(User {
+            username: String::from("mallory"),
+            password_hash: String::from("secret"),
+        });
+        let err = cfg.validate().unwrap_err();
+        assert!(err.to_string().starts_with("user mallory: "), "{}", err);
+    }
+
+    #[actix_web::test]
+    async fn test_health_checks() -> Result<(), Error> {
+        use crate::taxii21::auth::{PasswordHash, TEST_PARAMS};
+
+        let new_app_state = || {
+            let mut users = UserStore::new();
+            users.add_user("alice", &PasswordHash::new("secret", &TEST_PARAMS));
+            let mut app_state = new_limited_app_state();
+            app_state.set_users(users);
+            let root_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+            app_state.config_path = Some(PathBuf::from(root_dir).join("test/sample-server.toml"));
+            app_state
+        };
+        let app = test::init_service(new_app(Arc::new(Mutex::new(new_app_state())))).await;
+        // neither authenticated nor with an Accept header
+        let req = test::TestRequest::get().uri("/healthz").to_request();
+        let resp = app.call(req).await?;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let body = to_bytes(resp.into_body()).await?;
+        assert_eq!(r#"{"status":"ok"}"#, body);
+        let req = test::TestRequest::get().uri("/readyz").to_request();
+        let resp = app.call(req).await?;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let body = to_bytes(resp.into_body()).await?;
+        let readiness: Readiness = serde_json::from_slice(&body).unwrap();
+        assert_eq!("ready", readiness.status);
+        assert_eq!(Some(1), readiness.checks["backend"].collections);
+        assert_eq!(Some(1), readiness.checks["api_root:api1"].collections);
+        assert_eq!("ok", readiness.checks["config"].status);
+        let req = test::TestRequest::get().uri("/taxii2").to_request();
+        assert_eq!(
+            app.call(req).await?.status(),
+            http::StatusCode::UNAUTHORIZED
+        );
+
+        let mut app_state = new_app_state();
+        let root_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        app_state.add_file_backend(format!("{}/test/no-such-dir/", root_dir).as_str());
+        app_state.config_path = Some(PathBuf::from(root_dir).join("test/no-such-config.toml"));
+        let app = test::init_service(new_app(Arc::new(Mutex::new(app_state)))).await;
+        let req = test::TestRequest::get().uri("/readyz").to_request();
+        let resp = app.call(req).await?;
+        assert_eq!(resp.status(), http::StatusCode::SERVICE_UNAVAILABLE);
+        let body = to_bytes(resp.into_body()).await?;
+        let readiness: Readiness = serde_json::from_slice(&body).unwrap();
+        assert_eq!("degraded", readiness.status);
+        assert_eq!("failed", readiness.checks["backend"].status);
+        assert!(readiness.checks["backend"]
+            .error
+            .as_ref()
+            .unwrap()
+            .contains("no-such-dir"));
+        assert_eq!("failed", readiness.checks["api_root:api1"].status);
+        assert!(readiness.checks["config"]
+            .error
+            .as_ref()
+            .unwrap()
+            .contains("no-such-config.toml"));
+        let req = test::TestRequest::get().uri("/healthz").to_request();
+        assert_eq!(app.call(req).await?.status(), http::StatusCode::OK);
+
+        // collections of an api root missing from the backend, without a configuration file
+        let mut app_state = new_app_state();
+        app_state.config_path = None;
+        let collection = CollectionConfig::new("collection2", "collection 2");
+        app_state.add_collection("api1", &collection).unwrap();
+        let app = test::init_service(new_app(Arc::new(Mutex::new(app_state)))).await;
+        let req = test::TestRequest::get().uri("/readyz").to_request();
+        let resp = app.call(req).await?;
+        assert_eq!(resp.status(), http::StatusCode::SERVICE_UNAVAILABLE);
+        let body = to_bytes(resp.into_body()).await?;
+        let readiness: Readiness = serde_json::from_slice(&body).unwrap();
+        assert!(!readiness.checks.contains_key("config"));
+        assert_eq!("ok", readiness.checks["backend"].status);
+        assert_eq!(
+            Some(String::from("collections not in the backend: collection2")),
+            readiness.checks["api_root:api1"].error
+        );
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_chunked_request_limit() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let app_state = Arc::new(Mutex::new(new_limited_app_state()));
+        let server = HttpServer::new(move || new_app(app_state.clone()))
+            .workers(1)
+            .bind(("127.0.0.1", 0))
+            .unwrap();
+        let addr = server.addrs()[0];
+        actix_web::rt::spawn(server.run());
+
+        // without a Content-Length, the body is only known to be too long after a few chunks
+        let post = |chunks: &[String]| {
+            let mut request = String::from(
+                "POST /api1/collections/collection1/objects/ HTTP/1.1\r\n\
+                 Host: localhost\r\n\
+                 Accept: application/taxii+json;version=2.1\r\n\
+                 Content-Type: application/taxii+json;version=2.1\r\n\
+                 Transfer-Encoding: chunked\r\n\
+                 Connection: close\r\n\r\n",
+            );
+            for chunk in chunks {
+                request.push_str(format!("{:x}\r\n{}\r\n", chunk.len(), chunk).as_str());
+            }
+            request.push_str("0\r\n\r\n");
+            request
+        };
+        let status = |response: &str| -> u16 {
+            response
+                .split(' ')
+                .nth(1)
+                .and_then(|v| v.parse().ok())
+                .unwrap()
+        };
+
+        let chunks = vec![
+            String::from(r#"{"objects": [], "#),
+            format!(r#""padding": "{}","#, "x".repeat(600)),
+            format!(r#""more": "{}"}}"#, "x".repeat(600)),
+        ];
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream.write_all(post(&chunks).as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert_eq!(413, status(response.as_str()), "{}", response);
+        assert!(response.contains("request body too large"));
+
+        let chunks = vec![String::from(r#"{"objects": "#), String::from("[]}")];
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream.write_all(post(&chunks).as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert_eq!(202, status(response.as_str()), "{}", response);
+    }
+
     #[actix_web::test]
     async fn test_handle_api_root_errors() -> Result<(), Error> {
         let app_state = Arc::new(Mutex::new(AppState::new_empty()));
@@ -599,8 +2708,7 @@ mod tests {
         let response_body = resp.into_body();
         assert_eq!(to_bytes(response_body).await?.len(), 0);
 
-        let mut versions = Vec::<String>::new();
-        versions.push(String::from("api-root-version"));
+        let versions = vec![String::from("api-root-version")];
         {
             let mut app_state = app_state.lock().unwrap();
             app_state.api_roots.insert(
@@ -622,7 +2730,7 @@ mod tests {
         assert_eq!(resp.status(), http::StatusCode::OK);
         let response_body = resp.into_body();
         let response_body = to_bytes(response_body).await?;
-        assert!(response_body.len() > 0);
+        assert!(!response_body.is_empty());
         let api_root: APIRootConfig =
             match serde_json::from_slice::<APIRootConfig>(response_body.as_ref()) {
                 Ok(v) => v,
@@ -653,8 +2761,7 @@ mod tests {
         let app_state = Arc::new(Mutex::new(AppState::new_empty()));
         let app = new_app(app_state.clone());
         let app = test::init_service(app).await;
-        let mut versions = Vec::<String>::new();
-        versions.push(String::from("api-root-version"));
+        let versions = vec![String::from("api-root-version")];
         {
             let mut app_state = app_state.lock().unwrap();
             app_state.api_roots.insert(
@@ -694,7 +2801,7 @@ mod tests {
         let resp = app.call(req).await?;
         assert_eq!(resp.status(), http::StatusCode::OK);
         let response_body = to_bytes(resp.into_body()).await?;
-        assert!(response_body.len() > 0);
+        assert!(!response_body.is_empty());
         let status: Status = match serde_json::from_slice::<Status>(response_body.as_ref()) {
             Ok(v) => v,
             Err(err) => panic!("err={}", err),
@@ -710,8 +2817,7 @@ mod tests {
         let app_state = Arc::new(Mutex::new(AppState::new_empty()));
         let app = new_app(app_state.clone());
         let app = test::init_service(app).await;
-        let mut versions = Vec::<String>::new();
-        versions.push(String::from("api-root-version"));
+        let versions = vec![String::from("api-root-version")];
         {
             let mut app_state = app_state.lock().unwrap();
             app_state.api_roots.insert(
@@ -731,7 +2837,7 @@ mod tests {
         let resp = app.call(req).await?;
         assert_eq!(resp.status(), http::StatusCode::OK);
         let response_body = to_bytes(resp.into_body()).await?;
-        assert!(response_body.len() > 0);
+        assert!(!response_body.is_empty());
         let collections: Collections =
             match serde_json::from_slice::<Collections>(response_body.as_ref()) {
                 Ok(v) => v,
@@ -754,7 +2860,7 @@ mod tests {
         let resp = app.call(req).await?;
         assert_eq!(resp.status(), http::StatusCode::OK);
         let response_body = to_bytes(resp.into_body()).await?;
-        assert!(response_body.len() > 0);
+        assert!(!response_body.is_empty());
         let collections: Collections =
             match serde_json::from_slice::<Collections>(response_body.as_ref()) {
                 Ok(v) => v,
@@ -777,8 +2883,7 @@ mod tests {
         let app_state = Arc::new(Mutex::new(AppState::new_empty()));
         let app = new_app(app_state.clone());
         let app = test::init_service(app).await;
-        let mut versions = Vec::<String>::new();
-        versions.push(String::from("api-root-version"));
+        let versions = vec![String::from("api-root-version")];
         {
             let mut app_state = app_state.lock().unwrap();
             app_state.api_roots.insert(
@@ -813,7 +2918,7 @@ mod tests {
         let resp = app.call(req).await?;
         assert_eq!(resp.status(), http::StatusCode::OK);
         let response_body = to_bytes(resp.into_body()).await?;
-        assert!(response_body.len() > 0);
+        assert!(!response_body.is_empty());
         let collection: CollectionConfig =
             match serde_json::from_slice::<CollectionConfig>(response_body.as_ref()) {
                 Ok(v) => v,
@@ -828,14 +2933,13 @@ mod tests {
     #[actix_web::test]
     async fn test_handle_api_root_collection_manifest() -> Result<(), Error> {
         let mut app_state = AppState::new_empty();
-        let root_dir = std::env::var("HOME").unwrap();
-        let root_dir = format!("{}/rk/stix-rust/test/file-backend/", root_dir);
+        let root_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        let root_dir = format!("{}/test/file-backend/", root_dir);
         app_state.add_file_backend(root_dir.as_str());
         let app_state = Arc::new(Mutex::new(app_state));
         let app = new_app(app_state.clone());
         let app = test::init_service(app).await;
-        let mut versions = Vec::<String>::new();
-        versions.push(String::from("api-root-version"));
+        let versions = vec![String::from("api-root-version")];
         {
             let mut app_state = app_state.lock().unwrap();
             app_state.api_roots.insert(
@@ -855,7 +2959,7 @@ mod tests {
         let resp = app.call(req).await?;
         assert_eq!(resp.status(), http::StatusCode::OK);
         let response_body = to_bytes(resp.into_body()).await?;
-        assert!(response_body.len() > 0);
+        assert!(!response_body.is_empty());
         let manifest: Manifest = match serde_json::from_slice::<Manifest>(response_body.as_ref()) {
             Ok(v) => v,
             Err(err) => panic!("err={}", err),
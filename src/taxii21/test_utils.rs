@@ -0,0 +1,62 @@
+//! Helpers shared by the tests of the TAXII 2.1 client and of the tools built on it.
+
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use actix_web::HttpServer;
+
+use super::{
+    memory_backend::MemoryBackend,
+    server::{new_app, APIRoot, APIRootConfig, AppState, CollectionConfig, Object},
+};
+
+pub fn new_indicator(id: &str, modified: &str) -> Object {
+    let mut object = Object::new("indicator", id);
+    object.created = Some(String::from("2023-01-01T00:00:00.000Z"));
+    object.modified = Some(String::from(modified));
+    object.properties.insert(
+        String::from("pattern"),
+        serde_json::Value::from("[ipv4-addr:value = '198.51.100.1']"),
+    );
+    object
+}
+
+/// A backend holding the empty `collection1` collection.
+pub fn new_backend() -> Arc<Mutex<MemoryBackend>> {
+    let mut backend = MemoryBackend::new();
+    backend.add_collection("collection1");
+    Arc::new(Mutex::new(backend))
+}
+
+/// The state of a server holding `collection1` of a backend in the `api1` api root, two
+/// objects to a page.
+pub fn new_app_state(backend: Arc<Mutex<MemoryBackend>>) -> Arc<Mutex<AppState>> {
+    let mut app_state = AppState::new_empty();
+    app_state.server.title = String::from("test server");
+    app_state.default_server_record_limit = 2;
+    app_state.set_backend(backend);
+    let versions = vec![String::from("application/taxii+json;version=2.1")];
+    app_state.api_roots.insert(
+        String::from("api1"),
+        APIRoot::new(&APIRootConfig::new("api root 1", None, &versions, 10485760)),
+    );
+    let mut collection = CollectionConfig::new("collection1", "collection 1");
+    collection.can_read = true;
+    collection.can_write = true;
+    app_state.add_collection("api1", &collection).unwrap();
+    Arc::new(Mutex::new(app_state))
+}
+
+/// Serve `collection1` of a backend in the `api1` api root, two objects to a page.
+pub fn start_server(backend: Arc<Mutex<MemoryBackend>>) -> SocketAddr {
+    let app_state = new_app_state(backend);
+    let server = HttpServer::new(move || new_app(app_state.clone()))
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+    let addr = server.addrs()[0];
+    actix_web::rt::spawn(server.run());
+    addr
+}
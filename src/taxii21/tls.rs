@@ -0,0 +1,480 @@
+use std::{
+    any::Any,
+    collections::HashMap,
+    fs::File,
+    io::BufReader,
+    sync::{Arc, RwLock},
+};
+
+use actix_tls::accept::rustls_0_23::TlsStream;
+use actix_web::{dev::Extensions, rt::net::TcpStream};
+use rustls::{
+    client::danger::HandshakeSignatureValid,
+    crypto::CryptoProvider,
+    pki_types::{CertificateDer, PrivateKeyDer, UnixTime},
+    server::{
+        danger::{ClientCertVerified, ClientCertVerifier},
+        ClientHello, ResolvesServerCert, WebPkiClientVerifier,
+    },
+    sign::CertifiedKey,
+    DigitallySignedStruct, DistinguishedName, RootCertStore, ServerConfig, SignatureScheme,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use super::{api_keys::Scope, errors::MyError};
+
+/// The `[tls]` table of the server configuration.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TlsConfig {
+    /// The PEM certificate chain of the server, the server certificate first.
+    pub cert_path: String,
+    /// The PEM private key of the server certificate.
+    pub key_path: String,
+    /// The PEM CA bundle client certificates are verified against; no client certificates are
+    /// requested without it.
+    pub client_ca_path: Option<String>,
+    /// Also accept clients without a certificate, which then authenticate otherwise.
+    #[serde(default)]
+    pub client_cert_optional: bool,
+    /// The principals of client certificates by subject, e.g. `"O=Example, CN=feed-consumer"`.
+    /// Other certificates map to the common name of their subject, without access to any
+    /// collection.
+    #[serde(default)]
+    pub client_principals: HashMap<String, String>,
+}
+
+/// The verified client certificate of a connection, available as connection data.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClientCertificate {
+    pub subject: String,
+    /// The principal the subject maps to.
+    pub username: String,
+    /// No scopes for a subject without a mapping, which may not access anything.
+    pub scopes: Option<Vec<Scope>>,
+}
+
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>, MyError> {
+    let file = match File::open(path) {
+        Ok(v) => v,
+        Err(err) => return Err(MyError(format!("{}: {}", path, err))),
+    };
+    let certs: Result<Vec<_>, _> = rustls_pemfile::certs(&mut BufReader::new(file)).collect();
+    match certs {
+        Ok(v) if !v.is_empty() => Ok(v),
+        Ok(_) => Err(MyError(format!("{}: no certificates", path))),
+        Err(err) => Err(MyError(format!("{}: {}", path, err))),
+    }
+}
+
+fn load_key(path: &str) -> Result<PrivateKeyDer<'static>, MyError> {
+    let file = match File::open(path) {
+        Ok(v) => v,
+        Err(err) => return Err(MyError(format!("{}: {}", path, err))),
+    };
+    match rustls_pemfile::private_key(&mut BufReader::new(file)) {
+        Ok(Some(v)) => Ok(v),
+        Ok(None) => Err(MyError(format!("{}: no private key", path))),
+        Err(err) => Err(MyError(format!("{}: {}", path, err))),
+    }
+}
+
+fn load_certified_key(
+    config: &TlsConfig,
+    provider: &CryptoProvider,
+) -> Result<Arc<CertifiedKey>, MyError> {
+    let certs = load_certs(config.cert_path.as_str())?;
+    let key = load_key(config.key_path.as_str())?;
+    match CertifiedKey::from_der(certs, key, provider) {
+        Ok(v) => Ok(Arc::new(v)),
+        Err(err) => Err(MyError(format!("{}: {}", config.key_path, err))),
+    }
+}
+
+fn load_client_verifier(
+    config: &TlsConfig,
+    ca_path: &str,
+    provider: &Arc<CryptoProvider>,
+) -> Result<Arc<dyn ClientCertVerifier>, MyError> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(ca_path)? {
+        if let Err(err) = roots.add(cert) {
+            return Err(MyError(format!("{}: {}", ca_path, err)));
+        }
+    }
+    let mut builder =
+        WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider.clone());
+    if config.client_cert_optional {
+        builder = builder.allow_unauthenticated();
+    }
+    match builder.build() {
+        Ok(v) => Ok(v),
+        Err(err) => Err(MyError(format!("{}: {}", ca_path, err))),
+    }
+}
+
+/// The server certificate, replaced on reload.
+#[derive(Debug)]
+struct CertResolver {
+    key: RwLock<Arc<CertifiedKey>>,
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.key.read().unwrap().clone())
+    }
+}
+
+/// The client certificate verifier, replaced on reload. No CA names are sent as hints, since
+/// they could not be borrowed across a reload; clients then offer any certificate they have.
+#[derive(Debug)]
+struct ClientVerifier {
+    verifier: RwLock<Arc<dyn ClientCertVerifier>>,
+}
+
+impl ClientVerifier {
+    fn get(&self) -> Arc<dyn ClientCertVerifier> {
+        self.verifier.read().unwrap().clone()
+    }
+}
+
+impl ClientCertVerifier for ClientVerifier {
+    fn offer_client_auth(&self) -> bool {
+        self.get().offer_client_auth()
+    }
+    fn client_auth_mandatory(&self) -> bool {
+        self.get().client_auth_mandatory()
+    }
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+    fn verify_client_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        now: UnixTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        self.get()
+            .verify_client_cert(end_entity, intermediates, now)
+    }
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.get().verify_tls12_signature(message, cert, dss)
+    }
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.get().verify_tls13_signature(message, cert, dss)
+    }
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.get().supported_verify_schemes()
+    }
+}
+
+/// TLS termination for the server, with certificates that can be reloaded while it runs.
+pub struct Tls {
+    config: TlsConfig,
+    provider: Arc<CryptoProvider>,
+    cert: Arc<CertResolver>,
+    client: Option<Arc<ClientVerifier>>,
+}
+
+impl Tls {
+    pub fn new(config: &TlsConfig) -> Result<Tls, MyError> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let key = load_certified_key(config, &provider)?;
+        let client = match &config.client_ca_path {
+            Some(ca_path) => Some(Arc::new(ClientVerifier {
+                verifier: RwLock::new(load_client_verifier(config, ca_path, &provider)?),
+            })),
+            None => None,
+        };
+        Ok(Tls {
+            config: config.clone(),
+            provider,
+            cert: Arc::new(CertResolver {
+                key: RwLock::new(key),
+            }),
+            client,
+        })
+    }
+
+    /// The rustls configuration to listen with, which follows reloads.
+    pub fn server_config(&self) -> Result<ServerConfig, MyError> {
+        let builder = match ServerConfig::builder_with_provider(self.provider.clone())
+            .with_safe_default_protocol_versions()
+        {
+            Ok(v) => v,
+            Err(err) => return Err(MyError(err.to_string())),
+        };
+        let builder = match &self.client {
+            Some(client) => builder.with_client_cert_verifier(client.clone()),
+            None => builder.with_no_client_auth(),
+        };
+        Ok(builder.with_cert_resolver(self.cert.clone()))
+    }
+
+    /// Read the certificates, key and CA bundle again. New connections use them; when any of
+    /// them is bad, the ones read before are kept.
+    pub fn reload(&self) -> Result<(), MyError> {
+        let key = load_certified_key(&self.config, &self.provider)?;
+        let verifier = match (&self.client, &self.config.client_ca_path) {
+            (Some(_), Some(ca_path)) => {
+                Some(load_client_verifier(&self.config, ca_path, &self.provider)?)
+            }
+            _ => None,
+        };
+        *self.cert.key.write().unwrap() = key;
+        if let (Some(client), Some(verifier)) = (&self.client, verifier) {
+            *client.verifier.write().unwrap() = verifier;
+        }
+        Ok(())
+    }
+
+    /// The server certificate currently used.
+    pub fn certificate(&self) -> CertificateDer<'static> {
+        self.cert.key.read().unwrap().cert[0].clone()
+    }
+
+    /// The subject of a client certificate, and the principal it maps to.
+    pub fn client_certificate(&self, cert: &CertificateDer) -> Option<ClientCertificate> {
+        let cert = match x509_parser::parse_x509_certificate(cert.as_ref()) {
+            Ok((_, v)) => v,
+            Err(err) => return None,
+        };
+        let subject = cert.subject().to_string();
+        let (username, scopes) = match self.config.client_principals.get(subject.as_str()) {
+            Some(v) => (v.clone(), None),
+            None => {
+                let cn = cert
+                    .subject()
+                    .iter_common_name()
+                    .next()
+                    .and_then(|v| v.as_str().ok());
+                (String::from(cn?), Some(Vec::<Scope>::new()))
+            }
+        };
+        Some(ClientCertificate {
+            subject,
+            username,
+            scopes,
+        })
+    }
+
+    /// For `HttpServer::on_connect`: makes the verified client certificate of a TLS connection
+    /// available to the authentication middleware.
+    pub fn on_connect(&self, connection: &dyn Any, data: &mut Extensions) {
+        let stream = match connection.downcast_ref::<TlsStream<TcpStream>>() {
+            Some(v) => v,
+            None => return,
+        };
+        let (_, session) = stream.get_ref();
+        let cert = match session.peer_certificates().and_then(|v| v.first()) {
+            Some(v) => v,
+            None => return,
+        };
+        if let Some(cert) = self.client_certificate(cert) {
+            data.insert(cert);
+        }
+    }
+}
+
+/// Reload the certificates whenever the process gets SIGHUP.
+pub async fn reload_on_sighup(tls: Arc<Tls>) {
+    let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+        Ok(v) => v,
+        Err(err) => {
+            warn!("cannot handle SIGHUP: {}", err);
+            return;
+        }
+    };
+    while hangup.recv().await.is_some() {
+        match tls.reload() {
+            Ok(_) => info!("reloaded tls certificates"),
+            Err(err) => warn!("keeping the tls certificates: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, sync::Arc};
+
+    use super::{Tls, TlsConfig};
+
+    /// The certificates in test/tls are signed by a test CA, valid for 100 years, with EC P-256
+    /// keys: server.pem and server2.pem for localhost and 127.0.0.1, client.pem for
+    /// `O=Example, CN=feed-consumer`.
+    pub fn tls_config(dir: &str) -> TlsConfig {
+        TlsConfig {
+            cert_path: format!("{}/server.pem", dir),
+            key_path: format!("{}/server-key.pem", dir),
+            client_ca_path: Some(format!("{}/ca.pem", dir)),
+            client_cert_optional: false,
+            client_principals: std::collections::HashMap::new(),
+        }
+    }
+
+    fn test_dir() -> String {
+        format!("{}/test/tls", env::var("CARGO_MANIFEST_DIR").unwrap())
+    }
+
+    #[test]
+    fn test_client_certificate() {
+        let dir = test_dir();
+        let mut config = tls_config(dir.as_str());
+        let tls = Tls::new(&config).unwrap();
+        let client = super::load_certs(format!("{}/client.pem", dir).as_str()).unwrap();
+        let cert = tls.client_certificate(&client[0]).unwrap();
+        assert_eq!("O=Example, CN=feed-consumer", cert.subject);
+        assert_eq!("feed-consumer", cert.username);
+        assert_eq!(Some(vec![]), cert.scopes);
+
+        config.client_principals.insert(
+            String::from("O=Example, CN=feed-consumer"),
+            String::from("consumer"),
+        );
+        let tls = Tls::new(&config).unwrap();
+        let cert = tls.client_certificate(&client[0]).unwrap();
+        assert_eq!("consumer", cert.username);
+        assert_eq!(None, cert.scopes);
+
+        config.key_path = format!("{}/client-key.pem", dir);
+        assert!(Tls::new(&config).is_err());
+        config.key_path = format!("{}/missing.pem", dir);
+        assert!(Tls::new(&config).is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_client_certificate_authentication() {
+        use std::sync::Mutex;
+
+        use actix_web::HttpServer;
+
+        use crate::taxii21::{
+            acl::{Acl, AclEntry, AclPrincipal},
+            memory_backend::MemoryBackend,
+            server::{new_app, APIRoot, APIRootConfig, AppState, CollectionConfig, Collections},
+        };
+
+        let dir = test_dir();
+        let mut config = tls_config(dir.as_str());
+        config.client_principals.insert(
+            String::from("O=Example, CN=feed-consumer"),
+            String::from("feed-consumer"),
+        );
+        let tls = Arc::new(Tls::new(&config).unwrap());
+        let mut backend = MemoryBackend::new();
+        backend.add_collection("collection1");
+        let mut app_state = AppState::new_empty();
+        app_state.set_backend(Arc::new(Mutex::new(backend)));
+        let versions = vec![String::from("application/taxii+json;version=2.1")];
+        app_state.api_roots.insert(
+            String::from("api1"),
+            APIRoot::new(&APIRootConfig::new("api root 1", None, &versions, 1000)),
+        );
+        let mut collection = CollectionConfig::new("collection1", "collection 1");
+        collection.can_read = true;
+        collection.can_write = true;
+        app_state.add_collection("api1", &collection).unwrap();
+        let acl = vec![AclEntry {
+            principal: AclPrincipal::Named(String::from("feed-consumer")),
+            api_root: String::from("api1"),
+            collection: None,
+            permissions: vec![String::from("read")],
+        }];
+        app_state.set_acl(Acl::new(&acl).unwrap());
+        let app_state = Arc::new(Mutex::new(app_state));
+        let on_connect = tls.clone();
+        let server = HttpServer::new(move || new_app(app_state.clone()))
+            .workers(1)
+            .on_connect(move |connection, data| on_connect.on_connect(connection, data))
+            .bind_rustls_0_23(("127.0.0.1", 0), tls.server_config().unwrap())
+            .unwrap();
+        let url = format!(
+            "https://localhost:{}/api1/collections/",
+            server.addrs()[0].port()
+        );
+        actix_web::rt::spawn(server.run());
+
+        let read = |name: &str| std::fs::read(format!("{}/{}", dir, name)).unwrap();
+        let ca = reqwest::Certificate::from_pem(&read("ca.pem")).unwrap();
+        let identity =
+            reqwest::Identity::from_pkcs8_pem(&read("client.pem"), &read("client-key.pem"))
+                .unwrap();
+        let client = reqwest::Client::builder()
+            .add_root_certificate(ca.clone())
+            .identity(identity)
+            .build()
+            .unwrap();
+        let resp = client
+            .get(url.as_str())
+            .header("Accept", "application/taxii+json;version=2.1")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(200, resp.status().as_u16());
+        let collections: Collections = resp.json().await.unwrap();
+        let collections = collections.collections.unwrap();
+        assert_eq!(1, collections.len());
+        assert!(collections[0].can_read);
+        assert!(!collections[0].can_write);
+
+        // other credentials along with the certificate are rejected
+        let resp = client
+            .get(url.as_str())
+            .header("Accept", "application/taxii+json;version=2.1")
+            .header("Authorization", "Bearer 0123.secret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(400, resp.status().as_u16());
+
+        // the certificate is required
+        let client = reqwest::Client::builder()
+            .add_root_certificate(ca)
+            .build()
+            .unwrap();
+        let resp = client
+            .get(url.as_str())
+            .header("Accept", "application/taxii+json;version=2.1")
+            .send()
+            .await;
+        assert!(resp.is_err());
+    }
+
+    #[test]
+    fn test_reload() {
+        let dir = test_dir();
+        let tmp = env::temp_dir().join(format!("tls-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&tmp).unwrap();
+        for name in ["server.pem", "server-key.pem"] {
+            std::fs::copy(format!("{}/{}", dir, name), tmp.join(name)).unwrap();
+        }
+        let mut config = tls_config(dir.as_str());
+        config.cert_path = tmp.join("server.pem").to_string_lossy().to_string();
+        config.key_path = tmp.join("server-key.pem").to_string_lossy().to_string();
+        let tls = Arc::new(Tls::new(&config).unwrap());
+        let first = tls.certificate();
+
+        // a bad key keeps the certificate
+        std::fs::write(tmp.join("server-key.pem"), "").unwrap();
+        assert!(tls.reload().is_err());
+        assert_eq!(first, tls.certificate());
+
+        for name in ["server2.pem", "server2-key.pem"] {
+            let to = name.replace("server2", "server");
+            std::fs::copy(format!("{}/{}", dir, name), tmp.join(to)).unwrap();
+        }
+        tls.reload().unwrap();
+        assert_ne!(first, tls.certificate());
+        std::fs::remove_dir_all(&tmp).unwrap();
+    }
+}